@@ -77,6 +77,11 @@ where
         Ok(None)
     }
 
+    /// Balance of the account, for the contracts that are tokens
+    fn token_balance(&self, _account: &Identity) -> Option<u128> {
+        None
+    }
+
     fn handle_transaction_failed(
         &mut self,
         _tx: &BlobTransaction,
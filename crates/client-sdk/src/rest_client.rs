@@ -1,20 +1,25 @@
 use std::{
+    collections::VecDeque,
     future::Future,
     ops::{Deref, DerefMut},
     pin::Pin,
+    task::{Context, Poll},
     time::Duration,
 };
 
-use anyhow::{Context, Result};
+use anyhow::{Context as _, Result};
+use futures::Stream;
 use hyle_net::http::HttpClient;
 use sdk::{
     api::{
-        APIBlob, APIBlock, APIContract, APINodeContract, APIRegisterContract, APIStaking,
-        APITransaction, NodeInfo, TransactionWithBlobs,
+        APIBlob, APIBlock, APIContract, APIContractHistoryEntry, APIIdentity, APIIdentityBalance,
+        APIIdentityContract, APINodeContract, APIRegisterContract, APIStaking, APITransaction,
+        APITxSimulation, NodeInfo, TransactionWithBlobs, NEXT_CURSOR_HEADER,
     },
     BlobIndex, BlobTransaction, BlockHash, BlockHeight, ConsensusInfo, Contract, ContractName,
-    ProofTransaction, TxHash, UnsettledBlobTransaction, ValidatorPublicKey,
+    Identity, ProofTransaction, TxHash, UnsettledBlobTransaction, ValidatorPublicKey,
 };
+use serde::de::DeserializeOwned;
 
 #[derive(Clone)]
 pub struct IndexerApiHttpClient {
@@ -124,6 +129,39 @@ impl IndexerApiHttpClient {
         ))
     }
 
+    pub async fn get_identity(&self, identity: &Identity) -> Result<APIIdentity> {
+        self.get(&format!("v1/indexer/identity/{identity}"))
+            .await
+            .context(format!("getting identity {identity}"))
+    }
+
+    pub async fn get_identity_transactions(
+        &self,
+        identity: &Identity,
+    ) -> Result<Vec<APITransaction>> {
+        self.get(&format!("v1/indexer/identity/{identity}/transactions"))
+            .await
+            .context(format!("getting transactions of identity {identity}"))
+    }
+
+    pub async fn get_identity_contracts(
+        &self,
+        identity: &Identity,
+    ) -> Result<Vec<APIIdentityContract>> {
+        self.get(&format!("v1/indexer/identity/{identity}/contracts"))
+            .await
+            .context(format!("getting contracts of identity {identity}"))
+    }
+
+    pub async fn get_identity_balances(
+        &self,
+        identity: &Identity,
+    ) -> Result<Vec<APIIdentityBalance>> {
+        self.get(&format!("v1/indexer/identity/{identity}/balances"))
+            .await
+            .context(format!("getting balances of identity {identity}"))
+    }
+
     pub async fn get_blobs_by_tx_hash(&self, tx_hash: &TxHash) -> Result<Vec<APIBlob>> {
         self.get(&format!("v1/indexer/blobs/hash/{tx_hash}"))
             .await
@@ -141,6 +179,151 @@ impl IndexerApiHttpClient {
     }
 }
 
+impl IndexerApiHttpClient {
+    /// Streams all transactions, most recent first, fetching pages of `page_size`.
+    pub fn stream_transactions(&self, page_size: u32) -> IndexerPager<'_, APITransaction> {
+        IndexerPager::new(self, "v1/indexer/transactions".to_string(), page_size)
+    }
+
+    /// Streams all proof transactions, most recent first, fetching pages of `page_size`.
+    pub fn stream_proofs(&self, page_size: u32) -> IndexerPager<'_, APITransaction> {
+        IndexerPager::new(self, "v1/indexer/proofs".to_string(), page_size)
+    }
+
+    /// Streams the blob transactions of a contract, most recent first, fetching pages of `page_size`.
+    pub fn stream_blob_transactions_by_contract(
+        &self,
+        contract_name: &ContractName,
+        page_size: u32,
+    ) -> IndexerPager<'_, TransactionWithBlobs> {
+        IndexerPager::new(
+            self,
+            format!("v1/indexer/blob_transactions/contract/{contract_name}"),
+            page_size,
+        )
+    }
+
+    /// Streams the transactions of an identity, most recent first, fetching pages of `page_size`.
+    pub fn stream_identity_transactions(
+        &self,
+        identity: &Identity,
+        page_size: u32,
+    ) -> IndexerPager<'_, APITransaction> {
+        IndexerPager::new(
+            self,
+            format!("v1/indexer/identity/{identity}/transactions"),
+            page_size,
+        )
+    }
+
+    /// Streams all contracts by name, fetching pages of `page_size`.
+    pub fn stream_contracts(&self, page_size: u32) -> IndexerPager<'_, APIContract> {
+        IndexerPager::new(self, "v1/indexer/contracts".to_string(), page_size)
+    }
+}
+
+/// Walks a paginated indexer listing by following the cursor of each page.
+/// Items are streamed one by one, pages being fetched as needed.
+pub struct IndexerPager<'a, T> {
+    client: &'a IndexerApiHttpClient,
+    endpoint: String,
+    page_size: u32,
+    cursor: Option<String>,
+    buffer: VecDeque<T>,
+    done: bool,
+    fetching: Option<PageFuture<'a, T>>,
+}
+
+type PageFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<(Vec<T>, Option<String>)>> + Send + 'a>>;
+
+// The pager is never structurally pinned
+impl<T> Unpin for IndexerPager<'_, T> {}
+
+impl<'a, T: DeserializeOwned + Send + 'a> IndexerPager<'a, T> {
+    fn new(client: &'a IndexerApiHttpClient, endpoint: String, page_size: u32) -> Self {
+        IndexerPager {
+            client,
+            endpoint,
+            page_size,
+            cursor: None,
+            buffer: VecDeque::new(),
+            done: false,
+            fetching: None,
+        }
+    }
+
+    fn fetch_page(&self) -> PageFuture<'a, T> {
+        let client = self.client;
+        let mut endpoint = format!("{}?nb_results={}", self.endpoint, self.page_size);
+        if let Some(cursor) = &self.cursor {
+            endpoint.push_str(&format!("&cursor={cursor}"));
+        }
+        let context = format!("getting page of {}", self.endpoint);
+        Box::pin(async move {
+            client
+                .get_with_header(&endpoint, NEXT_CURSOR_HEADER)
+                .await
+                .context(context)
+        })
+    }
+
+    fn on_page(&mut self, page: &[T], next_cursor: Option<String>) {
+        self.done = next_cursor.is_none() || page.is_empty();
+        self.cursor = next_cursor;
+    }
+
+    /// Fetches the next page, or returns None once the listing is exhausted.
+    pub async fn next_page(&mut self) -> Result<Option<Vec<T>>> {
+        if !self.buffer.is_empty() {
+            return Ok(Some(self.buffer.drain(..).collect()));
+        }
+        if self.done {
+            return Ok(None);
+        }
+        let fetching = self.fetching.take().unwrap_or_else(|| self.fetch_page());
+        let (page, next_cursor) = fetching.await?;
+        self.on_page(&page, next_cursor);
+        if page.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(page))
+    }
+}
+
+impl<'a, T: DeserializeOwned + Send + 'a> Stream for IndexerPager<'a, T> {
+    type Item = Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(item) = self.buffer.pop_front() {
+                return Poll::Ready(Some(Ok(item)));
+            }
+            if self.done {
+                return Poll::Ready(None);
+            }
+            let mut fetching = match self.fetching.take() {
+                Some(fetching) => fetching,
+                None => self.fetch_page(),
+            };
+            match fetching.as_mut().poll(cx) {
+                Poll::Pending => {
+                    self.fetching = Some(fetching);
+                    return Poll::Pending;
+                }
+                Poll::Ready(Ok((page, next_cursor))) => {
+                    self.on_page(&page, next_cursor);
+                    self.buffer.extend(page);
+                }
+                Poll::Ready(Err(err)) => {
+                    self.done = true;
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+        }
+    }
+}
+
 impl Deref for IndexerApiHttpClient {
     type Target = HttpClient;
 
@@ -170,6 +353,11 @@ pub trait NodeApiClient {
         tx: ProofTransaction,
     ) -> Pin<Box<dyn Future<Output = Result<TxHash>> + Send + '_>>;
 
+    fn simulate_tx(
+        &self,
+        tx: BlobTransaction,
+    ) -> Pin<Box<dyn Future<Output = Result<APITxSimulation>> + Send + '_>>;
+
     fn get_consensus_info(
         &self,
     ) -> Pin<Box<dyn Future<Output = Result<ConsensusInfo>> + Send + '_>>;
@@ -189,6 +377,19 @@ pub trait NodeApiClient {
         contract_name: ContractName,
     ) -> Pin<Box<dyn Future<Output = Result<APINodeContract>> + Send + '_>>;
 
+    /// State of the contract once the block at `height` was processed
+    fn get_contract_at_height(
+        &self,
+        contract_name: ContractName,
+        height: BlockHeight,
+    ) -> Pin<Box<dyn Future<Output = Result<APINodeContract>> + Send + '_>>;
+
+    /// Changes of the contract, oldest first
+    fn get_contract_history(
+        &self,
+        contract_name: ContractName,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<APIContractHistoryEntry>>> + Send + '_>>;
+
     fn get_settled_height(
         &self,
         contract_name: ContractName,
@@ -260,6 +461,17 @@ impl NodeApiClient for NodeApiHttpClient {
         })
     }
 
+    fn simulate_tx(
+        &self,
+        tx: BlobTransaction,
+    ) -> Pin<Box<dyn Future<Output = Result<APITxSimulation>> + Send + '_>> {
+        Box::pin(async move {
+            self.post_json("v1/tx/simulate", &tx)
+                .await
+                .context("Simulating tx blob")
+        })
+    }
+
     fn get_consensus_info(
         &self,
     ) -> Pin<Box<dyn Future<Output = Result<ConsensusInfo>> + Send + '_>> {
@@ -311,6 +523,31 @@ impl NodeApiClient for NodeApiHttpClient {
         })
     }
 
+    fn get_contract_at_height(
+        &self,
+        contract_name: ContractName,
+        height: BlockHeight,
+    ) -> Pin<Box<dyn Future<Output = Result<APINodeContract>> + Send + '_>> {
+        Box::pin(async move {
+            self.get(&format!("v1/contract/{contract_name}?at_height={height}"))
+                .await
+                .context(format!(
+                    "getting contract {contract_name} at height {height}"
+                ))
+        })
+    }
+
+    fn get_contract_history(
+        &self,
+        contract_name: ContractName,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<APIContractHistoryEntry>>> + Send + '_>> {
+        Box::pin(async move {
+            self.get(&format!("v1/contract/{contract_name}/history"))
+                .await
+                .context(format!("getting history of contract {contract_name}"))
+        })
+    }
+
     fn get_unsettled_tx(
         &self,
         blob_tx_hash: TxHash,
@@ -363,6 +600,8 @@ pub mod test {
         pub node_info: Arc<Mutex<NodeInfo>>,
         pub staking_state: Arc<Mutex<APIStaking>>,
         pub contracts: Arc<Mutex<std::collections::HashMap<ContractName, Contract>>>,
+        pub contract_histories:
+            Arc<Mutex<std::collections::HashMap<ContractName, Vec<APIContractHistoryEntry>>>>,
         pub unsettled_txs: Arc<Mutex<std::collections::HashMap<TxHash, UnsettledBlobTransaction>>>,
         pub pending_proofs: Arc<Mutex<Vec<ProofTransaction>>>,
         pub pending_blobs: Arc<Mutex<Vec<BlobTransaction>>>,
@@ -387,6 +626,7 @@ pub mod test {
                 })),
                 staking_state: Arc::new(Mutex::new(APIStaking::default())),
                 contracts: Arc::new(Mutex::new(std::collections::HashMap::new())),
+                contract_histories: Arc::new(Mutex::new(std::collections::HashMap::new())),
                 unsettled_txs: Arc::new(Mutex::new(std::collections::HashMap::new())),
                 pending_proofs: Arc::new(Mutex::new(vec![])),
                 pending_blobs: Arc::new(Mutex::new(vec![])),
@@ -420,6 +660,20 @@ pub mod test {
                 .insert(contract.name.clone(), contract);
         }
 
+        /// Entries must be added in increasing block height
+        pub fn add_contract_history_entry(
+            &self,
+            contract_name: ContractName,
+            entry: APIContractHistoryEntry,
+        ) {
+            self.contract_histories
+                .lock()
+                .unwrap()
+                .entry(contract_name)
+                .or_default()
+                .push(entry);
+        }
+
         pub fn add_unsettled_tx(&self, tx_hash: TxHash, tx: UnsettledBlobTransaction) {
             self.unsettled_txs.lock().unwrap().insert(tx_hash, tx);
         }
@@ -455,6 +709,44 @@ pub mod test {
             Box::pin(async move { Ok(tx.hashed()) })
         }
 
+        fn simulate_tx(
+            &self,
+            tx: BlobTransaction,
+        ) -> Pin<Box<dyn Future<Output = Result<APITxSimulation>> + Send + '_>> {
+            Box::pin(async move {
+                let contracts = self.contracts.lock().unwrap();
+                let identity_error = tx.validate_identity().err().map(|e| e.to_string());
+                let unknown_contracts: Vec<ContractName> = tx
+                    .blobs
+                    .iter()
+                    .map(|blob| blob.contract_name.clone())
+                    .filter(|name| !contracts.contains_key(name))
+                    .collect();
+                let timeout_window = tx
+                    .blobs
+                    .iter()
+                    .filter_map(|blob| contracts.get(&blob.contract_name))
+                    .map(|contract| contract.timeout_window.clone())
+                    .min()
+                    .unwrap_or(TimeoutWindow::NoTimeout);
+                Ok(APITxSimulation {
+                    tx_hash: tx.hashed(),
+                    block_height: *self.block_height.lock().unwrap(),
+                    accepted: identity_error.is_none()
+                        && unknown_contracts.is_empty()
+                        && !tx.blobs.is_empty(),
+                    identity_error,
+                    hyle_blobs_error: None,
+                    unknown_contracts,
+                    timeout_window: match timeout_window {
+                        TimeoutWindow::NoTimeout => None,
+                        TimeoutWindow::Timeout(window) => Some(window.0),
+                    },
+                    hyle_tld_blobs: vec![],
+                })
+            })
+        }
+
         fn get_consensus_info(
             &self,
         ) -> Pin<Box<dyn Future<Output = Result<ConsensusInfo>> + Send + '_>> {
@@ -508,6 +800,53 @@ pub mod test {
             })
         }
 
+        fn get_contract_at_height(
+            &self,
+            contract_name: ContractName,
+            height: BlockHeight,
+        ) -> Pin<Box<dyn Future<Output = Result<APINodeContract>> + Send + '_>> {
+            Box::pin(async move {
+                let entry = self
+                    .contract_histories
+                    .lock()
+                    .unwrap()
+                    .get(&contract_name)
+                    .and_then(|history| {
+                        history
+                            .iter()
+                            .rev()
+                            .find(|entry| entry.block_height <= height)
+                            .cloned()
+                    })
+                    // A contract deleted at or before this height no longer exists
+                    .filter(|entry| !entry.deleted)
+                    .ok_or_else(|| anyhow::anyhow!("Contract not found"))?;
+                Ok(APINodeContract {
+                    contract_name,
+                    state_block_height: height,
+                    state_commitment: entry.state_commitment,
+                    program_id: entry.program_id,
+                    verifier: entry.verifier,
+                    timeout_window: entry.timeout_window,
+                })
+            })
+        }
+
+        fn get_contract_history(
+            &self,
+            contract_name: ContractName,
+        ) -> Pin<Box<dyn Future<Output = Result<Vec<APIContractHistoryEntry>>> + Send + '_>>
+        {
+            Box::pin(async move {
+                self.contract_histories
+                    .lock()
+                    .unwrap()
+                    .get(&contract_name)
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("Contract not found"))
+            })
+        }
+
         fn get_unsettled_tx(
             &self,
             blob_tx_hash: TxHash,
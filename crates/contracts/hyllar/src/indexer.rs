@@ -27,6 +27,10 @@ impl ContractHandler for Hyllar {
 
         (router.with_state(store), api)
     }
+
+    fn token_balance(&self, account: &Identity) -> Option<u128> {
+        ERC20::balance_of(self, &account.0).ok()
+    }
 }
 
 #[utoipa::path(
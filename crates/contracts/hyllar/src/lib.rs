@@ -201,6 +201,24 @@ impl ContractAction for HyllarAction {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_fungible_token_interface() {
+        // Fees paid in hyllar are read by the node through the standard interface
+        let action = HyllarAction::Transfer {
+            recipient: "bob@wallet".to_string(),
+            amount: 42,
+        };
+        let decoded: sdk::FungibleTokenAction =
+            borsh::from_slice(&borsh::to_vec(&action).unwrap()).unwrap();
+        assert_eq!(
+            decoded,
+            sdk::FungibleTokenAction::Transfer {
+                recipient: "bob@wallet".to_string(),
+                amount: 42,
+            }
+        );
+    }
+
     #[test]
     fn test_new_hyllar_token() {
         let token = Hyllar::default();
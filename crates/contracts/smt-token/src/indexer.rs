@@ -28,6 +28,13 @@ impl ContractHandler for SmtTokenProvableState {
 
         (router.with_state(store), api)
     }
+
+    fn token_balance(&self, account: &Identity) -> Option<u128> {
+        self.get_account(account)
+            .ok()
+            .flatten()
+            .map(|account| account.balance)
+    }
 }
 
 #[utoipa::path(
@@ -6,8 +6,8 @@ use client_sdk::{
 use sdk::{
     api::{APIFees, APIFeesBalance, APIStaking},
     utils::as_hyle_output,
-    Blob, Calldata, ContractName, RegisterContractEffect, StakingAction, StateCommitment,
-    ValidatorPublicKey, ZkContract,
+    Blob, Calldata, ContractName, DoubleSignEvidence, RegisterContractEffect, StakingAction,
+    StateCommitment, ValidatorPublicKey, ZkContract,
 };
 
 use crate::{
@@ -66,6 +66,13 @@ impl From<Staking> for APIStaking {
             delegations: val.delegations,
             total_bond: val.total_bond,
             fees: val.fees.into(),
+            unbonding: val.unbonding,
+            slashed: val
+                .slashed
+                .into_iter()
+                .map(|(validator, rounds)| (validator, rounds.into_iter().collect()))
+                .collect(),
+            burned: val.burned,
         }
     }
 }
@@ -99,6 +106,13 @@ impl From<APIStaking> for Staking {
             delegations: val.delegations,
             total_bond: val.total_bond,
             fees: val.fees.into(),
+            unbonding: val.unbonding,
+            slashed: val
+                .slashed
+                .into_iter()
+                .map(|(validator, rounds)| (validator, rounds.into_iter().collect()))
+                .collect(),
+            burned: val.burned,
         }
     }
 }
@@ -178,3 +192,35 @@ pub fn delegate(builder: &mut ProvableBlobTx, validator: ValidatorPublicKey) ->
     )?;
     Ok(())
 }
+
+pub fn unstake(
+    builder: &mut ProvableBlobTx,
+    contract_name: ContractName,
+    amount: u128,
+) -> anyhow::Result<()> {
+    builder.add_action(
+        contract_name,
+        StakingAction::Unstake { amount },
+        None,
+        None,
+        None,
+    )?;
+    Ok(())
+}
+
+pub fn slash(
+    builder: &mut ProvableBlobTx,
+    contract_name: ContractName,
+    evidence: DoubleSignEvidence,
+) -> anyhow::Result<()> {
+    builder.add_action(
+        contract_name,
+        StakingAction::Slash {
+            evidence: Box::new(evidence),
+        },
+        None,
+        None,
+        None,
+    )?;
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use hyllar::HyllarAction;
 use sdk::{
-    utils::parse_calldata, BlobIndex, Calldata, IndexedBlobs, RunResult, StakingAction, ZkContract,
+    utils::parse_calldata, BlobIndex, BlockHeight, Calldata, ContractName, IndexedBlobs, RunResult,
+    StakingAction, ZkContract,
 };
 use sha2::{Digest, Sha256};
 use state::Staking;
@@ -15,7 +16,7 @@ impl sdk::FullStateRevert for Staking {}
 
 impl ZkContract for Staking {
     fn execute(&mut self, calldata: &Calldata) -> RunResult {
-        let (action, execution_ctx) = parse_calldata::<StakingAction>(calldata)?;
+        let (action, mut execution_ctx) = parse_calldata::<StakingAction>(calldata)?;
 
         let output = match action {
             StakingAction::Stake { amount } => {
@@ -30,6 +31,27 @@ impl ZkContract for Staking {
                 check_transfer_blob(&calldata.blobs, calldata.index + 1, amount)?;
                 self.deposit_for_fees(holder, amount)
             }
+            StakingAction::Unstake { amount } => self.unstake(
+                execution_ctx.caller.clone(),
+                amount,
+                block_height(calldata)?,
+            ),
+            StakingAction::Withdraw { amount } => {
+                // Unbonded stake is transferred back by the staking contract
+                execution_ctx.is_in_callee_blobs(
+                    &ContractName("hyllar".to_string()),
+                    HyllarAction::Transfer {
+                        recipient: execution_ctx.caller.0.clone(),
+                        amount,
+                    },
+                )?;
+                self.withdraw(
+                    execution_ctx.caller.clone(),
+                    amount,
+                    block_height(calldata)?,
+                )
+            }
+            StakingAction::Slash { evidence } => self.slash(&evidence),
         };
 
         match output {
@@ -59,10 +81,32 @@ impl ZkContract for Staking {
                 hasher.update(i.0.to_le_bytes());
             }
         }
+        for u in self.unbonding.iter() {
+            hasher.update(&u.0 .0);
+            for (release, amount) in u.1 {
+                hasher.update(release.0.to_le_bytes());
+                hasher.update(amount.to_le_bytes());
+            }
+        }
+        for s in self.slashed.iter() {
+            hasher.update(&s.0 .0);
+            for (slot, view) in s.1 {
+                hasher.update(slot.to_le_bytes());
+                hasher.update(view.to_le_bytes());
+            }
+        }
         sdk::StateCommitment(hasher.finalize().to_vec())
     }
 }
 
+fn block_height(calldata: &Calldata) -> Result<BlockHeight, String> {
+    calldata
+        .tx_ctx
+        .as_ref()
+        .map(|tx_ctx| tx_ctx.block_height)
+        .ok_or("Missing tx context".to_string())
+}
+
 fn check_transfer_blob(blobs: &IndexedBlobs, index: BlobIndex, amount: u128) -> Result<(), String> {
     let transfer_action = sdk::utils::parse_structured_blob::<HyllarAction>(blobs, &index)
         .ok_or("No transfer blob found".to_string())?;
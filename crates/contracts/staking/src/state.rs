@@ -1,8 +1,11 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use anyhow::Result;
 use borsh::{BorshDeserialize, BorshSerialize};
-use sdk::{info, BlockHeight, Identity, LaneBytesSize, LaneId, ValidatorPublicKey};
+use sdk::{
+    info, BlockHeight, DoubleSignEvidence, Identity, LaneBytesSize, LaneId, Slot,
+    ValidatorPublicKey, View,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::fees::Fees;
@@ -21,11 +24,24 @@ pub struct Staking {
 
     /// Struct to handle fees
     pub(crate) fees: Fees,
+
+    /// Unstaked amounts, with the height from which they can be withdrawn
+    pub(crate) unbonding: BTreeMap<Identity, Vec<(BlockHeight, u128)>>,
+    /// Rounds for which each validator was slashed. Slashed validators can't be bonded anymore.
+    pub(crate) slashed: BTreeMap<ValidatorPublicKey, BTreeSet<(Slot, View)>>,
+    /// Total stake burned by slashing
+    pub(crate) burned: u128,
 }
 
 /// Minimal stake necessary to be part of consensus
 pub const MIN_STAKE: u128 = 32;
 
+/// Number of blocks an unstaked amount stays slashable before it can be withdrawn
+pub const UNBONDING_PERIOD: u64 = 1000;
+
+/// Percentage of the delegated stake burned when a validator is slashed
+pub const SLASHING_PERCENT: u128 = 10;
+
 impl Staking {
     pub fn new() -> Self {
         Staking {
@@ -35,6 +51,9 @@ impl Staking {
             bonded: Vec::new(),
             total_bond: 0,
             fees: Fees::default(),
+            unbonding: BTreeMap::new(),
+            slashed: BTreeMap::new(),
+            burned: 0,
         }
     }
 
@@ -52,12 +71,18 @@ impl Staking {
     pub fn is_bonded(&self, pubkey: &ValidatorPublicKey) -> bool {
         self.bonded.iter().any(|v| v == pubkey)
     }
+    pub fn is_slashed(&self, pubkey: &ValidatorPublicKey) -> bool {
+        self.slashed.contains_key(pubkey)
+    }
 
     /// Bond a staking validator
     pub fn bond(&mut self, validator: ValidatorPublicKey) -> Result<(), String> {
         if self.is_bonded(&validator) {
             return Err("Validator already bonded".to_string());
         }
+        if self.is_slashed(&validator) {
+            return Err("Validator was slashed".to_string());
+        }
 
         info!("🔐 Bonded validator {}", validator);
         if let Some(stake) = self.get_stake(&validator) {
@@ -66,13 +91,35 @@ impl Staking {
             }
             self.bonded.push(validator);
             self.bonded.sort(); // TODO insert in order?
-            self.total_bond += stake;
+            self.total_bond = self.compute_voting_power(&self.bonded);
             Ok(())
         } else {
             Err("Validator does not have enough stake".to_string())
         }
     }
 
+    /// Unbond a validator, removing it from consensus
+    pub fn unbond(&mut self, validator: &ValidatorPublicKey) -> Result<(), String> {
+        if !self.is_bonded(validator) {
+            return Err("Validator is not bonded".to_string());
+        }
+
+        info!("🔓 Unbonded validator {}", validator);
+        self.bonded.retain(|v| v != validator);
+        self.total_bond = self.compute_voting_power(&self.bonded);
+        Ok(())
+    }
+
+    /// Bonded validators that must leave consensus:
+    /// slashed ones, and those whose stake fell below the minimum
+    pub fn validators_to_unbond(&self) -> Vec<ValidatorPublicKey> {
+        self.bonded
+            .iter()
+            .filter(|v| self.is_slashed(v) || self.get_stake(v).unwrap_or(0) < MIN_STAKE)
+            .cloned()
+            .collect()
+    }
+
     /// Compute f value
     pub fn compute_f(&self) -> u128 {
         self.total_bond().div_euclid(3)
@@ -111,6 +158,7 @@ impl Staking {
             .entry(staker)
             .and_modify(|e| *e += amount)
             .or_insert(amount);
+        self.total_bond = self.compute_voting_power(&self.bonded);
         Ok("Staked".to_string())
     }
 
@@ -129,9 +177,138 @@ impl Staking {
             .entry(validator)
             .and_modify(|e| e.push(staker.clone()))
             .or_insert_with(|| vec![staker]);
+        self.total_bond = self.compute_voting_power(&self.bonded);
         Ok("Delegated".to_string())
     }
 
+    /// Start unbonding part of the stake. It keeps being slashable, and can be withdrawn
+    /// from `height + UNBONDING_PERIOD`.
+    pub fn unstake(
+        &mut self,
+        staker: Identity,
+        amount: u128,
+        height: BlockHeight,
+    ) -> Result<String, String> {
+        let stake = self.stakes.get(&staker).copied().unwrap_or(0);
+        if amount == 0 || amount > stake {
+            return Err(format!(
+                "Cannot unstake {amount} from {staker}, staking {stake}"
+            ));
+        }
+
+        info!("📤 Unstaking {} for {}", amount, staker);
+        if amount == stake {
+            self.stakes.remove(&staker);
+        } else {
+            self.stakes.insert(staker.clone(), stake - amount);
+        }
+        self.unbonding
+            .entry(staker)
+            .or_default()
+            .push((height + UNBONDING_PERIOD, amount));
+        self.total_bond = self.compute_voting_power(&self.bonded);
+        Ok("Unstaked".to_string())
+    }
+
+    /// Amount unstaked by `staker` that can be withdrawn at `height`
+    pub fn withdrawable(&self, staker: &Identity, height: BlockHeight) -> u128 {
+        self.unbonding
+            .get(staker)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter(|(release, _)| *release <= height)
+                    .map(|(_, amount)| amount)
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Withdraw unstaked funds whose unbonding period is over
+    pub fn withdraw(
+        &mut self,
+        staker: Identity,
+        amount: u128,
+        height: BlockHeight,
+    ) -> Result<String, String> {
+        let withdrawable = self.withdrawable(&staker, height);
+        if amount == 0 || amount > withdrawable {
+            return Err(format!(
+                "Cannot withdraw {amount} for {staker}, {withdrawable} is withdrawable at height {height}"
+            ));
+        }
+        self.take_unbonded(&staker, amount, height);
+        Ok("Withdrawn".to_string())
+    }
+
+    /// Remove `amount` from the unbonding entries of `staker` released at `height`, oldest first
+    fn take_unbonded(&mut self, staker: &Identity, mut amount: u128, height: BlockHeight) {
+        info!("💸 Withdrawing {} for {}", amount, staker);
+        let Some(entries) = self.unbonding.get_mut(staker) else {
+            return;
+        };
+        for (release, unbonding) in entries.iter_mut() {
+            if *release > height {
+                continue;
+            }
+            let taken = amount.min(*unbonding);
+            *unbonding -= taken;
+            amount -= taken;
+        }
+        entries.retain(|(_, unbonding)| *unbonding > 0);
+        if entries.is_empty() {
+            self.unbonding.remove(staker);
+        }
+    }
+
+    /// Slash a validator that signed conflicting consensus messages: `SLASHING_PERCENT` of
+    /// the stake delegated to it, including the stake still unbonding, is burned.
+    /// The validator can't be bonded anymore, and is unbonded by the consensus.
+    pub fn slash(&mut self, evidence: &DoubleSignEvidence) -> Result<String, String> {
+        evidence.verify()?;
+
+        let validator = evidence.offender().clone();
+        let round = (evidence.slot(), evidence.view());
+        if self
+            .slashed
+            .get(&validator)
+            .is_some_and(|rounds| rounds.contains(&round))
+        {
+            return Err(format!(
+                "Validator {validator} already slashed for slot {} view {}",
+                round.0, round.1
+            ));
+        }
+        let delegators = self
+            .delegations
+            .get(&validator)
+            .ok_or(format!("Unknown validator {validator}"))?
+            .clone();
+
+        let mut slashed = 0;
+        for delegator in delegators {
+            if let Some(stake) = self.stakes.get_mut(&delegator) {
+                let cut = *stake * SLASHING_PERCENT / 100;
+                *stake -= cut;
+                slashed += cut;
+            }
+            for (_, unbonding) in self.unbonding.get_mut(&delegator).into_iter().flatten() {
+                let cut = *unbonding * SLASHING_PERCENT / 100;
+                *unbonding -= cut;
+                slashed += cut;
+            }
+        }
+
+        info!(
+            "⚔️ Slashed validator {} for double-signing at slot {} view {}: {} burned",
+            validator, round.0, round.1, slashed
+        );
+        self.burned += slashed;
+        self.slashed.entry(validator).or_default().insert(round);
+        self.total_bond = self.compute_voting_power(&self.bonded);
+        Ok(format!("Slashed {slashed}"))
+    }
+
     //    ----------
     //      Fees
     //    ----------
@@ -172,21 +349,33 @@ impl Staking {
         use sdk::StakingAction;
         for action in &block.staking_actions {
             match action.clone() {
-                (identity, StakingAction::Stake { amount }) => {
+                (identity, StakingAction::Stake { amount }, _) => {
                     self.stake(identity, amount)?;
                 }
-                (identity, StakingAction::Delegate { validator }) => {
+                (identity, StakingAction::Delegate { validator }, _) => {
                     self.delegate_to(identity, validator)?;
                 }
-                (_identity, StakingAction::Distribute { claim: _ }) => todo!(),
-                (_identity, StakingAction::DepositForFees { holder, amount }) => {
+                (_identity, StakingAction::Distribute { claim: _ }, _) => todo!(),
+                (_identity, StakingAction::DepositForFees { holder, amount }, _) => {
                     self.deposit_for_fees(holder, amount)?;
                 }
+                (identity, StakingAction::Unstake { amount }, height) => {
+                    self.unstake(identity, amount, height)?;
+                }
+                (identity, StakingAction::Withdraw { amount }, height) => {
+                    self.withdraw(identity, amount, height)?;
+                }
+                (_identity, StakingAction::Slash { evidence }, _) => {
+                    self.slash(&evidence)?;
+                }
             }
         }
         for validator in block.new_bounded_validators.iter() {
             self.bond(validator.clone())?;
         }
+        for validator in block.new_unbonded_validators.iter() {
+            self.unbond(validator)?;
+        }
         Ok(())
     }
 }
@@ -196,3 +385,150 @@ impl Default for Staking {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use blst::min_pk::SecretKey;
+    use sdk::{
+        ConsensusProposalHash, ConsensusTimeoutMarker, Signed, SignedConsensusMessage,
+        ValidatorSignature,
+    };
+
+    use super::*;
+
+    const DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_";
+
+    fn validator_key() -> (SecretKey, ValidatorPublicKey) {
+        let sk = SecretKey::key_gen(&[1; 32], &[]).unwrap();
+        let pubkey = ValidatorPublicKey(sk.sk_to_pk().compress().to_vec());
+        (sk, pubkey)
+    }
+
+    fn timeout(sk: &SecretKey, parent: &str) -> SignedConsensusMessage {
+        let msg = (
+            3,
+            1,
+            ConsensusProposalHash(parent.to_string()),
+            ConsensusTimeoutMarker,
+        );
+        SignedConsensusMessage::Timeout(Signed {
+            signature: ValidatorSignature {
+                signature: sk.sign(&borsh::to_vec(&msg).unwrap(), DST, &[]).into(),
+                validator: ValidatorPublicKey(sk.sk_to_pk().compress().to_vec()),
+            },
+            msg,
+        })
+    }
+
+    fn bonded_staking(validator: &ValidatorPublicKey) -> Staking {
+        let mut staking = Staking::new();
+        staking.stake("alice".into(), 100).unwrap();
+        staking
+            .delegate_to("alice".into(), validator.clone())
+            .unwrap();
+        staking.bond(validator.clone()).unwrap();
+        staking
+    }
+
+    #[test]
+    fn test_unstake_and_withdraw() {
+        let validator = ValidatorPublicKey::new_for_tests("validator");
+        let mut staking = bonded_staking(&validator);
+
+        assert!(staking
+            .unstake("alice".into(), 101, BlockHeight(10))
+            .is_err());
+        staking
+            .unstake("alice".into(), 40, BlockHeight(10))
+            .unwrap();
+        staking
+            .unstake("alice".into(), 20, BlockHeight(20))
+            .unwrap();
+        assert_eq!(staking.get_stake(&validator), Some(40));
+        assert!(staking.validators_to_unbond().is_empty());
+
+        let released = BlockHeight(10 + UNBONDING_PERIOD);
+        assert_eq!(staking.withdrawable(&"alice".into(), released - 1), 0);
+        assert_eq!(staking.withdrawable(&"alice".into(), released), 40);
+        assert!(staking
+            .withdraw("alice".into(), 41, BlockHeight(15 + UNBONDING_PERIOD))
+            .is_err());
+        staking
+            .withdraw("alice".into(), 30, BlockHeight(15 + UNBONDING_PERIOD))
+            .unwrap();
+        assert_eq!(
+            staking.withdrawable(&"alice".into(), BlockHeight(20 + UNBONDING_PERIOD)),
+            30
+        );
+
+        // Falling below the minimum stake
+        staking
+            .unstake("alice".into(), 20, BlockHeight(30))
+            .unwrap();
+        assert_eq!(staking.validators_to_unbond(), vec![validator.clone()]);
+        staking.unbond(&validator).unwrap();
+        assert!(!staking.is_bonded(&validator));
+        assert_eq!(staking.total_bond(), 0);
+        assert!(staking.unbond(&validator).is_err());
+    }
+
+    #[test]
+    fn test_quorum_after_unstake() {
+        let mut staking = Staking::new();
+        let validators: Vec<ValidatorPublicKey> = (0..4)
+            .map(|i| ValidatorPublicKey::new_for_tests(&format!("validator{i}")))
+            .collect();
+        for (i, validator) in validators.iter().enumerate() {
+            let staker = Identity::new(format!("staker{i}"));
+            staking.stake(staker.clone(), 100).unwrap();
+            staking.delegate_to(staker, validator.clone()).unwrap();
+            staking.bond(validator.clone()).unwrap();
+        }
+        assert_eq!(staking.total_bond(), 400);
+
+        for i in 0..4 {
+            staking
+                .unstake(Identity::new(format!("staker{i}")), 60, BlockHeight(10))
+                .unwrap();
+        }
+        assert!(staking.validators_to_unbond().is_empty());
+        assert_eq!(staking.total_bond(), 160);
+        // The remaining stake of all validators is still a quorum, as well as the one of 3 of them
+        assert!(staking.compute_voting_power(&validators) > 2 * staking.compute_f());
+        assert!(staking.compute_voting_power(&validators[1..]) > 2 * staking.compute_f());
+    }
+
+    #[test]
+    fn test_slash() {
+        let (sk, validator) = validator_key();
+        let mut staking = bonded_staking(&validator);
+        staking
+            .unstake("alice".into(), 50, BlockHeight(10))
+            .unwrap();
+
+        let evidence = DoubleSignEvidence {
+            first: timeout(&sk, "a"),
+            second: timeout(&sk, "b"),
+        };
+        let honest = DoubleSignEvidence {
+            first: timeout(&sk, "a"),
+            second: timeout(&sk, "a"),
+        };
+        assert!(staking.slash(&honest).is_err());
+
+        staking.slash(&evidence).unwrap();
+        assert_eq!(staking.get_stake(&validator), Some(45));
+        assert_eq!(staking.total_bond(), 45);
+        assert_eq!(
+            staking.withdrawable(&"alice".into(), BlockHeight(10 + UNBONDING_PERIOD)),
+            45
+        );
+        assert_eq!(staking.burned, 10);
+        // The same offense is only slashed once
+        assert!(staking.slash(&evidence).is_err());
+
+        assert_eq!(staking.validators_to_unbond(), vec![validator.clone()]);
+        staking.unbond(&validator).unwrap();
+        assert!(staking.bond(validator).is_err());
+    }
+}
@@ -0,0 +1,305 @@
+//! # Keystore
+//!
+//! Password-encrypted validator secret, stored as an EIP-2335-style JSON file:
+//! the secret is encrypted with AES-128-CTR, using a key derived from the password with
+//! PBKDF2-HMAC-SHA256, and a SHA-256 checksum detects wrong passwords.
+//!
+//! Unlike EIP-2335, the encrypted secret is the seed the BLS key is generated from, the same
+//! hex value as `HYLE_VALIDATOR_SECRET`, so keys can move between the two.
+
+use std::path::{Path, PathBuf};
+
+use aes::cipher::{KeyIvInit, StreamCipher};
+use anyhow::{anyhow, bail, Context, Result};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::BlstCrypto;
+
+type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
+
+/// PBKDF2 iterations for new keystores, as in the EIP-2335 test vectors
+pub const PBKDF2_ROUNDS: u32 = 262_144;
+/// Most PBKDF2 iterations a keystore may ask for, so that a crafted keystore can't stall the node
+pub const MAX_PBKDF2_ROUNDS: u32 = 16 * PBKDF2_ROUNDS;
+const KEYSTORE_VERSION: u32 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Keystore {
+    pub crypto: KeystoreCrypto,
+    pub description: String,
+    /// Hex encoded validator public key of the secret
+    pub pubkey: String,
+    pub path: String,
+    pub uuid: String,
+    pub version: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeystoreCrypto {
+    pub kdf: KeystoreModule<KdfParams>,
+    pub checksum: KeystoreModule<EmptyParams>,
+    pub cipher: KeystoreModule<CipherParams>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeystoreModule<P> {
+    pub function: String,
+    pub params: P,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub dklen: u32,
+    pub c: u32,
+    pub prf: String,
+    pub salt: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmptyParams {}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CipherParams {
+    pub iv: String,
+}
+
+impl Keystore {
+    /// Encrypts a validator secret with `password`
+    pub fn encrypt(secret: &[u8], password: &str, description: &str) -> Result<Self> {
+        Self::encrypt_with_rounds(secret, password, description, PBKDF2_ROUNDS)
+    }
+
+    fn encrypt_with_rounds(
+        secret: &[u8],
+        password: &str,
+        description: &str,
+        rounds: u32,
+    ) -> Result<Self> {
+        let pubkey = BlstCrypto::from_secret(secret)?.validator_pubkey().clone();
+
+        let mut rng = rand::rng();
+        let mut salt = [0u8; 32];
+        rng.fill(&mut salt);
+        let mut iv = [0u8; 16];
+        rng.fill(&mut iv);
+
+        let kdf = KeystoreModule {
+            function: "pbkdf2".to_string(),
+            params: KdfParams {
+                dklen: 32,
+                c: rounds,
+                prf: "hmac-sha256".to_string(),
+                salt: hex::encode(salt),
+            },
+            message: String::new(),
+        };
+        let decryption_key = derive_key(&kdf.params, password)?;
+
+        let mut encrypted = secret.to_vec();
+        apply_cipher(&decryption_key, &iv, &mut encrypted)?;
+
+        Ok(Keystore {
+            crypto: KeystoreCrypto {
+                kdf,
+                checksum: KeystoreModule {
+                    function: "sha256".to_string(),
+                    params: EmptyParams {},
+                    message: hex::encode(checksum(&decryption_key, &encrypted)),
+                },
+                cipher: KeystoreModule {
+                    function: "aes-128-ctr".to_string(),
+                    params: CipherParams {
+                        iv: hex::encode(iv),
+                    },
+                    message: hex::encode(encrypted),
+                },
+            },
+            description: description.to_string(),
+            pubkey: hex::encode(&pubkey.0),
+            path: String::new(),
+            uuid: uuid::Uuid::new_v4().to_string(),
+            version: KEYSTORE_VERSION,
+        })
+    }
+
+    /// Decrypts the validator secret, failing if the password is wrong
+    pub fn decrypt(&self, password: &str) -> Result<Vec<u8>> {
+        if self.version != KEYSTORE_VERSION {
+            bail!("Unsupported keystore version {}", self.version);
+        }
+        let crypto = &self.crypto;
+        if crypto.kdf.function != "pbkdf2" || crypto.kdf.params.prf != "hmac-sha256" {
+            bail!(
+                "Unsupported key derivation {} ({})",
+                crypto.kdf.function,
+                crypto.kdf.params.prf
+            );
+        }
+        if crypto.checksum.function != "sha256" {
+            bail!("Unsupported checksum {}", crypto.checksum.function);
+        }
+        if crypto.cipher.function != "aes-128-ctr" {
+            bail!("Unsupported cipher {}", crypto.cipher.function);
+        }
+
+        let decryption_key = derive_key(&crypto.kdf.params, password)?;
+        let mut secret = hex::decode(&crypto.cipher.message).context("Invalid cipher message")?;
+        let expected_checksum =
+            hex::decode(&crypto.checksum.message).context("Invalid checksum message")?;
+        if checksum(&decryption_key, &secret) != expected_checksum.as_slice() {
+            bail!("Invalid keystore password");
+        }
+
+        let iv = hex::decode(&crypto.cipher.params.iv).context("Invalid cipher iv")?;
+        apply_cipher(&decryption_key, &iv, &mut secret)?;
+
+        let pubkey = BlstCrypto::from_secret(&secret)?.validator_pubkey().clone();
+        if hex::encode(&pubkey.0) != self.pubkey {
+            bail!(
+                "Keystore secret does not match its public key {}",
+                self.pubkey
+            );
+        }
+        Ok(secret)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Reading keystore {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Parsing keystore {}", path.display()))
+    }
+
+    /// Writes the keystore, readable by its owner only, replacing any existing file atomically
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = options
+            .open(&tmp_path)
+            .with_context(|| format!("Creating keystore {}", tmp_path.display()))?;
+        serde_json::to_writer_pretty(&file, self)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, path)
+            .with_context(|| format!("Writing keystore {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Replaces the keystore at `path` with a new random secret, encrypted with `new_password`.
+    /// The previous keystore is kept next to it, named after its public key, and its path is returned.
+    ///
+    /// The new secret is a different validator identity: the node signs with a new public key
+    /// after restarting, which holds no stake until it is bonded again.
+    pub fn rotate(path: &Path, password: &str, new_password: &str) -> Result<PathBuf> {
+        let previous = Self::load(path)?;
+        // Only the owner of the current key may rotate it
+        previous.decrypt(password)?;
+
+        let pubkey_prefix = previous
+            .pubkey
+            .get(..16)
+            .context("Invalid keystore public key")?;
+        let archive_path = path.with_extension(format!("{pubkey_prefix}.json"));
+        if archive_path.exists() {
+            bail!("{} already exists", archive_path.display());
+        }
+        previous.save(&archive_path)?;
+
+        let keystore = Self::encrypt(
+            &BlstCrypto::generate_secret(),
+            new_password,
+            &previous.description,
+        )?;
+        keystore.save(path)?;
+        Ok(archive_path)
+    }
+}
+
+fn derive_key(params: &KdfParams, password: &str) -> Result<[u8; 32]> {
+    if params.dklen != 32 {
+        bail!("Unsupported derived key length {}", params.dklen);
+    }
+    if params.c == 0 || params.c > MAX_PBKDF2_ROUNDS {
+        bail!(
+            "Unsupported kdf iteration count {}, expected at most {MAX_PBKDF2_ROUNDS}",
+            params.c
+        );
+    }
+    let salt = hex::decode(&params.salt).context("Invalid kdf salt")?;
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, params.c, &mut key);
+    Ok(key)
+}
+
+fn checksum(decryption_key: &[u8; 32], cipher_message: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    #[allow(clippy::indexing_slicing, reason = "key is 32 bytes")]
+    hasher.update(&decryption_key[16..]);
+    hasher.update(cipher_message);
+    hasher.finalize().to_vec()
+}
+
+fn apply_cipher(decryption_key: &[u8; 32], iv: &[u8], data: &mut [u8]) -> Result<()> {
+    #[allow(clippy::indexing_slicing, reason = "key is 32 bytes")]
+    let mut cipher = Aes128Ctr::new_from_slices(&decryption_key[..16], iv)
+        .map_err(|e| anyhow!("Invalid cipher parameters: {e}"))?;
+    cipher.apply_keystream(data);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keystore_roundtrip() {
+        let secret = BlstCrypto::secret_from_name("validator");
+        let keystore = Keystore::encrypt_with_rounds(&secret, "password", "test", 16).unwrap();
+        assert_eq!(
+            keystore.pubkey,
+            hex::encode(&BlstCrypto::new("validator").unwrap().validator_pubkey().0)
+        );
+        assert_ne!(keystore.crypto.cipher.message, hex::encode(secret));
+
+        let json = serde_json::to_string(&keystore).unwrap();
+        let keystore: Keystore = serde_json::from_str(&json).unwrap();
+        assert_eq!(keystore.decrypt("password").unwrap(), secret);
+        assert!(keystore.decrypt("wrong password").is_err());
+    }
+
+    #[test]
+    fn test_keystore_bounds_kdf_rounds() {
+        let secret = BlstCrypto::secret_from_name("validator");
+        let mut keystore = Keystore::encrypt_with_rounds(&secret, "password", "test", 16).unwrap();
+        keystore.crypto.kdf.params.c = MAX_PBKDF2_ROUNDS + 1;
+        assert!(keystore.decrypt("password").is_err());
+        keystore.crypto.kdf.params.c = 0;
+        assert!(keystore.decrypt("password").is_err());
+    }
+
+    #[test]
+    fn test_keystore_rotate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("validator.json");
+        let secret = BlstCrypto::secret_from_name("validator");
+        let keystore = Keystore::encrypt_with_rounds(&secret, "old", "test", 16).unwrap();
+        keystore.save(&path).unwrap();
+
+        assert!(Keystore::rotate(&path, "wrong", "new").is_err());
+        let archive_path = Keystore::rotate(&path, "old", "new").unwrap();
+
+        assert_eq!(Keystore::load(&archive_path).unwrap(), keystore);
+        let rotated = Keystore::load(&path).unwrap();
+        assert_ne!(rotated.pubkey, keystore.pubkey);
+        assert_eq!(rotated.description, "test");
+        assert!(rotated.decrypt("new").is_ok());
+    }
+}
@@ -17,8 +17,15 @@
 //!
 //! This module load the private key seed from the environment variable `HYLE_VALIDATOR_SECRET`.
 //! The content of the variable must be a hexadecimal string.
-//! If the variable is not set but HYLE_USE_KEYRING is set to 'true', it tries to load the key from the keyring.
-//! Otherwise it generates a private key from the validator name, which is highly unsecure.
+//! If the variable is not set but HYLE_VALIDATOR_KEYSTORE is, it decrypts the key from that keystore file
+//! (see the `keystore` module), using the password in HYLE_VALIDATOR_KEYSTORE_PASSWORD or in the file at
+//! HYLE_VALIDATOR_KEYSTORE_PASSWORD_FILE.
+//! Otherwise, if HYLE_USE_KEYRING is set to 'true', it tries to load the key from the keyring.
+//! Otherwise it generates a private key from the validator name, which is highly unsecure:
+//! `new_validator` can refuse this fallback, as the node does outside of solo mode.
+//!
+//! If HYLE_REMOTE_SIGNER is set to the path of a signer's Unix socket, the key stays in a separate signer
+//! process instead (see the `remote_signer` module), and none of the above is loaded.
 //!
 //! Note: you can use tools like seahorse (<https://wiki.gnome.org/Apps/Seahorse>) to manage your keyring
 //!
@@ -30,6 +37,13 @@
 
 use std::sync::Arc;
 
+pub mod keystore;
+#[cfg(unix)]
+pub mod remote_signer;
+pub mod signer;
+
+use signer::{LocalSigner, SigningKind, ValidatorSigner};
+
 use anyhow::{anyhow, bail, Error, Result};
 use blst::min_pk::{
     AggregatePublicKey, AggregateSignature as BlstAggregateSignature, PublicKey, SecretKey,
@@ -41,7 +55,7 @@ pub use hyle_model::{
 
 #[derive(Clone)]
 pub struct BlstCrypto {
-    sk: SecretKey,
+    signer: Arc<dyn ValidatorSigner>,
     validator_pubkey: ValidatorPublicKey,
 }
 pub type SharedBlstCrypto = Arc<BlstCrypto>;
@@ -57,38 +71,65 @@ const DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_";
 pub const SIG_SIZE: usize = 48;
 
 impl BlstCrypto {
+    /// Loads the validator key, falling back to the insecure name-derived key if none is configured.
     #[cfg(not(test))]
     pub fn new(validator_name: &str) -> Result<Self> {
-        let sk = Self::load_from_env().or_else(|err| {
-            if let Ok(use_keyring) = std::env::var("HYLE_USE_KEYRING") {
-                if use_keyring == "true" {
+        Self::new_validator(validator_name, true)
+    }
+
+    /// Signs with the remote signer at `HYLE_REMOTE_SIGNER` if set.
+    /// Otherwise loads the validator key from, in order, `HYLE_VALIDATOR_SECRET`, the keystore at
+    /// `HYLE_VALIDATOR_KEYSTORE` or the keyring if `HYLE_USE_KEYRING` is 'true'.
+    /// If none is configured, the key is derived from the validator name only when
+    /// `allow_insecure_fallback` is set, and an error is returned otherwise.
+    #[cfg(not(test))]
+    pub fn new_validator(validator_name: &str, allow_insecure_fallback: bool) -> Result<Self> {
+        if let Ok(path) = std::env::var("HYLE_REMOTE_SIGNER") {
+            println!("Using remote signer {path}...");
+            #[cfg(unix)]
+            {
+                let signer = remote_signer::RemoteSigner::connect(path.into())?;
+                return Ok(Self::with_signer(Arc::new(signer)));
+            }
+            #[cfg(not(unix))]
+            bail!("The remote signer is only available on Unix systems");
+        }
+        let sk = match Self::load_from_env() {
+            Ok(sk) => sk,
+            Err(err) => {
+                if let Ok(path) = std::env::var("HYLE_VALIDATOR_KEYSTORE") {
+                    Self::load_from_keystore(std::path::Path::new(&path))?
+                } else if std::env::var("HYLE_USE_KEYRING").is_ok_and(|v| v == "true") {
                     #[cfg(feature = "keyring")]
                     {
-                        return Self::load_from_keyring(validator_name);
+                        Self::load_from_keyring(validator_name)?
                     }
                     #[cfg(not(feature = "keyring"))]
                     {
-                        return Err(anyhow!("HYLE_USE_KEYRING is set to true but the keyring feature is not enabled. Please enable it with --features keyring"));
+                        bail!("HYLE_USE_KEYRING is set to true but the keyring feature is not enabled. Please enable it with --features keyring");
                     }
+                } else if allow_insecure_fallback {
+                    println!(
+                        "---------------------- 🚨 SECURITY 🚨  ------------------------------ "
+                    );
+                    println!();
+                    println!("WARN SAFETY: Could not load secret from env: '{err}', HYLE_VALIDATOR_KEYSTORE is not set and HYLE_USE_KEYRING != true, generating secret from validator name.");
+                    println!("Note: this is fine during local development phase, but a critical issue in production");
+                    println!();
+                    println!(
+                        "---------------------- 🚨 SECURITY 🚨  ------------------------------ "
+                    );
+                    let ikm = Self::secret_from_name(validator_name);
+
+                    SecretKey::key_gen(&ikm, &[])
+                        .map_err(|e| anyhow!("Could not generate key: {:?}", e))?
+                } else {
+                    bail!("No validator secret configured: set HYLE_VALIDATOR_SECRET, HYLE_VALIDATOR_KEYSTORE (see `hyle keys generate`) or HYLE_USE_KEYRING. Refusing to derive the secret from the validator name.");
                 }
             }
-            println!("---------------------- 🚨 SECURITY 🚨  ------------------------------ ");
-            println!();
-            println!("WARN SAFETY: Could not load secret from env: '{err}' and HYLE_USE_KEYRING != true, generating secret from validator name.");
-            println!("Note: this is fine during local development phase, but a critical issue in production");
-            println!();
-            println!("---------------------- 🚨 SECURITY 🚨  ------------------------------ ");
-            let ikm = Self::secret_from_name(validator_name);
-
-            SecretKey::key_gen(&ikm, &[]).map_err(|e| anyhow!("Could not generate key: {:?}", e))
-        })?;
-
-        let validator_pubkey = as_validator_pubkey(sk.sk_to_pk());
+        };
 
-        Ok(BlstCrypto {
-            sk,
-            validator_pubkey,
-        })
+        Ok(Self::from_secret_key(sk))
     }
 
     /// Load the secret key from the environment variable `HYLE_VALIDATOR_SECRET`.
@@ -100,12 +141,31 @@ impl BlstCrypto {
             .map_err(|e| anyhow!("Could not generate key from keyring secret: {:?}", e))
     }
 
+    /// Load the secret key from an encrypted keystore. The password is read from
+    /// `HYLE_VALIDATOR_KEYSTORE_PASSWORD`, or from the file at `HYLE_VALIDATOR_KEYSTORE_PASSWORD_FILE`.
+    #[cfg(not(test))]
+    fn load_from_keystore(path: &std::path::Path) -> Result<SecretKey> {
+        println!("Loading secret key from keystore {}...", path.display());
+        let password = match std::env::var("HYLE_VALIDATOR_KEYSTORE_PASSWORD") {
+            Ok(password) => password,
+            Err(_) => {
+                let password_file = std::env::var("HYLE_VALIDATOR_KEYSTORE_PASSWORD_FILE").map_err(|_| {
+                    anyhow!("HYLE_VALIDATOR_KEYSTORE_PASSWORD or HYLE_VALIDATOR_KEYSTORE_PASSWORD_FILE must be set to unlock the keystore")
+                })?;
+                std::fs::read_to_string(password_file)?
+                    .trim_end_matches(['\r', '\n'])
+                    .to_string()
+            }
+        };
+        let secret = keystore::Keystore::load(path)?.decrypt(&password)?;
+        SecretKey::key_gen(&secret, &[])
+            .map_err(|e| anyhow!("Could not generate key from keystore secret: {:?}", e))
+    }
+
     /// Load the secret key from the keyring. If the key does not exist, a new random one is generated.
     #[cfg(not(test))]
     #[cfg(feature = "keyring")]
     fn load_from_keyring(validator_name: &str) -> Result<SecretKey> {
-        use rand::Rng;
-
         println!("Loading secret key from keyring...");
         let user = whoami::username();
         let entry = keyring::Entry::new_with_target("hyle", validator_name, &user)?;
@@ -114,8 +174,7 @@ impl BlstCrypto {
             Ok(secret) => SecretKey::key_gen(&hex::decode(secret)?, &[])
                 .map_err(|e| anyhow!("Could not generate key from keyring secret: {:?}", e))?,
             Err(keyring::Error::NoEntry) => {
-                let mut ikm = [0u8; 32];
-                rand::rng().fill(&mut ikm);
+                let ikm = Self::generate_secret();
                 entry.set_password(&hex::encode(ikm))?;
                 SecretKey::key_gen(&ikm, &[])
                     .map_err(|e| anyhow!("Could not generate new key: {:?}", e))?
@@ -126,6 +185,22 @@ impl BlstCrypto {
         Ok(sk)
     }
 
+    /// Builds the key generated from `secret`, as stored in `HYLE_VALIDATOR_SECRET`, keystores and the keyring.
+    pub fn from_secret(secret: &[u8]) -> Result<Self> {
+        let sk = SecretKey::key_gen(secret, &[])
+            .map_err(|e| anyhow!("Could not generate key: {:?}", e))?;
+        Ok(Self::from_secret_key(sk))
+    }
+
+    /// Generates a new random secret
+    pub fn generate_secret() -> [u8; 32] {
+        use rand::Rng;
+
+        let mut ikm = [0u8; 32];
+        rand::rng().fill(&mut ikm);
+        ikm
+    }
+
     #[cfg(test)]
     pub fn new(validator_name: &str) -> Result<Self> {
         // here basically secret_key <=> validator_id which is very badly secure !
@@ -133,12 +208,7 @@ impl BlstCrypto {
 
         let sk = SecretKey::key_gen(&ikm, &[])
             .map_err(|e| anyhow!("Could not generate key: {:?}", e))?;
-        let validator_pubkey = as_validator_pubkey(sk.sk_to_pk());
-
-        Ok(BlstCrypto {
-            sk,
-            validator_pubkey,
-        })
+        Ok(Self::from_secret_key(sk))
     }
 
     pub fn secret_from_name(validator_name: &str) -> [u8; 32] {
@@ -161,6 +231,23 @@ impl BlstCrypto {
         Self::new(id.as_str())
     }
 
+    fn from_secret_key(sk: SecretKey) -> Self {
+        Self::with_signer(Arc::new(LocalSigner::new(sk)))
+    }
+
+    /// Signs with `signer`, which may keep the secret key outside of the process
+    pub fn with_signer(signer: Arc<dyn ValidatorSigner>) -> Self {
+        let validator_pubkey = signer.validator_pubkey().clone();
+        BlstCrypto {
+            signer,
+            validator_pubkey,
+        }
+    }
+
+    pub fn signer(&self) -> &Arc<dyn ValidatorSigner> {
+        &self.signer
+    }
+
     pub fn validator_pubkey(&self) -> &ValidatorPublicKey {
         &self.validator_pubkey
     }
@@ -169,7 +256,20 @@ impl BlstCrypto {
     where
         T: borsh::BorshSerialize,
     {
-        let signature = self.sign_msg(&msg)?.into();
+        self.sign_with_kind(msg, SigningKind::Generic)
+    }
+
+    /// Signs a message the signer may have to check against slashing-protection rules
+    pub fn sign_with_kind<T>(
+        &self,
+        msg: T,
+        kind: SigningKind,
+    ) -> Result<Signed<T, ValidatorSignature>, Error>
+    where
+        T: borsh::BorshSerialize,
+    {
+        let encoded = borsh::to_vec(&msg)?;
+        let signature = self.signer.sign_bytes(&encoded, &kind)?;
         Ok(Signed {
             msg,
             signature: ValidatorSignature {
@@ -210,7 +310,20 @@ impl BlstCrypto {
     where
         T: borsh::BorshSerialize + Clone,
     {
-        let self_signed = self.sign(msg.clone())?;
+        self.sign_aggregate_with_kind(msg, SigningKind::Generic, aggregates)
+    }
+
+    /// Adds our own signature of a message of `kind` to the aggregate
+    pub fn sign_aggregate_with_kind<T>(
+        &self,
+        msg: T,
+        kind: SigningKind,
+        aggregates: &[&SignedByValidator<T>],
+    ) -> Result<Signed<T, AggregateSignature>, Error>
+    where
+        T: borsh::BorshSerialize + Clone,
+    {
+        let self_signed = self.sign_with_kind(msg.clone(), kind)?;
         Self::aggregate(msg, &[aggregates, &[&self_signed]].concat())
     }
 
@@ -269,18 +382,6 @@ impl BlstCrypto {
         }
     }
 
-    fn sign_msg<T>(&self, msg: &T) -> Result<BlstSignature>
-    where
-        T: borsh::BorshSerialize,
-    {
-        let encoded = borsh::to_vec(msg)?;
-        Ok(self.sign_bytes(encoded.as_slice()))
-    }
-
-    fn sign_bytes(&self, msg: &[u8]) -> BlstSignature {
-        self.sk.sign(msg, DST, &[])
-    }
-
     fn verify_bytes(msg: &[u8], sig: &BlstSignature, pk: &PublicKey) -> bool {
         let err = sig.verify(true, msg, DST, &[], pk, true);
 
@@ -343,15 +444,20 @@ mod tests {
     fn test_sign_bytes() {
         let crypto = BlstCrypto::new_random().unwrap();
         let msg = b"hello";
-        let sig = crypto.sign_bytes(msg);
-        let valid = BlstCrypto::verify_bytes(msg, &sig, &crypto.sk.sk_to_pk());
+        let sig = crypto
+            .signer
+            .sign_bytes(msg, &SigningKind::Generic)
+            .unwrap();
+        let sig = BlstSignature::uncompress(&sig.0).unwrap();
+        let pk = PublicKey::uncompress(&crypto.validator_pubkey.0).unwrap();
+        let valid = BlstCrypto::verify_bytes(msg, &sig, &pk);
         assert!(valid);
     }
 
     #[test]
     fn test_sign() {
         let crypto = BlstCrypto::new_random().unwrap();
-        let pub_key = ValidatorPublicKey(crypto.sk.sk_to_pk().to_bytes().as_slice().to_vec());
+        let pub_key = crypto.validator_pubkey.clone();
         let msg = Data::default();
         let signed = crypto.sign(&msg).unwrap();
         let valid = BlstCrypto::verify(&signed).unwrap();
@@ -362,7 +468,7 @@ mod tests {
         msg: T,
     ) -> (SignedByValidator<T>, ValidatorPublicKey) {
         let crypto = BlstCrypto::new_random().unwrap();
-        let pub_key = ValidatorPublicKey(crypto.sk.sk_to_pk().to_bytes().as_slice().to_vec());
+        let pub_key = crypto.validator_pubkey.clone();
         (crypto.sign(msg).unwrap(), crypto.validator_pubkey.clone())
     }
 
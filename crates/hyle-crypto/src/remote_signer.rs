@@ -0,0 +1,593 @@
+//! # Remote signer
+//!
+//! Keeps the validator secret key out of the node process: the node connects to a signer over
+//! a Unix socket and sends it the messages to sign. The socket lives in a directory only
+//! accessible by its owner, which is what restricts who can get messages signed: there is no
+//! network transport, as it would need its own authentication.
+//!
+//! Each request and response is a borsh-encoded `SignerRequest` / `SignerResponse`, prefixed by
+//! its length as a big-endian u32. A connection carries any number of requests, answered in order.
+//!
+//! Consensus messages are sent as their typed content (`SigningKind`): the signer builds the
+//! bytes to sign from it, works out the slot and view they commit to, and refuses to sign two
+//! different proposals, votes or timeouts for the same slot and view, according to its
+//! `SlashingProtection` record. Generic messages that decode as a consensus message are
+//! refused, so the rules can't be bypassed by sending a consensus message as generic.
+
+use std::{
+    collections::BTreeMap,
+    io::{ErrorKind, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use blst::min_pk::{PublicKey, Signature as BlstSignature};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hyle_model::{ConsensusProposalHash, Signature, Slot, ValidatorPublicKey, View};
+use tokio::runtime::RuntimeFlavor;
+use tracing::warn;
+
+use crate::{
+    signer::{SigningKind, ValidatorSigner},
+    BlstCrypto,
+};
+
+/// Maximum size of a request or response
+pub const MAX_FRAME_SIZE: u32 = 1024 * 1024;
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+/// Number of slots for which signed messages are remembered
+const KEPT_SLOTS: Slot = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum SignerRequest {
+    PublicKey,
+    Sign { msg: Vec<u8>, kind: SigningKind },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum SignerResponse {
+    PublicKey(ValidatorPublicKey),
+    Signature(Signature),
+    Refused(String),
+}
+
+pub fn write_frame<T: BorshSerialize>(writer: &mut impl Write, msg: &T) -> Result<()> {
+    let data = borsh::to_vec(msg)?;
+    let len = u32::try_from(data.len())
+        .ok()
+        .filter(|len| *len <= MAX_FRAME_SIZE)
+        .context("Signer frame too large")?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(&data)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn read_frame<T: BorshDeserialize>(reader: &mut impl Read) -> Result<T> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len);
+    if len > MAX_FRAME_SIZE {
+        bail!("Signer frame of {len} bytes is too large");
+    }
+    let mut data = vec![0u8; len as usize];
+    reader.read_exact(&mut data)?;
+    Ok(borsh::from_slice(&data)?)
+}
+
+fn connect(path: &Path) -> Result<UnixStream> {
+    let stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    Ok(stream)
+}
+
+/// Client of a remote signer. Requests are blocking, and the connection is reopened if lost.
+/// On a multi-threaded tokio runtime, they run with `block_in_place` so that the other tasks
+/// of the worker are not stalled.
+pub struct RemoteSigner {
+    path: PathBuf,
+    stream: Mutex<Option<UnixStream>>,
+    validator_pubkey: ValidatorPublicKey,
+}
+
+impl RemoteSigner {
+    /// Connects to the signer listening on the Unix socket at `path`
+    pub fn connect(path: PathBuf) -> Result<Self> {
+        let mut stream = connect(&path)
+            .with_context(|| format!("Connecting to remote signer {}", path.display()))?;
+        let validator_pubkey = match exchange(&mut stream, &SignerRequest::PublicKey)? {
+            SignerResponse::PublicKey(pubkey) => pubkey,
+            other => bail!("Unexpected remote signer response: {:?}", other),
+        };
+        Ok(RemoteSigner {
+            path,
+            stream: Mutex::new(Some(stream)),
+            validator_pubkey,
+        })
+    }
+
+    fn request(&self, request: &SignerRequest) -> Result<SignerResponse> {
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(|| self.blocking_request(request))
+            }
+            _ => self.blocking_request(request),
+        }
+    }
+
+    fn blocking_request(&self, request: &SignerRequest) -> Result<SignerResponse> {
+        let mut stream = self
+            .stream
+            .lock()
+            .map_err(|_| anyhow!("Remote signer connection poisoned"))?;
+        // Retrying is safe: signing the same message again never breaks slashing-protection rules
+        let mut last_error = None;
+        for _ in 0..2 {
+            if stream.is_none() {
+                *stream = Some(connect(&self.path)?);
+            }
+            let Some(connection) = stream.as_mut() else {
+                continue;
+            };
+            match exchange(connection, request) {
+                Ok(response) => return Ok(response),
+                Err(e) => {
+                    *stream = None;
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow!("Remote signer unreachable")))
+    }
+}
+
+fn exchange(stream: &mut UnixStream, request: &SignerRequest) -> Result<SignerResponse> {
+    write_frame(stream, request)?;
+    read_frame(stream)
+}
+
+impl ValidatorSigner for RemoteSigner {
+    fn validator_pubkey(&self) -> &ValidatorPublicKey {
+        &self.validator_pubkey
+    }
+
+    fn sign_bytes(&self, msg: &[u8], kind: &SigningKind) -> Result<Signature> {
+        let request = SignerRequest::Sign {
+            msg: msg.to_vec(),
+            kind: kind.clone(),
+        };
+        let signature = match self.request(&request)? {
+            SignerResponse::Signature(signature) => signature,
+            SignerResponse::Refused(reason) => bail!("Remote signer refused to sign: {reason}"),
+            other => bail!("Unexpected remote signer response: {:?}", other),
+        };
+
+        let sig = BlstSignature::uncompress(&signature.0)
+            .map_err(|e| anyhow!("Could not parse Signature: {:?}", e))?;
+        let pk = PublicKey::uncompress(&self.validator_pubkey.0)
+            .map_err(|e| anyhow!("Could not parse PublicKey: {:?}", e))?;
+        if !BlstCrypto::verify_bytes(msg, &sig, &pk) {
+            bail!("Remote signer returned an invalid signature");
+        }
+        Ok(signature)
+    }
+}
+
+/// Record of the signed messages subject to slashing-protection rules.
+/// When backed by a file, each new record is saved before the message is signed.
+#[derive(Debug, Default, BorshSerialize, BorshDeserialize)]
+pub struct SlashingProtection {
+    proposals: BTreeMap<(Slot, View), ConsensusProposalHash>,
+    prepare_votes: BTreeMap<(Slot, View), ConsensusProposalHash>,
+    confirm_acks: BTreeMap<(Slot, View), ConsensusProposalHash>,
+    /// Parent hash of the signed timeouts
+    timeouts: BTreeMap<(Slot, View), ConsensusProposalHash>,
+    /// Messages below this slot are no longer recorded, and refused
+    min_slot: Slot,
+    #[borsh(skip)]
+    path: Option<PathBuf>,
+}
+
+impl SlashingProtection {
+    /// Loads the record saved at `path`, or starts a new one there
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut protection = if path.exists() {
+            let data = std::fs::read(path)
+                .with_context(|| format!("Reading slashing protection {}", path.display()))?;
+            borsh::from_slice::<Self>(&data)
+                .with_context(|| format!("Parsing slashing protection {}", path.display()))?
+        } else {
+            Self::default()
+        };
+        protection.path = Some(path.to_path_buf());
+        Ok(protection)
+    }
+
+    /// Checks that signing `msg` as `kind` follows the rules, and records it
+    pub fn check(&mut self, msg: &[u8], kind: &SigningKind) -> Result<()> {
+        kind.check_message(msg)?;
+        let Some((slot, view, hash)) = kind.round() else {
+            return Ok(());
+        };
+        if slot < self.min_slot {
+            bail!("Slot {slot} is too old to sign {:?}", kind);
+        }
+        let signed = match kind {
+            SigningKind::Prepare { .. } => &mut self.proposals,
+            SigningKind::PrepareVote { .. } => &mut self.prepare_votes,
+            SigningKind::ConfirmAck { .. } => &mut self.confirm_acks,
+            SigningKind::Timeout { .. } => &mut self.timeouts,
+            SigningKind::Generic => return Ok(()),
+        };
+        match signed.get(&(slot, view)) {
+            Some(previous) if *previous == hash => Ok(()),
+            Some(previous) => bail!(
+                "Already signed {} for slot {} view {}, refusing {:?}",
+                previous,
+                slot,
+                view,
+                kind
+            ),
+            None => {
+                signed.insert((slot, view), hash);
+                let min_slot = slot.saturating_sub(KEPT_SLOTS);
+                if min_slot > self.min_slot {
+                    self.min_slot = min_slot;
+                    for signed in [
+                        &mut self.proposals,
+                        &mut self.prepare_votes,
+                        &mut self.confirm_acks,
+                        &mut self.timeouts,
+                    ] {
+                        *signed = signed.split_off(&(min_slot, 0));
+                    }
+                }
+                self.save()
+            }
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let tmp_path = path.with_extension("tmp");
+        // The record must survive a crash once the message is signed: sync the data, then
+        // the directory entry of the renamed file
+        let mut file = std::fs::File::create(&tmp_path)
+            .with_context(|| format!("Creating slashing protection {}", tmp_path.display()))?;
+        file.write_all(&borsh::to_vec(self)?)
+            .with_context(|| format!("Writing slashing protection {}", tmp_path.display()))?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, path)
+            .with_context(|| format!("Writing slashing protection {}", path.display()))?;
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        std::fs::File::open(dir)
+            .and_then(|dir| dir.sync_all())
+            .with_context(|| format!("Syncing slashing protection directory {}", dir.display()))?;
+        Ok(())
+    }
+}
+
+/// Signer side of the protocol, signing with `signer` the requests allowed by `protection`
+pub struct SignerServer {
+    signer: Arc<dyn ValidatorSigner>,
+    protection: Mutex<SlashingProtection>,
+}
+
+impl SignerServer {
+    pub fn new(signer: Arc<dyn ValidatorSigner>, protection: SlashingProtection) -> Self {
+        SignerServer {
+            signer,
+            protection: Mutex::new(protection),
+        }
+    }
+
+    pub fn handle(&self, request: SignerRequest) -> SignerResponse {
+        match request {
+            SignerRequest::PublicKey => {
+                SignerResponse::PublicKey(self.signer.validator_pubkey().clone())
+            }
+            SignerRequest::Sign { msg, kind } => {
+                // Requests are serialized, so that concurrent ones can't both pass the checks
+                let Ok(mut protection) = self.protection.lock() else {
+                    return SignerResponse::Refused("Slashing protection poisoned".to_string());
+                };
+                if let Err(e) = protection.check(&msg, &kind) {
+                    return SignerResponse::Refused(e.to_string());
+                }
+                match self.signer.sign_bytes(&msg, &kind) {
+                    Ok(signature) => SignerResponse::Signature(signature),
+                    Err(e) => SignerResponse::Refused(e.to_string()),
+                }
+            }
+        }
+    }
+
+    /// Answers the requests of a connection until it is closed
+    pub fn serve_connection(&self, mut stream: impl Read + Write) -> Result<()> {
+        loop {
+            let request = match read_frame(&mut stream) {
+                Ok(request) => request,
+                Err(e)
+                    if e.downcast_ref::<std::io::Error>()
+                        .is_some_and(|e| e.kind() == ErrorKind::UnexpectedEof) =>
+                {
+                    return Ok(());
+                }
+                Err(e) => return Err(e),
+            };
+            write_frame(&mut stream, &self.handle(request))?;
+        }
+    }
+
+    /// Listens on the Unix socket at `path`, serving each connection in its own thread.
+    /// The socket is created in a directory only accessible by its owner, so that no other user
+    /// can connect to it, even before its own permissions are set.
+    pub fn listen(self: Arc<Self>, path: &Path) -> Result<()> {
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        private_dir(dir)?;
+        // Remove the socket left by a previous run
+        if std::fs::metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        self.serve(listener)
+    }
+
+    pub fn serve(self: Arc<Self>, listener: UnixListener) -> Result<()> {
+        for stream in listener.incoming() {
+            let server = self.clone();
+            let stream = stream?;
+            std::thread::spawn(move || server.log_connection(stream));
+        }
+        Ok(())
+    }
+
+    fn log_connection(&self, stream: impl Read + Write) {
+        if let Err(e) = self.serve_connection(stream) {
+            warn!("Signer connection closed: {e:#}");
+        }
+    }
+}
+
+/// Creates `dir` with mode 0700 if it doesn't exist, and checks that only its owner can access it
+fn private_dir(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    if !dir.exists() {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)
+            .with_context(|| format!("Creating signer directory {}", dir.display()))?;
+    }
+    let mode = std::fs::metadata(dir)?.permissions().mode() & 0o777;
+    if mode & 0o077 != 0 {
+        bail!(
+            "Signer directory {} must only be accessible by its owner (mode 700), not {:o}",
+            dir.display(),
+            mode
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use hyle_model::{AggregateSignature, ConsensusProposal, ConsensusTimeoutMarker, Hashed};
+
+    use super::*;
+
+    fn hash(name: &str) -> ConsensusProposalHash {
+        ConsensusProposalHash(name.to_string())
+    }
+
+    /// A proposal for `slot`, told apart from the others of the slot by `name`
+    fn consensus_proposal(slot: Slot, name: &str) -> Box<ConsensusProposal> {
+        Box::new(ConsensusProposal {
+            slot,
+            parent_hash: hash(name),
+            ..Default::default()
+        })
+    }
+
+    /// A `Prepare` header proposing `name` at `slot` and `view`, with its signing kind
+    fn proposal(slot: Slot, view: View, name: &str) -> (Vec<u8>, SigningKind) {
+        let kind = SigningKind::Prepare {
+            proposal: consensus_proposal(slot, name),
+            // A genesis ticket
+            ticket: vec![0],
+            view,
+            timestamp: 1_700_000_000_000,
+        };
+        (kind.message().unwrap().unwrap(), kind)
+    }
+
+    fn check(protection: &mut SlashingProtection, slot: Slot, view: View, name: &str) -> bool {
+        let (msg, kind) = proposal(slot, view, name);
+        protection.check(&msg, &kind).is_ok()
+    }
+
+    #[test]
+    fn test_slashing_protection() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("slashing_protection.bin");
+        let mut protection = SlashingProtection::load(&path).unwrap();
+
+        assert!(protection
+            .check(&borsh::to_vec(&42u64).unwrap(), &SigningKind::Generic)
+            .is_ok());
+        assert!(check(&mut protection, 5, 0, "a"));
+        assert!(check(&mut protection, 5, 0, "a"));
+        assert!(!check(&mut protection, 5, 0, "b"));
+        assert!(check(&mut protection, 5, 1, "b"));
+
+        // The record survives restarts
+        let mut protection = SlashingProtection::load(&path).unwrap();
+        assert!(!check(&mut protection, 5, 0, "b"));
+        assert!(check(&mut protection, 5, 1, "b"));
+
+        // Old slots are forgotten, and refused
+        assert!(check(&mut protection, 5 + KEPT_SLOTS + 1, 0, "c"));
+        assert!(!check(&mut protection, 5, 1, "b"));
+        assert!(check(&mut protection, 6, 0, "d"));
+    }
+
+    #[test]
+    fn test_slashing_protection_votes_and_timeouts() {
+        let mut protection = SlashingProtection::default();
+
+        let vote = |name: &str| SigningKind::PrepareVote {
+            proposal: consensus_proposal(3, name),
+            view: 0,
+        };
+        let vote_msg = |name: &str| borsh::to_vec(&consensus_proposal(3, name).hashed()).unwrap();
+        assert!(protection.check(&vote_msg("a"), &vote("a")).is_ok());
+        assert!(protection.check(&vote_msg("b"), &vote("b")).is_err());
+        // The message must be the one of its kind
+        assert!(protection.check(&vote_msg("a"), &vote("c")).is_err());
+
+        let confirm_ack = SigningKind::ConfirmAck {
+            proposal: consensus_proposal(3, "b"),
+            view: 0,
+        };
+        assert!(protection.check(&vote_msg("b"), &confirm_ack).is_ok());
+
+        let timeout = |name: &str| SigningKind::Timeout {
+            slot: 3,
+            view: 0,
+            parent_hash: hash(name),
+        };
+        let timeout_msg =
+            |name: &str| borsh::to_vec(&(3u64, 0u64, hash(name), ConsensusTimeoutMarker)).unwrap();
+        assert!(protection.check(&timeout_msg("p"), &timeout("p")).is_ok());
+        assert!(protection.check(&timeout_msg("p"), &timeout("p")).is_ok());
+        assert!(protection.check(&timeout_msg("q"), &timeout("q")).is_err());
+    }
+
+    #[test]
+    fn test_consensus_messages_are_refused_as_generic() {
+        let mut protection = SlashingProtection::default();
+        let (header, kind) = proposal(5, 0, "a");
+        assert!(protection.check(&header, &kind).is_ok());
+
+        // A second proposal for the same slot and view can't be signed as a generic message
+        let (other_header, _) = proposal(5, 0, "b");
+        assert!(protection
+            .check(&other_header, &SigningKind::Generic)
+            .is_err());
+        // Whatever the slot it claims to be for, or its ticket
+        let (_, kind) = proposal(1 << 50, 0, "b");
+        assert!(protection
+            .check(&kind.message().unwrap().unwrap(), &SigningKind::Generic)
+            .is_err());
+        let commit_ticket = SigningKind::Prepare {
+            proposal: consensus_proposal(5, "b"),
+            ticket: [
+                vec![1],
+                borsh::to_vec(&AggregateSignature::default()).unwrap(),
+            ]
+            .concat(),
+            view: 0,
+            timestamp: 1,
+        };
+        assert!(protection
+            .check(
+                &commit_ticket.message().unwrap().unwrap(),
+                &SigningKind::Generic
+            )
+            .is_err());
+
+        // Nor votes and timeouts
+        let vote = borsh::to_vec(&hash("any")).unwrap();
+        assert!(protection.check(&vote, &SigningKind::Generic).is_err());
+        let timeout = borsh::to_vec(&(1u64, 0u64, hash("p"), ConsensusTimeoutMarker)).unwrap();
+        assert!(protection.check(&timeout, &SigningKind::Generic).is_err());
+
+        // Other headers are generic
+        let header = borsh::to_vec(&(1_700_000_000_000u128, hash("a").0.into_bytes())).unwrap();
+        assert!(protection.check(&header, &SigningKind::Generic).is_ok());
+    }
+
+    fn start_server(dir: &Path) -> (BlstCrypto, PathBuf) {
+        let crypto = BlstCrypto::new("remote").unwrap();
+        let server = Arc::new(SignerServer::new(
+            crypto.signer().clone(),
+            SlashingProtection::default(),
+        ));
+        let path = dir.join("signer").join("signer.sock");
+        private_dir(path.parent().unwrap()).unwrap();
+        let listener = UnixListener::bind(&path).unwrap();
+        std::thread::spawn(move || server.serve(listener));
+        (crypto, path)
+    }
+
+    #[test]
+    fn test_remote_signer() {
+        let dir = tempfile::tempdir().unwrap();
+        let (crypto, path) = start_server(dir.path());
+
+        let remote =
+            BlstCrypto::with_signer(Arc::new(RemoteSigner::connect(path.clone()).unwrap()));
+        assert_eq!(remote.validator_pubkey(), crypto.validator_pubkey());
+
+        let signed = remote.sign(42u64).unwrap();
+        assert_eq!(signed, crypto.sign(42u64).unwrap());
+        assert!(BlstCrypto::verify(&signed).unwrap());
+
+        let (p1, p1_kind) = proposal(1, 0, "p1");
+        let (p2, p2_kind) = proposal(1, 0, "p2");
+        let (p2_view1, p2_view1_kind) = proposal(1, 1, "p2");
+        let signer = remote.signer();
+        assert!(signer.sign_bytes(&p1, &p1_kind).is_ok());
+        assert!(signer.sign_bytes(&p1, &p1_kind).is_ok());
+        assert!(signer.sign_bytes(&p2, &p2_kind).is_err());
+        assert!(signer.sign_bytes(&p2, &SigningKind::Generic).is_err());
+        assert!(signer.sign_bytes(&p2_view1, &p2_view1_kind).is_ok());
+
+        // Clients share the signer's protection
+        let other = RemoteSigner::connect(path).unwrap();
+        assert!(other.sign_bytes(&p2, &p2_kind).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_remote_signer_in_runtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let (crypto, path) = start_server(dir.path());
+
+        let remote = BlstCrypto::with_signer(Arc::new(RemoteSigner::connect(path).unwrap()));
+        let signed = remote.sign(42u64).unwrap();
+        assert_eq!(signed, crypto.sign(42u64).unwrap());
+    }
+
+    #[test]
+    fn test_signer_directory() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let private = dir.path().join("a").join("b");
+        private_dir(&private).unwrap();
+        assert_eq!(
+            std::fs::metadata(&private).unwrap().permissions().mode() & 0o777,
+            0o700
+        );
+
+        // Other users could connect to a socket in a shared directory
+        std::fs::set_permissions(&private, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(private_dir(&private).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! # Signer
+//!
+//! Signing is abstracted behind the `ValidatorSigner` trait, so that the validator secret key
+//! can be kept in-process (`LocalSigner`) or outside of the node (`remote_signer::RemoteSigner`).
+
+use anyhow::{anyhow, bail, Result};
+use blst::min_pk::SecretKey;
+use borsh::{BorshDeserialize, BorshSerialize};
+use hyle_model::{
+    prepare_header_bytes, AggregateSignature, ConsensusProposal, ConsensusProposalHash,
+    ConsensusTimeoutMarker, Hashed, Signature, Slot, ValidatorPublicKey, View,
+};
+
+use crate::{as_validator_pubkey, DST};
+
+/// What a signed message is, for the signer to apply slashing-protection rules.
+///
+/// Consensus messages are described by their content, from which the signer works out the
+/// slot, view and hash they commit to and the exact bytes to sign, rather than trusting a
+/// description sent along arbitrary bytes.
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum SigningKind {
+    /// Any message that is not a consensus one
+    Generic,
+    /// The header of the Prepare message proposing `proposal` at `view`: at most one proposal
+    /// can be signed for each slot and view
+    Prepare {
+        proposal: Box<ConsensusProposal>,
+        /// Borsh encoding of the ticket sent along the proposal
+        ticket: Vec<u8>,
+        view: View,
+        /// Timestamp of the message header
+        timestamp: u128,
+    },
+    /// The vote for `proposal` at `view`: at most one proposal can be voted for each slot and view
+    PrepareVote {
+        proposal: Box<ConsensusProposal>,
+        view: View,
+    },
+    /// The commit vote for `proposal` at `view`: at most one for each slot and view
+    ConfirmAck {
+        proposal: Box<ConsensusProposal>,
+        view: View,
+    },
+    /// The timeout of a slot and view: at most one parent can be signed for each
+    Timeout {
+        slot: Slot,
+        view: View,
+        parent_hash: ConsensusProposalHash,
+    },
+}
+
+impl SigningKind {
+    /// The slot, view and hash a message of this kind commits to, if it has slashing-protection rules
+    pub fn round(&self) -> Option<(Slot, View, ConsensusProposalHash)> {
+        match self {
+            SigningKind::Generic => None,
+            SigningKind::Prepare { proposal, view, .. }
+            | SigningKind::PrepareVote { proposal, view }
+            | SigningKind::ConfirmAck { proposal, view } => {
+                Some((proposal.slot, *view, proposal.hashed()))
+            }
+            SigningKind::Timeout {
+                slot,
+                view,
+                parent_hash,
+            } => Some((*slot, *view, parent_hash.clone())),
+        }
+    }
+
+    /// The bytes a consensus message of this kind signs, None for generic messages
+    pub fn message(&self) -> Result<Option<Vec<u8>>> {
+        let msg = match self {
+            SigningKind::Generic => return Ok(None),
+            SigningKind::Prepare {
+                proposal,
+                ticket,
+                view,
+                timestamp,
+            } => {
+                prepare_header_bytes(proposal, ticket, *view, *timestamp).map_err(|e| anyhow!(e))?
+            }
+            // Votes sign `(proposal_hash, marker)` and markers encode to nothing
+            SigningKind::PrepareVote { proposal, .. }
+            | SigningKind::ConfirmAck { proposal, .. } => borsh::to_vec(&proposal.hashed())?,
+            SigningKind::Timeout {
+                slot,
+                view,
+                parent_hash,
+            } => borsh::to_vec(&(slot, view, parent_hash, ConsensusTimeoutMarker))?,
+        };
+        Ok(Some(msg))
+    }
+
+    /// Checks that `msg` is the message this kind describes, so that a message can't be signed
+    /// under another kind to escape its rules: consensus messages must be the exact bytes built
+    /// from their kind, and generic messages must not decode as a consensus message.
+    pub fn check_message(&self, msg: &[u8]) -> Result<()> {
+        match self.message()? {
+            None if is_consensus_message(msg) => {
+                bail!("Refusing to sign a consensus message without its signing kind")
+            }
+            Some(expected) if expected != msg => {
+                bail!("Message does not match its signing kind {:?}", self)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Same encoding as the consensus `Ticket`, whose quorum certificates encode as their aggregate
+/// signature, so that Prepare headers are recognized without depending on the consensus types
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+enum TicketEncoding {
+    Genesis,
+    CommitQC(AggregateSignature),
+    TimeoutQC(AggregateSignature, TimeoutCertificateKindEncoding),
+    ForcedCommitQc,
+}
+
+/// Same encoding as the consensus `TCKind`
+#[derive(BorshDeserialize)]
+#[allow(dead_code)]
+enum TimeoutCertificateKindEncoding {
+    NilProposal,
+    PrepareQC((AggregateSignature, ConsensusProposal)),
+}
+
+/// Whether `msg` decodes as a consensus message with slashing-protection rules: a vote on a
+/// proposal hash, a timeout, or the header of a Prepare message
+pub fn is_consensus_message(msg: &[u8]) -> bool {
+    borsh::from_slice::<ConsensusProposalHash>(msg).is_ok()
+        || borsh::from_slice::<(Slot, View, ConsensusProposalHash, ConsensusTimeoutMarker)>(msg)
+            .is_ok()
+        || borsh::from_slice::<(u128, Vec<u8>)>(msg).is_ok_and(|(_, data)| {
+            borsh::from_slice::<(ConsensusProposalHash, TicketEncoding, View)>(&data).is_ok()
+        })
+}
+
+pub trait ValidatorSigner: Send + Sync {
+    fn validator_pubkey(&self) -> &ValidatorPublicKey;
+
+    /// Signs `msg`, the borsh encoding of the signed data, which `kind` describes.
+    fn sign_bytes(&self, msg: &[u8], kind: &SigningKind) -> Result<Signature>;
+}
+
+/// Signer holding the secret key in-process
+pub struct LocalSigner {
+    sk: SecretKey,
+    validator_pubkey: ValidatorPublicKey,
+}
+
+impl LocalSigner {
+    pub fn new(sk: SecretKey) -> Self {
+        let validator_pubkey = as_validator_pubkey(sk.sk_to_pk());
+        LocalSigner {
+            sk,
+            validator_pubkey,
+        }
+    }
+}
+
+impl ValidatorSigner for LocalSigner {
+    fn validator_pubkey(&self) -> &ValidatorPublicKey {
+        &self.validator_pubkey
+    }
+
+    fn sign_bytes(&self, msg: &[u8], _kind: &SigningKind) -> Result<Signature> {
+        Ok(self.sk.sign(msg, DST, &[]).into())
+    }
+}
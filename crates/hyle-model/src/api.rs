@@ -1,14 +1,16 @@
 use std::collections::BTreeMap;
 
+use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use strum::IntoDiscriminant;
 use utoipa::ToSchema;
 
 use crate::{
-    utils::TimestampMs, BlockHash, BlockHeight, ConsensusProposalHash, ContractName,
-    DataProposalHash, Identity, LaneBytesSize, LaneId, ProgramId, StateCommitment, TimeoutWindow,
-    Transaction, TransactionKind, TxHash, ValidatorPublicKey, Verifier,
+    utils::TimestampMs, BlobIndex, BlockHash, BlockHeight, ConsensusProposalHash, ContractName,
+    DataProposalHash, Identity, LaneBytesSize, LaneId, NameServiceConfig, PendingProgramIdUpdate,
+    ProgramId, StateCommitment, TimeoutWindow, Transaction, TransactionKind, TxHash,
+    ValidatorPublicKey, Verifier,
 };
 
 #[derive(Clone, Serialize, Deserialize, Debug, ToSchema)]
@@ -61,6 +63,13 @@ pub struct APIStaking {
 
     /// Struct to handle fees
     pub fees: APIFees,
+
+    /// Unstaked amounts, with the height from which they can be withdrawn
+    pub unbonding: BTreeMap<Identity, Vec<(BlockHeight, u128)>>,
+    /// Rounds (slot, view) for which each validator was slashed
+    pub slashed: BTreeMap<ValidatorPublicKey, Vec<(u64, u64)>>,
+    /// Total stake burned by slashing
+    pub burned: u128,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone, PartialEq, Eq)]
@@ -75,6 +84,21 @@ pub struct APIFees {
     pub balances: BTreeMap<ValidatorPublicKey, APIFeesBalance>,
 }
 
+/// Conflicting consensus messages signed by a validator
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, PartialEq, Eq)]
+pub struct APIEquivocation {
+    pub validator: ValidatorPublicKey,
+    pub slot: u64,
+    pub view: u64,
+    /// Kind of the conflicting messages: Prepare, Timeout, PrepareVote or ConfirmAck
+    pub kind: String,
+    /// Whether the evidence can be submitted to the staking contract for slashing
+    pub slashable: bool,
+    /// Hex-encoded borsh of the evidence: a DoubleSignEvidence when slashable,
+    /// the two conflicting votes otherwise
+    pub evidence: String,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct APIBlock {
     // Struct for the blocks table
@@ -129,6 +153,9 @@ impl From<TransactionKind> for TransactionTypeDb {
     }
 }
 
+/// Response header of the paginated indexer listings holding the cursor of the next page
+pub const NEXT_CURSOR_HEADER: &str = "x-next-cursor";
+
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone, PartialEq, Eq)]
 pub struct APITransaction {
     // Struct for the transactions table
@@ -206,6 +233,38 @@ pub struct APIContract {
     pub total_tx: u64,    // Total number of transactions associated with the contract
     pub unsettled_tx: u64, // Total number of unsettled transactions
     pub earliest_unsettled: Option<BlockHeight>, // Earliest unsettled transaction block height
+    #[serde(default)]
+    pub pending_program_id: Option<PendingProgramIdUpdate>, // Scheduled program id change
+    #[serde(default)]
+    pub min_program_id_update_delay: Option<BlockHeight>, // Minimum notice of program id changes, in blocks
+    #[serde(default)]
+    pub owner: Option<Identity>, // Identity managing the contract through the hyle TLD
+    #[serde(default)]
+    pub name_service: Option<NameServiceConfig>, // Lease terms, for a TLD in name service mode
+    #[serde(default)]
+    pub expiry_height: Option<BlockHeight>, // End of the lease, for a leased name
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct APIIdentity {
+    pub identity: String,                       // Identity
+    pub total_tx: u64,   // Total number of blob transactions sent by the identity
+    pub settled_tx: u64, // Number of those transactions that settled successfully
+    pub contracts: u64,  // Number of contracts the identity interacted with
+    pub last_block_height: Option<BlockHeight>, // Block height of the latest transaction
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct APIIdentityContract {
+    pub contract_name: String, // Contract the identity sent blobs to
+    pub total_tx: u64,         // Number of transactions of the identity with blobs for the contract
+    pub last_block_height: Option<BlockHeight>, // Block height of the latest of those transactions
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct APIIdentityBalance {
+    pub contract_name: ContractName, // Token contract
+    pub balance: u128,               // Balance of the identity in the token
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -277,6 +336,9 @@ fn test_deserialize_api_node_contract() {
         state: StateCommitment(vec![1, 2, 3]),
         verifier: Verifier("verifier1".to_string()),
         timeout_window: TimeoutWindow::Timeout(BlockHeight(32)),
+        owner: None,
+        name_service: None,
+        expiry_height: None,
     })
     .unwrap();
     let old_contract: APINodeContract = serde_json::from_value(old_json).unwrap();
@@ -305,6 +367,24 @@ fn test_deserialize_api_node_contract() {
     assert_eq!(new_contract.timeout_window, Some(123));
 }
 
+/// State of a contract from `block_height` until the next entry of its history
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct APIContractHistoryEntry {
+    pub block_height: BlockHeight, // Block height where the contract changed
+    pub state_commitment: StateCommitment,
+    pub program_id: ProgramId,
+    pub verifier: Verifier,
+    pub timeout_window: Option<u64>, // Timeout window for the contract
+    #[serde(default)]
+    pub owner: Option<Identity>, // Owner of the contract
+    #[serde(default)]
+    pub name_service: Option<NameServiceConfig>, // Lease terms, for a TLD in name service mode
+    #[serde(default)]
+    pub expiry_height: Option<BlockHeight>, // End of the lease, for a leased name
+    #[serde(default)]
+    pub deleted: bool, // The contract was deleted at this height, the other fields are its last state
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct APIContractState {
     // Struct for the contract_state table
@@ -325,3 +405,83 @@ pub struct APIBlob {
     pub proof_outputs: Vec<serde_json::Value>, // outputs of proofs
     pub verified: bool,        // Verification status
 }
+
+/// Report of a blob transaction simulated against the current node state, without mutating it
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+pub struct APITxSimulation {
+    pub tx_hash: TxHash,
+    /// Height of the node state the transaction was simulated against
+    pub block_height: BlockHeight,
+    /// Whether the node would sequence the transaction
+    pub accepted: bool,
+    /// Error returned by the identity validation, if any
+    pub identity_error: Option<String>,
+    /// Error of the static checks on the blobs of the 'hyle' contract, if any
+    pub hyle_blobs_error: Option<String>,
+    /// Contracts targeted by the transaction that are not registered
+    pub unknown_contracts: Vec<ContractName>,
+    /// Timeout window of the transaction in blocks, None if it never times out
+    pub timeout_window: Option<u64>,
+    /// Settlement outcome of the blobs handled by the hyle TLD, up to the first failure
+    pub hyle_tld_blobs: Vec<APIHyleTldBlobSimulation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq, Eq)]
+pub struct APIHyleTldBlobSimulation {
+    pub blob_index: BlobIndex,
+    /// Reason the blob would fail to settle, if any
+    pub error: Option<String>,
+}
+
+/// Reason for the mempool refusing a transaction submitted through the REST or TCP API
+#[derive(
+    Debug, Clone, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize, ToSchema,
+)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum MempoolAdmissionError {
+    /// The identity already has too many transactions waiting for dissemination
+    IdentityQuotaExceeded {
+        identity: Identity,
+        max_pending_txs: u32,
+    },
+    /// The contract already received too many bytes in the current slot
+    ContractQuotaExceeded {
+        contract_name: ContractName,
+        max_bytes_per_slot: u64,
+    },
+    /// The mempool holds too many bytes waiting for dissemination
+    MempoolFull { max_pending_bytes: u64 },
+    /// The client submitted too many transactions in the current rate-limit window
+    RateLimited { max_txs: u32, window_ms: u128 },
+}
+
+impl std::fmt::Display for MempoolAdmissionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MempoolAdmissionError::IdentityQuotaExceeded {
+                identity,
+                max_pending_txs,
+            } => write!(
+                f,
+                "Identity {identity} already has {max_pending_txs} pending transactions"
+            ),
+            MempoolAdmissionError::ContractQuotaExceeded {
+                contract_name,
+                max_bytes_per_slot,
+            } => write!(
+                f,
+                "Contract {contract_name} exceeded its quota of {max_bytes_per_slot} bytes for this slot"
+            ),
+            MempoolAdmissionError::MempoolFull { max_pending_bytes } => write!(
+                f,
+                "Mempool is full ({max_pending_bytes} pending bytes)"
+            ),
+            MempoolAdmissionError::RateLimited { max_txs, window_ms } => write!(
+                f,
+                "Exceeded the rate limit of {max_txs} txs per {window_ms}ms"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MempoolAdmissionError {}
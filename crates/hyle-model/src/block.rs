@@ -1,4 +1,7 @@
-use std::{cmp::Ordering, collections::BTreeMap};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+};
 
 use anyhow::Context;
 use anyhow::Result;
@@ -27,13 +30,30 @@ pub struct Block {
     pub blob_proof_outputs: Vec<HandledBlobProofOutput>,
     pub verified_blobs: Vec<(TxHash, BlobIndex, Option<usize>)>,
     pub new_bounded_validators: Vec<ValidatorPublicKey>,
-    pub staking_actions: Vec<(Identity, StakingAction)>,
+    pub new_unbonded_validators: Vec<ValidatorPublicKey>,
+    /// Staking actions settled in this block, with the height of their transaction,
+    /// which is the one the staking contract executed them at
+    pub staking_actions: Vec<(Identity, StakingAction, BlockHeight)>,
     pub registered_contracts:
         BTreeMap<ContractName, (TxHash, RegisterContractEffect, Option<Vec<u8>>)>,
     pub deleted_contracts: BTreeMap<ContractName, TxHash>,
     pub updated_states: BTreeMap<ContractName, StateCommitment>,
     pub updated_program_ids: BTreeMap<ContractName, ProgramId>,
     pub updated_timeout_windows: BTreeMap<ContractName, TimeoutWindow>,
+    /// Program id changes scheduled in this block, applied in a later one
+    pub scheduled_program_ids: BTreeMap<ContractName, PendingProgramIdUpdate>,
+    /// Contracts whose pending program id change was cancelled in this block
+    pub cancelled_program_ids: BTreeSet<ContractName>,
+    /// Minimum notice, in blocks, of the program id changes of contracts, set or raised in this block
+    pub updated_min_program_id_delays: BTreeMap<ContractName, BlockHeight>,
+    /// Owners of the contracts registered or transferred in this block
+    pub updated_owners: BTreeMap<ContractName, Identity>,
+    /// TLDs put in name service mode or reconfigured in this block
+    pub updated_name_services: BTreeMap<ContractName, NameServiceConfig>,
+    /// New expiry heights of the names leased or renewed in this block
+    pub updated_expiries: BTreeMap<ContractName, BlockHeight>,
+    /// Leased names that expired at this block and were removed
+    pub expired_contracts: BTreeSet<ContractName>,
     pub transactions_events: BTreeMap<TxHash, Vec<TransactionStateEvent>>,
 }
 
@@ -69,6 +89,36 @@ impl Block {
             chain_id: HYLE_TESTNET_CHAIN_ID, // TODO: make it configurable
         })
     }
+
+    /// State transitions of the transactions in this block
+    pub fn tx_state_events(&self) -> impl Iterator<Item = TxStateEvent> + '_ {
+        self.transactions_events
+            .iter()
+            .flat_map(move |(tx_hash, events)| {
+                events.iter().map(move |event| TxStateEvent {
+                    tx_hash: tx_hash.clone(),
+                    block_hash: self.hash.clone(),
+                    block_height: self.block_height,
+                    event: event.clone(),
+                })
+            })
+    }
+
+    /// Contracts registered, deleted, expired or updated in this block
+    pub fn modified_contracts(&self) -> BTreeSet<&ContractName> {
+        self.registered_contracts
+            .keys()
+            .chain(self.deleted_contracts.keys())
+            .chain(self.expired_contracts.iter())
+            .chain(self.updated_states.keys())
+            .chain(self.updated_program_ids.keys())
+            .chain(self.updated_timeout_windows.keys())
+            .chain(self.updated_min_program_id_delays.keys())
+            .chain(self.updated_owners.keys())
+            .chain(self.updated_name_services.keys())
+            .chain(self.updated_expiries.keys())
+            .collect()
+    }
 }
 
 impl Ord for Block {
@@ -544,6 +544,7 @@ impl Hashed<ProofDataHash> for ProofData {
 pub enum OnchainEffect {
     RegisterContract(RegisterContractEffect),
     DeleteContract(ContractName),
+    LeaseContract(LeaseContractEffect),
 }
 
 /// This struct has to be the zkvm committed output. It will be used by
@@ -879,6 +880,136 @@ impl ContractAction for UpdateContractProgramIdAction {
     }
 }
 
+/// Time-locked program id change: `Schedule` sets the program id `activation_delay` blocks after
+/// the one the action settles in, `Cancel` drops the pending change before it activates.
+/// `RequireMinDelay` commits the contract to schedule its program id changes at least
+/// `min_delay` blocks ahead, which disables `UpdateContractProgramIdAction` for it. The minimum
+/// can only be raised, so users can rely on the notice.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub enum ProgramIdUpdateSchedule {
+    Schedule {
+        program_id: ProgramId,
+        activation_delay: BlockHeight,
+    },
+    Cancel,
+    RequireMinDelay {
+        min_delay: BlockHeight,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct ScheduleContractProgramIdUpdateAction {
+    pub contract_name: ContractName,
+    pub schedule: ProgramIdUpdateSchedule,
+}
+
+impl ContractAction for ScheduleContractProgramIdUpdateAction {
+    fn as_blob(
+        &self,
+        contract_name: ContractName,
+        caller: Option<BlobIndex>,
+        callees: Option<Vec<BlobIndex>>,
+    ) -> Blob {
+        Blob {
+            contract_name,
+            data: BlobData::from(StructuredBlobData {
+                caller,
+                callees,
+                parameters: self.clone(),
+            }),
+        }
+    }
+}
+
+/// Hands a contract over to a new owner. The action fails if `current_owner` is not the owner
+/// anymore when it settles.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct TransferContractOwnershipAction {
+    pub contract_name: ContractName,
+    pub new_owner: Identity,
+    pub current_owner: Identity,
+}
+
+impl ContractAction for TransferContractOwnershipAction {
+    fn as_blob(
+        &self,
+        contract_name: ContractName,
+        caller: Option<BlobIndex>,
+        callees: Option<Vec<BlobIndex>>,
+    ) -> Blob {
+        Blob {
+            contract_name,
+            data: BlobData::from(StructuredBlobData {
+                caller,
+                callees,
+                parameters: self.clone(),
+            }),
+        }
+    }
+}
+
+/// Turns a TLD into a name service: names registered under it must then be leased,
+/// and leases are paid for with transfers of `fee_token` to `fee_recipient`.
+/// Sending it again updates the terms of future leases.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct ConfigureNameServiceAction {
+    pub tld: ContractName,
+    pub fee_token: ContractName,
+    pub fee_recipient: Identity,
+    pub fee_per_block: u128,
+    pub max_period: BlockHeight,
+}
+
+impl ConfigureNameServiceAction {
+    pub fn config(&self) -> NameServiceConfig {
+        NameServiceConfig {
+            fee_token: self.fee_token.clone(),
+            fee_recipient: self.fee_recipient.clone(),
+            fee_per_block: self.fee_per_block,
+            max_period: self.max_period,
+        }
+    }
+}
+
+impl ContractAction for ConfigureNameServiceAction {
+    fn as_blob(
+        &self,
+        contract_name: ContractName,
+        caller: Option<BlobIndex>,
+        callees: Option<Vec<BlobIndex>>,
+    ) -> Blob {
+        Blob {
+            contract_name,
+            data: BlobData::from(StructuredBlobData {
+                caller,
+                callees,
+                parameters: self.clone(),
+            }),
+        }
+    }
+}
+
+/// Lease terms of a TLD in name service mode
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "full", derive(utoipa::ToSchema))]
+pub struct NameServiceConfig {
+    /// Hyllar contract the fees are paid with
+    pub fee_token: ContractName,
+    pub fee_recipient: Identity,
+    /// Price of one block of lease
+    pub fee_per_block: u128,
+    /// Longest a name can be leased for, counted from the current block
+    pub max_period: BlockHeight,
+}
+
+/// Program id change scheduled for a contract
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+#[cfg_attr(feature = "full", derive(utoipa::ToSchema))]
+pub struct PendingProgramIdUpdate {
+    pub program_id: ProgramId,
+    pub activation_height: BlockHeight,
+}
+
 #[derive(
     Debug, Serialize, Deserialize, Default, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize,
 )]
@@ -933,6 +1064,7 @@ impl ContractAction for DeleteContractAction {
 
 /// Used by the Hyli node to recognize contract registration.
 /// Simply output this struct in your HyleOutput registered_contracts.
+/// Under a TLD in name service mode, it must be followed by a LeaseContractEffect for the same name.
 /// See uuid-tld for examples.
 #[derive(
     Debug, Serialize, Deserialize, Default, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize,
@@ -953,6 +1085,19 @@ pub struct RegisterContractEffect {
     pub timeout_window: Option<TimeoutWindow>,
 }
 
+/// Used by TLDs in name service mode to lease a name for `period` blocks.
+/// Output it after the RegisterContractEffect of a new name, or alone to renew an existing one:
+/// the lease is then extended from its current expiry.
+/// The transaction must pay the lease fee, see `NameServiceConfig`.
+#[derive(
+    Debug, Serialize, Deserialize, Default, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize,
+)]
+#[cfg_attr(feature = "full", derive(utoipa::ToSchema))]
+pub struct LeaseContractEffect {
+    pub contract_name: ContractName,
+    pub period: BlockHeight,
+}
+
 impl From<RegisterContractAction> for RegisterContractEffect {
     fn from(action: RegisterContractAction) -> Self {
         RegisterContractEffect {
@@ -20,8 +20,10 @@ pub mod api;
 
 mod contract;
 mod staking;
+mod token;
 pub use contract::*;
 pub use staking::*;
+pub use token::*;
 
 pub const HASH_DISPLAY_SIZE: usize = 3;
 
@@ -102,6 +102,40 @@ pub struct ConsensusProposal {
     pub timestamp: TimestampMs,
 }
 
+/// Marker of the votes committing a proposal: commit quorum certificates
+/// sign `(ConsensusProposalHash, ConfirmAckMarker)`.
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    BorshSerialize,
+    BorshDeserialize,
+    PartialEq,
+    Eq,
+    Hash,
+    Ord,
+    PartialOrd,
+)]
+pub struct ConfirmAckMarker;
+
+/// Marker of the timeout votes for a slot and view,
+/// signing `(Slot, View, parent ConsensusProposalHash, ConsensusTimeoutMarker)`.
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    BorshSerialize,
+    BorshDeserialize,
+    PartialEq,
+    Eq,
+    Hash,
+    Ord,
+    PartialOrd,
+)]
+pub struct ConsensusTimeoutMarker;
+
 /// This is the hash of the proposal, signed by validators
 /// Any consensus-critical data should be hashed here.
 impl Hashed<ConsensusProposalHash> for ConsensusProposal {
@@ -123,6 +157,10 @@ impl Hashed<ConsensusProposalHash> for ConsensusProposal {
                 hasher.update(&lane_id.0 .0);
                 hasher.update(cumul_size.0.to_le_bytes())
             }
+            ConsensusStakingAction::Unbond { validator } => {
+                hasher.update(b"unbond");
+                hasher.update(&validator.0)
+            }
         });
         hasher.update(self.timestamp.0.to_le_bytes());
         hasher.update(self.parent_hash.0.as_bytes());
@@ -182,6 +220,10 @@ pub enum ConsensusStakingAction {
         lane_id: LaneId,
         cumul_size: LaneBytesSize,
     },
+
+    /// Removing a bonded validator from the consensus, once its stake fell below
+    /// the minimum or it was slashed
+    Unbond { validator: ValidatorPublicKey },
 }
 
 impl From<SignedByValidator<ValidatorCandidacy>> for ConsensusStakingAction {
@@ -286,5 +328,14 @@ mod tests {
         assert_ne!(a.hashed(), b.hashed());
         b.parent_hash = ConsensusProposalHash("different".to_string());
         assert_eq!(a.hashed(), b.hashed());
+
+        // Unbonding a validator doesn't hash as bonding it
+        let validator = ValidatorPublicKey(vec![1, 2, 3]);
+        a.staking_actions = vec![ConsensusStakingAction::Unbond {
+            validator: validator.clone(),
+        }];
+        assert_ne!(a.hashed(), b.hashed());
+        b.staking_actions = vec![ConsensusStakingAction::Unbond { validator }];
+        assert_eq!(a.hashed(), b.hashed());
     }
 }
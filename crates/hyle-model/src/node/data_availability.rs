@@ -21,6 +21,20 @@ pub struct Contract {
     pub state: StateCommitment,
     pub verifier: Verifier,
     pub timeout_window: TimeoutWindow,
+    /// Identity allowed to manage the contract through the hyle TLD, besides Hyli itself.
+    /// None for contracts registered by another TLD, or before owners were recorded.
+    #[serde(default)]
+    pub owner: Option<Identity>,
+    /// Lease terms, when the contract is a TLD in name service mode
+    #[serde(default)]
+    pub name_service: Option<NameServiceConfig>,
+    /// Block height at which the contract is removed, for names leased from a name service
+    #[serde(default)]
+    pub expiry_height: Option<BlockHeight>,
+    /// Minimum notice, in blocks, of program id changes. When set, the program id can only
+    /// change through an update scheduled at least this many blocks ahead.
+    #[serde(default)]
+    pub min_program_id_update_delay: Option<BlockHeight>,
 }
 
 #[derive(
@@ -42,6 +56,9 @@ pub struct UnsettledBlobTransaction {
     pub tx_context: TxContext,
     pub blobs_hash: BlobsHashes,
     pub blobs: BTreeMap<BlobIndex, UnsettledBlobMetadata>,
+    /// Priority fee declared by the transaction
+    #[serde(default)]
+    pub fee: u128,
 }
 
 #[derive(
@@ -129,6 +146,10 @@ impl Hashed<HyleOutputHash> for HyleOutput {
         self.onchain_effects.iter().for_each(|c| match c {
             OnchainEffect::RegisterContract(c) => hasher.update(contract::Hashed::hashed(c).0),
             OnchainEffect::DeleteContract(cn) => hasher.update(cn.0.as_bytes()),
+            OnchainEffect::LeaseContract(lease) => {
+                hasher.update(lease.contract_name.0.as_bytes());
+                hasher.update(lease.period.0.to_le_bytes());
+            }
         });
         hasher.update(&self.program_outputs);
         HyleOutputHash(hasher.finalize().to_vec())
@@ -154,6 +175,30 @@ pub enum TransactionStateEvent {
     DroppedAsDuplicate,
 }
 
+impl TransactionStateEvent {
+    /// Whether no more events will follow for the transaction
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            TransactionStateEvent::Settled
+                | TransactionStateEvent::SettledAsFailed
+                | TransactionStateEvent::TimedOut
+                | TransactionStateEvent::DroppedAsDuplicate
+        )
+    }
+}
+
+/// A state transition of a transaction, in the block where it happened
+#[derive(
+    Debug, Clone, Serialize, Deserialize, ToSchema, BorshSerialize, BorshDeserialize, Eq, PartialEq,
+)]
+pub struct TxStateEvent {
+    pub tx_hash: TxHash,
+    pub block_hash: ConsensusProposalHash,
+    pub block_height: BlockHeight,
+    pub event: TransactionStateEvent,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, BorshSerialize, BorshDeserialize)]
 pub enum NodeStateEvent {
     NewBlock(Box<Block>),
@@ -0,0 +1,253 @@
+use blst::min_pk::{PublicKey, Signature as BlstSignature};
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// Domain separation tag of validator signatures, as in hyle-crypto
+const DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_";
+
+/// Bytes signed by the header of the Prepare message proposing `proposal` at `view`:
+/// the borsh encoding of (proposal hash, ticket, view), itself wrapped in the header with its
+/// timestamp. `ticket` is the borsh encoding of the ticket sent along the proposal.
+pub fn prepare_header_bytes(
+    proposal: &ConsensusProposal,
+    ticket: &[u8],
+    view: View,
+    timestamp: u128,
+) -> Result<Vec<u8>, String> {
+    let mut signed_data = borsh::to_vec(&proposal.hashed()).map_err(|e| e.to_string())?;
+    signed_data.extend_from_slice(ticket);
+    signed_data.extend_from_slice(&view.to_le_bytes());
+    borsh::to_vec(&(timestamp, signed_data)).map_err(|e| e.to_string())
+}
+
+/// A consensus message signed by a validator for a given slot and view.
+///
+/// Votes on a proposal hash don't carry the view they were cast in, and a validator can
+/// honestly vote for different proposals of a slot across views, so only messages signing
+/// their slot and view can prove double-signing.
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize, PartialEq, Eq)]
+pub enum SignedConsensusMessage {
+    /// A leader proposal, signed through the header of its Prepare message
+    Prepare {
+        proposal: Box<ConsensusProposal>,
+        /// Borsh encoding of the ticket sent along the proposal
+        ticket: Vec<u8>,
+        view: View,
+        /// Timestamp of the message header
+        timestamp: u128,
+        signature: ValidatorSignature,
+    },
+    /// A timeout vote for a slot and view without a proposal to commit
+    Timeout(SignedByValidator<(Slot, View, ConsensusProposalHash, ConsensusTimeoutMarker)>),
+}
+
+impl SignedConsensusMessage {
+    pub fn validator(&self) -> &ValidatorPublicKey {
+        match self {
+            SignedConsensusMessage::Prepare { signature, .. } => &signature.validator,
+            SignedConsensusMessage::Timeout(signed) => &signed.signature.validator,
+        }
+    }
+
+    pub fn slot(&self) -> Slot {
+        match self {
+            SignedConsensusMessage::Prepare { proposal, .. } => proposal.slot,
+            SignedConsensusMessage::Timeout(signed) => signed.msg.0,
+        }
+    }
+
+    pub fn view(&self) -> View {
+        match self {
+            SignedConsensusMessage::Prepare { view, .. } => *view,
+            SignedConsensusMessage::Timeout(signed) => signed.msg.1,
+        }
+    }
+
+    /// The hash the validator committed to for the slot and view: the proposal for a Prepare,
+    /// its parent for a timeout.
+    pub fn signed_hash(&self) -> ConsensusProposalHash {
+        match self {
+            SignedConsensusMessage::Prepare { proposal, .. } => proposal.hashed(),
+            SignedConsensusMessage::Timeout(signed) => signed.msg.2.clone(),
+        }
+    }
+
+    /// The bytes the validator signed
+    fn signed_bytes(&self) -> Result<Vec<u8>, String> {
+        match self {
+            SignedConsensusMessage::Prepare {
+                proposal,
+                ticket,
+                view,
+                timestamp,
+                ..
+            } => prepare_header_bytes(proposal, ticket, *view, *timestamp),
+            SignedConsensusMessage::Timeout(signed) => {
+                borsh::to_vec(&signed.msg).map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    fn signature(&self) -> &ValidatorSignature {
+        match self {
+            SignedConsensusMessage::Prepare { signature, .. } => signature,
+            SignedConsensusMessage::Timeout(signed) => &signed.signature,
+        }
+    }
+
+    /// Checks that the message was signed by its validator
+    pub fn verify_signature(&self) -> Result<(), String> {
+        let signature = self.signature();
+        let pk = PublicKey::uncompress(&signature.validator.0)
+            .map_err(|e| format!("Could not parse PublicKey: {e:?}"))?;
+        let sig = BlstSignature::uncompress(&signature.signature.0)
+            .map_err(|e| format!("Could not parse Signature: {e:?}"))?;
+        match sig.verify(true, &self.signed_bytes()?, DST, &[], &pk, true) {
+            blst::BLST_ERROR::BLST_SUCCESS => Ok(()),
+            err => Err(format!("Invalid signature: {err:?}")),
+        }
+    }
+}
+
+/// Proof that a validator signed two conflicting consensus messages:
+/// two messages of the same kind for the same slot and view, committing to different hashes.
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize, PartialEq, Eq)]
+pub struct DoubleSignEvidence {
+    pub first: SignedConsensusMessage,
+    pub second: SignedConsensusMessage,
+}
+
+impl DoubleSignEvidence {
+    pub fn offender(&self) -> &ValidatorPublicKey {
+        self.first.validator()
+    }
+
+    pub fn slot(&self) -> Slot {
+        self.first.slot()
+    }
+
+    pub fn view(&self) -> View {
+        self.first.view()
+    }
+
+    /// Checks that both messages are signed by the same validator and conflict
+    pub fn verify(&self) -> Result<(), String> {
+        if self.first.validator() != self.second.validator() {
+            return Err("Messages are signed by different validators".to_string());
+        }
+        if std::mem::discriminant(&self.first) != std::mem::discriminant(&self.second) {
+            return Err("Messages are of different kinds".to_string());
+        }
+        if self.first.slot() != self.second.slot() || self.first.view() != self.second.view() {
+            return Err(format!(
+                "Messages are for different rounds: slot {} view {}, and slot {} view {}",
+                self.first.slot(),
+                self.first.view(),
+                self.second.slot(),
+                self.second.view()
+            ));
+        }
+        if self.first.signed_hash() == self.second.signed_hash() {
+            return Err("Messages don't conflict".to_string());
+        }
+        self.first.verify_signature()?;
+        self.second.verify_signature()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blst::min_pk::SecretKey;
+
+    use super::*;
+
+    fn sign(sk: &SecretKey, msg: &[u8]) -> ValidatorSignature {
+        ValidatorSignature {
+            signature: sk.sign(msg, DST, &[]).into(),
+            validator: ValidatorPublicKey(sk.sk_to_pk().compress().to_vec()),
+        }
+    }
+
+    fn timeout(sk: &SecretKey, slot: Slot, view: View, parent: &str) -> SignedConsensusMessage {
+        let msg = (
+            slot,
+            view,
+            ConsensusProposalHash(parent.to_string()),
+            ConsensusTimeoutMarker,
+        );
+        SignedConsensusMessage::Timeout(Signed {
+            signature: sign(sk, &borsh::to_vec(&msg).unwrap()),
+            msg,
+        })
+    }
+
+    fn prepare(sk: &SecretKey, slot: Slot, view: View, timestamp: u64) -> SignedConsensusMessage {
+        let proposal = ConsensusProposal {
+            slot,
+            timestamp: utils::TimestampMs(timestamp as u128),
+            ..Default::default()
+        };
+        let ticket = vec![1, 2, 3];
+        let mut signed_data = borsh::to_vec(&proposal.hashed()).unwrap();
+        signed_data.extend_from_slice(&ticket);
+        signed_data.extend_from_slice(&view.to_le_bytes());
+        SignedConsensusMessage::Prepare {
+            proposal: Box::new(proposal),
+            ticket,
+            view,
+            timestamp: 42,
+            signature: sign(sk, &borsh::to_vec(&(42u128, signed_data)).unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_double_sign_evidence() {
+        let sk = SecretKey::key_gen(&[1; 32], &[]).unwrap();
+        let other = SecretKey::key_gen(&[2; 32], &[]).unwrap();
+
+        let evidence = DoubleSignEvidence {
+            first: prepare(&sk, 3, 1, 100),
+            second: prepare(&sk, 3, 1, 200),
+        };
+        assert_eq!(evidence.verify(), Ok(()));
+        assert_eq!(evidence.slot(), 3);
+        assert_eq!(evidence.view(), 1);
+
+        let evidence = DoubleSignEvidence {
+            first: timeout(&sk, 3, 1, "a"),
+            second: timeout(&sk, 3, 1, "b"),
+        };
+        assert_eq!(evidence.verify(), Ok(()));
+
+        // Honest messages
+        for (first, second) in [
+            (prepare(&sk, 3, 1, 100), prepare(&sk, 3, 1, 100)),
+            (prepare(&sk, 3, 1, 100), prepare(&sk, 3, 2, 200)),
+            (prepare(&sk, 3, 1, 100), prepare(&sk, 4, 1, 200)),
+            (timeout(&sk, 3, 1, "a"), timeout(&sk, 3, 2, "b")),
+            (prepare(&sk, 3, 1, 100), timeout(&sk, 3, 1, "a")),
+        ] {
+            assert!(DoubleSignEvidence { first, second }.verify().is_err());
+        }
+
+        // Different validators
+        let evidence = DoubleSignEvidence {
+            first: timeout(&sk, 3, 1, "a"),
+            second: timeout(&other, 3, 1, "b"),
+        };
+        assert!(evidence.verify().is_err());
+
+        // Forged signature
+        let SignedConsensusMessage::Timeout(mut forged) = timeout(&other, 3, 1, "b") else {
+            unreachable!()
+        };
+        forged.signature.validator = evidence.offender().clone();
+        let evidence = DoubleSignEvidence {
+            first: timeout(&sk, 3, 1, "a"),
+            second: SignedConsensusMessage::Timeout(forged),
+        };
+        assert!(evidence.verify().is_err());
+    }
+}
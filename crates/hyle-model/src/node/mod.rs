@@ -1,9 +1,11 @@
 mod consensus;
 mod crypto;
 mod data_availability;
+mod evidence;
 mod mempool;
 
 pub use consensus::*;
 pub use crypto::*;
 pub use data_availability::*;
+pub use evidence::*;
 pub use mempool::*;
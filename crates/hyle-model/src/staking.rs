@@ -29,6 +29,20 @@ pub enum StakingAction {
         holder: ValidatorPublicKey,
         amount: u128,
     },
+
+    /// Starts unbonding part of the stake, which can be withdrawn after the unbonding period
+    Unstake {
+        amount: u128,
+    },
+    /// Withdraws unbonded stake, transferred back by a hyllar callee blob
+    Withdraw {
+        amount: u128,
+    },
+    /// Slashes the stake delegated to a validator that signed conflicting consensus messages
+    #[cfg(feature = "full")]
+    Slash {
+        evidence: Box<DoubleSignEvidence>,
+    },
 }
 
 impl ContractAction for StakingAction {
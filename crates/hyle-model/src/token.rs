@@ -0,0 +1,52 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// Standard ERC-20 like interface of fungible token contracts.
+///
+/// The node reads fee payments from blobs encoded this way, whatever the token contract: a
+/// token used to pay fees must encode its calls with the same variants in the same order.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub enum FungibleTokenAction {
+    TotalSupply,
+    BalanceOf {
+        account: String,
+    },
+    /// Moves `amount` tokens from the identity of the transaction to `recipient`
+    Transfer {
+        recipient: String,
+        amount: u128,
+    },
+    TransferFrom {
+        owner: String,
+        recipient: String,
+        amount: u128,
+    },
+    Approve {
+        spender: String,
+        amount: u128,
+    },
+    Allowance {
+        owner: String,
+        spender: String,
+    },
+}
+
+impl ContractAction for FungibleTokenAction {
+    fn as_blob(
+        &self,
+        contract_name: ContractName,
+        caller: Option<BlobIndex>,
+        callees: Option<Vec<BlobIndex>>,
+    ) -> Blob {
+        Blob {
+            contract_name,
+            data: BlobData::from(StructuredBlobData {
+                caller,
+                callees,
+                parameters: self.clone(),
+            }),
+        }
+    }
+}
@@ -13,14 +13,77 @@ use utoipa::{
 
 use crate::{api::APIRegisterContract, *};
 
-#[derive(
-    Debug, Serialize, Deserialize, Default, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize,
-)]
+/// First transaction version whose blob transactions carry a priority fee.
+/// Earlier versions are encoded with the original layout, so transactions sent by older
+/// clients and those stored in lanes and blocks before fees existed still decode.
+pub const TRANSACTION_VERSION_WITH_FEE: u32 = 2;
+
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq, Eq)]
 pub struct Transaction {
     pub version: u32,
     pub transaction_data: TransactionData,
 }
 
+impl Transaction {
+    /// Version the transaction is encoded with: fees can only be encoded from
+    /// `TRANSACTION_VERSION_WITH_FEE` on.
+    fn encoded_version(&self) -> u32 {
+        match &self.transaction_data {
+            TransactionData::Blob(tx) if tx.fee > 0 => {
+                self.version.max(TRANSACTION_VERSION_WITH_FEE)
+            }
+            _ => self.version,
+        }
+    }
+}
+
+impl BorshSerialize for Transaction {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let version = self.encoded_version();
+        version.serialize(writer)?;
+        match &self.transaction_data {
+            TransactionData::Blob(tx) if version < TRANSACTION_VERSION_WITH_FEE => {
+                0u8.serialize(writer)?;
+                tx.identity.serialize(writer)?;
+                tx.blobs.serialize(writer)
+            }
+            data => data.serialize(writer),
+        }
+    }
+}
+
+impl BorshDeserialize for Transaction {
+    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+        let version = u32::deserialize_reader(reader)?;
+        if version >= TRANSACTION_VERSION_WITH_FEE {
+            return Ok(Transaction {
+                version,
+                transaction_data: TransactionData::deserialize_reader(reader)?,
+            });
+        }
+        let transaction_data = match u8::deserialize_reader(reader)? {
+            0 => TransactionData::Blob(BlobTransaction::new(
+                Identity::deserialize_reader(reader)?,
+                Vec::<Blob>::deserialize_reader(reader)?,
+            )),
+            1 => TransactionData::Proof(ProofTransaction::deserialize_reader(reader)?),
+            2 => TransactionData::VerifiedProof(VerifiedProofTransaction::deserialize_reader(
+                reader,
+            )?),
+            kind => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Unknown transaction kind {kind}"),
+                ))
+            }
+        };
+        Ok(Transaction {
+            version,
+            transaction_data,
+        })
+    }
+}
+
 impl Transaction {
     pub fn metadata(&self, parent_data_proposal_hash: DataProposalHash) -> TransactionMetadata {
         TransactionMetadata {
@@ -138,8 +201,12 @@ impl std::fmt::Debug for ProofTransaction {
 
 impl Transaction {
     pub fn wrap(data: TransactionData) -> Self {
+        let version = match &data {
+            TransactionData::Blob(tx) if tx.fee > 0 => TRANSACTION_VERSION_WITH_FEE,
+            _ => 1,
+        };
         Transaction {
-            version: 1,
+            version,
             transaction_data: data,
         }
     }
@@ -207,6 +274,11 @@ impl Hashed<TxHash> for VerifiedProofTransaction {
 pub struct BlobTransaction {
     pub identity: Identity,
     pub blobs: Vec<Blob>,
+    /// Priority fee paid to the validator disseminating the transaction, deposited for it by
+    /// staking `DepositForFees` blobs. The transaction fails to settle if they don't cover it.
+    /// Only encoded in transactions from `TRANSACTION_VERSION_WITH_FEE` on.
+    #[serde(default)]
+    pub fee: u128,
     // FIXME: add a nonce or something to prevent BlobTransaction to share the same hash
     #[borsh(skip)]
     #[serde(skip_serializing, skip_deserializing)]
@@ -221,10 +293,18 @@ impl BlobTransaction {
         BlobTransaction {
             identity: identity.into(),
             blobs,
+            fee: 0,
             hash_cache: RwLock::new(None),
             blobshash_cache: RwLock::new(None),
         }
     }
+
+    /// Sets the priority fee of the transaction
+    pub fn with_fee(mut self, fee: u128) -> Self {
+        self.fee = fee;
+        self.hash_cache = RwLock::new(None);
+        self
+    }
 }
 
 // Custom implem to skip the cached fields
@@ -233,6 +313,7 @@ impl std::fmt::Debug for BlobTransaction {
         f.debug_struct("BlobTransaction")
             .field("identity", &self.identity)
             .field("blobs", &self.blobs)
+            .field("fee", &self.fee)
             .finish()
     }
 }
@@ -243,6 +324,7 @@ impl PartialSchema for BlobTransaction {
             ObjectBuilder::new()
                 .property("identity", Identity::schema())
                 .property("blobs", ArrayBuilder::new().items(Blob::schema()).build())
+                .property("fee", u128::schema())
                 .required("identity")
                 .required("blobs")
                 .build(),
@@ -257,6 +339,7 @@ impl Clone for BlobTransaction {
         BlobTransaction {
             identity: self.identity.clone(),
             blobs: self.blobs.clone(),
+            fee: self.fee,
             hash_cache: RwLock::new(self.hash_cache.read().unwrap().clone()),
             blobshash_cache: RwLock::new(self.blobshash_cache.read().unwrap().clone()),
         }
@@ -265,7 +348,7 @@ impl Clone for BlobTransaction {
 
 impl PartialEq for BlobTransaction {
     fn eq(&self, other: &Self) -> bool {
-        self.identity == other.identity && self.blobs == other.blobs
+        self.identity == other.identity && self.blobs == other.blobs && self.fee == other.fee
     }
 }
 
@@ -287,6 +370,10 @@ impl Hashed<TxHash> for BlobTransaction {
         for blob in self.blobs.iter() {
             hasher.update(blob.hashed().0);
         }
+        // Transactions without fee keep the hash they had before fees existed
+        if self.fee > 0 {
+            hasher.update(self.fee.to_le_bytes());
+        }
         let hash_bytes = hasher.finalize();
         let tx_hash = TxHash(hex::encode(hash_bytes));
         *self.hash_cache.write().unwrap() = Some(tx_hash.clone());
@@ -417,3 +504,54 @@ impl std::fmt::Display for BlobsHashes {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `Transaction::from(BlobTransaction::new("a@b", [blob "b" [1, 2]]))`, encoded before
+    /// transactions had a fee
+    const PRE_FEE_BLOB_TX: [u8; 27] = [
+        1, 0, 0, 0, // version
+        0, // TransactionData::Blob
+        3, 0, 0, 0, b'a', b'@', b'b', // identity
+        1, 0, 0, 0, // blobs
+        1, 0, 0, 0, b'b', // contract name
+        2, 0, 0, 0, 1, 2, // data
+    ];
+
+    fn blob_tx() -> BlobTransaction {
+        BlobTransaction::new(
+            "a@b",
+            vec![Blob {
+                contract_name: "b".into(),
+                data: BlobData(vec![1, 2]),
+            }],
+        )
+    }
+
+    #[test]
+    fn test_decode_pre_fee_transaction() {
+        let tx: Transaction = borsh::from_slice(&PRE_FEE_BLOB_TX).unwrap();
+        assert_eq!(tx, Transaction::from(blob_tx()));
+        // Transactions without fee are still encoded the same, and keep their hash
+        assert_eq!(borsh::to_vec(&tx).unwrap(), PRE_FEE_BLOB_TX);
+    }
+
+    #[test]
+    fn test_transaction_fee_roundtrip() {
+        let tx: Transaction = blob_tx().with_fee(42).into();
+        assert_eq!(tx.version, TRANSACTION_VERSION_WITH_FEE);
+        let decoded: Transaction = borsh::from_slice(&borsh::to_vec(&tx).unwrap()).unwrap();
+        assert_eq!(decoded, tx);
+        assert_ne!(decoded.hashed(), Transaction::from(blob_tx()).hashed());
+
+        // A fee set on a version 1 transaction is not dropped
+        let tx = Transaction {
+            version: 1,
+            transaction_data: TransactionData::Blob(blob_tx().with_fee(42)),
+        };
+        let decoded: Transaction = borsh::from_slice(&borsh::to_vec(&tx).unwrap()).unwrap();
+        assert_eq!(decoded.transaction_data, tx.transaction_data);
+    }
+}
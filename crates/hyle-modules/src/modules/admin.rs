@@ -1,9 +1,14 @@
 //! Public API for interacting with the node.
 
 use crate::{
-    bus::{metrics::BusMetrics, BusClientSender, SharedMessageBus},
+    bus::{
+        command_response::{CmdRespClient, Query},
+        metrics::BusMetrics,
+        BusClientSender, SharedMessageBus,
+    },
     log_error, module_bus_client, module_handle_messages,
     modules::{signal::ShutdownModule, Module},
+    node_state::{module::QueryNodeStateSnapshot, snapshot::NodeStateSnapshot},
 };
 use anyhow::{Context, Result};
 pub use axum::Router;
@@ -29,6 +34,7 @@ use super::{
 module_bus_client! {
     struct AdminBusClient {
         sender(signal::PersistModule),
+        sender(Query<QueryNodeStateSnapshot, NodeStateSnapshot>),
     }
 }
 
@@ -61,6 +67,7 @@ impl Module for AdminApi {
         let app = ctx.router.merge(
             Router::new()
                 .route("/v1/admin/persist", post(persist))
+                .route("/v1/admin/snapshot", post(export_snapshot))
                 .with_state(RouterState {
                     bus: AdminBusClient::new_from_bus(bus.new_handle()).await,
                 }),
@@ -91,6 +98,46 @@ pub async fn persist(State(mut state): State<RouterState>) -> Result<impl IntoRe
     Ok(())
 }
 
+/// Returns a snapshot of the node state, in the file format a node can be started from.
+/// Its height and hash are also sent in the `x-snapshot-height` and `x-snapshot-hash` headers.
+pub async fn export_snapshot(
+    State(mut state): State<RouterState>,
+) -> Result<impl IntoResponse, AppError> {
+    tracing::info!("Exporting node state snapshot");
+    let snapshot = state
+        .bus
+        .shutdown_aware_request::<()>(QueryNodeStateSnapshot {})
+        .await
+        .context("Exporting node state snapshot")?;
+    let mut bytes = vec![];
+    let hash = snapshot
+        .write(&mut bytes)
+        .context("Serializing node state snapshot")?;
+    info!(
+        "📸 Exported node state snapshot at height {} (hash {})",
+        snapshot.height(),
+        hash.0
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"node_state_{}.snapshot\"",
+                    snapshot.height()
+                ),
+            ),
+            (
+                header::HeaderName::from_static("x-snapshot-height"),
+                snapshot.height().0.to_string(),
+            ),
+            (header::HeaderName::from_static("x-snapshot-hash"), hash.0),
+        ],
+        bytes,
+    ))
+}
+
 pub struct RouterState {
     bus: AdminBusClient,
 }
@@ -158,6 +205,10 @@ impl Clone for AdminBusClient {
         AdminBusClient::new(
             Pick::<BusMetrics>::get(self).clone(),
             Pick::<tokio::sync::broadcast::Sender<PersistModule>>::get(self).clone(),
+            Pick::<
+                tokio::sync::broadcast::Sender<Query<QueryNodeStateSnapshot, NodeStateSnapshot>>,
+            >::get(self)
+            .clone(),
             Pick::<tokio::sync::broadcast::Receiver<ShutdownModule>>::get(self).resubscribe(),
             Pick::<tokio::sync::broadcast::Receiver<PersistModule>>::get(self).resubscribe(),
         )
@@ -1,38 +1,146 @@
+use std::collections::HashMap;
+
 use anyhow::Result;
 use sdk::{
     api::{APIBlock, APITransaction, TransactionTypeDb},
-    NodeStateEvent, TransactionData,
+    hyle_model_utils::TimestampMs,
+    BlockHeight, ConsensusProposalHash, ContractName, Hashed, Identity, LaneId, NodeStateEvent,
+    SignedBlock, Transaction, TransactionData, TxHash, TxId, TxStateEvent,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::mpsc,
+    task::{AbortHandle, JoinSet},
 };
-use serde::Serialize;
+use tracing::{debug, info, warn};
 
 use crate::{
     bus::{BusClientSender, SharedMessageBus},
-    modules::websocket::WsTopicMessage,
+    modules::websocket::{
+        PeerAddress, WsInMessage, WsPeerDisconnected, WsPeerMessage, WsTopicMessage,
+    },
+    utils::{
+        da_codec::{DataAvailabilityClient, DataAvailabilityEvent, DataAvailabilityRequest},
+        tx_watchers::{TxWatchers, WatchUpdate},
+    },
 };
-use crate::{module_bus_client, module_handle_messages, modules::Module};
+use crate::{log_error, module_bus_client, module_handle_messages, modules::Module};
 
 #[derive(Debug, Clone, Serialize)]
 pub enum WebsocketOutEvent {
     NodeStateEvent(NodeStateEvent),
     NewBlock(APIBlock),
     NewTx(APITransaction),
+    /// A state transition of a watched transaction
+    TxEvent(TxStateEvent),
+    /// The watch of the transaction was refused, or expired before it reached a final state
+    WatchEnded {
+        tx_hash: TxHash,
+        reason: String,
+    },
+}
+
+/// Messages clients send on the websocket, wrapped as `{"Message": ...}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum WebsocketInMessage {
+    /// Replaces the subscription of the connection
+    Subscribe(WebsocketSubscription),
+    Unsubscribe,
+    /// Streams the state transitions of the transaction until it settles,
+    /// fails, times out or is dropped
+    WatchTx(TxHash),
+}
+
+/// Transactions a connection is interested in.
+/// A transaction matches if it has one of the hashes, is sent by one of the identities,
+/// or targets one of the contracts. An empty subscription matches every transaction.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebsocketSubscription {
+    pub contracts: Vec<ContractName>,
+    pub identities: Vec<Identity>,
+    pub tx_hashes: Vec<TxHash>,
+    /// Replays the matching transactions from this height, read from DA storage,
+    /// before streaming the new ones
+    pub from_height: Option<BlockHeight>,
+}
+
+impl WebsocketSubscription {
+    pub fn matches(&self, tx_hash: &TxHash, tx: &Transaction) -> bool {
+        if self.contracts.is_empty() && self.identities.is_empty() && self.tx_hashes.is_empty() {
+            return true;
+        }
+        if self.tx_hashes.contains(tx_hash) {
+            return true;
+        }
+        match &tx.transaction_data {
+            TransactionData::Blob(blob_tx) => {
+                self.identities.contains(&blob_tx.identity)
+                    || blob_tx
+                        .blobs
+                        .iter()
+                        .any(|blob| self.contracts.contains(&blob.contract_name))
+            }
+            TransactionData::Proof(proof_tx) => self.contracts.contains(&proof_tx.contract_name),
+            TransactionData::VerifiedProof(proof_tx) => {
+                self.contracts.contains(&proof_tx.contract_name)
+            }
+        }
+    }
 }
 
 module_bus_client! {
 #[derive(Debug)]
 pub struct NodeWebsocketConnectorBusClient {
     sender(WsTopicMessage<WebsocketOutEvent>),
+    sender(WsPeerMessage<WebsocketOutEvent>),
     receiver(NodeStateEvent),
+    receiver(WsInMessage<WebsocketInMessage>),
+    receiver(WsPeerDisconnected),
 }
 }
 
+module_bus_client! {
+#[derive(Debug)]
+struct SubscriptionBusClient {
+    sender(WsPeerMessage<WebsocketOutEvent>),
+}
+}
+
+/// Transactions of a block streamed live that match a subscription
+#[derive(Debug)]
+struct LiveBlock {
+    height: BlockHeight,
+    events: Vec<WebsocketOutEvent>,
+}
+
+/// Each subscription is served by its own task, so that a slow connection
+/// only holds back its own backfill.
+struct PeerSubscription {
+    filter: WebsocketSubscription,
+    /// Live blocks waiting to be sent by the task, dropped when the connection is too far behind
+    live: mpsc::Sender<LiveBlock>,
+    task: AbortHandle,
+}
+
 pub struct NodeWebsocketConnector {
     bus: NodeWebsocketConnectorBusClient,
+    shared_bus: SharedMessageBus,
     events: Vec<String>,
+    da_address: String,
+    buffer_size: usize,
+    subscriptions: HashMap<PeerAddress, PeerSubscription>,
+    /// Connections waiting for the state transitions of transactions
+    watched_txs: TxWatchers<PeerAddress>,
+    subscription_tasks: JoinSet<Result<()>>,
 }
 
 pub struct NodeWebsocketConnectorCtx {
     pub events: Vec<String>,
+    /// DA server the backfills are read from
+    pub da_address: String,
+    /// Number of live blocks buffered per subscription while it is backfilled
+    pub buffer_size: usize,
 }
 
 impl Module for NodeWebsocketConnector {
@@ -41,7 +149,13 @@ impl Module for NodeWebsocketConnector {
     async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
         Ok(Self {
             bus: NodeWebsocketConnectorBusClient::new_from_bus(bus.new_handle()).await,
+            shared_bus: bus.new_handle(),
             events: ctx.events,
+            da_address: ctx.da_address,
+            buffer_size: ctx.buffer_size.max(1),
+            subscriptions: HashMap::new(),
+            watched_txs: TxWatchers::default(),
+            subscription_tasks: JoinSet::new(),
         })
     }
 
@@ -51,6 +165,18 @@ impl Module for NodeWebsocketConnector {
             listen<NodeStateEvent> msg => {
                 self.handle_node_state_event(msg)?;
             },
+            listen<WsInMessage<WebsocketInMessage>> msg => {
+                self.handle_ws_message(msg);
+            },
+            listen<WsPeerDisconnected> msg => {
+                self.unsubscribe(&msg.addr);
+                self.watched_txs.disconnect(&msg.addr);
+            },
+            Some(res) = self.subscription_tasks.join_next() => {
+                if let Ok(res) = res {
+                    let _ = log_error!(res, "Serving websocket subscription");
+                }
+            },
         };
         Ok(())
     }
@@ -61,9 +187,245 @@ impl NodeWebsocketConnector {
         self.handle("node_state", &event, Self::handle_node_state);
         self.handle("new_block", &event, Self::handle_new_block);
         self.handle("new_tx", &event, Self::handle_new_tx);
+        self.handle_subscriptions(&event);
+        self.handle_watched_txs(&event);
+        Ok(())
+    }
+
+    fn handle_ws_message(&mut self, msg: WsInMessage<WebsocketInMessage>) {
+        match msg.message {
+            WebsocketInMessage::Subscribe(filter) => self.subscribe(msg.addr, filter),
+            WebsocketInMessage::Unsubscribe => self.unsubscribe(&msg.addr),
+            WebsocketInMessage::WatchTx(tx_hash) => {
+                debug!("Watching tx {} for {}", tx_hash, msg.addr);
+                if let Err(e) = self.watched_txs.watch(msg.addr.clone(), tx_hash.clone()) {
+                    let _ = self.bus.send(WsPeerMessage::new(
+                        msg.addr,
+                        WebsocketOutEvent::WatchEnded {
+                            tx_hash,
+                            reason: e.to_string(),
+                        },
+                    ));
+                }
+            }
+        }
+    }
+
+    fn handle_watched_txs(&mut self, event: &NodeStateEvent) {
+        let NodeStateEvent::NewBlock(block) = event;
+        for update in self.watched_txs.on_block(block) {
+            let (addr, event) = match update {
+                WatchUpdate::Event(addr, event) => (addr, WebsocketOutEvent::TxEvent(event)),
+                WatchUpdate::Expired(addr, tx_hash) => (
+                    addr,
+                    WebsocketOutEvent::WatchEnded {
+                        tx_hash,
+                        reason: "Watch expired".to_string(),
+                    },
+                ),
+            };
+            let _ = self.bus.send(WsPeerMessage::new(addr, event));
+        }
+    }
+
+    fn subscribe(&mut self, addr: PeerAddress, filter: WebsocketSubscription) {
+        self.unsubscribe(&addr);
+        let (live, live_receiver) = mpsc::channel(self.buffer_size);
+        let task = self.subscription_tasks.spawn(Self::serve_subscription(
+            self.shared_bus.new_handle(),
+            self.da_address.clone(),
+            addr.clone(),
+            filter.clone(),
+            live_receiver,
+        ));
+        self.subscriptions
+            .insert(addr, PeerSubscription { filter, live, task });
+    }
+
+    fn unsubscribe(&mut self, addr: &PeerAddress) {
+        if let Some(subscription) = self.subscriptions.remove(addr) {
+            subscription.task.abort();
+        }
+    }
+
+    fn handle_subscriptions(&mut self, event: &NodeStateEvent) {
+        let NodeStateEvent::NewBlock(block) = event;
+
+        let mut dropped = vec![];
+        for (addr, subscription) in self.subscriptions.iter() {
+            // Blocks without matching transactions are sent too, they end the backfill
+            let live_block = LiveBlock {
+                height: block.block_height,
+                events: Self::tx_events(
+                    &subscription.filter,
+                    &block.hash,
+                    &block.block_timestamp,
+                    block
+                        .txs
+                        .iter()
+                        .map(|(id, tx)| (block.lane_ids.get(&id.1).cloned(), id, tx)),
+                ),
+            };
+            if subscription.live.try_send(live_block).is_err() {
+                warn!(
+                    "Websocket {} subscription is too far behind, dropping it",
+                    addr
+                );
+                dropped.push(addr.clone());
+            }
+        }
+        for addr in dropped {
+            self.unsubscribe(&addr);
+        }
+    }
+
+    /// Sends the transactions of a subscription to its connection.
+    /// With `from_height`, the blocks before the first live one are replayed from the DA server,
+    /// the live blocks waiting in the meantime. Each block is sent once, from either source.
+    async fn serve_subscription(
+        bus: SharedMessageBus,
+        da_address: String,
+        addr: PeerAddress,
+        filter: WebsocketSubscription,
+        mut live: mpsc::Receiver<LiveBlock>,
+    ) -> Result<()> {
+        let mut bus = SubscriptionBusClient::new_from_bus(bus).await;
+        // Height of the last block sent to the connection
+        let mut sent_height = None;
+
+        if let Some(from) = filter.from_height {
+            let Some(first_live) = live.recv().await else {
+                return Ok(());
+            };
+            if from < first_live.height {
+                info!(
+                    "Backfilling websocket {} from block {} to {}",
+                    addr,
+                    from,
+                    first_live.height - 1
+                );
+                let _ = log_error!(
+                    Self::backfill(
+                        &mut bus,
+                        &da_address,
+                        &addr,
+                        &filter,
+                        from,
+                        first_live.height,
+                        &mut sent_height
+                    )
+                    .await,
+                    "Backfilling websocket subscription"
+                );
+                debug!("Backfill of websocket {} done", addr);
+            }
+            Self::send_live_block(&mut bus, &addr, first_live, &mut sent_height).await?;
+        }
+
+        while let Some(block) = live.recv().await {
+            Self::send_live_block(&mut bus, &addr, block, &mut sent_height).await?;
+        }
+        Ok(())
+    }
+
+    async fn send_live_block(
+        bus: &mut SubscriptionBusClient,
+        addr: &PeerAddress,
+        block: LiveBlock,
+        sent_height: &mut Option<BlockHeight>,
+    ) -> Result<()> {
+        if sent_height.is_some_and(|sent| block.height <= sent) {
+            return Ok(());
+        }
+        for event in block.events {
+            bus.send_waiting_if_full(WsPeerMessage::new(addr.clone(), event))
+                .await?;
+        }
+        *sent_height = Some(block.height);
+        Ok(())
+    }
+
+    /// Streams the blocks from `from` up to `until` excluded from the DA server,
+    /// and sends their matching transactions
+    async fn backfill(
+        bus: &mut SubscriptionBusClient,
+        da_address: &str,
+        addr: &PeerAddress,
+        filter: &WebsocketSubscription,
+        from: BlockHeight,
+        until: BlockHeight,
+        sent_height: &mut Option<BlockHeight>,
+    ) -> Result<()> {
+        let mut client = DataAvailabilityClient::connect_with_opts(
+            format!("websocket_backfill_{addr}"),
+            Some(1024 * 1024 * 1024),
+            da_address.to_string(),
+        )
+        .await?;
+        client.send(DataAvailabilityRequest(from)).await?;
+
+        while let Some(event) = client.recv().await {
+            let block = match event {
+                DataAvailabilityEvent::SignedBlock(block) => block,
+                DataAvailabilityEvent::MempoolStatusEvent(_) => continue,
+                DataAvailabilityEvent::BlocksPruned {
+                    requested,
+                    first_available,
+                } => {
+                    warn!(
+                        "Blocks {}..{} are pruned, backfilling websocket {} from {}",
+                        requested, first_available, addr, first_available
+                    );
+                    continue;
+                }
+            };
+            if block.height() >= until {
+                break;
+            }
+            for event in Self::backfill_events(&block, filter) {
+                bus.send_waiting_if_full(WsPeerMessage::new(addr.clone(), event))
+                    .await?;
+            }
+            *sent_height = Some(block.height());
+            if block.height() + 1 == until {
+                break;
+            }
+        }
         Ok(())
     }
 
+    fn backfill_events(
+        block: &SignedBlock,
+        filter: &WebsocketSubscription,
+    ) -> Vec<WebsocketOutEvent> {
+        let txs: Vec<_> = block.iter_txs_with_id().collect();
+        Self::tx_events(
+            filter,
+            &block.hashed(),
+            &block.consensus_proposal.timestamp,
+            txs.iter()
+                .map(|(lane_id, id, tx)| (Some(lane_id.clone()), id, *tx)),
+        )
+    }
+
+    /// Events of the transactions of a block matching the filter.
+    /// `txs` are all the transactions of the block in order, which gives their index.
+    fn tx_events<'a>(
+        filter: &WebsocketSubscription,
+        block_hash: &ConsensusProposalHash,
+        timestamp: &TimestampMs,
+        txs: impl Iterator<Item = (Option<LaneId>, &'a TxId, &'a Transaction)>,
+    ) -> Vec<WebsocketOutEvent> {
+        txs.enumerate()
+            .filter(|(_, (_, id, tx))| filter.matches(&id.1, tx))
+            .map(|(idx, (lane_id, id, tx))| {
+                WebsocketOutEvent::NewTx(Self::api_transaction(
+                    id, tx, idx, block_hash, timestamp, lane_id,
+                ))
+            })
+            .collect()
+    }
+
     fn handle(
         &mut self,
         topic: &str,
@@ -97,35 +459,193 @@ impl NodeWebsocketConnector {
 
     fn handle_new_tx(event: NodeStateEvent) -> Vec<WebsocketOutEvent> {
         let NodeStateEvent::NewBlock(block) = event;
-        let mut txs = Vec::new();
-
-        for (idx, (id, tx)) in block.txs.iter().enumerate() {
-            let metadata = tx.metadata(id.0.clone());
-            let transaction_type = match tx.transaction_data {
-                TransactionData::Blob(_) => TransactionTypeDb::BlobTransaction,
-                TransactionData::Proof(_) => TransactionTypeDb::ProofTransaction,
-                TransactionData::VerifiedProof(_) => TransactionTypeDb::ProofTransaction,
-            };
-            let identity = match &tx.transaction_data {
-                TransactionData::Blob(tx) => Some(tx.identity.0.clone()),
-                _ => None,
-            };
-            let transaction_status = sdk::api::TransactionStatusDb::Sequenced;
-            let lane_id = block.lane_ids.get(&metadata.id.1).cloned();
-            let api_tx = APITransaction {
-                tx_hash: metadata.id.1,
-                parent_dp_hash: metadata.id.0,
-                version: metadata.version,
-                transaction_type,
-                transaction_status,
-                block_hash: Some(block.hash.clone()),
-                index: Some(idx as u32),
-                timestamp: Some(block.block_timestamp.clone()),
-                lane_id,
-                identity,
-            };
-            txs.push(WebsocketOutEvent::NewTx(api_tx));
+
+        Self::tx_events(
+            &WebsocketSubscription::default(),
+            &block.hash,
+            &block.block_timestamp,
+            block
+                .txs
+                .iter()
+                .map(|(id, tx)| (block.lane_ids.get(&id.1).cloned(), id, tx)),
+        )
+    }
+
+    fn api_transaction(
+        id: &TxId,
+        tx: &Transaction,
+        idx: usize,
+        block_hash: &ConsensusProposalHash,
+        timestamp: &TimestampMs,
+        lane_id: Option<LaneId>,
+    ) -> APITransaction {
+        let metadata = tx.metadata(id.0.clone());
+        let transaction_type = match tx.transaction_data {
+            TransactionData::Blob(_) => TransactionTypeDb::BlobTransaction,
+            TransactionData::Proof(_) => TransactionTypeDb::ProofTransaction,
+            TransactionData::VerifiedProof(_) => TransactionTypeDb::ProofTransaction,
+        };
+        let identity = match &tx.transaction_data {
+            TransactionData::Blob(tx) => Some(tx.identity.0.clone()),
+            _ => None,
+        };
+        APITransaction {
+            tx_hash: metadata.id.1,
+            parent_dp_hash: metadata.id.0,
+            version: metadata.version,
+            transaction_type,
+            transaction_status: sdk::api::TransactionStatusDb::Sequenced,
+            block_hash: Some(block_hash.clone()),
+            index: Some(idx as u32),
+            timestamp: Some(timestamp.clone()),
+            lane_id,
+            identity,
         }
-        txs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdk::{
+        Blob, BlobData, BlobTransaction, Block, ConsensusProposal, DataProposal, ValidatorPublicKey,
+    };
+
+    use super::*;
+
+    fn blob_tx(identity: &str, contract: &str) -> Transaction {
+        BlobTransaction::new(
+            identity,
+            vec![Blob {
+                contract_name: contract.into(),
+                data: BlobData(vec![1]),
+            }],
+        )
+        .into()
+    }
+
+    #[test]
+    fn test_subscription_matches() {
+        let alice_tx = blob_tx("alice@hydentity", "hyllar");
+        let bob_tx = blob_tx("bob@hydentity", "oranj");
+
+        let all = WebsocketSubscription::default();
+        assert!(all.matches(&alice_tx.hashed(), &alice_tx));
+
+        let by_contract = WebsocketSubscription {
+            contracts: vec!["hyllar".into()],
+            ..Default::default()
+        };
+        assert!(by_contract.matches(&alice_tx.hashed(), &alice_tx));
+        assert!(!by_contract.matches(&bob_tx.hashed(), &bob_tx));
+
+        let by_identity = WebsocketSubscription {
+            identities: vec!["bob@hydentity".into()],
+            ..Default::default()
+        };
+        assert!(!by_identity.matches(&alice_tx.hashed(), &alice_tx));
+        assert!(by_identity.matches(&bob_tx.hashed(), &bob_tx));
+
+        let by_hash = WebsocketSubscription {
+            tx_hashes: vec![alice_tx.hashed()],
+            ..Default::default()
+        };
+        assert!(by_hash.matches(&alice_tx.hashed(), &alice_tx));
+        assert!(!by_hash.matches(&bob_tx.hashed(), &bob_tx));
+
+        let subscription: WebsocketSubscription =
+            serde_json::from_str(r#"{"contracts": ["hyllar"], "from_height": 3}"#).unwrap();
+        assert_eq!(subscription.from_height, Some(BlockHeight(3)));
+        assert_eq!(subscription.contracts, vec![ContractName::from("hyllar")]);
+    }
+
+    #[test]
+    fn test_backfill_events() {
+        let alice_tx = blob_tx("alice@hydentity", "hyllar");
+        let bob_tx = blob_tx("bob@hydentity", "oranj");
+        let block = SignedBlock {
+            data_proposals: vec![(
+                LaneId::default(),
+                vec![DataProposal::new(None, vec![alice_tx.clone(), bob_tx])],
+            )],
+            consensus_proposal: ConsensusProposal {
+                slot: 4,
+                timestamp: TimestampMs(1234),
+                ..Default::default()
+            },
+            certificate: Default::default(),
+        };
+
+        let events = NodeWebsocketConnector::backfill_events(
+            &block,
+            &WebsocketSubscription {
+                identities: vec!["alice@hydentity".into()],
+                ..Default::default()
+            },
+        );
+        assert_eq!(events.len(), 1);
+        let Some(WebsocketOutEvent::NewTx(tx)) = events.first() else {
+            panic!("Expected a transaction, got {events:?}");
+        };
+        assert_eq!(tx.tx_hash, alice_tx.hashed());
+        assert_eq!(tx.block_hash, Some(block.hashed()));
+        assert_eq!(tx.index, Some(0));
+        assert_eq!(tx.timestamp, Some(TimestampMs(1234)));
+        assert_eq!(tx.identity.as_deref(), Some("alice@hydentity"));
+    }
+
+    #[test]
+    fn test_backfill_events_match_live_ones() {
+        let block = SignedBlock {
+            data_proposals: vec![
+                (
+                    LaneId(ValidatorPublicKey(vec![1])),
+                    vec![DataProposal::new(
+                        None,
+                        vec![blob_tx("alice@hydentity", "hyllar")],
+                    )],
+                ),
+                (
+                    LaneId(ValidatorPublicKey(vec![2])),
+                    vec![DataProposal::new(
+                        None,
+                        vec![
+                            blob_tx("bob@hydentity", "oranj"),
+                            blob_tx("carol@hydentity", "hyllar"),
+                        ],
+                    )],
+                ),
+            ],
+            consensus_proposal: ConsensusProposal {
+                slot: 4,
+                timestamp: TimestampMs(1234),
+                ..Default::default()
+            },
+            certificate: Default::default(),
+        };
+        let txs: Vec<_> = block.iter_txs_with_id().collect();
+        let live_block = Block {
+            hash: block.hashed(),
+            block_height: block.height(),
+            block_timestamp: block.consensus_proposal.timestamp.clone(),
+            txs: txs
+                .iter()
+                .map(|(_, id, tx)| (id.clone(), (*tx).clone()))
+                .collect(),
+            lane_ids: txs
+                .iter()
+                .map(|(lane_id, id, _)| (id.1.clone(), lane_id.clone()))
+                .collect(),
+            ..Default::default()
+        };
+
+        let live =
+            NodeWebsocketConnector::handle_new_tx(NodeStateEvent::NewBlock(Box::new(live_block)));
+        let backfilled =
+            NodeWebsocketConnector::backfill_events(&block, &WebsocketSubscription::default());
+        assert_eq!(live.len(), 3);
+        assert_eq!(
+            serde_json::to_value(&live).unwrap(),
+            serde_json::to_value(&backfilled).unwrap()
+        );
     }
 }
@@ -7,7 +7,10 @@ use anyhow::{anyhow, Error, Result};
 use borsh::{BorshDeserialize, BorshSerialize};
 use client_sdk::contract_indexer::{ContractHandler, ContractStateStore};
 use sdk::*;
-use std::{any::TypeId, ops::Deref, path::PathBuf, sync::Arc};
+use std::{
+    any::TypeId, collections::BTreeMap, future::Future, ops::Deref, path::PathBuf, pin::Pin,
+    sync::Arc,
+};
 use tokio::sync::RwLock;
 use tracing::debug;
 
@@ -40,6 +43,53 @@ pub struct ContractStateIndexerCtx {
     pub data_directory: PathBuf,
     pub contract_name: ContractName,
     pub api: SharedBuildApiCtx,
+    pub token_balances: TokenBalances,
+}
+
+pub type BalanceFuture = Pin<Box<dyn Future<Output = Option<u128>> + Send>>;
+pub type BalanceOf = Arc<dyn Fn(Identity) -> BalanceFuture + Send + Sync>;
+
+/// Reads the balances of the token contracts indexed by the contract state indexers,
+/// so that the indexer can serve the balances of an identity.
+#[derive(Clone, Default)]
+pub struct TokenBalances(Arc<std::sync::RwLock<BTreeMap<ContractName, BalanceOf>>>);
+
+impl TokenBalances {
+    pub fn register(&self, contract_name: ContractName, balance_of: BalanceOf) {
+        #[allow(clippy::expect_used, reason = "not held across await")]
+        self.0
+            .write()
+            .expect("logic issue")
+            .insert(contract_name, balance_of);
+    }
+
+    /// Balances of the identity in the token contracts it has an account in
+    pub async fn of(&self, identity: &Identity) -> Vec<(ContractName, u128)> {
+        #[allow(clippy::expect_used, reason = "not held across await")]
+        let contracts: Vec<(ContractName, BalanceOf)> = self
+            .0
+            .read()
+            .expect("logic issue")
+            .iter()
+            .map(|(contract_name, balance_of)| (contract_name.clone(), balance_of.clone()))
+            .collect();
+        let mut balances = vec![];
+        for (contract_name, balance_of) in contracts {
+            if let Some(balance) = balance_of(identity.clone()).await {
+                balances.push((contract_name, balance));
+            }
+        }
+        balances
+    }
+}
+
+impl std::fmt::Debug for TokenBalances {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0.read() {
+            Ok(contracts) => f.debug_list().entries(contracts.keys()).finish(),
+            Err(_) => f.write_str("TokenBalances"),
+        }
+    }
 }
 
 impl<State, Event> Module for ContractStateIndexer<State, Event>
@@ -67,6 +117,18 @@ where
         store.contract_name = ctx.contract_name.clone();
         let store = Arc::new(RwLock::new(store));
 
+        let balances_store = Arc::clone(&store);
+        ctx.token_balances.register(
+            ctx.contract_name.clone(),
+            Arc::new(move |identity: Identity| -> BalanceFuture {
+                let store = Arc::clone(&balances_store);
+                Box::pin(async move {
+                    let store = store.read().await;
+                    store.state.as_ref()?.token_balance(&identity)
+                })
+            }),
+        );
+
         let (nested, mut api) = State::api(Arc::clone(&store)).await;
         if let Ok(mut o) = ctx.api.openapi.lock() {
             // Deduplicate operation ids
@@ -375,6 +437,7 @@ mod tests {
             contract_name,
             data_directory: PathBuf::from("test_data"),
             api: Default::default(),
+            token_balances: Default::default(),
         };
 
         ContractStateIndexer::<MockState>::build(
@@ -1,16 +1,25 @@
 use std::collections::BTreeMap;
 use std::path::PathBuf;
 
-use anyhow::Result;
-use sdk::{BlockHeight, Hashed, MempoolStatusEvent, SignedBlock};
-use tokio::task::yield_now;
+use anyhow::{bail, Result};
+use sdk::{Block, BlockHeight, Hashed, MempoolStatusEvent, SignedBlock};
+use tokio::{
+    task::yield_now,
+    time::{Duration, Instant},
+};
 use tracing::{debug, error, info, trace, warn};
 
 use crate::{
     bus::{BusClientSender, SharedMessageBus},
     modules::{module_bus_client, Module},
-    node_state::{metrics::NodeStateMetrics, module::NodeStateEvent, NodeState, NodeStateStore},
-    utils::da_codec::{DataAvailabilityClient, DataAvailabilityEvent, DataAvailabilityRequest},
+    node_state::{
+        metrics::NodeStateMetrics, module::NodeStateEvent, store_file::NodeStateFile, NodeState,
+    },
+    utils::{
+        da_codec::DataAvailabilityEvent,
+        da_sources::{DASources, DAStream},
+        light_client::{LightClient, TrustedValidatorSet},
+    },
 };
 use crate::{log_error, module_handle_messages};
 
@@ -27,6 +36,9 @@ pub struct DAListener {
     config: DAListenerConf,
     bus: DAListenerBusClient,
     node_state: NodeState,
+    /// Verifies the streamed blocks, if a trusted validator set is configured
+    light_client: Option<LightClient>,
+    sources: DASources,
     start_block: BlockHeight,
     block_buffer: BTreeMap<BlockHeight, SignedBlock>,
 }
@@ -34,19 +46,37 @@ pub struct DAListener {
 pub struct DAListenerConf {
     pub data_directory: PathBuf,
     pub da_read_from: String,
+    /// DA servers to fail over to, in order, when `da_read_from` stalls or cannot be reached
+    pub da_fallbacks: Vec<String>,
+    /// Also stream blocks from the next source, only process the blocks both agree on,
+    /// and halt if they disagree or the next source cannot be reached
+    pub cross_check: bool,
     pub start_block: Option<BlockHeight>,
     pub timeout_client_secs: u64,
+    /// Verify the blocks against this validator set instead of trusting the DA server
+    pub trusted_validators: Option<TrustedValidatorSet>,
+}
+
+impl DAListenerConf {
+    /// `da_read_from` followed by the fallbacks
+    pub fn sources(&self) -> Vec<String> {
+        std::iter::once(self.da_read_from.clone())
+            .chain(self.da_fallbacks.iter().cloned())
+            .collect()
+    }
 }
 
 impl Module for DAListener {
     type Context = DAListenerConf;
 
     async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
-        let node_state_store = Self::load_from_disk_or_default::<NodeStateStore>(
+        let node_state_store = Self::load_from_disk::<NodeStateFile>(
             ctx.data_directory
                 .join("da_listener_node_state.bin")
                 .as_path(),
-        );
+        )
+        .map(|file| file.0)
+        .unwrap_or_default();
 
         let node_state = NodeState {
             store: node_state_store,
@@ -62,6 +92,22 @@ impl Module for DAListener {
             },
         );
 
+        let light_client = match ctx.trusted_validators.clone() {
+            Some(trusted) => Some(
+                match Self::load_from_disk::<LightClient>(
+                    ctx.data_directory
+                        .join("da_listener_light_client.bin")
+                        .as_path(),
+                ) {
+                    Some(light_client) => light_client,
+                    None => LightClient::new(trusted)?,
+                },
+            ),
+            None => None,
+        };
+
+        let sources = DASources::new("raw_da_listener", ctx.sources(), ctx.cross_check);
+
         let bus = DAListenerBusClient::new_from_bus(bus.new_handle()).await;
 
         for name in node_state.contracts.keys() {
@@ -73,6 +119,8 @@ impl Module for DAListener {
             start_block,
             bus,
             node_state,
+            light_client,
+            sources,
             block_buffer: BTreeMap::new(),
         })
     }
@@ -82,13 +130,25 @@ impl Module for DAListener {
     }
 
     async fn persist(&mut self) -> Result<()> {
+        if let Some(light_client) = &self.light_client {
+            _ = log_error!(
+                Self::save_on_disk::<LightClient>(
+                    self.config
+                        .data_directory
+                        .join("da_listener_light_client.bin")
+                        .as_path(),
+                    light_client,
+                ),
+                "Saving light client"
+            );
+        }
         log_error!(
-            Self::save_on_disk::<NodeStateStore>(
+            Self::save_on_disk(
                 self.config
                     .data_directory
                     .join("da_listener_node_state.bin")
                     .as_path(),
-                &self.node_state,
+                &NodeStateFile(&self.node_state.store),
             ),
             "Saving node state"
         )
@@ -96,17 +156,16 @@ impl Module for DAListener {
 }
 
 impl DAListener {
-    async fn start_client(&self, block_height: BlockHeight) -> Result<DataAvailabilityClient> {
-        let mut client = DataAvailabilityClient::connect_with_opts(
-            "raw_da_listener".to_string(),
-            Some(1024 * 1024 * 1024),
-            self.config.da_read_from.clone(),
-        )
-        .await?;
-
-        client.send(DataAvailabilityRequest(block_height)).await?;
-
-        Ok(client)
+    /// Verifies the block if a trusted validator set is configured, then processes it
+    fn handle_signed_block(&mut self, block: &SignedBlock) -> Result<Block> {
+        if let Some(light_client) = self.light_client.as_mut() {
+            light_client.verify(block)?;
+        }
+        let processed_block = self.node_state.handle_signed_block(block)?;
+        if let Some(light_client) = self.light_client.as_mut() {
+            light_client.process_block(&processed_block)?;
+        }
+        Ok(processed_block)
     }
 
     async fn process_block(&mut self, block: SignedBlock) -> Result<()> {
@@ -118,7 +177,7 @@ impl DAListener {
                 block.consensus_proposal.slot,
                 block.consensus_proposal.hashed()
             );
-            let processed_block = self.node_state.handle_signed_block(&block)?;
+            let processed_block = self.handle_signed_block(&block)?;
             self.bus
                 .send_waiting_if_full(NodeStateEvent::NewBlock(Box::new(processed_block)))
                 .await?;
@@ -149,7 +208,7 @@ impl DAListener {
                         block.consensus_proposal.hashed()
                     );
                 }
-                let processed_block = self.node_state.handle_signed_block(&block)?;
+                let processed_block = self.handle_signed_block(&block)?;
                 trace!("📦 Handled block outputs: {:?}", processed_block);
                 self.bus
                     .send_waiting_if_full(NodeStateEvent::NewBlock(Box::new(processed_block)))
@@ -186,7 +245,7 @@ impl DAListener {
                     block.consensus_proposal.slot,
                     block.consensus_proposal.hashed()
                 );
-                let processed_block = self.node_state.handle_signed_block(&block)?;
+                let processed_block = self.handle_signed_block(&block)?;
                 debug!("📦 Handled buffered block outputs: {:?}", processed_block);
                 self.bus
                     .send_waiting_if_full(NodeStateEvent::NewBlock(Box::new(processed_block)))
@@ -246,24 +305,76 @@ impl DAListener {
                 on_self self,
             };
         } else {
-            let mut client = self.start_client(self.start_block).await?;
+            let timeout = Duration::from_secs(self.config.timeout_client_secs);
+            let mut client = self.sources.connect(self.start_block).await?;
+            let mut witness = self.sources.connect_witness(self.start_block).await?;
+            // Only the primary stream counts as progress, the witness may be ahead
+            let mut deadline = Instant::now() + timeout;
+            // Sources that pruned the blocks we need since the last processed block
+            let mut pruned_sources = 0;
 
             module_handle_messages! {
                 on_self self,
-                _ = tokio::time::sleep(tokio::time::Duration::from_secs(self.config.timeout_client_secs)) => {
-                    warn!("No blocks received in the last {} seconds, restarting client", self.config.timeout_client_secs);
-                    client = self.start_client(self.node_state.current_height + 1).await?;
+                _ = tokio::time::sleep_until(deadline) => {
+                    warn!("No blocks received from {} in the last {} seconds, failing over", self.sources.current(), self.config.timeout_client_secs);
+                    client = self.sources.failover(self.node_state.current_height + 1).await?;
+                    witness = self.sources.connect_witness(self.node_state.current_height + 1).await?;
+                    deadline = Instant::now() + timeout;
                 }
                 frame = client.recv() => {
-                    if let Some(streamed_signed_block) = frame {
-                        let _ = log_error!(self.processing_next_frame(streamed_signed_block).await, "Consuming da stream");
+                    deadline = Instant::now() + timeout;
+                    if let Some(DataAvailabilityEvent::BlocksPruned { requested, first_available }) = frame {
+                        pruned_sources += 1;
+                        if pruned_sources >= self.sources.count() {
+                            bail!(
+                                "All DA sources pruned blocks {}..{}, the listener cannot catch up",
+                                requested,
+                                first_available
+                            );
+                        }
+                        warn!(
+                            "DA source {} pruned blocks {}..{}, failing over",
+                            self.sources.current(),
+                            requested,
+                            first_available
+                        );
+                        client = self.sources.failover(self.node_state.current_height + 1).await?;
+                        witness = self.sources.connect_witness(self.node_state.current_height + 1).await?;
+                    } else if let Some(streamed_signed_block) = frame {
+                        let event = match streamed_signed_block {
+                            DataAvailabilityEvent::SignedBlock(block) => {
+                                pruned_sources = 0;
+                                self.sources
+                                    .received(DAStream::Primary, block)?
+                                    .map(DataAvailabilityEvent::SignedBlock)
+                            }
+                            event => Some(event),
+                        };
+                        if let Some(event) = event {
+                            let _ = log_error!(self.processing_next_frame(event).await, "Consuming da stream");
+                        }
                         if let Err(e) = client.ping().await {
                             warn!("Ping failed: {}. Restarting client...", e);
-                            client = self.start_client(self.node_state.current_height + 1).await?;
+                            client = self.sources.connect(self.node_state.current_height + 1).await?;
                         }
                     } else {
                         warn!("DA stream connection lost. Reconnecting...");
-                        client = self.start_client(self.node_state.current_height + 1).await?;
+                        client = self.sources.connect(self.node_state.current_height + 1).await?;
+                    }
+                }
+                frame = DASources::recv_witness(&mut witness) => {
+                    match frame {
+                        Some(DataAvailabilityEvent::SignedBlock(block)) => {
+                            // The witness confirms the primary block held at its height
+                            if let Some(block) = self.sources.received(DAStream::Witness, block)? {
+                                let _ = log_error!(self.processing_next_frame(DataAvailabilityEvent::SignedBlock(block)).await, "Consuming da stream");
+                            }
+                        }
+                        Some(_) => {}
+                        None => {
+                            warn!("DA witness stream connection lost. Reconnecting...");
+                            witness = self.sources.connect_witness(self.node_state.current_height + 1).await?;
+                        }
                     }
                 }
             };
@@ -280,6 +391,18 @@ impl DAListener {
             DataAvailabilityEvent::MempoolStatusEvent(mempool_status_event) => {
                 self.bus.send_waiting_if_full(mempool_status_event).await?;
             }
+            DataAvailabilityEvent::BlocksPruned {
+                requested,
+                first_available,
+            } => {
+                // Handled by the streaming loop, which fails over to another source
+                bail!(
+                    "DA server pruned blocks {}..{}, only blocks from height {} are available",
+                    requested,
+                    first_available,
+                    first_available
+                );
+            }
         }
 
         Ok(())
@@ -99,6 +99,10 @@ async fn setup_with_timeout(
         verifier: "test".into(),
         program_id: ProgramId(vec![]),
         timeout_window: TimeoutWindow::Timeout(BlockHeight(timeout)),
+        owner: None,
+        name_service: None,
+        expiry_height: None,
+        min_program_id_update_delay: None,
     });
 
     let auto_prover = new_simple_auto_prover(api_client.clone()).await?;
@@ -1237,6 +1241,10 @@ async fn scenario_auto_prover_artificial_middle_blob_failure_setup(
         program_id: ProgramId(vec![]),
         verifier: "test".into(),
         timeout_window: TimeoutWindow::Timeout(BlockHeight(20)),
+        owner: None,
+        name_service: None,
+        expiry_height: None,
+        min_program_id_update_delay: None,
     });
 
     let register = RegisterContractEffect {
@@ -1253,6 +1261,10 @@ async fn scenario_auto_prover_artificial_middle_blob_failure_setup(
         program_id: ProgramId(vec![]),
         verifier: "test".into(),
         timeout_window: TimeoutWindow::Timeout(BlockHeight(20)),
+        owner: None,
+        name_service: None,
+        expiry_height: None,
+        min_program_id_update_delay: None,
     });
     (node_state, Arc::new(api_client))
 }
@@ -1441,6 +1453,10 @@ async fn test_auto_prover_catchup_mixed_pending_and_failures() -> Result<()> {
         state: StateCommitment(vec![2, 0, 0, 0]),
         verifier: "test".into(),
         timeout_window: TimeoutWindow::Timeout(BlockHeight(20)),
+        owner: None,
+        name_service: None,
+        expiry_height: None,
+        min_program_id_update_delay: None,
     });
 
     let mut auto_prover = new_buffering_auto_prover(api_client.clone(), 0, 20).await?;
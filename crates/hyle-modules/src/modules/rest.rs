@@ -14,6 +14,7 @@ use axum::{
     routing::get,
     Json,
 };
+use hyle_net::net::{HyleNetIntoMakeServiceWithconnectInfo, HyleNetSocketAddr};
 use prometheus::{Encoder, Registry, TextEncoder};
 use sdk::{api::NodeInfo, *};
 use tokio::time::Instant;
@@ -187,11 +188,17 @@ impl RestApi {
             let token = axum_cancel_token.clone();
             async move {
                 log_error!(
-                    axum::serve(listener, app)
-                        .with_graceful_shutdown(async move {
-                            token.cancelled().await;
-                        })
-                        .await,
+                    // Handlers can tell clients apart by address, e.g. to rate limit them
+                    axum::serve(
+                        listener,
+                        HyleNetIntoMakeServiceWithconnectInfo(
+                            app.into_make_service_with_connect_info::<HyleNetSocketAddr>(),
+                        ),
+                    )
+                    .with_graceful_shutdown(async move {
+                        token.cancelled().await;
+                    })
+                    .await,
                     "serving Axum"
                 )?;
                 Ok::<(), anyhow::Error>(())
@@ -1,14 +1,22 @@
 use std::collections::BTreeMap;
 
 use anyhow::Result;
+use borsh::{BorshDeserialize, BorshSerialize};
 use sdk::{BlockHeight, DataEvent, Hashed, SignedBlock};
-use tokio::task::yield_now;
+use tokio::{
+    task::yield_now,
+    time::{Duration, Instant},
+};
 use tracing::{debug, info, warn};
 
 use crate::{
     bus::{BusClientSender, SharedMessageBus},
     modules::{da_listener::DAListenerConf, module_bus_client, Module},
-    utils::da_codec::{DataAvailabilityClient, DataAvailabilityEvent, DataAvailabilityRequest},
+    utils::{
+        da_codec::DataAvailabilityEvent,
+        da_sources::{DASources, DAStream},
+        light_client::LightClient,
+    },
 };
 use crate::{log_error, module_handle_messages};
 
@@ -19,11 +27,23 @@ struct SignedDAListenerBusClient {
 }
 }
 
+/// The light client state only holds for the block height it was saved at
+#[derive(BorshSerialize, BorshDeserialize)]
+struct LightClientStore {
+    current_block: BlockHeight,
+    light_client: LightClient,
+}
+
 /// Module that listens to the raw data availability stream and sends the signed blocks to the bus
 pub struct SignedDAListener {
     config: DAListenerConf,
     bus: SignedDAListenerBusClient,
     current_block: BlockHeight,
+    /// Verifies the streamed blocks, if a trusted validator set is configured.
+    /// Without a node state, only the bonds of the blocks are followed.
+    /// Its state is saved with the next block height, which the listener resumes from.
+    light_client: Option<LightClient>,
+    sources: DASources,
     block_buffer: BTreeMap<BlockHeight, SignedBlock>,
 }
 
@@ -31,13 +51,40 @@ impl Module for SignedDAListener {
     type Context = DAListenerConf;
 
     async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
-        let current_block = ctx.start_block.unwrap_or_default();
+        let mut current_block = ctx.start_block.unwrap_or_default();
+
+        let light_client = match ctx.trusted_validators.clone() {
+            Some(trusted) => Some(
+                match Self::load_from_disk::<LightClientStore>(
+                    ctx.data_directory
+                        .join("signed_da_listener_light_client.bin")
+                        .as_path(),
+                ) {
+                    Some(store) => {
+                        if store.current_block != current_block {
+                            info!(
+                                "Resuming at block {} where the light client state was saved",
+                                store.current_block
+                            );
+                        }
+                        current_block = store.current_block;
+                        store.light_client
+                    }
+                    None => LightClient::new(trusted)?,
+                },
+            ),
+            None => None,
+        };
+
+        let sources = DASources::new("signed_da_listener", ctx.sources(), ctx.cross_check);
 
         let bus = SignedDAListenerBusClient::new_from_bus(bus.new_handle()).await;
 
         Ok(SignedDAListener {
             config: ctx,
             current_block,
+            light_client,
+            sources,
             bus,
             block_buffer: BTreeMap::new(),
         })
@@ -46,20 +93,35 @@ impl Module for SignedDAListener {
     fn run(&mut self) -> impl futures::Future<Output = Result<()>> + Send {
         self.start()
     }
+
+    async fn persist(&mut self) -> Result<()> {
+        let Some(light_client) = self.light_client.take() else {
+            return Ok(());
+        };
+        let store = LightClientStore {
+            current_block: self.current_block,
+            light_client,
+        };
+        let res = Self::save_on_disk::<LightClientStore>(
+            self.config
+                .data_directory
+                .join("signed_da_listener_light_client.bin")
+                .as_path(),
+            &store,
+        );
+        self.light_client = Some(store.light_client);
+        log_error!(res, "Saving light client")
+    }
 }
 
 impl SignedDAListener {
-    async fn start_client(&self, block_height: BlockHeight) -> Result<DataAvailabilityClient> {
-        let mut client = DataAvailabilityClient::connect_with_opts(
-            "signed_da_listener".to_string(),
-            Some(1024 * 1024 * 1024),
-            self.config.da_read_from.clone(),
-        )
-        .await?;
-
-        client.send(DataAvailabilityRequest(block_height)).await?;
-
-        Ok(client)
+    /// Checks the block against the trusted validator set, if one is configured
+    fn verify(&mut self, block: &SignedBlock) -> Result<()> {
+        if let Some(light_client) = self.light_client.as_mut() {
+            light_client.verify(block)?;
+            light_client.process_signed_block(block);
+        }
+        Ok(())
     }
 
     async fn process_block(&mut self, block: SignedBlock) -> Result<()> {
@@ -89,6 +151,7 @@ impl SignedDAListener {
                         block.consensus_proposal.hashed()
                     );
                 }
+                self.verify(&block)?;
                 self.bus
                     .send_waiting_if_full(DataEvent::OrderedSignedBlock(block))
                     .await?;
@@ -126,6 +189,7 @@ impl SignedDAListener {
                     block.consensus_proposal.slot,
                     block.consensus_proposal.hashed()
                 );
+                self.verify(&block)?;
                 self.bus
                     .send_waiting_if_full(DataEvent::OrderedSignedBlock(block))
                     .await?;
@@ -179,24 +243,55 @@ impl SignedDAListener {
                 on_self self,
             };
         } else {
-            let mut client = self.start_client(self.current_block).await?;
+            let timeout = Duration::from_secs(self.config.timeout_client_secs);
+            let mut client = self.sources.connect(self.current_block).await?;
+            let mut witness = self.sources.connect_witness(self.current_block).await?;
+            // Only the primary stream counts as progress, the witness may be ahead
+            let mut deadline = Instant::now() + timeout;
 
             module_handle_messages! {
                 on_self self,
-                _ = tokio::time::sleep(tokio::time::Duration::from_secs(self.config.timeout_client_secs)) => {
-                    warn!("No blocks received in the last {} seconds, restarting client", self.config.timeout_client_secs);
-                    client = self.start_client(self.current_block).await?;
+                _ = tokio::time::sleep_until(deadline) => {
+                    warn!("No blocks received from {} in the last {} seconds, failing over", self.sources.current(), self.config.timeout_client_secs);
+                    client = self.sources.failover(self.current_block).await?;
+                    witness = self.sources.connect_witness(self.current_block).await?;
+                    deadline = Instant::now() + timeout;
                 }
                 frame = client.recv() => {
+                    deadline = Instant::now() + timeout;
                     if let Some(streamed_signed_block) = frame {
-                        let _ = log_error!(self.processing_next_frame(streamed_signed_block).await, "Consuming da stream");
+                        let event = match streamed_signed_block {
+                            DataAvailabilityEvent::SignedBlock(block) => self
+                                .sources
+                                .received(DAStream::Primary, block)?
+                                .map(DataAvailabilityEvent::SignedBlock),
+                            event => Some(event),
+                        };
+                        if let Some(event) = event {
+                            let _ = log_error!(self.processing_next_frame(event).await, "Consuming da stream");
+                        }
                         if let Err(e) = client.ping().await {
                             warn!("Ping failed: {}. Restarting client...", e);
-                            client = self.start_client(self.current_block).await?;
+                            client = self.sources.connect(self.current_block).await?;
                         }
                     } else {
                         warn!("DA stream connection lost. Reconnecting...");
-                        client = self.start_client(self.current_block).await?;
+                        client = self.sources.connect(self.current_block).await?;
+                    }
+                }
+                frame = DASources::recv_witness(&mut witness) => {
+                    match frame {
+                        Some(DataAvailabilityEvent::SignedBlock(block)) => {
+                            // The witness confirms the primary block held at its height
+                            if let Some(block) = self.sources.received(DAStream::Witness, block)? {
+                                let _ = log_error!(self.processing_next_frame(DataAvailabilityEvent::SignedBlock(block)).await, "Consuming da stream");
+                            }
+                        }
+                        Some(_) => {}
+                        None => {
+                            warn!("DA witness stream connection lost. Reconnecting...");
+                            witness = self.sources.connect_witness(self.current_block).await?;
+                        }
                     }
                 }
             };
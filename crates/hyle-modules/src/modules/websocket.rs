@@ -22,9 +22,15 @@ use futures::{
 };
 use hyle_net::net::{HyleNetIntoMakeServiceWithconnectInfo, HyleNetSocketAddr};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use tokio::{sync::Mutex, task::JoinSet};
+use tokio::{
+    sync::{
+        mpsc::{self, error::TrySendError},
+        Mutex,
+    },
+    task::JoinSet,
+};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 // ---- Bus ------
 
@@ -55,13 +61,34 @@ impl<T> WsTopicMessage<T> {
         }
     }
 }
+/// Message sent to a single connection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsPeerMessage<T> {
+    pub addr: PeerAddress,
+    pub message: T,
+}
+impl<T> WsPeerMessage<T> {
+    pub fn new(addr: impl Into<PeerAddress>, message: T) -> Self {
+        Self {
+            addr: addr.into(),
+            message,
+        }
+    }
+}
+/// Sent when a connection is closed, or dropped because it does not keep up
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WsPeerDisconnected {
+    pub addr: PeerAddress,
+}
 
 module_bus_client! {
 #[derive(Debug)]
 pub struct WebSocketBusClient<In: Send + Sync + Clone + 'static, Out: Send + Sync + Clone + 'static> {
     sender(WsInMessage<In>),
+    sender(WsPeerDisconnected),
     receiver(WsBroadcastMessage<Out>),
     receiver(WsTopicMessage<Out>),
+    receiver(WsPeerMessage<Out>),
 }
 }
 
@@ -78,6 +105,9 @@ pub struct WebSocketConfig {
     pub health_path: String,
     /// The interval at which to check for new peers
     pub peer_check_interval: Duration,
+    /// Number of outbound messages buffered per connection.
+    /// Connections that let their buffer fill up are dropped.
+    pub peer_buffer_size: usize,
 }
 
 impl Default for WebSocketConfig {
@@ -87,6 +117,7 @@ impl Default for WebSocketConfig {
             ws_path: "/ws".to_string(),
             health_path: "/ws_health".to_string(),
             peer_check_interval: Duration::from_millis(100),
+            peer_buffer_size: 1000,
         }
     }
 }
@@ -103,16 +134,15 @@ where
 {
     bus: WebSocketBusClient<In, Out>,
     app: Option<Router>,
-    peer_senders: HashMap<PeerAddress, SplitSink<WebSocket, Message>>,
+    peer_senders: HashMap<PeerAddress, mpsc::Sender<Message>>,
+    /// Tasks writing the buffered messages of each connection to its socket
+    peer_writers: JoinSet<()>,
     topic_listeners: HashMap<Topic, Vec<PeerAddress>>,
     #[allow(clippy::type_complexity)]
-    peer_receivers: JoinSet<
-        Option<(
-            PeerAddress,
-            SplitStream<WebSocket>,
-            Result<WsMsg<In>, Error>,
-        )>,
-    >,
+    peer_receivers: JoinSet<(
+        PeerAddress,
+        Option<(SplitStream<WebSocket>, Result<WsMsg<In>, Error>)>,
+    )>,
     new_peers: NewPeers,
     config: WebSocketConfig,
 }
@@ -138,6 +168,7 @@ where
             bus: WebSocketBusClient::new_from_bus(bus.new_handle()).await,
             app: Some(app),
             peer_senders: HashMap::new(),
+            peer_writers: JoinSet::new(),
             topic_listeners: HashMap::new(),
             peer_receivers: JoinSet::new(),
             new_peers,
@@ -193,9 +224,16 @@ where
                     break;
                 }
             }
-            Some(Ok(Some(msg))) = self.peer_receivers.join_next() => {
+            listen<WsPeerMessage<Out>> msg => {
+                if let Err(e) = self.peer_message(msg.addr, msg.message) {
+                    error!("Error sending outbound message: {}", e);
+                    break;
+                }
+            }
+            Some(_) = self.peer_writers.join_next() => {}
+            Some(Ok((addr, msg))) = self.peer_receivers.join_next() => {
                 match msg {
-                    (addr, socket_stream, Ok(msg)) => {
+                    Some((socket_stream, Ok(msg))) => {
                         debug!("Received message: {:?}", msg);
                         match msg {
                             WsMsg::RegisterTopic(topic) => {
@@ -209,9 +247,13 @@ where
                         // Add it again to the receiver
                         self.peer_receivers.spawn(Self::process_websocket_incoming(addr, socket_stream));
                     }
-                    (_, _, Err(e)) => {
+                    Some((_, Err(e))) => {
                         error!("Error receiving message: {}", e);
                     }
+                    None => {
+                        debug!("WebSocket connection {} closed", addr);
+                        self.disconnect_peer(&addr);
+                    }
                 }
             }
             _ = axum_cancel_token.cancelled() => {
@@ -223,8 +265,10 @@ where
                 // Check for new peers
                 let mut peers = self.new_peers.0.lock().await;
                 for (addr, peer) in peers.drain(..) {
-                    let (sender, receiver) = peer.split();
+                    let (sink, receiver) = peer.split();
+                    let (sender, outgoing) = mpsc::channel(self.config.peer_buffer_size.max(1));
                     self.peer_senders.insert(addr.clone(), sender);
+                    self.peer_writers.spawn(Self::process_websocket_outgoing(sink, outgoing));
                     self.peer_receivers.spawn(Self::process_websocket_incoming(addr, receiver));
                 }
             }
@@ -257,26 +301,51 @@ where
         let _ = log_warn!(self.bus.send(msg), "Sending WsInMessage message to bus.");
     }
 
+    /// Queues a message for a connection without waiting for it to be written,
+    /// so that a slow client does not hold back the others.
+    fn send_to_peer(&mut self, addr: &PeerAddress, text: Message) -> bool {
+        let Some(sender) = self.peer_senders.get(addr) else {
+            return false;
+        };
+        match sender.try_send(text) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                warn!(
+                    "WebSocket {} does not keep up with its messages, disconnecting",
+                    addr
+                );
+                self.disconnect_peer(addr);
+                false
+            }
+            Err(TrySendError::Closed(_)) => {
+                debug!("WebSocket {} is closed", addr);
+                self.disconnect_peer(addr);
+                false
+            }
+        }
+    }
+
+    fn disconnect_peer(&mut self, addr: &PeerAddress) {
+        // Dropping the sender stops the writer task, which closes the socket
+        if self.peer_senders.remove(addr).is_some() {
+            for listeners in self.topic_listeners.values_mut() {
+                listeners.retain(|x| x != addr);
+            }
+            let _ = log_warn!(
+                self.bus.send(WsPeerDisconnected { addr: addr.clone() }),
+                "Sending WsPeerDisconnected message to bus."
+            );
+        }
+    }
+
     async fn topic_message(&mut self, topic: Topic, msg: Out) -> Result<()> {
         let text = serde_json::to_string(&msg).context("Failed to serialize outbound message")?;
-        let text: Message = Message::Text(text.clone().into());
-
-        if let Some(sender_addr) = self.topic_listeners.get(&topic) {
-            for addr in sender_addr.clone() {
-                let sender = self
-                    .peer_senders
-                    .get_mut(&addr)
-                    .ok_or_else(|| anyhow!("No peer sender for topic: {}", topic))?;
-                if let Err(e) = sender
-                    .send(text.clone())
-                    .await
-                    .context("Failed to send message")
-                {
-                    debug!("Failed to send message to topic {topic}: {e}");
-                    self.topic_listeners
-                        .entry(topic.clone())
-                        .or_default()
-                        .retain(|x| *x != addr);
+        let text: Message = Message::Text(text.into());
+
+        if let Some(listeners) = self.topic_listeners.get(&topic) {
+            for addr in listeners.clone() {
+                if !self.send_to_peer(&addr, text.clone()) {
+                    debug!("Failed to send message to topic {topic} for {addr}");
                 }
             }
         } else {
@@ -286,61 +355,60 @@ where
         Ok(())
     }
 
-    async fn broadcast_message(&mut self, msg: Out) -> Result<()> {
-        let mut at_least_one_ok = false;
-
+    fn peer_message(&mut self, addr: PeerAddress, msg: Out) -> Result<()> {
         let text = serde_json::to_string(&msg).context("Failed to serialize outbound message")?;
-        let text: Message = Message::Text(text.clone().into());
-
-        let send_futures: Vec<_> = self
-            .peer_senders
-            .iter_mut()
-            .map(|(addr, peer)| {
-                let text = text.clone();
-                let addr = addr.clone();
-                async move { (addr, peer.send(text).await) }
-            })
-            .collect();
+        if !self.send_to_peer(&addr, Message::Text(text.into())) {
+            debug!("Failed to send message to WebSocket {}", addr);
+        }
+        Ok(())
+    }
 
-        let results = futures::future::join_all(send_futures).await;
+    async fn broadcast_message(&mut self, msg: Out) -> Result<()> {
+        let text = serde_json::to_string(&msg).context("Failed to serialize outbound message")?;
+        let text: Message = Message::Text(text.into());
 
-        for (addr, result) in results {
-            match result {
-                Ok(_) => {
-                    at_least_one_ok = true;
-                }
-                Err(e) => {
-                    debug!("Failed to send message to WebSocket {}: {}", addr, e);
-                    self.peer_senders.remove(&addr);
-                }
+        let peers: Vec<PeerAddress> = self.peer_senders.keys().cloned().collect();
+        for addr in peers {
+            if !self.send_to_peer(&addr, text.clone()) {
+                debug!("Failed to send message to WebSocket {}", addr);
             }
         }
 
-        if at_least_one_ok || self.peer_senders.is_empty() {
-            Ok(())
-        } else {
-            Err(anyhow!("Failed to send message to all WebSocket peers"))
+        Ok(())
+    }
+
+    async fn process_websocket_outgoing(
+        mut sink: SplitSink<WebSocket, Message>,
+        mut outgoing: mpsc::Receiver<Message>,
+    ) {
+        while let Some(msg) = outgoing.recv().await {
+            if let Err(e) = sink.send(msg).await {
+                debug!("Failed to send message to WebSocket: {}", e);
+                break;
+            }
         }
+        let _ = sink.close().await;
     }
 
     async fn process_websocket_incoming(
         addr: String,
         mut receiver: SplitStream<WebSocket>,
-    ) -> Option<(
+    ) -> (
         PeerAddress,
-        SplitStream<WebSocket>,
-        Result<WsMsg<In>, Error>,
-    )> {
+        Option<(SplitStream<WebSocket>, Result<WsMsg<In>, Error>)>,
+    ) {
         while let Some(msg) = receiver.next().await {
             match msg {
                 Ok(Message::Text(text)) => {
                     debug!("Received message: {:?}", text);
-                    return Some((
+                    return (
                         addr,
-                        receiver,
-                        serde_json::from_str::<WsMsg<In>>(text.as_str())
-                            .context("Failed to parse message"),
-                    ));
+                        Some((
+                            receiver,
+                            serde_json::from_str::<WsMsg<In>>(text.as_str())
+                                .context("Failed to parse message"),
+                        )),
+                    );
                 }
                 Ok(Message::Close(_)) => {
                     debug!("Client initiated close");
@@ -355,7 +423,7 @@ where
                 } // Ignore other message types
             }
         }
-        None
+        (addr, None)
     }
 }
 
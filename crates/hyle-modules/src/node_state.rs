@@ -6,7 +6,11 @@ use contract_registration::validate_contract_registration_metadata;
 use contract_registration::{validate_contract_name_registration, validate_state_commitment_size};
 use hyle_tld::{handle_blob_for_hyle_tld, validate_hyle_contract_blobs};
 use metrics::NodeStateMetrics;
+use name_service::{check_lease_fees, ContractLeases, LeaseFee};
 use ordered_tx_map::OrderedTxMap;
+use priority_fee::check_priority_fee;
+use program_id_updates::ProgramIdUpdates;
+use sdk::api::{APIHyleTldBlobSimulation, APITxSimulation};
 use sdk::verifiers::{NativeVerifiers, NATIVE_VERIFIERS_CONTRACT_LIST};
 use sdk::*;
 use serde::{Deserialize, Serialize};
@@ -15,11 +19,18 @@ use timeouts::Timeouts;
 use tracing::{debug, error, info, trace};
 
 mod api;
+pub mod archive;
 pub mod contract_registration;
+pub mod history;
 mod hyle_tld;
 pub mod metrics;
 pub mod module;
+mod name_service;
 mod ordered_tx_map;
+pub mod priority_fee;
+mod program_id_updates;
+pub mod snapshot;
+pub mod store_file;
 mod timeouts;
 
 #[derive(Debug, Clone)]
@@ -29,6 +40,11 @@ enum SideEffect {
     UpdateState,
     UpdateProgramId,
     UpdateTimeoutWindow,
+    ScheduleProgramId(ProgramId, BlockHeight),
+    CancelProgramIdUpdate,
+    TransferOwnership,
+    ConfigureNameService,
+    Lease(LeaseFee),
     Delete,
 }
 
@@ -38,6 +54,10 @@ pub struct ModifiedContractFields {
     pub state: bool,
     pub verifier: bool,
     pub timeout_window: bool,
+    pub min_program_id_update_delay: bool,
+    pub owner: bool,
+    pub name_service: bool,
+    pub expiry_height: bool,
 }
 
 impl ModifiedContractFields {
@@ -47,6 +67,10 @@ impl ModifiedContractFields {
             state: true,
             verifier: true,
             timeout_window: true,
+            min_program_id_update_delay: true,
+            owner: true,
+            name_service: true,
+            expiry_height: true,
         }
     }
 }
@@ -141,6 +165,8 @@ impl std::ops::DerefMut for NodeState {
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct NodeStateStore {
     timeouts: Timeouts,
+    program_id_updates: ProgramIdUpdates,
+    contract_leases: ContractLeases,
     pub current_height: BlockHeight,
     // This field is public for testing purposes
     pub contracts: HashMap<ContractName, Contract>,
@@ -155,6 +181,10 @@ pub fn hyle_contract_definition() -> Contract {
         state: StateCommitment::default(),
         verifier: Verifier("hyle".to_owned()),
         timeout_window: TimeoutWindow::NoTimeout,
+        owner: None,
+        name_service: None,
+        expiry_height: None,
+        min_program_id_update_delay: None,
     }
 }
 
@@ -163,6 +193,8 @@ impl Default for NodeStateStore {
     fn default() -> Self {
         let mut ret = Self {
             timeouts: Timeouts::default(),
+            program_id_updates: ProgramIdUpdates::default(),
+            contract_leases: ContractLeases::default(),
             current_height: BlockHeight(0),
             contracts: HashMap::new(),
             unsettled_transactions: OrderedTxMap::default(),
@@ -211,6 +243,15 @@ impl NodeState {
                     _ => None,
                 })
                 .collect(),
+            new_unbonded_validators: signed_block
+                .consensus_proposal
+                .staking_actions
+                .iter()
+                .filter_map(|v| match v {
+                    ConsensusStakingAction::Unbond { validator } => Some(validator.clone()),
+                    _ => None,
+                })
+                .collect(),
             timed_out_txs: vec![], // Added below as it needs the block
             dropped_duplicate_txs: vec![],
             registered_contracts: BTreeMap::new(),
@@ -218,12 +259,21 @@ impl NodeState {
             updated_states: BTreeMap::new(),
             updated_program_ids: BTreeMap::new(),
             updated_timeout_windows: BTreeMap::new(),
+            scheduled_program_ids: BTreeMap::new(),
+            cancelled_program_ids: BTreeSet::new(),
+            updated_min_program_id_delays: BTreeMap::new(),
+            updated_owners: BTreeMap::new(),
+            updated_name_services: BTreeMap::new(),
+            updated_expiries: BTreeMap::new(),
+            expired_contracts: BTreeSet::new(),
             transactions_events: BTreeMap::new(),
             dp_parent_hashes: BTreeMap::new(),
             lane_ids: BTreeMap::new(),
         };
 
         self.clear_timeouts(&mut block_under_construction);
+        self.activate_program_id_updates(&mut block_under_construction);
+        self.expire_contracts(&mut block_under_construction);
 
         let mut next_unsettled_txs = BTreeSet::new();
         // Handle all transactions
@@ -419,6 +469,63 @@ impl NodeState {
         }
     }
 
+    /// Runs the checks a blob transaction goes through when sequenced, without mutating the state.
+    pub fn simulate_blob_tx(&self, tx: &BlobTransaction) -> APITxSimulation {
+        let identity_error = tx.validate_identity().err().map(|e| e.to_string());
+        let hyle_blobs_error = validate_hyle_contract_blobs(tx).err();
+        let unknown_contracts: Vec<ContractName> = tx
+            .blobs
+            .iter()
+            .map(|blob| &blob.contract_name)
+            .filter(|contract_name| !self.contracts.contains_key(*contract_name))
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        // Blobs for the hyle TLD are settled in order, each seeing the changes of the previous ones
+        let mut contract_changes = BTreeMap::new();
+        let mut hyle_tld_blobs = vec![];
+        for (index, blob) in tx.blobs.iter().enumerate() {
+            if blob.contract_name.0 != "hyle" {
+                continue;
+            }
+            let error = handle_blob_for_hyle_tld(
+                &self.contracts,
+                &mut contract_changes,
+                blob,
+                &tx.identity,
+            )
+            .err()
+            .map(|e| e.to_string());
+            let failed = error.is_some();
+            hyle_tld_blobs.push(APIHyleTldBlobSimulation {
+                blob_index: BlobIndex(index),
+                error,
+            });
+            if failed {
+                break;
+            }
+        }
+
+        APITxSimulation {
+            tx_hash: tx.hashed(),
+            block_height: self.current_height,
+            accepted: identity_error.is_none()
+                && hyle_blobs_error.is_none()
+                && unknown_contracts.is_empty()
+                && !tx.blobs.is_empty(),
+            identity_error,
+            hyle_blobs_error,
+            unknown_contracts,
+            timeout_window: match self.get_tx_timeout_window(&tx.blobs) {
+                TimeoutWindow::NoTimeout => None,
+                TimeoutWindow::Timeout(window) => Some(window.0),
+            },
+            hyle_tld_blobs,
+        }
+    }
+
     fn handle_blob_tx(
         &mut self,
         parent_dp_hash: DataProposalHash,
@@ -522,6 +629,7 @@ impl NodeState {
             tx_context,
             blobs_hash,
             blobs,
+            fee: tx.fee,
         }) {
             Some(should_settle) => should_try_and_settle = should_settle && should_try_and_settle,
             None => {
@@ -730,6 +838,8 @@ impl NodeState {
                 &self.contracts,
                 updated_contracts,
                 unsettled_tx.blobs.values(),
+                &unsettled_tx.identity,
+                self.current_height,
                 vec![],
                 events,
             ) {
@@ -740,6 +850,39 @@ impl NodeState {
             }
         };
 
+        // Names leased by the TX must be paid for by the TX itself, or it settles as failed.
+        let result = result.and_then(|res| {
+            match check_lease_fees(
+                &res.contract_changes,
+                unsettled_tx.blobs.values().map(|b| &b.blob),
+            ) {
+                Ok(()) => Ok(res),
+                Err(err) => {
+                    let msg = format!("Could not settle leases: {err}");
+                    debug!("{msg}");
+                    events.push(TransactionStateEvent::SettleEvent(msg));
+                    Err(())
+                }
+            }
+        });
+
+        // The priority fee must be paid to the validator whose lane carried the TX, or it settles as failed.
+        let result = result.and_then(|res| {
+            match check_priority_fee(
+                unsettled_tx.fee,
+                &unsettled_tx.tx_context.lane_id,
+                unsettled_tx.blobs.values().map(|b| &b.blob),
+            ) {
+                Ok(()) => Ok(res),
+                Err(err) => {
+                    let msg = format!("Could not settle priority fee: {err}");
+                    debug!("{msg}");
+                    events.push(TransactionStateEvent::SettleEvent(msg));
+                    Err(())
+                }
+            }
+        });
+
         // If some blobs are still sequenced behind others, we can only settle this TX as failed.
         // (failed TX won't change the state, so we can settle it right away).
         if result.is_ok()
@@ -771,6 +914,8 @@ impl NodeState {
         contracts: &HashMap<ContractName, Contract>,
         mut contract_changes: BTreeMap<ContractName, ModifiedContractData>,
         mut blob_iter: impl Iterator<Item = &'a UnsettledBlobMetadata> + Clone,
+        identity: &Identity,
+        current_height: BlockHeight,
         mut blob_proof_output_indices: Vec<usize>,
         events: &mut Vec<TransactionStateEvent>,
     ) -> Option<Result<SettlementResult, ()>> {
@@ -799,6 +944,7 @@ impl NodeState {
                 contracts,
                 &mut contract_changes,
                 &current_blob.blob,
+                identity,
             ) {
                 Ok(()) => {
                     tracing::trace!("Settlement - OK side effect");
@@ -806,6 +952,8 @@ impl NodeState {
                         contracts,
                         contract_changes,
                         blob_iter.clone(),
+                        identity,
+                        current_height,
                         blob_proof_output_indices.clone(),
                         events,
                     )
@@ -834,6 +982,7 @@ impl NodeState {
                 contract_name,
                 proof_metadata,
                 current_blob,
+                current_height,
             ) {
                 // Not a valid proof, log it and try the next one.
                 let msg = format!(
@@ -860,6 +1009,8 @@ impl NodeState {
                 contracts,
                 current_contracts,
                 blob_iter.clone(),
+                identity,
+                current_height,
                 blob_proof_output_indices.clone(),
                 events,
             ) {
@@ -944,9 +1095,11 @@ impl NodeState {
                 if let Ok(structured_blob) = StructuredBlob::try_from(blob) {
                     let staking_action: StakingAction = structured_blob.data.parameters;
 
-                    block_under_construction
-                        .staking_actions
-                        .push((settled_tx.identity.clone(), staking_action));
+                    block_under_construction.staking_actions.push((
+                        settled_tx.identity.clone(),
+                        staking_action,
+                        settled_tx.tx_context.block_height,
+                    ));
                 } else {
                     error!("Failed to parse StakingAction");
                 }
@@ -958,46 +1111,27 @@ impl NodeState {
             match mc {
                 None => {
                     debug!("✏️ Delete {} contract", contract_name);
-                    self.contracts.remove(&contract_name);
-
-                    let mut potentially_blocked_contracts = HashSet::new();
-
-                    // Time-out all transactions for this contract
-                    while let Some(tx_hash) = self
-                        .unsettled_transactions
-                        .get_next_unsettled_tx(&contract_name)
-                        .cloned()
-                    {
-                        if let Some(popped_tx) = self.unsettled_transactions.remove(&tx_hash) {
-                            info!("⏳ Timeout tx {} (from contract deletion)", &tx_hash);
-
-                            potentially_blocked_contracts
-                                .extend(OrderedTxMap::get_contracts_blocked_by_tx(&popped_tx));
-                            block_under_construction
-                                .transactions_events
-                                .entry(tx_hash.clone())
-                                .or_default()
-                                .push(TransactionStateEvent::TimedOut);
-                            block_under_construction
-                                .dp_parent_hashes
-                                .insert(tx_hash.clone(), popped_tx.parent_dp_hash);
-                            block_under_construction
-                                .lane_ids
-                                .insert(tx_hash, popped_tx.tx_context.lane_id);
-                        }
-                    }
-
-                    for contract in potentially_blocked_contracts {
-                        if let Some(tx_hash) =
-                            self.unsettled_transactions.get_next_unsettled_tx(&contract)
-                        {
-                            next_txs_to_try_and_settle.insert(tx_hash.clone());
-                        }
-                    }
+                    next_txs_to_try_and_settle
+                        .extend(self.remove_contract(block_under_construction, &contract_name));
 
                     block_under_construction
                         .registered_contracts
                         .remove(&contract_name);
+                    block_under_construction
+                        .updated_owners
+                        .remove(&contract_name);
+                    block_under_construction
+                        .updated_min_program_id_delays
+                        .remove(&contract_name);
+                    block_under_construction
+                        .updated_name_services
+                        .remove(&contract_name);
+                    block_under_construction
+                        .updated_expiries
+                        .remove(&contract_name);
+                    block_under_construction
+                        .scheduled_program_ids
+                        .remove(&contract_name);
 
                     block_under_construction
                         .deleted_contracts
@@ -1011,9 +1145,9 @@ impl NodeState {
                         info!("📝 Registering contract {}", contract_name);
 
                         // Let's find the metadata - for now it's unsupported to register the same contract twice in a single TX.
-                        let metadata = side_effects.into_iter().find_map(|se| {
+                        let metadata = side_effects.iter().find_map(|se| {
                             if let SideEffect::Register(m) = se {
-                                Some(m)
+                                Some(m.clone())
                             } else {
                                 None
                             }
@@ -1048,6 +1182,89 @@ impl NodeState {
                     self.contracts
                         .insert(contract.name.clone(), contract.clone());
 
+                    for side_effect in &side_effects {
+                        match side_effect {
+                            SideEffect::ScheduleProgramId(program_id, activation_delay) => {
+                                let current_height = self.current_height;
+                                let update = self.program_id_updates.schedule(
+                                    contract_name.clone(),
+                                    program_id.clone(),
+                                    current_height,
+                                    *activation_delay,
+                                );
+                                debug!(
+                                    "⏲️  Schedule '{}' program_id {} at height {}",
+                                    &contract_name,
+                                    hex::encode(&program_id.0),
+                                    update.activation_height
+                                );
+                                block_under_construction
+                                    .cancelled_program_ids
+                                    .remove(&contract_name);
+                                block_under_construction
+                                    .scheduled_program_ids
+                                    .insert(contract_name.clone(), update);
+                            }
+                            SideEffect::CancelProgramIdUpdate => {
+                                if self.program_id_updates.cancel(&contract_name).is_none() {
+                                    continue;
+                                }
+                                debug!("⏲️  Cancel '{}' pending program_id update", &contract_name);
+                                block_under_construction
+                                    .scheduled_program_ids
+                                    .remove(&contract_name);
+                                block_under_construction
+                                    .cancelled_program_ids
+                                    .insert(contract_name.clone());
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    if fields.min_program_id_update_delay {
+                        if let Some(min_delay) = contract.min_program_id_update_delay {
+                            debug!(
+                                "⏲️  Modify '{}' minimum program_id update delay to {}",
+                                &contract_name, min_delay
+                            );
+
+                            block_under_construction
+                                .updated_min_program_id_delays
+                                .insert(contract.name.clone(), min_delay);
+                        }
+                    }
+                    if fields.owner {
+                        if let Some(owner) = &contract.owner {
+                            debug!("✍️  Modify '{}' owner to {}", &contract_name, owner);
+
+                            block_under_construction
+                                .updated_owners
+                                .insert(contract.name.clone(), owner.clone());
+                        }
+                    }
+                    if fields.name_service {
+                        if let Some(config) = &contract.name_service {
+                            debug!(
+                                "🏷️  Modify '{}' name service to {} {} per block",
+                                &contract_name, config.fee_per_block, config.fee_token
+                            );
+
+                            block_under_construction
+                                .updated_name_services
+                                .insert(contract.name.clone(), config.clone());
+                        }
+                    }
+                    if fields.expiry_height {
+                        if let Some(expiry_height) = contract.expiry_height {
+                            debug!("🏷️  Lease '{}' until {}", &contract_name, expiry_height);
+
+                            self.contract_leases
+                                .set(contract.name.clone(), expiry_height);
+                            block_under_construction
+                                .updated_expiries
+                                .insert(contract.name.clone(), expiry_height);
+                        }
+                    }
                     if fields.state {
                         debug!(
                             "✍️  Modify '{}' state to {}",
@@ -1296,6 +1513,7 @@ impl NodeState {
         contract_name: &ContractName,
         proof_metadata: &(ProgramId, HyleOutput),
         current_blob: &UnsettledBlobMetadata,
+        current_height: BlockHeight,
     ) -> Result<()> {
         validate_state_commitment_size(&proof_metadata.1.next_state)?;
 
@@ -1344,6 +1562,32 @@ impl NodeState {
                         &effect.state_commitment,
                     )?;
 
+                    // Names of a name service are reserved until they expire, and must be leased
+                    if contract.name_service.is_some() && effect.contract_name != contract.name {
+                        if Self::get_contract(contracts, contract_changes, &effect.contract_name)
+                            .is_ok()
+                        {
+                            bail!("Name {} is already taken", effect.contract_name);
+                        }
+                        let leased = proof_metadata
+                            .1
+                            .onchain_effects
+                            .iter()
+                            .any(|eff| match eff {
+                                OnchainEffect::LeaseContract(lease) => {
+                                    lease.contract_name == effect.contract_name
+                                }
+                                _ => false,
+                            });
+                        if !leased {
+                            bail!(
+                                "Name {} is registered without a lease from {}",
+                                effect.contract_name,
+                                contract.name
+                            );
+                        }
+                    }
+
                     let metadata = StructuredBlobData::<RegisterContractAction>::try_from(
                         current_blob.blob.data.clone(),
                     )?;
@@ -1360,6 +1604,11 @@ impl NodeState {
                                     .timeout_window
                                     .clone()
                                     .unwrap_or(contract.timeout_window.clone()),
+                                // Sub-contracts are managed by the TLD that registered them
+                                owner: None,
+                                name_service: None,
+                                expiry_height: None,
+                                min_program_id_update_delay: None,
                             }),
                             ModifiedContractFields::all(),
                             vec![SideEffect::Register(
@@ -1385,6 +1634,75 @@ impl NodeState {
                             )
                         });
                 }
+                OnchainEffect::LeaseContract(lease) => {
+                    let Some(config) = &contract.name_service else {
+                        bail!("Contract {} is not a name service", contract.name);
+                    };
+                    validate_contract_name_registration(&contract.name, &lease.contract_name)?;
+                    if lease.contract_name == contract.name {
+                        bail!("Name service {} cannot lease itself", contract.name);
+                    }
+                    if lease.period.0 == 0 || lease.period > config.max_period {
+                        bail!(
+                            "Lease period {} must be between 1 and {} blocks",
+                            lease.period,
+                            config.max_period
+                        );
+                    }
+
+                    // New names are leased from now on, renewals extend the current lease
+                    let leased =
+                        Self::get_contract(contracts, contract_changes, &lease.contract_name)?
+                            .clone();
+                    let lease_start = leased
+                        .expiry_height
+                        .map_or(current_height, |expiry| expiry.max(current_height));
+                    let expiry_height = lease_start
+                        .0
+                        .checked_add(lease.period.0)
+                        .map(BlockHeight)
+                        .context("Lease expiry overflow")?;
+                    if expiry_height.0 > current_height.0.saturating_add(config.max_period.0) {
+                        bail!(
+                            "Lease of {} cannot end at {}, more than {} blocks from now",
+                            lease.contract_name,
+                            expiry_height,
+                            config.max_period
+                        );
+                    }
+                    let amount = config
+                        .fee_per_block
+                        .checked_mul(lease.period.0 as u128)
+                        .context("Lease fee overflow")?;
+
+                    let fee = SideEffect::Lease(LeaseFee {
+                        token: config.fee_token.clone(),
+                        recipient: config.fee_recipient.clone(),
+                        amount,
+                    });
+                    contract_changes
+                        .entry(lease.contract_name.clone())
+                        .and_modify(|c| {
+                            if let Some(contract) = c.0.as_mut() {
+                                contract.expiry_height = Some(expiry_height);
+                            }
+                            c.1.expiry_height = true;
+                            c.2.push(fee.clone());
+                        })
+                        .or_insert_with(|| {
+                            (
+                                Some(Contract {
+                                    expiry_height: Some(expiry_height),
+                                    ..leased
+                                }),
+                                ModifiedContractFields {
+                                    expiry_height: true,
+                                    ..ModifiedContractFields::default()
+                                },
+                                vec![fee],
+                            )
+                        });
+                }
             }
         }
 
@@ -1450,21 +1768,7 @@ impl NodeState {
                     self.settle_txs_until_done(block_under_construction, blob_tx_to_try_and_settle);
 
                 // For each transaction that could not be settled, if it is the next one to be settled, reset its timeout
-                for unsettled_tx in next_unsettled_txs {
-                    if self.unsettled_transactions.is_next_to_settle(&unsettled_tx) {
-                        let block_height = self.current_height;
-                        #[allow(clippy::unwrap_used, reason = "must exist because of above checks")]
-                        let tx = self.unsettled_transactions.get(&unsettled_tx).unwrap();
-                        // Get the contract's timeout window
-                        let timeout_window =
-                            self.get_tx_timeout_window(tx.blobs.values().map(|b| &b.blob));
-                        if let TimeoutWindow::Timeout(timeout_window) = timeout_window {
-                            // Set the timeout for the transaction
-                            self.timeouts
-                                .set(unsettled_tx.clone(), block_height, timeout_window);
-                        }
-                    }
-                }
+                self.reset_timeouts(next_unsettled_txs);
 
                 true
             } else {
@@ -1474,6 +1778,132 @@ impl NodeState {
 
         block_under_construction.timed_out_txs = txs_at_timeout;
     }
+
+    /// Apply the program id changes scheduled to activate at this block.
+    fn activate_program_id_updates(&mut self, block_under_construction: &mut Block) {
+        let updates = self
+            .program_id_updates
+            .drop(&block_under_construction.block_height);
+        for (contract_name, program_id) in updates {
+            let Some(contract) = self.contracts.get_mut(&contract_name) else {
+                continue;
+            };
+            info!(
+                "⏲️  Activating '{}' program_id {}",
+                &contract_name,
+                hex::encode(&program_id.0)
+            );
+            contract.program_id = program_id.clone();
+            block_under_construction
+                .updated_program_ids
+                .insert(contract_name, program_id);
+        }
+    }
+
+    /// Remove the names whose lease ends at this block.
+    /// Their unsettled transactions time out, as for a deleted contract.
+    fn expire_contracts(&mut self, block_under_construction: &mut Block) {
+        let block_height = block_under_construction.block_height;
+        let mut blob_tx_to_try_and_settle = BTreeSet::new();
+        for contract_name in self.contract_leases.drop(&block_height) {
+            // Skip names renewed or deleted since
+            if self
+                .contracts
+                .get(&contract_name)
+                .is_none_or(|c| c.expiry_height != Some(block_height))
+            {
+                continue;
+            }
+            info!("🏷️  Lease of '{}' expired", &contract_name);
+            blob_tx_to_try_and_settle
+                .extend(self.remove_contract(block_under_construction, &contract_name));
+            block_under_construction
+                .expired_contracts
+                .insert(contract_name);
+        }
+        if blob_tx_to_try_and_settle.is_empty() {
+            return;
+        }
+
+        let next_unsettled_txs =
+            self.settle_txs_until_done(block_under_construction, blob_tx_to_try_and_settle);
+        self.reset_timeouts(next_unsettled_txs);
+    }
+
+    /// Remove a contract, timing out all its unsettled transactions.
+    /// Returns the transactions they were blocking, to try and settle next.
+    fn remove_contract(
+        &mut self,
+        block_under_construction: &mut Block,
+        contract_name: &ContractName,
+    ) -> BTreeSet<TxHash> {
+        self.contracts.remove(contract_name);
+
+        let mut potentially_blocked_contracts = HashSet::new();
+
+        // Time-out all transactions for this contract
+        while let Some(tx_hash) = self
+            .unsettled_transactions
+            .get_next_unsettled_tx(contract_name)
+            .cloned()
+        {
+            if let Some(popped_tx) = self.unsettled_transactions.remove(&tx_hash) {
+                info!("⏳ Timeout tx {} (from contract removal)", &tx_hash);
+
+                potentially_blocked_contracts
+                    .extend(OrderedTxMap::get_contracts_blocked_by_tx(&popped_tx));
+                block_under_construction
+                    .transactions_events
+                    .entry(tx_hash.clone())
+                    .or_default()
+                    .push(TransactionStateEvent::TimedOut);
+                block_under_construction
+                    .dp_parent_hashes
+                    .insert(tx_hash.clone(), popped_tx.parent_dp_hash);
+                block_under_construction
+                    .lane_ids
+                    .insert(tx_hash, popped_tx.tx_context.lane_id);
+            }
+        }
+
+        // A pending program id change dies with the contract
+        self.program_id_updates.cancel(contract_name);
+
+        potentially_blocked_contracts
+            .into_iter()
+            .filter_map(|contract| {
+                self.unsettled_transactions
+                    .get_next_unsettled_tx(&contract)
+                    .cloned()
+            })
+            .collect()
+    }
+
+    /// Set a timeout for the transactions that are now next to settle
+    fn reset_timeouts(&mut self, unsettled_txs: BTreeSet<TxHash>) {
+        for unsettled_tx in unsettled_txs {
+            if self.unsettled_transactions.is_next_to_settle(&unsettled_tx) {
+                let block_height = self.current_height;
+                #[allow(clippy::unwrap_used, reason = "must exist because of above checks")]
+                let tx = self.unsettled_transactions.get(&unsettled_tx).unwrap();
+                // Get the contract's timeout window
+                let timeout_window = self.get_tx_timeout_window(tx.blobs.values().map(|b| &b.blob));
+                if let TimeoutWindow::Timeout(timeout_window) = timeout_window {
+                    // Set the timeout for the transaction
+                    self.timeouts
+                        .set(unsettled_tx.clone(), block_height, timeout_window);
+                }
+            }
+        }
+    }
+
+    /// Program id change waiting to activate for a contract, if any
+    pub fn get_pending_program_id_update(
+        &self,
+        contract_name: &ContractName,
+    ) -> Option<&PendingProgramIdUpdate> {
+        self.program_id_updates.get(contract_name)
+    }
 }
 
 #[cfg(any(test, feature = "test"))]
@@ -1756,6 +2186,10 @@ pub mod test {
                     state: tx.state_commitment.clone(),
                     verifier: tx.verifier.clone(),
                     timeout_window: tx.timeout_window.clone().unwrap_or_default(),
+                    owner: None,
+                    name_service: None,
+                    expiry_height: None,
+                    min_program_id_update_delay: None,
                 },
             );
         }
@@ -1,12 +1,15 @@
 use anyhow::anyhow;
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query as QueryParams, State},
     http::StatusCode,
     response::IntoResponse,
     Json, Router,
 };
 use client_sdk::contract_indexer::AppError;
-use sdk::{api::APINodeContract, *};
+use sdk::{
+    api::{APIContractHistoryEntry, APINodeContract, APITxSimulation},
+    *,
+};
 use tracing::error;
 use utoipa::OpenApi;
 use utoipa_axum::{router::OpenApiRouter, routes};
@@ -19,7 +22,10 @@ use crate::{
         SharedMessageBus,
     },
     modules::signal::ShutdownModule,
-    node_state::module::{QueryBlockHeight, QueryUnsettledTx, QueryUnsettledTxCount},
+    node_state::module::{
+        QueryBlockHeight, QueryContractAtHeight, QueryContractHistory, QueryPendingProgramIdUpdate,
+        QueryTxSimulation, QueryUnsettledTx, QueryUnsettledTxCount,
+    },
 };
 
 use super::module::{NodeStateCtx, QuerySettledHeight};
@@ -27,10 +33,14 @@ use super::module::{NodeStateCtx, QuerySettledHeight};
 bus_client! {
 struct RestBusClient {
     sender(Query<ContractName, (BlockHeight, Contract)>),
+    sender(Query<QueryContractAtHeight, Contract>),
+    sender(Query<QueryContractHistory, Vec<APIContractHistoryEntry>>),
+    sender(Query<QueryPendingProgramIdUpdate, PendingProgramIdUpdate>),
     sender(Query<QuerySettledHeight, BlockHeight>),
     sender(Query<QueryUnsettledTxCount, u64>),
     sender(Query<QueryBlockHeight, BlockHeight>),
     sender(Query<QueryUnsettledTx, UnsettledBlobTransaction>),
+    sender(Query<QueryTxSimulation, APITxSimulation>),
     receiver(ShutdownModule),
 }
 }
@@ -50,11 +60,14 @@ pub async fn api(bus: SharedMessageBus, ctx: &NodeStateCtx) -> Router<()> {
     let (router, api) = OpenApiRouter::with_openapi(NodeStateAPI::openapi())
         .routes(routes!(get_block_height))
         .routes(routes!(get_contract))
+        .routes(routes!(get_contract_history))
+        .routes(routes!(get_contract_pending_program_id))
         .routes(routes!(get_contract_settled_height))
         .routes(routes!(get_contract_unsettled_txs_count))
         .routes(routes!(get_unsettled_txs_count))
         // TODO: figure out if we want to rely on the indexer instead
         .routes(routes!(get_unsettled_tx))
+        .routes(routes!(simulate_tx))
         .split_for_parts();
 
     if let Ok(mut o) = ctx.api.openapi.lock() {
@@ -64,11 +77,18 @@ pub async fn api(bus: SharedMessageBus, ctx: &NodeStateCtx) -> Router<()> {
     router.with_state(state)
 }
 
+#[derive(Debug, serde::Deserialize)]
+pub struct ContractParams {
+    #[serde(alias = "height")]
+    pub at_height: Option<u64>,
+}
+
 #[utoipa::path(
     get,
     path = "/contract/{name}",
     params(
-        ("name" = String, Path, description = "Contract name")
+        ("name" = String, Path, description = "Contract name"),
+        ("at_height" = Option<u64>, Query, description = "Get the state at this block height instead of the current one, from the height the node started recording its history; archive nodes also return the owner and lease of past states")
     ),
     tag = "Node State",
     responses(
@@ -77,10 +97,19 @@ pub async fn api(bus: SharedMessageBus, ctx: &NodeStateCtx) -> Router<()> {
 )]
 pub async fn get_contract(
     Path(name): Path<ContractName>,
+    QueryParams(params): QueryParams<ContractParams>,
     State(mut state): State<RouterState>,
 ) -> Result<impl IntoResponse, AppError> {
     let name_clone = name.clone();
-    match state.bus.shutdown_aware_request::<()>(name).await {
+    let response = match params.at_height.map(BlockHeight) {
+        Some(height) => state
+            .bus
+            .shutdown_aware_request::<()>(QueryContractAtHeight(name, height))
+            .await
+            .map(|contract| (height, contract)),
+        None => state.bus.shutdown_aware_request::<()>(name).await,
+    };
+    match response {
         Ok((block_height, contract)) => Ok(Json(APINodeContract {
             contract_name: name_clone,
             state_block_height: block_height,
@@ -94,6 +123,11 @@ pub async fn get_contract(
         })),
         err => {
             if let Err(e) = err.as_ref() {
+                if e.to_string().contains("is not archived")
+                    || e.to_string().contains("is not recorded")
+                {
+                    return Err(AppError(StatusCode::NOT_FOUND, anyhow!("{}", e)));
+                }
                 if e.to_string().contains("Contract not found") {
                     return Err(AppError(
                         StatusCode::NOT_FOUND,
@@ -111,6 +145,90 @@ pub async fn get_contract(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/contract/{name}/history",
+    params(
+        ("name" = String, Path, description = "Contract name")
+    ),
+    description = "Changes of the state commitment, program id, verifier or timeout window of the contract, oldest first, ending with a deletion entry if it was deleted. Archive nodes also return changes of its owner and lease",
+    tag = "Node State",
+    responses(
+        (status = OK, body = Vec<APIContractHistoryEntry>)
+    )
+)]
+pub async fn get_contract_history(
+    Path(name): Path<ContractName>,
+    State(mut state): State<RouterState>,
+) -> Result<impl IntoResponse, AppError> {
+    let name_clone = name.clone();
+    match state
+        .bus
+        .shutdown_aware_request::<()>(QueryContractHistory(name))
+        .await
+    {
+        Ok(history) => Ok(Json(history)),
+        err => {
+            if let Err(e) = err.as_ref() {
+                if e.to_string().contains("not found") {
+                    return Err(AppError(
+                        StatusCode::NOT_FOUND,
+                        anyhow!("Contract {} not found", name_clone),
+                    ));
+                }
+            }
+            error!("{:?}", err);
+
+            Err(AppError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                anyhow!("Error while getting history of contract {}", name_clone),
+            ))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/contract/{name}/pending_program_id",
+    params(
+        ("name" = String, Path, description = "Contract name")
+    ),
+    description = "Program id change scheduled for the contract, and the height it activates at",
+    tag = "Node State",
+    responses(
+        (status = OK, body = PendingProgramIdUpdate)
+    )
+)]
+pub async fn get_contract_pending_program_id(
+    Path(name): Path<ContractName>,
+    State(mut state): State<RouterState>,
+) -> Result<impl IntoResponse, AppError> {
+    let name_clone = name.clone();
+    match state
+        .bus
+        .shutdown_aware_request::<()>(QueryPendingProgramIdUpdate(name))
+        .await
+    {
+        Ok(update) => Ok(Json(update)),
+        err => {
+            if let Err(e) = err.as_ref() {
+                if e.to_string().contains("not found") {
+                    return Err(AppError(StatusCode::NOT_FOUND, anyhow!("{e}")));
+                }
+            }
+            error!("{:?}", err);
+
+            Err(AppError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                anyhow!(
+                    "Error while getting pending program id of contract {}",
+                    name_clone
+                ),
+            ))
+        }
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/contract/{name}/settled_height",
@@ -271,6 +389,36 @@ pub async fn get_block_height(
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/tx/simulate",
+    tag = "Node State",
+    description = "Checks how the node would handle a blob transaction, without submitting it",
+    responses(
+        (status = OK, body = APITxSimulation)
+    )
+)]
+pub async fn simulate_tx(
+    State(mut state): State<RouterState>,
+    Json(tx): Json<BlobTransaction>,
+) -> Result<impl IntoResponse, AppError> {
+    match state
+        .bus
+        .shutdown_aware_request::<()>(QueryTxSimulation(tx))
+        .await
+    {
+        Ok(simulation) => Ok(Json(simulation)),
+        err => {
+            error!("{:?}", err);
+
+            Err(AppError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                anyhow!("Error while simulating transaction"),
+            ))
+        }
+    }
+}
+
 impl Clone for RouterState {
     fn clone(&self) -> Self {
         use crate::utils::static_type_map::Pick;
@@ -281,6 +429,22 @@ impl Clone for RouterState {
                     &self.bus,
                 )
                 .clone(),
+                Pick::<tokio::sync::broadcast::Sender<Query<QueryContractAtHeight, Contract>>>::get(
+                    &self.bus,
+                )
+                .clone(),
+                Pick::<
+                    tokio::sync::broadcast::Sender<
+                        Query<QueryContractHistory, Vec<APIContractHistoryEntry>>,
+                    >,
+                >::get(&self.bus)
+                .clone(),
+                Pick::<
+                    tokio::sync::broadcast::Sender<
+                        Query<QueryPendingProgramIdUpdate, PendingProgramIdUpdate>,
+                    >,
+                >::get(&self.bus)
+                .clone(),
                 Pick::<
                     tokio::sync::broadcast::Sender<
                         Query<QuerySettledHeight, BlockHeight>,
@@ -301,6 +465,10 @@ impl Clone for RouterState {
                     >,
                 >::get(&self.bus)
                 .clone(),
+                Pick::<
+                    tokio::sync::broadcast::Sender<Query<QueryTxSimulation, APITxSimulation>>,
+                >::get(&self.bus)
+                .clone(),
                 Pick::<tokio::sync::broadcast::Receiver<ShutdownModule>>::get(&self.bus).resubscribe(),
             ),
         }
@@ -0,0 +1,341 @@
+//! History of the node state kept by archive nodes.
+//!
+//! After each block, the contracts it touched are stored at the block height, so the state of a
+//! contract at any height is the last entry at or below it, and its history is the list of
+//! entries that changed it. Blob transactions still unsettled at the end of the block they were
+//! sequenced in are stored too, so they remain queryable once settled.
+//!
+//! An archive enabled on an existing node only starts at the first block it processed: heights
+//! before it are reported as not archived rather than as missing contracts.
+
+use std::{collections::BTreeSet, path::Path};
+
+use anyhow::Result;
+use fjall::{Config, Keyspace, PartitionCreateOptions, PartitionHandle};
+use sdk::{
+    api::APIContractHistoryEntry, Block, BlockHeight, Contract, ContractName, TimeoutWindow,
+    TxHash, TxId, UnsettledBlobTransaction,
+};
+use tracing::{info, trace};
+
+use super::NodeState;
+
+pub struct NodeStateArchive {
+    db: Keyspace,
+    contracts_by_height: PartitionHandle,
+    unsettled_txs: PartitionHandle,
+    meta: PartitionHandle,
+    first_height: Option<BlockHeight>,
+}
+
+const FIRST_HEIGHT_KEY: &str = "first_height";
+
+/// Contract name, a separator, then the big-endian height so that keys sort by height
+fn contract_key(name: &ContractName, height: BlockHeight) -> Vec<u8> {
+    let mut key = Vec::with_capacity(name.0.len() + 9);
+    key.extend_from_slice(name.0.as_bytes());
+    key.push(0);
+    key.extend_from_slice(&height.0.to_be_bytes());
+    key
+}
+
+fn key_height(key: &[u8]) -> Result<BlockHeight> {
+    let height_bytes = key
+        .get(key.len().saturating_sub(8)..)
+        .and_then(|bytes| <[u8; 8]>::try_from(bytes).ok())
+        .ok_or_else(|| anyhow::anyhow!("Invalid archive key"))?;
+    Ok(BlockHeight(u64::from_be_bytes(height_bytes)))
+}
+
+fn history_entry(block_height: BlockHeight, contract: &Contract) -> APIContractHistoryEntry {
+    APIContractHistoryEntry {
+        block_height,
+        state_commitment: contract.state.clone(),
+        program_id: contract.program_id.clone(),
+        verifier: contract.verifier.clone(),
+        timeout_window: match contract.timeout_window {
+            TimeoutWindow::NoTimeout => None,
+            TimeoutWindow::Timeout(window) => Some(window.0),
+        },
+        owner: contract.owner.clone(),
+        name_service: contract.name_service.clone(),
+        expiry_height: contract.expiry_height,
+        deleted: false,
+    }
+}
+
+impl NodeStateArchive {
+    pub fn new(path: &Path) -> Result<Self> {
+        let db = Config::new(path).open()?;
+        let contracts_by_height =
+            db.open_partition("contracts_by_height", PartitionCreateOptions::default())?;
+        let unsettled_txs =
+            db.open_partition("unsettled_txs", PartitionCreateOptions::default())?;
+        let meta = db.open_partition("meta", PartitionCreateOptions::default())?;
+
+        let first_height = match meta.get(FIRST_HEIGHT_KEY)? {
+            Some(value) => Some(borsh::from_slice(&value)?),
+            // Archives created before the first height was recorded: the first block archived
+            // everything, so the lowest stored height is where the archive starts
+            None => {
+                let mut first_height: Option<BlockHeight> = None;
+                for key in contracts_by_height.keys() {
+                    let height = key_height(&key?)?;
+                    first_height = Some(first_height.map_or(height, |first| first.min(height)));
+                }
+                if let Some(height) = first_height {
+                    meta.insert(FIRST_HEIGHT_KEY, borsh::to_vec(&height)?)?;
+                }
+                first_height
+            }
+        };
+
+        info!(
+            "🗄️ Archive opened with {} contract state(s), starting at height {:?}",
+            contracts_by_height.len()?,
+            first_height
+        );
+
+        Ok(NodeStateArchive {
+            db,
+            contracts_by_height,
+            unsettled_txs,
+            meta,
+            first_height,
+        })
+    }
+
+    /// Height of the first block archived, None if no block was archived yet
+    pub fn first_height(&self) -> Option<BlockHeight> {
+        self.first_height
+    }
+
+    pub fn persist(&self) -> Result<()> {
+        self.db
+            .persist(fjall::PersistMode::Buffer)
+            .map_err(Into::into)
+    }
+
+    /// Stores the contracts and unsettled transactions of a block processed by `node_state`
+    pub fn archive_block(&mut self, block: &Block, node_state: &NodeState) -> Result<()> {
+        let height = block.block_height;
+        let modified: BTreeSet<&ContractName> = if self.first_height.is_none() {
+            // Starting from a snapshot or an existing node state: archive everything once
+            self.meta
+                .insert(FIRST_HEIGHT_KEY, borsh::to_vec(&height)?)?;
+            self.first_height = Some(height);
+            node_state.contracts.keys().collect()
+        } else {
+            block.modified_contracts()
+        };
+        for name in modified {
+            // Deleted contracts are stored as None
+            let contract = node_state.contracts.get(name);
+            trace!("🗄️ Archiving contract {} at height {}", name, height);
+            self.contracts_by_height
+                .insert(contract_key(name, height), borsh::to_vec(&contract)?)?;
+        }
+
+        for (TxId(_, tx_hash), _) in &block.txs {
+            if let Some(unsettled) = node_state.unsettled_transactions.get(tx_hash) {
+                self.unsettled_txs
+                    .insert(tx_hash.0.as_bytes(), borsh::to_vec(unsettled)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// State of the contract once the block at `height` was processed, None if it did not exist.
+    /// Fails for heights before the archive starts.
+    pub fn get_contract(
+        &self,
+        name: &ContractName,
+        height: BlockHeight,
+    ) -> Result<Option<Contract>> {
+        match self.first_height {
+            Some(first_height) if height >= first_height => {}
+            Some(first_height) => anyhow::bail!(
+                "Height {} is not archived, the archive starts at height {}",
+                height,
+                first_height
+            ),
+            None => anyhow::bail!("Height {} is not archived, the archive is empty", height),
+        }
+        match self
+            .contracts_by_height
+            .range(contract_key(name, BlockHeight(0))..=contract_key(name, height))
+            .next_back()
+        {
+            Some(item) => Ok(borsh::from_slice(&item?.1)?),
+            None => Ok(None),
+        }
+    }
+
+    /// Changes of the contract, oldest first, None if it never existed.
+    /// A deleted contract keeps its history, ended by an entry marked as deleted.
+    pub fn get_contract_history(
+        &self,
+        name: &ContractName,
+    ) -> Result<Option<Vec<APIContractHistoryEntry>>> {
+        let mut history: Vec<APIContractHistoryEntry> = vec![];
+        for item in self
+            .contracts_by_height
+            .range(contract_key(name, BlockHeight(0))..=contract_key(name, BlockHeight(u64::MAX)))
+        {
+            let (key, value) = item?;
+            let height = key_height(&key)?;
+            let entry = match borsh::from_slice::<Option<Contract>>(&value)? {
+                Some(contract) => history_entry(height, &contract),
+                None => match history.last() {
+                    Some(last) if !last.deleted => APIContractHistoryEntry {
+                        block_height: height,
+                        deleted: true,
+                        ..last.clone()
+                    },
+                    _ => continue,
+                },
+            };
+            let unchanged = history.last().is_some_and(|last| {
+                APIContractHistoryEntry {
+                    block_height: height,
+                    ..last.clone()
+                } == entry
+            });
+            if !unchanged {
+                history.push(entry);
+            }
+        }
+        Ok((!history.is_empty()).then_some(history))
+    }
+
+    /// Blob transaction as it was sequenced, even if it has since been settled
+    pub fn get_unsettled_tx(&self, tx_hash: &TxHash) -> Result<Option<UnsettledBlobTransaction>> {
+        match self.unsettled_txs.get(tx_hash.0.as_bytes())? {
+            Some(value) => Ok(Some(borsh::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdk::{
+        BlobIndex, BlobTransaction, ContractAction, DeleteContractAction, Hashed, Identity,
+        OnchainEffect, StateCommitment,
+    };
+
+    use super::*;
+    use crate::node_state::test::*;
+
+    #[test_log::test(tokio::test)]
+    async fn test_archive_contract_history() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let mut archive = NodeStateArchive::new(tmpdir.path()).unwrap();
+        let mut state = new_node_state().await;
+        let c1 = ContractName::new("c1");
+
+        // Enabled on an existing node: the archive starts at the first block it processes
+        let block =
+            state.craft_block_and_handle(1, vec![make_register_contract_tx(c1.clone()).into()]);
+        archive.archive_block(&block, &state).unwrap();
+
+        let blob_tx = BlobTransaction::new(
+            Identity::new("test@c1"),
+            vec![sdk::Blob {
+                contract_name: c1.clone(),
+                data: sdk::BlobData(vec![0, 1, 2, 3]),
+            }],
+        );
+        let block = state.craft_block_and_handle(2, vec![blob_tx.clone().into()]);
+        archive.archive_block(&block, &state).unwrap();
+
+        let hyle_output = make_hyle_output(blob_tx.clone(), BlobIndex(0));
+        let proof = new_proof_tx(&c1, &hyle_output, &blob_tx.hashed());
+        let block = state.craft_block_and_handle(3, vec![proof.into()]);
+        archive.archive_block(&block, &state).unwrap();
+
+        assert_eq!(archive.first_height(), Some(BlockHeight(1)));
+        let err = archive.get_contract(&c1, BlockHeight(0)).unwrap_err();
+        assert!(err.to_string().contains("is not archived"));
+        for (height, expected) in [
+            (1, vec![0, 1, 2, 3]),
+            (2, vec![0, 1, 2, 3]),
+            (3, vec![4, 5, 6]),
+        ] {
+            assert_eq!(
+                archive
+                    .get_contract(&c1, BlockHeight(height))
+                    .unwrap()
+                    .unwrap()
+                    .state,
+                StateCommitment(expected)
+            );
+        }
+        // Contracts whose names start with another contract name are kept apart
+        assert!(archive
+            .get_contract(&ContractName::new("c"), BlockHeight(3))
+            .unwrap()
+            .is_none());
+
+        // Only the changes of the contract are in its history
+        let history = archive.get_contract_history(&c1).unwrap().unwrap();
+        assert_eq!(
+            history
+                .iter()
+                .map(|entry| (entry.block_height.0, entry.state_commitment.0.clone()))
+                .collect::<Vec<_>>(),
+            vec![(1, vec![0, 1, 2, 3]), (3, vec![4, 5, 6])]
+        );
+        assert!(archive
+            .get_contract_history(&ContractName::new("c"))
+            .unwrap()
+            .is_none());
+
+        // The blob transaction is settled, but still archived
+        assert!(state
+            .unsettled_transactions
+            .get(&blob_tx.hashed())
+            .is_none());
+        assert_eq!(
+            archive
+                .get_unsettled_tx(&blob_tx.hashed())
+                .unwrap()
+                .unwrap()
+                .identity,
+            blob_tx.identity
+        );
+
+        // Deleting the contract keeps its history, ended by a deletion entry
+        let delete_tx = BlobTransaction::new(
+            Identity::new("test@c1"),
+            vec![DeleteContractAction {
+                contract_name: c1.clone(),
+            }
+            .as_blob(c1.clone(), None, None)],
+        );
+        let mut hyle_output =
+            make_hyle_output_with_state(delete_tx.clone(), BlobIndex(0), &[4, 5, 6], &[4, 5, 6]);
+        hyle_output
+            .onchain_effects
+            .push(OnchainEffect::DeleteContract(c1.clone()));
+        let proof = new_proof_tx(&c1, &hyle_output, &delete_tx.hashed());
+        let block = state.craft_block_and_handle(4, vec![delete_tx.into(), proof.into()]);
+        assert!(block.deleted_contracts.contains_key(&c1));
+        archive.archive_block(&block, &state).unwrap();
+
+        assert!(archive.get_contract(&c1, BlockHeight(4)).unwrap().is_none());
+        let history = archive.get_contract_history(&c1).unwrap().unwrap();
+        assert_eq!(history.len(), 3);
+        let deletion = history.last().unwrap();
+        assert_eq!(deletion.block_height, BlockHeight(4));
+        assert!(deletion.deleted);
+        assert_eq!(deletion.state_commitment, StateCommitment(vec![4, 5, 6]));
+
+        // The first archived height survives a restart
+        drop(archive);
+        let archive = NodeStateArchive::new(tmpdir.path()).unwrap();
+        assert_eq!(archive.first_height(), Some(BlockHeight(1)));
+        assert!(archive.get_contract(&c1, BlockHeight(0)).is_err());
+        assert!(archive.get_contract(&c1, BlockHeight(4)).unwrap().is_none());
+    }
+}
@@ -0,0 +1,208 @@
+//! Compact history of the contracts, kept by every node: an entry is only recorded when the state
+//! commitment, program id, verifier or timeout window of a contract changes, or when it is
+//! deleted. Archive nodes also keep full snapshots of the contracts, see [`super::archive`].
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use sdk::{
+    api::APIContractHistoryEntry, Block, BlockHeight, Contract, ContractName, ProgramId,
+    StateCommitment, TimeoutWindow, Verifier,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+struct HistoryEntry {
+    block_height: BlockHeight,
+    state_commitment: StateCommitment,
+    program_id: ProgramId,
+    verifier: Verifier,
+    timeout_window: TimeoutWindow,
+    /// The contract was deleted at this height, the other fields are its last state
+    deleted: bool,
+}
+
+impl HistoryEntry {
+    fn new(block_height: BlockHeight, contract: &Contract) -> Self {
+        HistoryEntry {
+            block_height,
+            state_commitment: contract.state.clone(),
+            program_id: contract.program_id.clone(),
+            verifier: contract.verifier.clone(),
+            timeout_window: contract.timeout_window.clone(),
+            deleted: false,
+        }
+    }
+
+    fn same_contract(&self, other: &HistoryEntry) -> bool {
+        self.state_commitment == other.state_commitment
+            && self.program_id == other.program_id
+            && self.verifier == other.verifier
+            && self.timeout_window == other.timeout_window
+            && self.deleted == other.deleted
+    }
+}
+
+#[derive(Debug, Default, Clone, BorshSerialize, BorshDeserialize)]
+pub struct ContractHistory {
+    /// Height of the first block recorded, None if no block was recorded yet
+    first_height: Option<BlockHeight>,
+    contracts: BTreeMap<ContractName, Vec<HistoryEntry>>,
+}
+
+impl ContractHistory {
+    /// Records the contracts modified by a block, as they are once it is processed.
+    /// A deleted contract keeps its history, ended by an entry marked as deleted.
+    pub fn record_block(&mut self, block: &Block, contracts: &HashMap<ContractName, Contract>) {
+        let modified: BTreeSet<&ContractName> = if self.first_height.is_none() {
+            // Starting from a snapshot or an existing node state: record everything once
+            self.first_height = Some(block.block_height);
+            contracts.keys().collect()
+        } else {
+            block.modified_contracts()
+        };
+        for name in modified {
+            let entry = match contracts.get(name) {
+                Some(contract) => HistoryEntry::new(block.block_height, contract),
+                None => match self.contracts.get(name).and_then(|history| history.last()) {
+                    Some(last) if !last.deleted => HistoryEntry {
+                        block_height: block.block_height,
+                        deleted: true,
+                        ..last.clone()
+                    },
+                    _ => continue,
+                },
+            };
+            let history = self.contracts.entry(name.clone()).or_default();
+            if !history
+                .last()
+                .is_some_and(|last| last.same_contract(&entry))
+            {
+                history.push(entry);
+            }
+        }
+    }
+
+    /// Height of the first block recorded, earlier heights are unknown to this node
+    pub fn first_height(&self) -> Option<BlockHeight> {
+        self.first_height
+    }
+
+    /// Changes of the contract, oldest first, None if it never existed
+    pub fn get(&self, name: &ContractName) -> Option<Vec<APIContractHistoryEntry>> {
+        let history = self.contracts.get(name)?;
+        Some(
+            history
+                .iter()
+                .map(|entry| APIContractHistoryEntry {
+                    block_height: entry.block_height,
+                    state_commitment: entry.state_commitment.clone(),
+                    program_id: entry.program_id.clone(),
+                    verifier: entry.verifier.clone(),
+                    timeout_window: match entry.timeout_window {
+                        TimeoutWindow::NoTimeout => None,
+                        TimeoutWindow::Timeout(window) => Some(window.0),
+                    },
+                    // Only archive nodes keep the ownership and lease of past states
+                    owner: None,
+                    name_service: None,
+                    expiry_height: None,
+                    deleted: entry.deleted,
+                })
+                .collect(),
+        )
+    }
+
+    /// State of the contract once the block at `height` was processed, None if it did not exist.
+    /// Only the fields kept by the compact history are set.
+    pub fn contract_at_height(&self, name: &ContractName, height: BlockHeight) -> Option<Contract> {
+        let history = self.contracts.get(name)?;
+        let after = history.partition_point(|entry| entry.block_height <= height);
+        let entry = after.checked_sub(1).and_then(|index| history.get(index))?;
+        (!entry.deleted).then(|| Contract {
+            name: name.clone(),
+            program_id: entry.program_id.clone(),
+            state: entry.state_commitment.clone(),
+            verifier: entry.verifier.clone(),
+            timeout_window: entry.timeout_window.clone(),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contract(state: u8) -> Contract {
+        Contract {
+            name: "c1".into(),
+            program_id: ProgramId(vec![1]),
+            state: StateCommitment(vec![state]),
+            verifier: Verifier("test".into()),
+            timeout_window: TimeoutWindow::NoTimeout,
+            ..Default::default()
+        }
+    }
+
+    fn block(height: u64, updated: &[&str]) -> Block {
+        Block {
+            block_height: BlockHeight(height),
+            updated_states: updated
+                .iter()
+                .map(|name| (ContractName::new(*name), StateCommitment::default()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_contract_history() {
+        let c1 = ContractName::new("c1");
+        let mut history = ContractHistory::default();
+        let mut contracts = HashMap::from([(c1.clone(), contract(0))]);
+
+        history.record_block(&block(1, &[]), &contracts);
+        contracts.insert(c1.clone(), contract(1));
+        history.record_block(&block(3, &["c1"]), &contracts);
+        // Unchanged contracts are not recorded again
+        history.record_block(&block(4, &["c1"]), &contracts);
+        contracts.insert(c1.clone(), contract(2));
+        history.record_block(&block(6, &["c1"]), &contracts);
+
+        let heights: Vec<u64> = history
+            .get(&c1)
+            .unwrap()
+            .iter()
+            .map(|entry| entry.block_height.0)
+            .collect();
+        assert_eq!(heights, vec![1, 3, 6]);
+        assert_eq!(history.first_height(), Some(BlockHeight(1)));
+
+        assert!(history.contract_at_height(&c1, BlockHeight(0)).is_none());
+        for (height, state) in [(1, 0), (2, 0), (3, 1), (5, 1), (6, 2), (100, 2)] {
+            assert_eq!(
+                history
+                    .contract_at_height(&c1, BlockHeight(height))
+                    .unwrap()
+                    .state,
+                StateCommitment(vec![state])
+            );
+        }
+
+        // Deleting the contract keeps its history, ended by a deletion entry
+        contracts.remove(&c1);
+        let mut deleted = block(7, &[]);
+        deleted
+            .deleted_contracts
+            .insert(c1.clone(), Default::default());
+        history.record_block(&deleted, &contracts);
+        let entries = history.get(&c1).unwrap();
+        assert_eq!(entries.len(), 4);
+        let deletion = entries.last().unwrap();
+        assert!(deletion.deleted);
+        assert_eq!(deletion.block_height, BlockHeight(7));
+        assert_eq!(deletion.state_commitment, StateCommitment(vec![2]));
+        assert!(history.contract_at_height(&c1, BlockHeight(7)).is_none());
+        assert!(history.contract_at_height(&c1, BlockHeight(6)).is_some());
+    }
+}
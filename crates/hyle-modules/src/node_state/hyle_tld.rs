@@ -4,6 +4,7 @@ use crate::node_state::{
 };
 use anyhow::{bail, Result};
 use sdk::secp256k1::CheckSecp256k1;
+use sdk::verifiers::NATIVE_VERIFIERS_CONTRACT_LIST;
 use sdk::*;
 use std::collections::{BTreeMap, HashMap};
 
@@ -11,30 +12,49 @@ use super::SideEffect;
 
 pub const HYLI_TLD_ID: &str = "hyli@wallet";
 
+/// Handles a blob sent to the hyle TLD by `identity`.
+/// Contracts registered here are owned by the registering identity: only their owner, or Hyli,
+/// can then delete, update or transfer them.
 pub fn handle_blob_for_hyle_tld(
     contracts: &HashMap<ContractName, Contract>,
     contract_changes: &mut BTreeMap<ContractName, ModifiedContractData>,
     current_blob: &Blob,
+    identity: &Identity,
 ) -> Result<()> {
-    // TODO: check the identity of the caller here.
-
     // TODO: support unstructured blobs as well ?
     if let Ok(reg) =
         StructuredBlobData::<RegisterContractAction>::try_from(current_blob.data.clone())
     {
-        handle_register_blob(contracts, contract_changes, &reg.parameters)?;
+        handle_register_blob(contracts, contract_changes, &reg.parameters, identity)?;
     } else if let Ok(reg) =
         StructuredBlobData::<DeleteContractAction>::try_from(current_blob.data.clone())
     {
-        handle_delete_blob(contracts, contract_changes, &reg.parameters)?;
+        handle_delete_blob(contracts, contract_changes, &reg.parameters, identity)?;
     } else if let Ok(reg) =
         StructuredBlobData::<UpdateContractProgramIdAction>::try_from(current_blob.data.clone())
     {
-        handle_update_program_id_blob(contracts, contract_changes, &reg.parameters)?;
+        handle_update_program_id_blob(contracts, contract_changes, &reg.parameters, identity)?;
     } else if let Ok(reg) =
         StructuredBlobData::<UpdateContractTimeoutWindowAction>::try_from(current_blob.data.clone())
     {
-        handle_update_timeout_window_blob(contracts, contract_changes, &reg.parameters)?;
+        handle_update_timeout_window_blob(contracts, contract_changes, &reg.parameters, identity)?;
+    } else if let Ok(reg) = StructuredBlobData::<ScheduleContractProgramIdUpdateAction>::try_from(
+        current_blob.data.clone(),
+    ) {
+        handle_schedule_program_id_update_blob(
+            contracts,
+            contract_changes,
+            &reg.parameters,
+            identity,
+        )?;
+    } else if let Ok(reg) =
+        StructuredBlobData::<TransferContractOwnershipAction>::try_from(current_blob.data.clone())
+    {
+        handle_transfer_ownership_blob(contracts, contract_changes, &reg.parameters, identity)?;
+    } else if let Ok(reg) =
+        StructuredBlobData::<ConfigureNameServiceAction>::try_from(current_blob.data.clone())
+    {
+        handle_configure_name_service_blob(contracts, contract_changes, &reg.parameters, identity)?;
     } else if StructuredBlobData::<NukeTxAction>::try_from(current_blob.data.clone()).is_ok() {
         // Do nothing
     } else {
@@ -43,10 +63,37 @@ pub fn handle_blob_for_hyle_tld(
     Ok(())
 }
 
+/// Identities of the hyle contract and of the native verifiers are not proven by anything,
+/// so they cannot own contracts
+fn can_own_contracts(identity: &Identity) -> bool {
+    identity
+        .0
+        .rsplit_once('@')
+        .is_some_and(|(_, identity_contract)| {
+            identity_contract != "hyle"
+                && !NATIVE_VERIFIERS_CONTRACT_LIST.contains(&identity_contract)
+        })
+}
+
+/// Hyli can manage every contract, other identities only the contracts they own
+fn check_owner(contract: &Contract, identity: &Identity) -> Result<()> {
+    if identity.0 != HYLI_TLD_ID
+        && (!can_own_contracts(identity) || contract.owner.as_ref() != Some(identity))
+    {
+        bail!(
+            "Identity {} is not the owner of contract {}",
+            identity,
+            contract.name
+        );
+    }
+    Ok(())
+}
+
 fn handle_register_blob(
     contracts: &HashMap<ContractName, Contract>,
     contract_changes: &mut BTreeMap<ContractName, ModifiedContractData>,
     reg: &RegisterContractAction,
+    identity: &Identity,
 ) -> Result<()> {
     // Check name, it's either a direct subdomain or a TLD
     validate_contract_registration_metadata(
@@ -73,6 +120,10 @@ fn handle_register_blob(
                 state: reg.state_commitment.clone(),
                 verifier: reg.verifier.clone(),
                 timeout_window: reg.timeout_window.clone().unwrap_or_default(),
+                owner: can_own_contracts(identity).then(|| identity.clone()),
+                name_service: None,
+                expiry_height: None,
+                min_program_id_update_delay: None,
             }),
             ModifiedContractFields::all(),
             vec![SideEffect::Register(reg.constructor_metadata.clone())],
@@ -85,6 +136,7 @@ fn handle_delete_blob(
     contracts: &HashMap<ContractName, Contract>,
     contract_changes: &mut BTreeMap<ContractName, ModifiedContractData>,
     delete: &DeleteContractAction,
+    identity: &Identity,
 ) -> Result<()> {
     // For now, Hyli is allowed to delete all contracts but itself
     if delete.contract_name.0 == "hyle" {
@@ -92,27 +144,25 @@ fn handle_delete_blob(
     }
 
     // Check it's registered
-    if contracts.contains_key(&delete.contract_name)
-        || contract_changes.contains_key(&delete.contract_name)
-    {
-        contract_changes.insert(
-            delete.contract_name.clone(),
-            (
-                None,
-                ModifiedContractFields::all(),
-                vec![SideEffect::Delete],
-            ),
-        );
-        Ok(())
-    } else {
-        bail!("Contract {} is already registered", delete.contract_name.0);
-    }
+    let contract = NodeState::get_contract(contracts, contract_changes, &delete.contract_name)?;
+    check_owner(contract, identity)?;
+
+    contract_changes.insert(
+        delete.contract_name.clone(),
+        (
+            None,
+            ModifiedContractFields::all(),
+            vec![SideEffect::Delete],
+        ),
+    );
+    Ok(())
 }
 
 fn handle_update_program_id_blob(
     contracts: &HashMap<ContractName, Contract>,
     contract_changes: &mut BTreeMap<ContractName, ModifiedContractData>,
     update: &UpdateContractProgramIdAction,
+    identity: &Identity,
 ) -> Result<()> {
     // For now, Hyli is allowed to delete all contracts but itself
     if update.contract_name.0 == "hyle" {
@@ -121,6 +171,14 @@ fn handle_update_program_id_blob(
 
     let contract =
         NodeState::get_contract(contracts, contract_changes, &update.contract_name)?.clone();
+    check_owner(&contract, identity)?;
+    if let Some(min_delay) = contract.min_program_id_update_delay {
+        bail!(
+            "Program id changes of contract {} must be scheduled at least {} blocks ahead",
+            update.contract_name,
+            min_delay
+        );
+    }
 
     let new_update = SideEffect::UpdateProgramId;
     contract_changes
@@ -152,6 +210,7 @@ fn handle_update_timeout_window_blob(
     contracts: &HashMap<ContractName, Contract>,
     contract_changes: &mut BTreeMap<ContractName, ModifiedContractData>,
     update: &UpdateContractTimeoutWindowAction,
+    identity: &Identity,
 ) -> Result<()> {
     // For now, Hyli is allowed to delete all contracts but itself
     if update.contract_name.0 == "hyle" {
@@ -160,6 +219,7 @@ fn handle_update_timeout_window_blob(
 
     let contract =
         NodeState::get_contract(contracts, contract_changes, &update.contract_name)?.clone();
+    check_owner(&contract, identity)?;
 
     let new_update = SideEffect::UpdateTimeoutWindow;
     contract_changes
@@ -187,10 +247,202 @@ fn handle_update_timeout_window_blob(
     Ok(())
 }
 
+fn handle_schedule_program_id_update_blob(
+    contracts: &HashMap<ContractName, Contract>,
+    contract_changes: &mut BTreeMap<ContractName, ModifiedContractData>,
+    schedule: &ScheduleContractProgramIdUpdateAction,
+    identity: &Identity,
+) -> Result<()> {
+    if schedule.contract_name.0 == "hyle" {
+        bail!("Cannot udpate Hyli contract");
+    }
+
+    let contract =
+        NodeState::get_contract(contracts, contract_changes, &schedule.contract_name)?.clone();
+    check_owner(&contract, identity)?;
+
+    // The change itself is applied by the node state once the activation height is reached
+    let new_update = match &schedule.schedule {
+        ProgramIdUpdateSchedule::Schedule {
+            program_id,
+            activation_delay,
+        } => {
+            if activation_delay.0 == 0 {
+                bail!("Activation delay must be at least one block");
+            }
+            if let Some(min_delay) = contract.min_program_id_update_delay {
+                if *activation_delay < min_delay {
+                    bail!(
+                        "Activation delay {} is below the minimum of {} blocks of contract {}",
+                        activation_delay,
+                        min_delay,
+                        schedule.contract_name
+                    );
+                }
+            }
+            SideEffect::ScheduleProgramId(program_id.clone(), *activation_delay)
+        }
+        ProgramIdUpdateSchedule::Cancel => SideEffect::CancelProgramIdUpdate,
+        ProgramIdUpdateSchedule::RequireMinDelay { min_delay } => {
+            if min_delay.0 == 0 {
+                bail!("Minimum delay must be at least one block");
+            }
+            // Lowering the minimum would let the owner shorten the notice users rely on
+            if contract
+                .min_program_id_update_delay
+                .is_some_and(|current| *min_delay < current)
+            {
+                bail!(
+                    "Minimum delay of contract {} cannot be lowered",
+                    schedule.contract_name
+                );
+            }
+            contract_changes
+                .entry(schedule.contract_name.clone())
+                .and_modify(|c| {
+                    if let Some(contract) = c.0.as_mut() {
+                        contract.min_program_id_update_delay = Some(*min_delay);
+                    }
+                    c.1.min_program_id_update_delay = true;
+                })
+                .or_insert_with(|| {
+                    (
+                        Some(Contract {
+                            min_program_id_update_delay: Some(*min_delay),
+                            ..contract
+                        }),
+                        ModifiedContractFields {
+                            min_program_id_update_delay: true,
+                            ..ModifiedContractFields::default()
+                        },
+                        vec![],
+                    )
+                });
+            return Ok(());
+        }
+    };
+    contract_changes
+        .entry(schedule.contract_name.clone())
+        .and_modify(|c| c.2.push(new_update.clone()))
+        .or_insert_with(|| {
+            (
+                Some(contract),
+                ModifiedContractFields::default(),
+                vec![new_update],
+            )
+        });
+    Ok(())
+}
+
+fn handle_transfer_ownership_blob(
+    contracts: &HashMap<ContractName, Contract>,
+    contract_changes: &mut BTreeMap<ContractName, ModifiedContractData>,
+    transfer: &TransferContractOwnershipAction,
+    identity: &Identity,
+) -> Result<()> {
+    if transfer.contract_name.0 == "hyle" {
+        bail!("Cannot transfer Hyli contract");
+    }
+
+    let contract =
+        NodeState::get_contract(contracts, contract_changes, &transfer.contract_name)?.clone();
+    check_owner(&contract, identity)?;
+    // Contracts without an owner are Hyli's to hand over
+    let current_owner = contract
+        .owner
+        .clone()
+        .unwrap_or_else(|| Identity::new(HYLI_TLD_ID));
+    if current_owner != transfer.current_owner {
+        bail!(
+            "Contract {} is not owned by {}",
+            transfer.contract_name,
+            transfer.current_owner
+        );
+    }
+    if !can_own_contracts(&transfer.new_owner) {
+        bail!("Identity {} cannot own contracts", transfer.new_owner);
+    }
+
+    let new_update = SideEffect::TransferOwnership;
+    contract_changes
+        .entry(transfer.contract_name.clone())
+        .and_modify(|c| {
+            if let Some(contract) = c.0.as_mut() {
+                contract.owner = Some(transfer.new_owner.clone());
+            }
+            c.1.owner = true;
+            c.2.push(new_update.clone());
+        })
+        .or_insert_with(|| {
+            (
+                Some(Contract {
+                    owner: Some(transfer.new_owner.clone()),
+                    ..contract
+                }),
+                ModifiedContractFields {
+                    owner: true,
+                    ..ModifiedContractFields::default()
+                },
+                vec![new_update],
+            )
+        });
+    Ok(())
+}
+
+fn handle_configure_name_service_blob(
+    contracts: &HashMap<ContractName, Contract>,
+    contract_changes: &mut BTreeMap<ContractName, ModifiedContractData>,
+    configure: &ConfigureNameServiceAction,
+    identity: &Identity,
+) -> Result<()> {
+    if configure.tld.0 == "hyle" {
+        bail!("Cannot configure Hyli contract");
+    }
+
+    let contract = NodeState::get_contract(contracts, contract_changes, &configure.tld)?.clone();
+    check_owner(&contract, identity)?;
+    if configure.tld.0.contains('.') {
+        bail!("Only TLDs can be name services, not {}", configure.tld);
+    }
+    if configure.max_period.0 == 0 {
+        bail!("Max lease period must be at least one block");
+    }
+    if configure.fee_recipient.0.is_empty() {
+        bail!("Name service fees need a recipient");
+    }
+
+    let config = configure.config();
+    let new_update = SideEffect::ConfigureNameService;
+    contract_changes
+        .entry(configure.tld.clone())
+        .and_modify(|c| {
+            if let Some(contract) = c.0.as_mut() {
+                contract.name_service = Some(config.clone());
+            }
+            c.1.name_service = true;
+            c.2.push(new_update.clone());
+        })
+        .or_insert_with(|| {
+            (
+                Some(Contract {
+                    name_service: Some(config),
+                    ..contract
+                }),
+                ModifiedContractFields {
+                    name_service: true,
+                    ..ModifiedContractFields::default()
+                },
+                vec![new_update],
+            )
+        });
+    Ok(())
+}
+
 /// Validates hyle contract blobs by ensuring actions are authorized and properly signed
 ///
 /// This function ensures that:
-/// 1. Only authorized identities (HYLI_TLD_ID) can perform DeleteContractAction actions
+/// 1. Contract management actions are well-formed; whether the identity owns the contract is
+///    only known at settlement, see `handle_blob_for_hyle_tld`
 /// 2. NukeTxAction actions are accompanied by a valid secp256k1 signature
 /// 3. The secp256k1 signature covers the transaction hashes to be "nuked"
 /// 4. The signature comes exclusively from the Hyli identity (HYLI_TLD_SIG)
@@ -199,26 +451,33 @@ pub fn validate_hyle_contract_blobs(tx: &BlobTransaction) -> Result<(), String>
     // Collect NukeTxAction blobs and secp256k1 blobs
     for (index, blob) in tx.blobs.iter().enumerate() {
         if blob.contract_name.0 == "hyle" {
-            // Check identity authorization for privileged actions
-            if StructuredBlobData::<UpdateContractProgramIdAction>::try_from(blob.data.clone())
-                .is_ok()
-                || StructuredBlobData::<DeleteContractAction>::try_from(blob.data.clone()).is_ok()
-                || StructuredBlobData::<UpdateContractTimeoutWindowAction>::try_from(
-                    blob.data.clone(),
-                )
-                .is_ok()
-            {
-                if tx.identity.0 != HYLI_TLD_ID {
-                    return Err(format!(
-                        "Unauthorized action for 'hyle' TLD from identity: {}",
-                        tx.identity.0
-                    ));
-                }
+            // Owner-restricted actions are checked against the contract owner at settlement
+            let is_owner_action =
+                StructuredBlobData::<UpdateContractProgramIdAction>::try_from(blob.data.clone())
+                    .is_ok()
+                    || StructuredBlobData::<DeleteContractAction>::try_from(blob.data.clone())
+                        .is_ok()
+                    || StructuredBlobData::<UpdateContractTimeoutWindowAction>::try_from(
+                        blob.data.clone(),
+                    )
+                    .is_ok()
+                    || StructuredBlobData::<ScheduleContractProgramIdUpdateAction>::try_from(
+                        blob.data.clone(),
+                    )
+                    .is_ok()
+                    || StructuredBlobData::<TransferContractOwnershipAction>::try_from(
+                        blob.data.clone(),
+                    )
+                    .is_ok()
+                    || StructuredBlobData::<ConfigureNameServiceAction>::try_from(
+                        blob.data.clone(),
+                    )
+                    .is_ok();
+            if is_owner_action {
+                continue;
             }
             // Collect NukeTxAction blobs for signature validation
-            else if let Ok(nuke_data) =
-                StructuredBlobData::<NukeTxAction>::try_from(blob.data.clone())
-            {
+            if let Ok(nuke_data) = StructuredBlobData::<NukeTxAction>::try_from(blob.data.clone()) {
                 let calldata = Calldata {
                     tx_hash: tx.hashed(),
                     identity: tx.identity.clone(),
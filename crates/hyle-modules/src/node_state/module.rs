@@ -1,17 +1,31 @@
 //! State required for participation in consensus by the node.
 
+use super::archive::NodeStateArchive;
+use super::history::ContractHistory;
 use super::metrics::NodeStateMetrics;
-use super::{NodeState, NodeStateStore};
+use super::snapshot::NodeStateSnapshot;
+use super::store_file::NodeStateFile;
+use super::NodeState;
 use crate::bus::SharedMessageBus;
 use crate::bus::{command_response::Query, BusClientSender};
 use crate::log_error;
 use crate::module_handle_messages;
 use crate::modules::{module_bus_client, Module, SharedBuildApiCtx};
-use anyhow::Result;
-use sdk::*;
+use anyhow::{Context, Result};
+use sdk::{
+    api::{APIContractHistoryEntry, APITxSimulation},
+    *,
+};
+use staking::state::Staking;
 use std::path::PathBuf;
 use tracing::info;
 
+/// Files holding the hash of the last processed block and the staking state at that block,
+/// saved along the node state so snapshots can be exported right after a restart
+pub const NODE_STATE_BLOCK_HASH_FILE: &str = "node_state_block_hash.bin";
+pub const NODE_STATE_STAKING_FILE: &str = "node_state_staking.bin";
+pub const CONTRACT_HISTORY_FILE: &str = "contract_history.bin";
+
 /// NodeStateModule maintains a NodeState,
 /// listens to DA, and sends events when it has processed blocks.
 /// Node state module is separate from DataAvailabiliity
@@ -20,6 +34,18 @@ pub struct NodeStateModule {
     bus: NodeStateBusClient,
     inner: NodeState,
     data_directory: PathBuf,
+    /// Hash of the last processed block, None if no block was processed yet
+    last_block_hash: Option<ConsensusProposalHash>,
+    /// Staking state once the last block was processed, maintained like the consensus one.
+    /// None if the node state was started before it was tracked.
+    staking: Option<Staking>,
+    /// Full history of the contracts and unsettled transactions, kept by archive nodes
+    archive: Option<NodeStateArchive>,
+    /// Compact history of the contracts, kept by every node
+    contract_history: ContractHistory,
+    snapshot_interval: u64,
+    /// Last snapshot taken every `snapshot_interval` blocks, only kept in memory
+    periodic_snapshot: Option<NodeStateSnapshot>,
 }
 
 pub use sdk::NodeStateEvent;
@@ -36,16 +62,55 @@ pub struct QueryUnsettledTxCount(pub Option<ContractName>);
 #[derive(Clone)]
 pub struct QueryUnsettledTx(pub TxHash);
 
+/// State of a contract once the block at the given height was processed
+#[derive(Clone)]
+pub struct QueryContractAtHeight(pub ContractName, pub BlockHeight);
+
+/// Changes of a contract, oldest first
+#[derive(Clone)]
+pub struct QueryContractHistory(pub ContractName);
+
+/// Program id change scheduled for a contract and not activated yet
+#[derive(Clone)]
+pub struct QueryPendingProgramIdUpdate(pub ContractName);
+
+/// Simulates a blob transaction against the current node state
+#[derive(Clone)]
+pub struct QueryTxSimulation(pub BlobTransaction);
+
+/// Returns a snapshot of the current node state, without writing it to disk
+#[derive(Clone)]
+pub struct QueryNodeStateSnapshot {}
+
+/// Returns the last snapshot taken every `snapshot_interval` blocks,
+/// or None if it was taken at the `known` height.
+#[derive(Clone)]
+pub struct QueryPeriodicNodeStateSnapshot {
+    pub known: Option<BlockHeight>,
+}
+
+/// Replaces the node state with a snapshot fetched from peers.
+/// Only accepted while no block has been processed.
+#[derive(Clone)]
+pub struct LoadNodeStateSnapshot(pub NodeStateSnapshot);
+
 module_bus_client! {
 #[derive(Debug)]
 pub struct NodeStateBusClient {
     sender(NodeStateEvent),
     receiver(DataEvent),
     receiver(Query<ContractName, (BlockHeight, Contract)>),
+    receiver(Query<QueryContractAtHeight, Contract>),
+    receiver(Query<QueryContractHistory, Vec<APIContractHistoryEntry>>),
+    receiver(Query<QueryPendingProgramIdUpdate, PendingProgramIdUpdate>),
     receiver(Query<QuerySettledHeight, BlockHeight>),
     receiver(Query<QueryUnsettledTxCount, u64>),
     receiver(Query<QueryBlockHeight , BlockHeight>),
     receiver(Query<QueryUnsettledTx, UnsettledBlobTransaction>),
+    receiver(Query<QueryTxSimulation, APITxSimulation>),
+    receiver(Query<QueryNodeStateSnapshot, NodeStateSnapshot>),
+    receiver(Query<QueryPeriodicNodeStateSnapshot, Option<NodeStateSnapshot>>),
+    receiver(Query<LoadNodeStateSnapshot, ()>),
 }
 }
 
@@ -53,6 +118,10 @@ pub struct NodeStateCtx {
     pub node_id: String,
     pub data_directory: PathBuf,
     pub api: SharedBuildApiCtx,
+    /// Keep the state of the contracts at every height, and the settled transactions
+    pub archive: bool,
+    /// Take a snapshot of the node state every N blocks, to serve it to peers. 0 disables it.
+    pub snapshot_interval: u64,
 }
 
 impl Module for NodeStateModule {
@@ -67,14 +136,37 @@ impl Module for NodeStateModule {
         }
         let metrics = NodeStateMetrics::global(ctx.node_id.clone(), "node_state");
 
-        let store = Self::load_from_disk_or_default::<NodeStateStore>(
-            ctx.data_directory.join("node_state.bin").as_path(),
+        let node_state_path = ctx.data_directory.join("node_state.bin");
+        let fresh = !node_state_path.exists();
+        let store = Self::load_from_disk::<NodeStateFile>(node_state_path.as_path())
+            .map(|file| file.0)
+            .unwrap_or_default();
+        let last_block_hash = Self::load_from_disk::<ConsensusProposalHash>(
+            ctx.data_directory
+                .join(NODE_STATE_BLOCK_HASH_FILE)
+                .as_path(),
         );
+        let staking = Self::load_from_disk::<Staking>(
+            ctx.data_directory.join(NODE_STATE_STAKING_FILE).as_path(),
+        )
+        .or_else(|| fresh.then(Staking::default));
 
         for name in store.contracts.keys() {
             info!("📝 Loaded contract state for {}", name);
         }
 
+        let archive = match ctx.archive {
+            true => Some(
+                NodeStateArchive::new(&ctx.data_directory.join("node_state_archive"))
+                    .context("Opening node state archive")?,
+            ),
+            false => None,
+        };
+
+        let contract_history = Self::load_from_disk_or_default::<ContractHistory>(
+            ctx.data_directory.join(CONTRACT_HISTORY_FILE).as_path(),
+        );
+
         let node_state = NodeState { store, metrics };
         let bus = NodeStateBusClient::new_from_bus(bus.new_handle()).await;
 
@@ -82,6 +174,12 @@ impl Module for NodeStateModule {
             bus,
             inner: node_state,
             data_directory: ctx.data_directory,
+            last_block_hash,
+            staking,
+            archive,
+            contract_history,
+            snapshot_interval: ctx.snapshot_interval,
+            periodic_snapshot: None,
         })
     }
 
@@ -98,6 +196,51 @@ impl Module for NodeStateModule {
                     None => Err(anyhow::anyhow!("Contract {} not found", cmd)),
                 }
             }
+            command_response<QueryContractAtHeight, Contract> cmd => {
+                let QueryContractAtHeight(name, height) = cmd;
+                if *height > self.inner.current_height {
+                    anyhow::bail!("Height {} is not processed yet, the node is at height {}", height, self.inner.current_height);
+                }
+                let contract = match &self.archive {
+                    Some(archive) => archive.get_contract(name, *height)?,
+                    None if *height == self.inner.current_height => {
+                        self.inner.contracts.get(name).cloned()
+                    }
+                    None => match self.contract_history.first_height() {
+                        Some(first_height) if *height >= first_height => {
+                            self.contract_history.contract_at_height(name, *height)
+                        }
+                        first_height => anyhow::bail!(
+                            "Height {} is not recorded, the contract history starts at height {:?}",
+                            height,
+                            first_height
+                        ),
+                    },
+                };
+                match contract {
+                    Some(contract) => Ok(contract),
+                    None => Err(anyhow::anyhow!("Contract {} not found at height {}", name, height)),
+                }
+            }
+            command_response<QueryContractHistory, Vec<APIContractHistoryEntry>> cmd => {
+                let history = match &self.archive {
+                    Some(archive) => archive.get_contract_history(&cmd.0)?,
+                    None => self.contract_history.get(&cmd.0),
+                };
+                match history {
+                    Some(history) => Ok(history),
+                    None => Err(anyhow::anyhow!("Contract {} not found", cmd.0)),
+                }
+            }
+            command_response<QueryPendingProgramIdUpdate, PendingProgramIdUpdate> cmd => {
+                if !self.inner.contracts.contains_key(&cmd.0) {
+                    return Err(anyhow::anyhow!("Contract {} not found", cmd.0));
+                }
+                match self.inner.get_pending_program_id_update(&cmd.0) {
+                    Some(update) => Ok(update.clone()),
+                    None => Err(anyhow::anyhow!("No pending program id update found for contract {}", cmd.0)),
+                }
+            }
             command_response<QuerySettledHeight, BlockHeight> cmd => {
                 if !self.inner.contracts.contains_key(&cmd.0) {
                     return Err(anyhow::anyhow!("Contract {} not found", cmd.0));
@@ -116,14 +259,49 @@ impl Module for NodeStateModule {
             command_response<QueryUnsettledTx, UnsettledBlobTransaction> tx_hash => {
                 match self.inner.unsettled_transactions.get(&tx_hash.0) {
                     Some(tx) => Ok(tx.clone()),
-                    None => Err(anyhow::anyhow!("Transaction not found")),
+                    None => match &self.archive {
+                        Some(archive) => archive.get_unsettled_tx(&tx_hash.0)?.ok_or_else(|| anyhow::anyhow!("Transaction not found")),
+                        None => Err(anyhow::anyhow!("Transaction not found")),
+                    },
+                }
+            }
+            command_response<QueryTxSimulation, APITxSimulation> cmd => {
+                Ok(self.inner.simulate_blob_tx(&cmd.0))
+            }
+            command_response<QueryNodeStateSnapshot, NodeStateSnapshot> _ => {
+                self.snapshot()
+            }
+            command_response<QueryPeriodicNodeStateSnapshot, Option<NodeStateSnapshot>> cmd => {
+                match &self.periodic_snapshot {
+                    None => anyhow::bail!("No periodic snapshot taken since startup"),
+                    Some(snapshot) if Some(snapshot.height()) == cmd.known => Ok(None),
+                    Some(snapshot) => Ok(Some(snapshot.clone())),
                 }
             }
+            command_response<LoadNodeStateSnapshot, ()> cmd => {
+                self.load_snapshot(cmd.0.clone())
+            }
             listen<DataEvent> block => {
                 match block {
                     DataEvent::OrderedSignedBlock(block) => {
                         // TODO: If we are in a broken state, this will likely kill the node every time.
                         let node_state_block = self.inner.handle_signed_block(&block)?;
+                        if let Some(archive) = &mut self.archive {
+                            archive.archive_block(&node_state_block, &self.inner)?;
+                        }
+                        self.contract_history.record_block(&node_state_block, &self.inner.contracts);
+                        if let Some(staking) = self.staking.as_mut() {
+                            let updated = staking.process_block(&node_state_block).map_err(|e| anyhow::anyhow!(e));
+                            if log_error!(updated, "Updating staking state, it is no longer tracked until the node state is resynced").is_err() {
+                                // The staking state no longer matches the chain, no snapshot must carry it
+                                self.staking = None;
+                                self.periodic_snapshot = None;
+                            }
+                        }
+                        self.last_block_hash = Some(node_state_block.hash.clone());
+                        if self.snapshot_interval > 0 && node_state_block.block_height.0 % self.snapshot_interval == 0 {
+                            self.periodic_snapshot = log_error!(self.snapshot(), "Taking periodic node state snapshot").ok();
+                        }
                         _ = log_error!(self
                             .bus
                             .send(NodeStateEvent::NewBlock(Box::new(node_state_block))), "Sending DataEvent while processing SignedBlock");
@@ -136,12 +314,86 @@ impl Module for NodeStateModule {
     }
 
     async fn persist(&mut self) -> Result<()> {
+        if let Some(archive) = &self.archive {
+            _ = log_error!(archive.persist(), "Persisting node state archive");
+        }
+        _ = log_error!(
+            Self::save_on_disk::<ContractHistory>(
+                self.data_directory.join(CONTRACT_HISTORY_FILE).as_path(),
+                &self.contract_history,
+            ),
+            "Saving contract history"
+        );
+        if let Some(block_hash) = &self.last_block_hash {
+            _ = log_error!(
+                Self::save_on_disk::<ConsensusProposalHash>(
+                    self.data_directory
+                        .join(NODE_STATE_BLOCK_HASH_FILE)
+                        .as_path(),
+                    block_hash,
+                ),
+                "Saving last block hash"
+            );
+        }
+        let staking_path = self.data_directory.join(NODE_STATE_STAKING_FILE);
+        match &self.staking {
+            Some(staking) => {
+                _ = log_error!(
+                    Self::save_on_disk::<Staking>(staking_path.as_path(), staking),
+                    "Saving staking state"
+                );
+            }
+            // Don't reload a staking state that no longer matches the node state
+            None if staking_path.exists() => {
+                _ = log_error!(
+                    std::fs::remove_file(&staking_path),
+                    "Removing outdated staking state"
+                );
+            }
+            None => {}
+        }
         log_error!(
-            Self::save_on_disk::<NodeStateStore>(
+            Self::save_on_disk(
                 self.data_directory.join("node_state.bin").as_path(),
-                &self.inner,
+                &NodeStateFile(&self.inner.store),
             ),
             "Saving node state"
         )
     }
 }
+
+impl NodeStateModule {
+    fn load_snapshot(&mut self, snapshot: NodeStateSnapshot) -> Result<()> {
+        if self.last_block_hash.is_some() || self.inner.current_height.0 > 0 {
+            anyhow::bail!(
+                "Node state is already at height {}, refusing to load a snapshot",
+                self.inner.current_height
+            );
+        }
+        info!(
+            "📸 Loaded node state snapshot at height {} (block {})",
+            snapshot.height(),
+            snapshot.block_hash
+        );
+        self.last_block_hash = Some(snapshot.block_hash);
+        self.inner.store = snapshot.store;
+        self.staking = Some(snapshot.staking);
+        Ok(())
+    }
+
+    fn snapshot(&self) -> Result<NodeStateSnapshot> {
+        let Some(block_hash) = self.last_block_hash.clone() else {
+            anyhow::bail!("No block processed yet, cannot tell the snapshot block hash");
+        };
+        let Some(staking) = self.staking.clone() else {
+            anyhow::bail!(
+                "The staking state is not tracked by this node state, it must be resynced to export snapshots"
+            );
+        };
+        Ok(NodeStateSnapshot::new(
+            block_hash,
+            self.inner.store.clone(),
+            staking,
+        ))
+    }
+}
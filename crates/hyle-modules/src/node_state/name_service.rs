@@ -0,0 +1,160 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{bail, Result};
+use borsh::{BorshDeserialize, BorshSerialize};
+use sdk::{Blob, BlockHeight, ContractName, FungibleTokenAction, Identity, StructuredBlobData};
+
+use super::{ModifiedContractData, SideEffect};
+
+/// Fee owed by a transaction for leasing a name
+#[derive(Debug, Clone)]
+pub struct LeaseFee {
+    pub token: ContractName,
+    pub recipient: Identity,
+    pub amount: u128,
+}
+
+/// Expiry heights of the leased names, used to remove them once expired.
+/// The expiry itself is stored on the contract: entries for names renewed or deleted
+/// in the meantime are left behind and ignored when the height is reached.
+#[derive(Default, Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct ContractLeases {
+    by_block: HashMap<BlockHeight, Vec<ContractName>>,
+}
+
+impl ContractLeases {
+    pub fn set(&mut self, contract_name: ContractName, expiry_height: BlockHeight) {
+        self.by_block
+            .entry(expiry_height)
+            .or_default()
+            .push(contract_name);
+    }
+
+    /// Removes and returns the names whose lease ended at `at`
+    pub fn drop(&mut self, at: &BlockHeight) -> Vec<ContractName> {
+        self.by_block.remove(at).unwrap_or_default()
+    }
+}
+
+/// Checks that the leases settled by a transaction are paid for by its own token transfers,
+/// read through the standard fungible token interface so any conforming token can be used.
+/// Only direct transfers from the transaction identity count, and each one pays once.
+pub fn check_lease_fees<'a>(
+    contract_changes: &BTreeMap<ContractName, ModifiedContractData>,
+    blobs: impl Iterator<Item = &'a Blob>,
+) -> Result<()> {
+    let mut due = BTreeMap::<(ContractName, Identity), u128>::new();
+    for (_, _, side_effects) in contract_changes.values() {
+        for side_effect in side_effects {
+            if let SideEffect::Lease(fee) = side_effect {
+                let amount = due
+                    .entry((fee.token.clone(), fee.recipient.clone()))
+                    .or_default();
+                *amount = amount.saturating_add(fee.amount);
+            }
+        }
+    }
+    if due.is_empty() {
+        return Ok(());
+    }
+
+    let mut paid = BTreeMap::<(ContractName, Identity), u128>::new();
+    for blob in blobs {
+        let Ok(data) = StructuredBlobData::<FungibleTokenAction>::try_from(blob.data.clone())
+        else {
+            continue;
+        };
+        if data.caller.is_some() {
+            continue;
+        }
+        if let FungibleTokenAction::Transfer { recipient, amount } = data.parameters {
+            let total = paid
+                .entry((blob.contract_name.clone(), Identity(recipient)))
+                .or_default();
+            *total = total.saturating_add(amount);
+        }
+    }
+
+    for ((token, recipient), amount) in due {
+        let transferred = paid
+            .get(&(token.clone(), recipient.clone()))
+            .copied()
+            .unwrap_or_default();
+        if transferred < amount {
+            bail!(
+                "Lease fee of {} {} to {} is not paid, only {} transferred",
+                amount,
+                token,
+                recipient,
+                transferred
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdk::ContractAction;
+
+    #[test]
+    fn contract_leases() {
+        let mut leases = ContractLeases::default();
+        let c1 = ContractName::new("c1");
+        let c2 = ContractName::new("c2");
+
+        leases.set(c1.clone(), BlockHeight(10));
+        leases.set(c2.clone(), BlockHeight(10));
+        leases.set(c1.clone(), BlockHeight(20));
+
+        assert_eq!(leases.drop(&BlockHeight(10)), vec![c1.clone(), c2]);
+        assert!(leases.drop(&BlockHeight(10)).is_empty());
+        assert_eq!(leases.drop(&BlockHeight(20)), vec![c1]);
+    }
+
+    #[test]
+    fn lease_fees() {
+        let fee = LeaseFee {
+            token: "hyllar".into(),
+            recipient: Identity::new("registrar@wallet"),
+            amount: 100,
+        };
+        let changes = BTreeMap::from([(
+            ContractName::new("bob.ns"),
+            (None, Default::default(), vec![SideEffect::Lease(fee)]),
+        )]);
+        let transfer = |contract: &str, recipient: &str, amount: u128| {
+            FungibleTokenAction::Transfer {
+                recipient: recipient.to_string(),
+                amount,
+            }
+            .as_blob(contract.into(), None, None)
+        };
+
+        assert!(check_lease_fees(&changes, [].iter()).is_err());
+        assert!(check_lease_fees(
+            &changes,
+            [
+                transfer("hyllar", "registrar@wallet", 60),
+                transfer("hyllar", "registrar@wallet", 40),
+            ]
+            .iter()
+        )
+        .is_ok());
+        // Wrong token, wrong recipient, not enough
+        assert!(check_lease_fees(
+            &changes,
+            [transfer("hyllar2", "registrar@wallet", 100)].iter()
+        )
+        .is_err());
+        assert!(
+            check_lease_fees(&changes, [transfer("hyllar", "bob@wallet", 100)].iter()).is_err()
+        );
+        assert!(check_lease_fees(
+            &changes,
+            [transfer("hyllar", "registrar@wallet", 99)].iter()
+        )
+        .is_err());
+    }
+}
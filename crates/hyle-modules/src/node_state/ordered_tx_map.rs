@@ -11,6 +11,14 @@ pub struct OrderedTxMap {
 }
 
 impl OrderedTxMap {
+    /// Rebuilds the map from its fields, as read from a store saved in an older layout
+    pub(super) fn from_parts(
+        map: HashMap<TxHash, UnsettledBlobTransaction>,
+        tx_order: HashMap<ContractName, VecDeque<TxHash>>,
+    ) -> Self {
+        OrderedTxMap { map, tx_order }
+    }
+
     pub fn get(&self, hash: &TxHash) -> Option<&UnsettledBlobTransaction> {
         self.map.get(hash)
     }
@@ -174,6 +182,7 @@ mod tests {
                 },
             )]),
             tx_context: TxContext::default(),
+            fee: 0,
         }
     }
 
@@ -0,0 +1,73 @@
+//! Priority fees declared by blob transactions.
+//!
+//! The fee is paid to the validator whose lane carries the transaction, by staking
+//! `DepositForFees` blobs naming it as holder. The staking contract only settles a deposit
+//! followed by the matching token transfer, so once the transaction settles the fee is paid.
+
+use anyhow::{bail, Result};
+use sdk::{Blob, LaneId, StakingAction, StructuredBlobData};
+
+/// Name of the contract the priority fees are deposited to
+pub const FEE_CONTRACT: &str = "staking";
+
+/// Sum of the fees deposited for the operator of `lane_id` by the blobs
+pub fn deposited_fee<'a>(lane_id: &LaneId, blobs: impl Iterator<Item = &'a Blob>) -> u128 {
+    blobs
+        .filter(|blob| blob.contract_name.0 == FEE_CONTRACT)
+        .filter_map(|blob| {
+            let data = StructuredBlobData::<StakingAction>::try_from(blob.data.clone()).ok()?;
+            match data.parameters {
+                StakingAction::DepositForFees { holder, amount } if holder == lane_id.0 => {
+                    Some(amount)
+                }
+                _ => None,
+            }
+        })
+        .fold(0, u128::saturating_add)
+}
+
+/// Checks that the fee declared by a transaction is deposited for the operator of its lane
+pub fn check_priority_fee<'a>(
+    fee: u128,
+    lane_id: &LaneId,
+    blobs: impl Iterator<Item = &'a Blob>,
+) -> Result<()> {
+    if fee == 0 {
+        return Ok(());
+    }
+    let deposited = deposited_fee(lane_id, blobs);
+    if deposited < fee {
+        bail!("Priority fee of {fee} is not paid to {lane_id}, only {deposited} deposited");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use sdk::{ContractAction, ValidatorPublicKey};
+
+    use super::*;
+
+    fn deposit(holder: &LaneId, amount: u128) -> Blob {
+        StakingAction::DepositForFees {
+            holder: holder.0.clone(),
+            amount,
+        }
+        .as_blob(FEE_CONTRACT.into(), None, None)
+    }
+
+    #[test]
+    fn priority_fee() {
+        let lane = LaneId(ValidatorPublicKey(vec![1]));
+        let other = LaneId(ValidatorPublicKey(vec![2]));
+
+        assert!(check_priority_fee(0, &lane, [].iter()).is_ok());
+        assert!(check_priority_fee(10, &lane, [].iter()).is_err());
+        assert!(
+            check_priority_fee(10, &lane, [deposit(&lane, 4), deposit(&lane, 6)].iter()).is_ok()
+        );
+        // Deposits for another validator don't pay this one
+        assert!(check_priority_fee(10, &lane, [deposit(&other, 10)].iter()).is_err());
+        assert!(check_priority_fee(10, &lane, [deposit(&lane, 9)].iter()).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::{BTreeMap, HashMap};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use sdk::{BlockHeight, ContractName, PendingProgramIdUpdate, ProgramId};
+
+/// Program id changes waiting for their activation height.
+/// A contract has at most one pending change: scheduling a new one replaces it.
+#[derive(Default, Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct ProgramIdUpdates {
+    pending: BTreeMap<ContractName, PendingProgramIdUpdate>,
+    by_block: HashMap<BlockHeight, Vec<ContractName>>,
+}
+
+impl ProgramIdUpdates {
+    pub fn schedule(
+        &mut self,
+        contract_name: ContractName,
+        program_id: ProgramId,
+        block_height: BlockHeight,
+        activation_delay: BlockHeight,
+    ) -> PendingProgramIdUpdate {
+        let update = PendingProgramIdUpdate {
+            program_id,
+            activation_height: block_height + activation_delay,
+        };
+        self.by_block
+            .entry(update.activation_height)
+            .or_default()
+            .push(contract_name.clone());
+        self.pending.insert(contract_name, update.clone());
+        update
+    }
+
+    /// Drops the pending change of a contract, if any.
+    /// Its entry in `by_block` is left behind and ignored when the height is reached.
+    pub fn cancel(&mut self, contract_name: &ContractName) -> Option<PendingProgramIdUpdate> {
+        self.pending.remove(contract_name)
+    }
+
+    pub fn get(&self, contract_name: &ContractName) -> Option<&PendingProgramIdUpdate> {
+        self.pending.get(contract_name)
+    }
+
+    /// Removes and returns the changes activating at `at`
+    pub fn drop(&mut self, at: &BlockHeight) -> Vec<(ContractName, ProgramId)> {
+        self.by_block
+            .remove(at)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|contract_name| {
+                // Skip changes that were cancelled or rescheduled at another height
+                if self.pending.get(&contract_name)?.activation_height != *at {
+                    return None;
+                }
+                let update = self.pending.remove(&contract_name)?;
+                Some((contract_name, update.program_id))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn program_id_updates() {
+        let mut updates = ProgramIdUpdates::default();
+        let c1 = ContractName::new("c1");
+        let c2 = ContractName::new("c2");
+
+        updates.schedule(
+            c1.clone(),
+            ProgramId(vec![1]),
+            BlockHeight(1),
+            BlockHeight(10),
+        );
+        updates.schedule(
+            c2.clone(),
+            ProgramId(vec![2]),
+            BlockHeight(1),
+            BlockHeight(10),
+        );
+        // Rescheduling replaces the pending change
+        updates.schedule(
+            c2.clone(),
+            ProgramId(vec![3]),
+            BlockHeight(2),
+            BlockHeight(10),
+        );
+        assert_eq!(updates.get(&c2).unwrap().activation_height, BlockHeight(12));
+
+        assert_eq!(
+            updates.drop(&BlockHeight(11)),
+            vec![(c1.clone(), ProgramId(vec![1]))]
+        );
+        assert!(updates.get(&c1).is_none());
+
+        assert!(updates.cancel(&c2).is_some());
+        assert!(updates.drop(&BlockHeight(12)).is_empty());
+        assert!(updates.get(&c2).is_none());
+    }
+}
@@ -0,0 +1,165 @@
+//! Snapshots of the node state, used to bootstrap a node without replaying every block.
+
+use std::{
+    fs,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use borsh::{BorshDeserialize, BorshSerialize};
+use sdk::{BlockHeight, ConsensusProposalHash};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use staking::state::Staking;
+
+use super::NodeStateStore;
+
+/// Full node state at a given height: contracts, unsettled transactions and their timeouts,
+/// the staking state consensus resumes from, and the hash of the block this state was computed at.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct NodeStateSnapshot {
+    /// Hash of the last block processed by `store`
+    pub block_hash: ConsensusProposalHash,
+    pub store: NodeStateStore,
+    /// Staking state once the same block was processed
+    pub staking: Staking,
+}
+
+#[derive(
+    Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, BorshSerialize, BorshDeserialize,
+)]
+pub struct NodeStateSnapshotHash(pub String);
+
+/// On-disk layout of a snapshot file, the hash is stored to detect corrupted files.
+#[derive(BorshSerialize, BorshDeserialize)]
+struct SnapshotFile {
+    hash: NodeStateSnapshotHash,
+    snapshot: NodeStateSnapshot,
+}
+
+impl NodeStateSnapshot {
+    pub fn new(block_hash: ConsensusProposalHash, store: NodeStateStore, staking: Staking) -> Self {
+        Self {
+            block_hash,
+            store,
+            staking,
+        }
+    }
+
+    pub fn height(&self) -> BlockHeight {
+        self.store.current_height
+    }
+
+    /// Hash of the borsh-serialized snapshot.
+    /// Borsh sorts map keys, so two nodes at the same height get the same hash.
+    pub fn hash(&self) -> Result<NodeStateSnapshotHash> {
+        let mut hasher = Sha3_256::new();
+        borsh::to_writer(&mut hasher, self).context("Hashing node state snapshot")?;
+        Ok(NodeStateSnapshotHash(hex::encode(hasher.finalize())))
+    }
+
+    /// Writes the snapshot in the file layout, returns its hash
+    pub fn write(&self, writer: &mut impl Write) -> Result<NodeStateSnapshotHash> {
+        let hash = self.hash()?;
+        // Same layout as SnapshotFile, written field by field to avoid cloning the store
+        borsh::to_writer(&mut *writer, &hash)?;
+        borsh::to_writer(&mut *writer, self)?;
+        Ok(hash)
+    }
+
+    pub fn write_to(&self, path: &Path) -> Result<NodeStateSnapshotHash> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).context("Creating snapshot directory")?;
+        }
+        let mut writer = BufWriter::new(
+            fs::File::create(path)
+                .with_context(|| format!("Creating snapshot file {}", path.display()))?,
+        );
+        let hash = self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(hash)
+    }
+
+    /// Reads a snapshot file and checks its content matches the stored hash.
+    pub fn read_from(path: &Path) -> Result<Self> {
+        let mut reader = BufReader::new(
+            fs::File::open(path)
+                .with_context(|| format!("Opening snapshot file {}", path.display()))?,
+        );
+        let SnapshotFile { hash, snapshot } =
+            borsh::from_reader(&mut reader).context("Decoding snapshot file")?;
+        let computed = snapshot.hash()?;
+        if computed != hash {
+            bail!(
+                "Snapshot {} is corrupted: expected hash {}, got {}",
+                path.display(),
+                hash.0,
+                computed.0
+            );
+        }
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdk::*;
+
+    use super::*;
+
+    fn snapshot() -> NodeStateSnapshot {
+        let mut store = NodeStateStore {
+            current_height: BlockHeight(12),
+            ..Default::default()
+        };
+        store.contracts.insert(
+            "c1".into(),
+            Contract {
+                name: "c1".into(),
+                ..Default::default()
+            },
+        );
+        let mut staking = Staking::default();
+        staking
+            .stake(Identity::new("validator@hydentity"), 100)
+            .unwrap();
+        NodeStateSnapshot::new(ConsensusProposalHash("abc".into()), store, staking)
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("snapshots").join("node_state.snapshot");
+
+        let snapshot = snapshot();
+        let hash = snapshot.write_to(&path)?;
+        assert_eq!(hash, snapshot.hash()?);
+
+        let read = NodeStateSnapshot::read_from(&path)?;
+        assert_eq!(read.height(), BlockHeight(12));
+        assert_eq!(read.block_hash, ConsensusProposalHash("abc".into()));
+        assert_eq!(read.hash()?, hash);
+        assert!(read.store.contracts.contains_key(&"c1".into()));
+        assert_eq!(read.staking, snapshot.staking);
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_corrupted() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("node_state.snapshot");
+        snapshot().write_to(&path)?;
+
+        // Tamper with the content
+        let mut bytes = fs::read(&path)?;
+        let Some(last) = bytes.last_mut() else {
+            bail!("Empty snapshot file");
+        };
+        *last ^= 0xFF;
+        fs::write(&path, bytes)?;
+
+        assert!(NodeStateSnapshot::read_from(&path).is_err());
+        Ok(())
+    }
+}
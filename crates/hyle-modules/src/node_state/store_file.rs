@@ -0,0 +1,203 @@
+//! On-disk layout of the node state store.
+//!
+//! Files start with a header holding the layout version. Files written before the header
+//! existed hold the store in its original layout, before contracts had owners, leases or a
+//! minimum program id update delay, and transactions a fee. They are migrated when loaded.
+
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap, VecDeque},
+    io::{Error, ErrorKind, Read, Result, Write},
+};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use sdk::*;
+use tracing::info;
+
+use super::{
+    name_service::ContractLeases, ordered_tx_map::OrderedTxMap,
+    program_id_updates::ProgramIdUpdates, timeouts::Timeouts, NodeStateStore,
+};
+
+const NODE_STATE_FILE_MAGIC: [u8; 8] = *b"HYLENODE";
+const NODE_STATE_FILE_VERSION: u32 = 1;
+
+/// Node state store as saved on disk: the header, then the store.
+/// Stores are written from a reference, `NodeStateFile(&store)`, to avoid cloning them.
+pub struct NodeStateFile<S = NodeStateStore>(pub S);
+
+impl<S: Borrow<NodeStateStore>> BorshSerialize for NodeStateFile<S> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&NODE_STATE_FILE_MAGIC)?;
+        NODE_STATE_FILE_VERSION.serialize(writer)?;
+        self.0.borrow().serialize(writer)
+    }
+}
+
+impl BorshDeserialize for NodeStateFile {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != NODE_STATE_FILE_MAGIC {
+            // No header: these bytes are the start of a store in the original layout
+            let mut reader = (&magic[..]).chain(reader);
+            let legacy = LegacyNodeStateStore::deserialize_reader(&mut reader)?;
+            info!("Migrating node state saved in the original layout");
+            return Ok(NodeStateFile(legacy.into()));
+        }
+        match u32::deserialize_reader(reader)? {
+            NODE_STATE_FILE_VERSION => {
+                Ok(NodeStateFile(NodeStateStore::deserialize_reader(reader)?))
+            }
+            version => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Unknown node state file version {version}"),
+            )),
+        }
+    }
+}
+
+#[derive(BorshDeserialize)]
+struct LegacyNodeStateStore {
+    timeouts: Timeouts,
+    current_height: BlockHeight,
+    contracts: HashMap<ContractName, LegacyContract>,
+    unsettled_transactions: LegacyOrderedTxMap,
+}
+
+#[derive(BorshDeserialize)]
+struct LegacyContract {
+    name: ContractName,
+    program_id: ProgramId,
+    state: StateCommitment,
+    verifier: Verifier,
+    timeout_window: TimeoutWindow,
+}
+
+#[derive(BorshDeserialize)]
+struct LegacyOrderedTxMap {
+    map: HashMap<TxHash, LegacyUnsettledBlobTransaction>,
+    tx_order: HashMap<ContractName, VecDeque<TxHash>>,
+}
+
+#[derive(BorshDeserialize)]
+struct LegacyUnsettledBlobTransaction {
+    identity: Identity,
+    parent_dp_hash: DataProposalHash,
+    hash: TxHash,
+    tx_context: TxContext,
+    blobs_hash: BlobsHashes,
+    blobs: BTreeMap<BlobIndex, UnsettledBlobMetadata>,
+}
+
+impl From<LegacyNodeStateStore> for NodeStateStore {
+    fn from(legacy: LegacyNodeStateStore) -> Self {
+        let contracts = legacy
+            .contracts
+            .into_iter()
+            .map(|(name, contract)| {
+                (
+                    name,
+                    Contract {
+                        name: contract.name,
+                        program_id: contract.program_id,
+                        state: contract.state,
+                        verifier: contract.verifier,
+                        timeout_window: contract.timeout_window,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        let map = legacy
+            .unsettled_transactions
+            .map
+            .into_iter()
+            .map(|(hash, tx)| {
+                (
+                    hash,
+                    UnsettledBlobTransaction {
+                        identity: tx.identity,
+                        parent_dp_hash: tx.parent_dp_hash,
+                        hash: tx.hash,
+                        tx_context: tx.tx_context,
+                        blobs_hash: tx.blobs_hash,
+                        blobs: tx.blobs,
+                        fee: 0,
+                    },
+                )
+            })
+            .collect();
+        NodeStateStore {
+            timeouts: legacy.timeouts,
+            program_id_updates: ProgramIdUpdates::default(),
+            contract_leases: ContractLeases::default(),
+            current_height: legacy.current_height,
+            contracts,
+            unsettled_transactions: OrderedTxMap::from_parts(
+                map,
+                legacy.unsettled_transactions.tx_order,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_original_layout() -> anyhow::Result<()> {
+        let tx_hash = TxHash("tx".into());
+        let contract_name = ContractName::new("c1");
+        // Store encoded before contracts and transactions got new fields
+        let legacy = borsh::to_vec(&(
+            Timeouts::default(),
+            BlockHeight(12),
+            HashMap::from([(
+                contract_name.clone(),
+                (
+                    contract_name.clone(),
+                    ProgramId(vec![1]),
+                    StateCommitment(vec![2]),
+                    Verifier("test".into()),
+                    TimeoutWindow::NoTimeout,
+                ),
+            )]),
+            HashMap::from([(
+                tx_hash.clone(),
+                (
+                    Identity::new("alice@c1"),
+                    DataProposalHash("dp".into()),
+                    tx_hash.clone(),
+                    TxContext::default(),
+                    BlobsHashes::default(),
+                    BTreeMap::<BlobIndex, UnsettledBlobMetadata>::new(),
+                ),
+            )]),
+            HashMap::from([(contract_name.clone(), VecDeque::from([tx_hash.clone()]))]),
+        ))?;
+
+        let NodeStateFile(store) = borsh::from_slice::<NodeStateFile>(&legacy)?;
+        assert_eq!(store.current_height, BlockHeight(12));
+        let contract = store.contracts.get(&contract_name).unwrap();
+        assert_eq!(contract.program_id, ProgramId(vec![1]));
+        assert_eq!(contract.owner, None);
+        let tx = store.unsettled_transactions.get(&tx_hash).unwrap();
+        assert_eq!(tx.fee, 0);
+        assert_eq!(
+            store
+                .unsettled_transactions
+                .get_next_unsettled_tx(&contract_name),
+            Some(&tx_hash)
+        );
+
+        // Saved again with the header, in the current layout
+        let saved = borsh::to_vec(&NodeStateFile(&store))?;
+        assert!(saved.starts_with(&NODE_STATE_FILE_MAGIC));
+        let NodeStateFile(reloaded) = borsh::from_slice::<NodeStateFile>(&saved)?;
+        assert_eq!(reloaded.contracts.len(), store.contracts.len());
+        assert!(reloaded.unsettled_transactions.get(&tx_hash).is_some());
+        Ok(())
+    }
+}
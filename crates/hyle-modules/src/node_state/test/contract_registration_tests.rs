@@ -314,6 +314,27 @@ pub fn make_update_timeout_window_tx_with_hyli(
         ],
     )
 }
+pub fn make_schedule_program_id_tx_with_hyli(
+    tld: ContractName,
+    contract_name: ContractName,
+    schedule: ProgramIdUpdateSchedule,
+) -> BlobTransaction {
+    BlobTransaction::new(
+        HYLI_TLD_ID.to_string(),
+        vec![
+            HydentityAction::VerifyIdentity {
+                nonce: 0,
+                account: HYLI_TLD_ID.to_string(),
+            }
+            .as_blob(HYLI_WALLET.into()),
+            ScheduleContractProgramIdUpdateAction {
+                contract_name,
+                schedule,
+            }
+            .as_blob(tld, None, None),
+        ],
+    )
+}
 #[test_log::test(tokio::test)]
 async fn test_register_contract_and_delete_hyle() {
     let mut state = new_node_state().await;
@@ -598,6 +619,343 @@ async fn test_hyle_contract_update_timeout_window() {
     );
 }
 
+#[test_log::test(tokio::test)]
+async fn test_hyle_contract_scheduled_program_id_update() {
+    let mut state = new_node_state().await;
+    let register_wallet = make_register_tx("hyle@hyle".into(), "hyle".into(), "wallet".into());
+    let register_hyli_at_wallet = make_register_hyli_wallet_identity_tx();
+
+    let mut output = make_hyle_output(register_hyli_at_wallet.clone(), BlobIndex(0));
+    let register_hyli_at_wallet_proof =
+        new_proof_tx(&"wallet".into(), &output, &register_hyli_at_wallet.hashed());
+
+    let register_contract = make_register_tx("hyle@hyle".into(), "hyle".into(), "contract".into());
+    let contract_name = ContractName::new("contract");
+
+    state.craft_block_and_handle(
+        1,
+        vec![
+            register_wallet.into(),
+            register_hyli_at_wallet.into(),
+            register_hyli_at_wallet_proof.into(),
+            register_contract.into(),
+        ],
+    );
+
+    let schedule_tx = make_schedule_program_id_tx_with_hyli(
+        "hyle".into(),
+        contract_name.clone(),
+        ProgramIdUpdateSchedule::Schedule {
+            program_id: ProgramId(vec![7]),
+            activation_delay: BlockHeight(2),
+        },
+    );
+    let output = make_hyle_output_bis(schedule_tx.clone(), BlobIndex(0));
+    let verify_hyli_proof = new_proof_tx(&"wallet".into(), &output, &schedule_tx.hashed());
+
+    let block = state.craft_block_and_handle(2, vec![schedule_tx.into(), verify_hyli_proof.into()]);
+
+    let pending = PendingProgramIdUpdate {
+        program_id: ProgramId(vec![7]),
+        activation_height: BlockHeight(4),
+    };
+    assert_eq!(
+        block.scheduled_program_ids.get(&contract_name),
+        Some(&pending)
+    );
+    assert_eq!(
+        state.get_pending_program_id_update(&contract_name),
+        Some(&pending)
+    );
+
+    // The program id only changes once the activation height is reached
+    let block = state.craft_block_and_handle(3, vec![]);
+    assert!(block.updated_program_ids.is_empty());
+    assert_eq!(
+        state.contracts.get(&contract_name).unwrap().program_id,
+        ProgramId(vec![])
+    );
+
+    let block = state.craft_block_and_handle(4, vec![]);
+    assert_eq!(
+        block.updated_program_ids.get(&contract_name),
+        Some(&ProgramId(vec![7]))
+    );
+    assert_eq!(
+        state.contracts.get(&contract_name).unwrap().program_id,
+        ProgramId(vec![7])
+    );
+    assert!(state
+        .get_pending_program_id_update(&contract_name)
+        .is_none());
+
+    // A pending change can be cancelled before it activates
+    let schedule_tx = make_schedule_program_id_tx_with_hyli(
+        "hyle".into(),
+        contract_name.clone(),
+        ProgramIdUpdateSchedule::Schedule {
+            program_id: ProgramId(vec![8]),
+            activation_delay: BlockHeight(2),
+        },
+    );
+    let output = make_hyle_output_with_state(schedule_tx.clone(), BlobIndex(0), &[7, 8, 9], &[1]);
+    let verify_hyli_proof = new_proof_tx(&"wallet".into(), &output, &schedule_tx.hashed());
+    state.craft_block_and_handle(5, vec![schedule_tx.into(), verify_hyli_proof.into()]);
+    assert!(state
+        .get_pending_program_id_update(&contract_name)
+        .is_some());
+
+    let cancel_tx = make_schedule_program_id_tx_with_hyli(
+        "hyle".into(),
+        contract_name.clone(),
+        ProgramIdUpdateSchedule::Cancel,
+    );
+    let output = make_hyle_output_with_state(cancel_tx.clone(), BlobIndex(0), &[1], &[2]);
+    let verify_hyli_proof = new_proof_tx(&"wallet".into(), &output, &cancel_tx.hashed());
+    let block = state.craft_block_and_handle(6, vec![cancel_tx.into(), verify_hyli_proof.into()]);
+    assert!(block.cancelled_program_ids.contains(&contract_name));
+    assert!(state
+        .get_pending_program_id_update(&contract_name)
+        .is_none());
+
+    let block = state.craft_block_and_handle(7, vec![]);
+    assert!(block.updated_program_ids.is_empty());
+    assert_eq!(
+        state.contracts.get(&contract_name).unwrap().program_id,
+        ProgramId(vec![7])
+    );
+}
+
+/// Sends `action` to the hyle TLD from an identity proven by the "wallet" contract
+fn craft_wallet_tx_and_handle(
+    state: &mut NodeState,
+    height: u64,
+    sender: &Identity,
+    action: Blob,
+    wallet_state: u8,
+) -> Block {
+    let tx = BlobTransaction::new(
+        sender.clone(),
+        vec![
+            Blob {
+                contract_name: "wallet".into(),
+                data: BlobData(vec![]),
+            },
+            action,
+        ],
+    );
+    let output = make_hyle_output_with_state(
+        tx.clone(),
+        BlobIndex(0),
+        &[wallet_state],
+        &[wallet_state + 1],
+    );
+    let proof = new_proof_tx(&"wallet".into(), &output, &tx.hashed());
+    state.craft_block_and_handle(height, vec![tx.into(), proof.into()])
+}
+
+#[test_log::test(tokio::test)]
+async fn test_hyle_contract_ownership() {
+    let mut state = new_node_state().await;
+    state.contracts.insert(
+        "wallet".into(),
+        Contract {
+            name: "wallet".into(),
+            program_id: ProgramId(vec![]),
+            state: StateCommitment(vec![0]),
+            verifier: Verifier("test".into()),
+            timeout_window: TimeoutWindow::NoTimeout,
+            owner: None,
+            name_service: None,
+            expiry_height: None,
+            min_program_id_update_delay: None,
+        },
+    );
+    let contract_name = ContractName::new("contract");
+    let owner = Identity::new("alice@wallet");
+    let new_owner = Identity::new("bob@wallet");
+
+    let register = RegisterContractAction {
+        verifier: "test".into(),
+        program_id: ProgramId(vec![]),
+        state_commitment: StateCommitment(vec![0, 1, 2, 3]),
+        contract_name: contract_name.clone(),
+        ..Default::default()
+    }
+    .as_blob("hyle".into(), None, None);
+    let block = craft_wallet_tx_and_handle(&mut state, 1, &owner, register, 0);
+    assert_eq!(block.updated_owners.get(&contract_name), Some(&owner));
+    assert_eq!(
+        state.contracts.get(&contract_name).unwrap().owner,
+        Some(owner.clone())
+    );
+
+    let transfer = |current_owner: &Identity| {
+        TransferContractOwnershipAction {
+            contract_name: contract_name.clone(),
+            new_owner: new_owner.clone(),
+            current_owner: current_owner.clone(),
+        }
+        .as_blob("hyle".into(), None, None)
+    };
+
+    // Only the owner can transfer the contract
+    let block = craft_wallet_tx_and_handle(&mut state, 2, &new_owner, transfer(&owner), 1);
+    assert_eq!(block.failed_txs.len(), 1);
+    // And the current owner must match
+    let block = craft_wallet_tx_and_handle(&mut state, 3, &owner, transfer(&new_owner), 1);
+    assert_eq!(block.failed_txs.len(), 1);
+
+    let block = craft_wallet_tx_and_handle(&mut state, 4, &owner, transfer(&owner), 1);
+    assert_eq!(block.updated_owners.get(&contract_name), Some(&new_owner));
+    assert_eq!(
+        state.contracts.get(&contract_name).unwrap().owner,
+        Some(new_owner.clone())
+    );
+
+    // Identities of the native verifiers are not proven, so they cannot own contracts
+    let to_verifier = TransferContractOwnershipAction {
+        contract_name: contract_name.clone(),
+        new_owner: Identity::new("bob@blst"),
+        current_owner: new_owner.clone(),
+    }
+    .as_blob("hyle".into(), None, None);
+    let block = craft_wallet_tx_and_handle(&mut state, 5, &new_owner, to_verifier, 2);
+    assert_eq!(block.failed_txs.len(), 1);
+
+    // The previous owner cannot manage the contract anymore
+    let delete = DeleteContractAction {
+        contract_name: contract_name.clone(),
+    }
+    .as_blob("hyle".into(), None, None);
+    let block = craft_wallet_tx_and_handle(&mut state, 6, &owner, delete.clone(), 2);
+    assert!(block.deleted_contracts.is_empty());
+
+    let update = UpdateContractTimeoutWindowAction {
+        contract_name: contract_name.clone(),
+        timeout_window: TimeoutWindow::Timeout(BlockHeight(45)),
+    }
+    .as_blob("hyle".into(), None, None);
+    let block = craft_wallet_tx_and_handle(&mut state, 7, &new_owner, update, 2);
+    assert_eq!(
+        block.updated_timeout_windows.get(&contract_name),
+        Some(&TimeoutWindow::Timeout(BlockHeight(45)))
+    );
+
+    let block = craft_wallet_tx_and_handle(&mut state, 8, &new_owner, delete, 3);
+    assert!(block.deleted_contracts.contains_key(&contract_name));
+}
+
+#[test_log::test(tokio::test)]
+async fn test_hyle_contract_min_program_id_update_delay() {
+    let mut state = new_node_state().await;
+    state.contracts.insert(
+        "wallet".into(),
+        Contract {
+            name: "wallet".into(),
+            program_id: ProgramId(vec![]),
+            state: StateCommitment(vec![0]),
+            verifier: Verifier("test".into()),
+            timeout_window: TimeoutWindow::NoTimeout,
+            ..Default::default()
+        },
+    );
+    let contract_name = ContractName::new("contract");
+    let owner = Identity::new("alice@wallet");
+
+    let register = RegisterContractAction {
+        verifier: "test".into(),
+        program_id: ProgramId(vec![]),
+        state_commitment: StateCommitment(vec![0, 1, 2, 3]),
+        contract_name: contract_name.clone(),
+        ..Default::default()
+    }
+    .as_blob("hyle".into(), None, None);
+    craft_wallet_tx_and_handle(&mut state, 1, &owner, register, 0);
+
+    let schedule = |schedule: ProgramIdUpdateSchedule| {
+        ScheduleContractProgramIdUpdateAction {
+            contract_name: contract_name.clone(),
+            schedule,
+        }
+        .as_blob("hyle".into(), None, None)
+    };
+    let block = craft_wallet_tx_and_handle(
+        &mut state,
+        2,
+        &owner,
+        schedule(ProgramIdUpdateSchedule::RequireMinDelay {
+            min_delay: BlockHeight(10),
+        }),
+        1,
+    );
+    assert!(block.failed_txs.is_empty());
+    assert_eq!(
+        state
+            .contracts
+            .get(&contract_name)
+            .unwrap()
+            .min_program_id_update_delay,
+        Some(BlockHeight(10))
+    );
+    // The governance change is visible in the settled block
+    assert_eq!(
+        block.updated_min_program_id_delays.get(&contract_name),
+        Some(&BlockHeight(10))
+    );
+
+    // The owner cannot skip the notice anymore
+    let update = UpdateContractProgramIdAction {
+        contract_name: contract_name.clone(),
+        program_id: ProgramId(vec![7]),
+    }
+    .as_blob("hyle".into(), None, None);
+    let block = craft_wallet_tx_and_handle(&mut state, 3, &owner, update, 2);
+    assert_eq!(block.failed_txs.len(), 1);
+    assert!(block.updated_program_ids.is_empty());
+
+    // Nor schedule a change with a shorter notice, or lower the minimum
+    let block = craft_wallet_tx_and_handle(
+        &mut state,
+        4,
+        &owner,
+        schedule(ProgramIdUpdateSchedule::Schedule {
+            program_id: ProgramId(vec![7]),
+            activation_delay: BlockHeight(5),
+        }),
+        2,
+    );
+    assert_eq!(block.failed_txs.len(), 1);
+    let block = craft_wallet_tx_and_handle(
+        &mut state,
+        5,
+        &owner,
+        schedule(ProgramIdUpdateSchedule::RequireMinDelay {
+            min_delay: BlockHeight(5),
+        }),
+        2,
+    );
+    assert_eq!(block.failed_txs.len(), 1);
+
+    let block = craft_wallet_tx_and_handle(
+        &mut state,
+        6,
+        &owner,
+        schedule(ProgramIdUpdateSchedule::Schedule {
+            program_id: ProgramId(vec![7]),
+            activation_delay: BlockHeight(10),
+        }),
+        2,
+    );
+    assert_eq!(
+        block
+            .scheduled_program_ids
+            .get(&contract_name)
+            .map(|update| update.activation_height),
+        Some(BlockHeight(16))
+    );
+}
+
 #[test_log::test(tokio::test)]
 async fn test_hyle_sub_delete() {
     let mut state = new_node_state().await;
@@ -1050,6 +1408,10 @@ async fn domino_settlement_after_contract_delete() {
             state: StateCommitment(vec![0, 1, 2, 3]),
             verifier: Verifier("test".into()),
             timeout_window: TimeoutWindow::Timeout(BlockHeight(100)),
+            owner: None,
+            name_service: None,
+            expiry_height: None,
+            min_program_id_update_delay: None,
         },
     );
     let a = ContractName::new("a");
@@ -1102,3 +1464,190 @@ async fn domino_settlement_after_contract_delete() {
     // This should domino through and settle the tx_ab and tx_a if not already settled
     assert!(block_b.successful_txs.contains(&tx_a_id));
 }
+
+/// Sends `ns_blob` to the "ns" name service from bob, along with a hyllar transfer of `fee`,
+/// and proves both blobs: the ns proof outputs `effects`.
+fn craft_lease_tx_and_handle(
+    state: &mut NodeState,
+    height: u64,
+    ns_blob: Blob,
+    effects: Vec<OnchainEffect>,
+    fee: u128,
+    step: u8,
+) -> Block {
+    let tx = BlobTransaction::new(
+        "bob@ns",
+        vec![
+            FungibleTokenAction::Transfer {
+                recipient: "alice@wallet".to_string(),
+                amount: fee,
+            }
+            .as_blob("hyllar".into(), None, None),
+            ns_blob,
+        ],
+    );
+    let hyllar_output = make_hyle_output_with_state(tx.clone(), BlobIndex(0), &[step], &[step + 1]);
+    let mut ns_output = make_hyle_output_with_state(tx.clone(), BlobIndex(1), &[step], &[step + 1]);
+    ns_output.onchain_effects = effects;
+    let hyllar_proof = new_proof_tx(&"hyllar".into(), &hyllar_output, &tx.hashed());
+    let ns_proof = new_proof_tx(&"ns".into(), &ns_output, &tx.hashed());
+    state.craft_block_and_handle(
+        height,
+        vec![tx.into(), hyllar_proof.into(), ns_proof.into()],
+    )
+}
+
+#[test_log::test(tokio::test)]
+async fn test_name_service_leases() {
+    let mut state = new_node_state().await;
+    let alice = Identity::new("alice@wallet");
+    // Transactions with invalid proofs time out right away instead of blocking the next ones
+    for (name, timeout_window, owner) in [
+        ("wallet", TimeoutWindow::NoTimeout, None),
+        ("hyllar", TimeoutWindow::Timeout(BlockHeight(1)), None),
+        (
+            "ns",
+            TimeoutWindow::Timeout(BlockHeight(1)),
+            Some(alice.clone()),
+        ),
+    ] {
+        state.contracts.insert(
+            name.into(),
+            Contract {
+                name: name.into(),
+                program_id: ProgramId(vec![]),
+                state: StateCommitment(vec![0]),
+                verifier: Verifier("test".into()),
+                timeout_window,
+                owner,
+                name_service: None,
+                expiry_height: None,
+                min_program_id_update_delay: None,
+            },
+        );
+    }
+    let name = ContractName::new("bob.ns");
+    let register = RegisterContractAction {
+        verifier: "test".into(),
+        program_id: ProgramId(vec![]),
+        state_commitment: StateCommitment(vec![0]),
+        contract_name: name.clone(),
+        timeout_window: Some(TimeoutWindow::NoTimeout),
+        ..Default::default()
+    };
+    let register_effects = |period: u64| {
+        vec![
+            OnchainEffect::RegisterContract(register.clone().into()),
+            OnchainEffect::LeaseContract(LeaseContractEffect {
+                contract_name: name.clone(),
+                period: BlockHeight(period),
+            }),
+        ]
+    };
+    let renew_effects = |period: u64| {
+        vec![OnchainEffect::LeaseContract(LeaseContractEffect {
+            contract_name: name.clone(),
+            period: BlockHeight(period),
+        })]
+    };
+    let renew_blob = Blob {
+        contract_name: "ns".into(),
+        data: BlobData(vec![]),
+    };
+
+    // Leases are rejected until the TLD is a name service
+    let block = craft_lease_tx_and_handle(
+        &mut state,
+        1,
+        register.as_blob("ns".into(), None, None),
+        register_effects(20),
+        200,
+        0,
+    );
+    assert!(block.successful_txs.is_empty());
+
+    let configure = ConfigureNameServiceAction {
+        tld: "ns".into(),
+        fee_token: "hyllar".into(),
+        fee_recipient: alice.clone(),
+        fee_per_block: 10,
+        max_period: BlockHeight(100),
+    };
+    let block = craft_wallet_tx_and_handle(
+        &mut state,
+        2,
+        &alice,
+        configure.as_blob("hyle".into(), None, None),
+        0,
+    );
+    assert_eq!(
+        block.updated_name_services.get(&ContractName::new("ns")),
+        Some(&configure.config())
+    );
+
+    // Names must now be leased, and the lease paid for
+    let block = craft_lease_tx_and_handle(
+        &mut state,
+        3,
+        register.as_blob("ns".into(), None, None),
+        register_effects(20)[..1].to_vec(),
+        200,
+        0,
+    );
+    assert!(block.successful_txs.is_empty());
+    let block = craft_lease_tx_and_handle(
+        &mut state,
+        4,
+        register.as_blob("ns".into(), None, None),
+        register_effects(20),
+        199,
+        0,
+    );
+    assert_eq!(block.failed_txs.len(), 1);
+
+    let block = craft_lease_tx_and_handle(
+        &mut state,
+        5,
+        register.as_blob("ns".into(), None, None),
+        register_effects(20),
+        200,
+        0,
+    );
+    assert!(block.registered_contracts.contains_key(&name));
+    assert_eq!(block.updated_expiries.get(&name), Some(&BlockHeight(25)));
+    assert_eq!(
+        state.contracts.get(&name).unwrap().expiry_height,
+        Some(BlockHeight(25))
+    );
+
+    // The name is taken until it expires
+    let block = craft_lease_tx_and_handle(
+        &mut state,
+        6,
+        register.as_blob("ns".into(), None, None),
+        register_effects(20),
+        200,
+        1,
+    );
+    assert!(block.successful_txs.is_empty());
+
+    // Renewals extend the lease, up to the max period from now
+    let block =
+        craft_lease_tx_and_handle(&mut state, 7, renew_blob.clone(), renew_effects(90), 900, 1);
+    assert!(block.successful_txs.is_empty());
+    let block =
+        craft_lease_tx_and_handle(&mut state, 8, renew_blob.clone(), renew_effects(10), 100, 1);
+    assert_eq!(block.updated_expiries.get(&name), Some(&BlockHeight(35)));
+
+    // Unsettled transactions of the name time out when it expires
+    let tx = BlobTransaction::new("carol@bob.ns", vec![new_blob("bob.ns")]);
+    state.craft_block_and_handle(9, vec![tx.clone().into()]);
+    for height in 10..35 {
+        let block = state.craft_block_and_handle(height, vec![]);
+        assert!(block.expired_contracts.is_empty());
+    }
+    let block = state.craft_block_and_handle(35, vec![]);
+    assert!(block.expired_contracts.contains(&name));
+    assert!(!state.contracts.contains_key(&name));
+    assert!(state.unsettled_transactions.get(&tx.hashed()).is_none());
+}
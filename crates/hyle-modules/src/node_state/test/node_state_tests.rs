@@ -1787,3 +1787,93 @@ async fn test_nuke_transaction_forces_target_to_fail() {
     // Verify that tx2 is no longer in unsettled transactions
     assert!(state.unsettled_transactions.get(&tx2_hash).is_none());
 }
+
+#[test_log::test(tokio::test)]
+async fn test_simulate_blob_tx() {
+    let mut state = new_node_state().await;
+    let c1 = ContractName::new("c1");
+    state.handle_register_contract_effect(&make_register_contract_effect(c1.clone()));
+
+    let simulation = state.simulate_blob_tx(&BlobTransaction::new(
+        "test@c1",
+        vec![new_blob("c1"), new_blob("unknown")],
+    ));
+    assert!(!simulation.accepted);
+    assert_eq!(simulation.identity_error, None);
+    assert_eq!(
+        simulation.unknown_contracts,
+        vec![ContractName::new("unknown")]
+    );
+
+    let simulation =
+        state.simulate_blob_tx(&BlobTransaction::new("no_contract", vec![new_blob("c1")]));
+    assert!(!simulation.accepted);
+    assert!(simulation.identity_error.is_some());
+
+    // Registering an existing contract is sequenced but fails to settle
+    let simulation = state.simulate_blob_tx(&make_register_tx(
+        "hyle@hyle".into(),
+        "hyle".into(),
+        c1.clone(),
+    ));
+    assert!(simulation.accepted);
+    assert_eq!(simulation.hyle_tld_blobs.len(), 1);
+    assert!(simulation.hyle_tld_blobs.first().unwrap().error.is_some());
+
+    let simulation = state.simulate_blob_tx(&make_register_tx(
+        "hyle@hyle".into(),
+        "hyle".into(),
+        "c2".into(),
+    ));
+    assert!(simulation.accepted);
+    assert_eq!(simulation.hyle_tld_blobs.first().unwrap().error, None);
+    assert_eq!(simulation.timeout_window, None);
+
+    // Nothing was registered
+    assert!(!state.contracts.contains_key(&ContractName::new("c2")));
+}
+
+#[test_log::test(tokio::test)]
+async fn test_priority_fee_must_be_deposited_for_the_lane_operator() {
+    let mut state = new_node_state().await;
+    let c1 = ContractName::new("c1");
+    let staking = ContractName::new(priority_fee::FEE_CONTRACT);
+    state.handle_register_contract_effect(&make_register_contract_effect(c1.clone()));
+    state.handle_register_contract_effect(&make_register_contract_effect(staking.clone()));
+
+    let identity = Identity::new("test@c1");
+    let unpaid = BlobTransaction::new(identity.clone(), vec![new_blob("c1")]).with_fee(10);
+    let deposit = StakingAction::DepositForFees {
+        holder: LaneId::default().0,
+        amount: 10,
+    }
+    .as_blob(staking.clone(), None, None);
+    let paid = BlobTransaction::new(identity, vec![new_blob("c1"), deposit]).with_fee(10);
+
+    state.craft_block_and_handle(1, vec![unpaid.clone().into(), paid.clone().into()]);
+
+    let proofs: Vec<Transaction> = vec![
+        new_proof_tx(
+            &c1,
+            &make_hyle_output(unpaid.clone(), BlobIndex(0)),
+            &unpaid.hashed(),
+        )
+        .into(),
+        new_proof_tx(
+            &c1,
+            &make_hyle_output(paid.clone(), BlobIndex(0)),
+            &paid.hashed(),
+        )
+        .into(),
+        new_proof_tx(
+            &staking,
+            &make_hyle_output(paid.clone(), BlobIndex(1)),
+            &paid.hashed(),
+        )
+        .into(),
+    ];
+    let block = state.craft_block_and_handle(2, proofs);
+
+    assert_eq!(block.failed_txs, vec![unpaid.hashed()]);
+    assert_eq!(block.successful_txs, vec![paid.hashed()]);
+}
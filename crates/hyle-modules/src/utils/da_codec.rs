@@ -11,6 +11,12 @@ pub struct DataAvailabilityRequest(pub BlockHeight);
 pub enum DataAvailabilityEvent {
     SignedBlock(SignedBlock),
     MempoolStatusEvent(MempoolStatusEvent),
+    /// Sent when the requested start height is below the lowest block the server retains.
+    /// Streaming then resumes from `first_available`.
+    BlocksPruned {
+        requested: BlockHeight,
+        first_available: BlockHeight,
+    },
 }
 
 pub type DataAvailabilityServer = TcpServer<DataAvailabilityRequest, DataAvailabilityEvent>;
@@ -0,0 +1,466 @@
+//! DA servers a listener can stream blocks from.
+//!
+//! Blocks are streamed from one source at a time, failing over to the next one when it stalls
+//! or cannot be reached. With cross-checking enabled, the following source is streamed as a
+//! witness: its blocks are not processed, but a primary block is only processed once the
+//! witness streamed the same one. A witness that stays unreachable halts the listener.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use anyhow::{bail, Result};
+use opentelemetry::{
+    metrics::{Counter, Gauge},
+    KeyValue,
+};
+use sdk::{BlockHeight, ConsensusProposalHash, Hashed, SignedBlock};
+use tracing::{info, warn};
+
+use super::da_codec::{DataAvailabilityClient, DataAvailabilityEvent, DataAvailabilityRequest};
+
+/// Primary blocks held until the witness streams them
+const MAX_UNCONFIRMED_BLOCKS: usize = 1_000;
+/// Hashes streamed by the witness ahead of the primary stream
+const MAX_WITNESSED_BLOCKS: usize = 10_000;
+/// Interval between reconnections of the witness
+const WITNESS_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+/// Reconnections of the witness without receiving a block from it, before halting
+const MAX_WITNESS_RETRIES: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DAStream {
+    Primary,
+    Witness,
+}
+
+/// Stream of the witness source
+pub enum Witness {
+    /// Cross-checking is disabled
+    Disabled,
+    /// The witness cannot be reached, it is reconnected after an interval
+    Disconnected,
+    Connected(DataAvailabilityClient),
+}
+
+#[derive(Debug, Clone)]
+struct DASourcesMetrics {
+    source_height: Gauge<u64>,
+    source_lag: Gauge<u64>,
+    failovers: Counter<u64>,
+}
+
+impl DASourcesMetrics {
+    fn global() -> Self {
+        let my_meter = opentelemetry::global::meter("da_listener");
+
+        DASourcesMetrics {
+            source_height: my_meter.u64_gauge("da_listener_source_height").build(),
+            source_lag: my_meter.u64_gauge("da_listener_source_lag").build(),
+            failovers: my_meter.u64_counter("da_listener_failovers").build(),
+        }
+    }
+}
+
+pub struct DASources {
+    client_name: String,
+    sources: Vec<String>,
+    current: usize,
+    cross_check: bool,
+    /// Last block height received from each source
+    heights: Vec<Option<BlockHeight>>,
+    /// Primary blocks waiting for the witness to stream the same block
+    unconfirmed: BTreeMap<BlockHeight, SignedBlock>,
+    /// Hashes streamed by the witness, waiting for the primary block
+    witnessed: BTreeMap<BlockHeight, ConsensusProposalHash>,
+    /// Reconnections of the witness since the last block it streamed
+    witness_retries: u32,
+    metrics: DASourcesMetrics,
+}
+
+impl DASources {
+    pub fn new(client_name: impl Into<String>, sources: Vec<String>, cross_check: bool) -> Self {
+        if cross_check && sources.len() < 2 {
+            warn!("DA cross-checking needs at least two sources, it is disabled");
+        }
+        DASources {
+            client_name: client_name.into(),
+            heights: vec![None; sources.len()],
+            sources,
+            current: 0,
+            cross_check,
+            unconfirmed: BTreeMap::new(),
+            witnessed: BTreeMap::new(),
+            witness_retries: 0,
+            metrics: DASourcesMetrics::global(),
+        }
+    }
+
+    /// Number of configured sources
+    pub fn count(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Source the blocks are currently streamed from
+    pub fn current(&self) -> &str {
+        self.sources
+            .get(self.current)
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+
+    fn witness_index(&self) -> Option<usize> {
+        match self.cross_check && self.sources.len() > 1 {
+            true => Some((self.current + 1) % self.sources.len()),
+            false => None,
+        }
+    }
+
+    async fn connect_to(&self, index: usize, from: BlockHeight) -> Result<DataAvailabilityClient> {
+        let Some(source) = self.sources.get(index) else {
+            bail!("No DA source configured");
+        };
+        let mut client = DataAvailabilityClient::connect_with_opts(
+            self.client_name.clone(),
+            Some(1024 * 1024 * 1024),
+            source.clone(),
+        )
+        .await?;
+
+        client.send(DataAvailabilityRequest(from)).await?;
+
+        Ok(client)
+    }
+
+    /// Streams blocks from height `from`, from the current source or the next reachable one
+    pub async fn connect(&mut self, from: BlockHeight) -> Result<DataAvailabilityClient> {
+        for _ in 0..self.sources.len() {
+            match self.connect_to(self.current, from).await {
+                Ok(client) => return Ok(client),
+                Err(e) => {
+                    warn!(
+                        "Cannot stream blocks from DA source {}: {e:#}",
+                        self.current()
+                    );
+                    self.next_source();
+                }
+            }
+        }
+        bail!("None of the DA sources {:?} can be reached", self.sources)
+    }
+
+    /// Streams blocks from height `from`, starting with the source after the current one
+    pub async fn failover(&mut self, from: BlockHeight) -> Result<DataAvailabilityClient> {
+        self.next_source();
+        self.connect(from).await
+    }
+
+    fn next_source(&mut self) {
+        if self.sources.len() < 2 {
+            return;
+        }
+        let previous = self.current;
+        self.current = (self.current + 1) % self.sources.len();
+        // Pending comparisons were made against streams that are not followed anymore.
+        // The held blocks were not processed, they are streamed again from the new source.
+        self.unconfirmed.clear();
+        self.witnessed.clear();
+        self.witness_retries = 0;
+        self.metrics.failovers.add(
+            1,
+            &[
+                KeyValue::new("client", self.client_name.clone()),
+                KeyValue::new("from", self.sources[previous].clone()),
+                KeyValue::new("to", self.sources[self.current].clone()),
+            ],
+        );
+        info!("🔀 Failing over to DA source {}", self.current());
+    }
+
+    /// Streams blocks from height `from` from the witness source, if cross-checking is enabled.
+    /// Fails once the witness was reconnected too many times without streaming a block.
+    pub async fn connect_witness(&mut self, from: BlockHeight) -> Result<Witness> {
+        let Some(index) = self.witness_index() else {
+            return Ok(Witness::Disabled);
+        };
+        if self.witness_retries >= MAX_WITNESS_RETRIES {
+            bail!(
+                "DA witness {} streamed no block after {} reconnections, blocks cannot be cross-checked",
+                self.sources[index],
+                self.witness_retries
+            );
+        }
+        self.witness_retries += 1;
+        match self.connect_to(index, from).await {
+            Ok(client) => Ok(Witness::Connected(client)),
+            Err(e) => {
+                warn!(
+                    "Cannot cross-check blocks with DA source {}: {e:#}",
+                    self.sources[index]
+                );
+                Ok(Witness::Disconnected)
+            }
+        }
+    }
+
+    /// Next event of the witness stream. Resolves to `None` when the witness must be
+    /// reconnected, after an interval, and never resolves without cross-checking.
+    pub async fn recv_witness(witness: &mut Witness) -> Option<DataAvailabilityEvent> {
+        if let Witness::Connected(client) = witness {
+            if let Some(event) = client.recv().await {
+                _ = client.ping().await;
+                return Some(event);
+            }
+            *witness = Witness::Disconnected;
+        }
+        match witness {
+            Witness::Disabled => std::future::pending().await,
+            _ => {
+                tokio::time::sleep(WITNESS_RETRY_INTERVAL).await;
+                None
+            }
+        }
+    }
+
+    /// Records a block received on a stream, and returns the primary block that can be processed.
+    /// With cross-checking, a primary block is held until the witness streams a block at the same
+    /// height. Fails if both sources streamed different blocks at the same height, or if the
+    /// witness falls too far behind.
+    pub fn received(
+        &mut self,
+        stream: DAStream,
+        block: SignedBlock,
+    ) -> Result<Option<SignedBlock>> {
+        let index = match stream {
+            DAStream::Primary => Some(self.current),
+            DAStream::Witness => self.witness_index(),
+        };
+        let Some(index) = index else {
+            return Ok(None);
+        };
+        let height = block.height();
+        if let Some(last) = self.heights.get_mut(index) {
+            *last = Some(height.max(last.unwrap_or_default()));
+        }
+        self.record_lag();
+
+        let Some(witness_index) = self.witness_index() else {
+            return Ok(Some(block));
+        };
+        match stream {
+            DAStream::Primary => match self.witnessed.remove(&height) {
+                Some(witness_hash) => {
+                    self.check(height, &block.hashed(), &witness_hash, witness_index)?;
+                    Ok(Some(block))
+                }
+                None => {
+                    if self.unconfirmed.len() >= MAX_UNCONFIRMED_BLOCKS {
+                        bail!(
+                            "DA witness {} is more than {MAX_UNCONFIRMED_BLOCKS} blocks behind {}, blocks cannot be cross-checked",
+                            self.sources[witness_index],
+                            self.current(),
+                        );
+                    }
+                    self.unconfirmed.insert(height, block);
+                    Ok(None)
+                }
+            },
+            DAStream::Witness => {
+                self.witness_retries = 0;
+                let hash = block.hashed();
+                match self.unconfirmed.remove(&height) {
+                    Some(primary) => {
+                        self.check(height, &primary.hashed(), &hash, witness_index)?;
+                        Ok(Some(primary))
+                    }
+                    None => {
+                        self.witnessed.insert(height, hash);
+                        while self.witnessed.len() > MAX_WITNESSED_BLOCKS {
+                            self.witnessed.pop_first();
+                        }
+                        Ok(None)
+                    }
+                }
+            }
+        }
+    }
+
+    fn check(
+        &self,
+        height: BlockHeight,
+        primary: &ConsensusProposalHash,
+        witness: &ConsensusProposalHash,
+        witness_index: usize,
+    ) -> Result<()> {
+        if primary != witness {
+            bail!(
+                "DA sources diverge at height {height}: {} streamed {primary}, {} streamed {witness}",
+                self.current(),
+                self.sources[witness_index],
+            );
+        }
+        Ok(())
+    }
+
+    fn record_lag(&self) {
+        let Some(highest) = self.heights.iter().flatten().max() else {
+            return;
+        };
+        for (source, height) in self.sources.iter().zip(&self.heights) {
+            let Some(height) = height else {
+                continue;
+            };
+            let labels = [
+                KeyValue::new("client", self.client_name.clone()),
+                KeyValue::new("source", source.clone()),
+            ];
+            self.metrics.source_height.record(height.0, &labels);
+            self.metrics
+                .source_lag
+                .record(highest.0 - height.0, &labels);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdk::{AggregateSignature, ConsensusProposal};
+
+    use super::*;
+    use crate::utils::da_codec::DataAvailabilityServer;
+
+    fn block(slot: u64, timestamp: u128) -> SignedBlock {
+        SignedBlock {
+            data_proposals: vec![],
+            consensus_proposal: ConsensusProposal {
+                slot,
+                timestamp: sdk::hyle_model_utils::TimestampMs(timestamp),
+                ..Default::default()
+            },
+            certificate: AggregateSignature::default(),
+        }
+    }
+
+    #[test]
+    fn test_cross_check() {
+        let mut sources = DASources::new(
+            "test",
+            vec!["127.0.0.1:4141".into(), "127.0.0.1:4142".into()],
+            true,
+        );
+
+        // Primary blocks are held until the witness streams them
+        assert_eq!(
+            sources.received(DAStream::Primary, block(1, 1)).unwrap(),
+            None
+        );
+        assert_eq!(
+            sources.received(DAStream::Primary, block(2, 2)).unwrap(),
+            None
+        );
+        assert_eq!(
+            sources.received(DAStream::Witness, block(1, 1)).unwrap(),
+            Some(block(1, 1))
+        );
+        assert_eq!(
+            sources.heights,
+            vec![Some(BlockHeight(2)), Some(BlockHeight(1))]
+        );
+
+        // A witness ahead of the primary confirms its blocks as they come
+        assert_eq!(
+            sources.received(DAStream::Witness, block(3, 3)).unwrap(),
+            None
+        );
+        assert_eq!(
+            sources.received(DAStream::Primary, block(3, 3)).unwrap(),
+            Some(block(3, 3))
+        );
+
+        // Without cross-checking, primary blocks are processed right away
+        let mut sources = DASources::new("test", vec!["127.0.0.1:4141".into()], true);
+        assert_eq!(
+            sources.received(DAStream::Primary, block(1, 1)).unwrap(),
+            Some(block(1, 1))
+        );
+        assert_eq!(
+            sources.received(DAStream::Witness, block(1, 2)).unwrap(),
+            None
+        );
+        assert_eq!(sources.heights, vec![Some(BlockHeight(1))]);
+    }
+
+    #[test]
+    fn test_disagreeing_witness() {
+        let mut sources = DASources::new(
+            "test",
+            vec!["127.0.0.1:4141".into(), "127.0.0.1:4142".into()],
+            true,
+        );
+
+        // The witness streamed another block at height 1, the primary one is never returned
+        sources.received(DAStream::Primary, block(1, 1)).unwrap();
+        assert!(sources.received(DAStream::Witness, block(1, 2)).is_err());
+
+        sources.received(DAStream::Witness, block(2, 2)).unwrap();
+        assert!(sources.received(DAStream::Primary, block(2, 3)).is_err());
+    }
+
+    /// Address nothing listens on
+    fn unreachable_address() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_failover() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let _server = DataAvailabilityServer::start(port, "DaServer")
+            .await
+            .unwrap();
+        let reachable = format!("127.0.0.1:{port}");
+
+        let mut sources =
+            DASources::new("test", vec![unreachable_address(), reachable.clone()], true);
+
+        // Unreachable sources are skipped
+        sources.connect(BlockHeight(0)).await.unwrap();
+        assert_eq!(sources.current(), reachable);
+
+        // Failing over drops the blocks held for the previous witness
+        sources.received(DAStream::Primary, block(1, 1)).unwrap();
+        sources.failover(BlockHeight(1)).await.unwrap();
+        assert_eq!(sources.current(), reachable);
+        assert!(sources.unconfirmed.is_empty());
+
+        let mut sources = DASources::new("test", vec![unreachable_address()], false);
+        assert!(sources.connect(BlockHeight(0)).await.is_err());
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_witness_retries() {
+        let mut sources = DASources::new(
+            "test",
+            vec![unreachable_address(), unreachable_address()],
+            true,
+        );
+        for _ in 0..MAX_WITNESS_RETRIES {
+            assert!(matches!(
+                sources.connect_witness(BlockHeight(0)).await,
+                Ok(Witness::Disconnected)
+            ));
+        }
+        // Halts instead of silently not cross-checking
+        assert!(sources.connect_witness(BlockHeight(0)).await.is_err());
+
+        // Receiving a block from the witness resets the retries
+        sources.received(DAStream::Witness, block(1, 1)).unwrap();
+        assert!(sources.connect_witness(BlockHeight(0)).await.is_ok());
+
+        let mut sources = DASources::new("test", vec![unreachable_address()], true);
+        assert!(matches!(
+            sources.connect_witness(BlockHeight(0)).await,
+            Ok(Witness::Disabled)
+        ));
+    }
+}
@@ -0,0 +1,462 @@
+//! Verification of the blocks streamed by an untrusted DA server.
+//!
+//! Starting from a validator set trusted at some height, each following block must carry a
+//! commit quorum certificate signed by more than 2/3 of the bonded stake, and its data
+//! proposals must be exactly the ones between the previous cut and the cut the validators
+//! signed, so that a DA server can't leave transactions out. The validator set then follows
+//! the bonds and staking actions of the verified blocks.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hyle_crypto::BlstCrypto;
+use sdk::{
+    Block, BlockHeight, ConfirmAckMarker, ConsensusProposalHash, ConsensusStakingAction,
+    DataProposalHash, Hashed, Identity, LaneId, Signed, SignedBlock, ValidatorPublicKey,
+};
+use serde::{Deserialize, Serialize};
+use staking::state::Staking;
+use tracing::{debug, info};
+
+/// Validator set the verification starts from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustedValidatorSet {
+    /// Blocks up to this height are trusted as is, the following ones are verified
+    pub height: BlockHeight,
+    /// Hash of the block at `height`, to check that the next block builds on it
+    #[serde(default)]
+    pub hash: Option<ConsensusProposalHash>,
+    /// Bonded validators and their stake
+    pub validators: BTreeMap<ValidatorPublicKey, u128>,
+}
+
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
+pub struct LightClient {
+    staking: Staking,
+    trusted_height: BlockHeight,
+    last_verified: Option<(BlockHeight, ConsensusProposalHash)>,
+    /// Tip of each lane in the cut of the last block, once one was seen
+    last_cut: Option<BTreeMap<LaneId, DataProposalHash>>,
+}
+
+impl LightClient {
+    pub fn new(trusted: TrustedValidatorSet) -> Result<Self> {
+        if trusted.validators.is_empty() {
+            bail!("The trusted validator set is empty");
+        }
+        let mut staking = Staking::new();
+        for (validator, stake) in trusted.validators {
+            let identity = Identity(format!("{}@trusted", hex::encode(&validator.0)));
+            let trusted = staking
+                .stake(identity.clone(), stake)
+                .and_then(|_| staking.delegate_to(identity, validator.clone()))
+                .and_then(|_| staking.bond(validator.clone()));
+            if let Err(e) = trusted {
+                bail!("Trusting validator {validator}: {e}");
+            }
+        }
+        info!(
+            "🔐 Verifying blocks after height {} with {} trusted validators",
+            trusted.height,
+            staking.bonded().len()
+        );
+        Ok(Self {
+            staking,
+            trusted_height: trusted.height,
+            last_verified: trusted.hash.map(|hash| (trusted.height, hash)),
+            last_cut: None,
+        })
+    }
+
+    pub fn bonded(&self) -> &Vec<ValidatorPublicKey> {
+        self.staking.bonded()
+    }
+
+    /// Checks that the block was committed by the current validator set.
+    /// Blocks must be verified in order, and before their staking changes are applied.
+    pub fn verify(&mut self, block: &SignedBlock) -> Result<()> {
+        let height = block.height();
+        let hash = block.hashed();
+        if height <= self.trusted_height {
+            if height == BlockHeight(0) {
+                self.verify_genesis(block)?;
+            }
+            debug!("Block {} {} is trusted", height, hash);
+            self.last_cut = Some(Self::cut_tips(block));
+            return Ok(());
+        }
+
+        if let Some((last_height, last_hash)) = &self.last_verified {
+            if height != *last_height + 1 {
+                bail!(
+                    "Block {height} {hash} does not follow the last verified block {last_height}"
+                );
+            }
+            if block.parent_hash() != last_hash {
+                bail!(
+                    "Block {height} {hash} has parent {} instead of the last verified block {last_hash}",
+                    block.parent_hash()
+                );
+            }
+        }
+
+        self.verify_certificate(block)
+            .with_context(|| format!("Invalid commit certificate for block {height} {hash}"))?;
+        self.verify_data_proposals(block)
+            .with_context(|| format!("Invalid data proposals in block {height} {hash}"))?;
+
+        self.last_verified = Some((height, hash));
+        self.last_cut = Some(Self::cut_tips(block));
+        Ok(())
+    }
+
+    fn cut_tips(block: &SignedBlock) -> BTreeMap<LaneId, DataProposalHash> {
+        block
+            .consensus_proposal
+            .cut
+            .iter()
+            .map(|(lane_id, dp_hash, _, _)| (lane_id.clone(), dp_hash.clone()))
+            .collect()
+    }
+
+    /// Applies the staking changes of a block processed by the node state
+    pub fn process_block(&mut self, block: &Block) -> Result<()> {
+        if block.block_height <= self.trusted_height {
+            return Ok(());
+        }
+        self.staking
+            .process_block(block)
+            .map_err(|e| anyhow::anyhow!("Following staking of block {}: {e}", block.block_height))
+    }
+
+    /// Applies the bonds and unbonds of a signed block, for listeners without a node state.
+    /// Stake changes carried by transactions are not followed, so a bonded candidate
+    /// only gains voting power if it was staked in the trusted set.
+    pub fn process_signed_block(&mut self, block: &SignedBlock) {
+        if block.height() <= self.trusted_height {
+            return;
+        }
+        for action in &block.consensus_proposal.staking_actions {
+            match action {
+                ConsensusStakingAction::Bond { candidate } => {
+                    if let Err(e) = self.staking.bond(candidate.signature.validator.clone()) {
+                        debug!(
+                            "Not bonding {} in block {}: {}",
+                            candidate.signature.validator,
+                            block.height(),
+                            e
+                        );
+                    }
+                }
+                ConsensusStakingAction::Unbond { validator } => {
+                    if let Err(e) = self.staking.unbond(validator) {
+                        debug!(
+                            "Not unbonding {} in block {}: {}",
+                            validator,
+                            block.height(),
+                            e
+                        );
+                    }
+                }
+                ConsensusStakingAction::PayFeesForDaDi { .. } => {}
+            }
+        }
+    }
+
+    /// The genesis block has no certificate, its validators must be the trusted ones
+    fn verify_genesis(&self, block: &SignedBlock) -> Result<()> {
+        let mut validators: Vec<&ValidatorPublicKey> = block
+            .consensus_proposal
+            .staking_actions
+            .iter()
+            .filter_map(|action| match action {
+                ConsensusStakingAction::Bond { candidate } => Some(&candidate.signature.validator),
+                _ => None,
+            })
+            .collect();
+        validators.sort();
+        if validators.iter().copied().ne(self.staking.bonded().iter()) {
+            bail!(
+                "Genesis block bonds {:?}, expected the trusted validators {:?}",
+                validators,
+                self.staking.bonded()
+            );
+        }
+        Ok(())
+    }
+
+    fn verify_certificate(&self, block: &SignedBlock) -> Result<()> {
+        let certificate = &block.certificate;
+        if let Some(unknown) = certificate
+            .validators
+            .iter()
+            .find(|v| !self.staking.is_bonded(v))
+        {
+            bail!("Signed by {unknown}, which is not a bonded validator");
+        }
+
+        let signed = Signed {
+            msg: (block.hashed(), ConfirmAckMarker),
+            signature: certificate.clone(),
+        };
+        if !BlstCrypto::verify_aggregate(&signed)? {
+            bail!("Aggregate signature does not match the block");
+        }
+
+        let voting_power = self
+            .staking
+            .compute_voting_power(certificate.validators.as_slice());
+        let f = self.staking.compute_f();
+        if voting_power < 2 * f + 1 {
+            bail!(
+                "Signed by {voting_power} of {} bonded stake, at least {} is required",
+                self.staking.total_bond(),
+                2 * f + 1
+            );
+        }
+        Ok(())
+    }
+
+    /// The certificate signs the cut: each lane of the cut must carry its data proposals from
+    /// the tip of the lane in the previous cut to the one of this cut. Before a first cut is
+    /// known, lanes without data proposals can't be checked.
+    fn verify_data_proposals(&self, block: &SignedBlock) -> Result<()> {
+        let cut = &block.consensus_proposal.cut;
+        if let Some((lane_id, _)) = block
+            .data_proposals
+            .iter()
+            .find(|(lane_id, _)| !cut.iter().any(|(cut_lane, _, _, _)| cut_lane == lane_id))
+        {
+            bail!("Lane {lane_id} is not part of the cut");
+        }
+
+        for (lane_id, cut_hash, _, _) in cut {
+            let data_proposals = block
+                .data_proposals
+                .iter()
+                .find(|(lane, _)| lane == lane_id)
+                .map(|(_, data_proposals)| data_proposals.as_slice())
+                .unwrap_or_default();
+            let previous_tip = self.last_cut.as_ref().map(|tips| tips.get(lane_id));
+
+            let (Some(first), Some(last)) = (data_proposals.first(), data_proposals.last()) else {
+                match previous_tip {
+                    Some(Some(tip)) if tip == cut_hash => continue,
+                    None => continue,
+                    _ => bail!("Lane {lane_id} is missing its data proposals up to {cut_hash}"),
+                }
+            };
+            if last.hashed() != *cut_hash {
+                bail!(
+                    "Lane {lane_id} ends on {} instead of {cut_hash}",
+                    last.hashed()
+                );
+            }
+            // A lane missing from the previous cut starts in this block
+            if let Some(previous_tip) = previous_tip {
+                if first.parent_data_proposal_hash.as_ref() != previous_tip {
+                    bail!(
+                        "Lane {lane_id} starts on {} instead of following the previous cut",
+                        first.hashed()
+                    );
+                }
+            }
+            for (parent, child) in data_proposals.iter().zip(data_proposals.iter().skip(1)) {
+                if child.parent_data_proposal_hash.as_ref() != Some(&parent.hashed()) {
+                    bail!(
+                        "Data proposal {} of lane {lane_id} does not follow {}",
+                        child.hashed(),
+                        parent.hashed()
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdk::{
+        AggregateSignature, BlobTransaction, ConsensusProposal, DataProposal, LaneBytesSize, PoDA,
+        Transaction,
+    };
+
+    use super::*;
+
+    fn commit(
+        signers: &[&BlstCrypto],
+        consensus_proposal: ConsensusProposal,
+        data_proposals: Vec<(LaneId, Vec<DataProposal>)>,
+    ) -> SignedBlock {
+        let msg = (consensus_proposal.hashed(), ConfirmAckMarker);
+        let votes: Vec<_> = signers
+            .iter()
+            .map(|crypto| crypto.sign(msg.clone()).unwrap())
+            .collect();
+        let certificate: AggregateSignature =
+            BlstCrypto::aggregate(msg, &votes.iter().collect::<Vec<_>>())
+                .unwrap()
+                .signature;
+        SignedBlock {
+            data_proposals,
+            consensus_proposal,
+            certificate,
+        }
+    }
+
+    #[test]
+    fn test_verify_blocks() {
+        let validators: Vec<BlstCrypto> =
+            (0..4).map(|_| BlstCrypto::new_random().unwrap()).collect();
+        let outsider = BlstCrypto::new_random().unwrap();
+        let mut light_client = LightClient::new(TrustedValidatorSet {
+            height: BlockHeight(1),
+            hash: None,
+            validators: validators
+                .iter()
+                .map(|crypto| (crypto.validator_pubkey().clone(), 100))
+                .collect(),
+        })
+        .unwrap();
+
+        let lane_id = LaneId(validators.first().unwrap().validator_pubkey().clone());
+        let data_proposal = DataProposal::new(None, vec![]);
+        let proposal = ConsensusProposal {
+            slot: 2,
+            cut: vec![(
+                lane_id.clone(),
+                data_proposal.hashed(),
+                LaneBytesSize(0),
+                Default::default(),
+            )],
+            ..Default::default()
+        };
+        let data_proposals = vec![(lane_id.clone(), vec![data_proposal])];
+
+        // 2 of 4 validators is not a quorum
+        let two: Vec<&BlstCrypto> = validators.iter().take(2).collect();
+        assert!(light_client
+            .verify(&commit(&two, proposal.clone(), data_proposals.clone()))
+            .is_err());
+
+        // Signers must be bonded
+        let mut with_outsider: Vec<&BlstCrypto> = validators.iter().take(2).collect();
+        with_outsider.push(&outsider);
+        assert!(light_client
+            .verify(&commit(
+                &with_outsider,
+                proposal.clone(),
+                data_proposals.clone()
+            ))
+            .is_err());
+
+        // Transactions must be the ones the validators signed
+        let three: Vec<&BlstCrypto> = validators.iter().take(3).collect();
+        let tx = BlobTransaction::new("alice@hydentity", vec![]).into();
+        let tampered = vec![(lane_id, vec![DataProposal::new(None, vec![tx])])];
+        assert!(light_client
+            .verify(&commit(&three, proposal.clone(), tampered))
+            .is_err());
+
+        let block = commit(&three, proposal, data_proposals);
+        light_client.verify(&block).unwrap();
+
+        // The next block must build on the verified one
+        let next = ConsensusProposal {
+            slot: 3,
+            parent_hash: ConsensusProposalHash("unknown".into()),
+            ..Default::default()
+        };
+        assert!(light_client.verify(&commit(&three, next, vec![])).is_err());
+        let next = ConsensusProposal {
+            slot: 3,
+            parent_hash: block.hashed(),
+            ..Default::default()
+        };
+        light_client.verify(&commit(&three, next, vec![])).unwrap();
+    }
+
+    #[test]
+    fn test_verify_cut_lanes() {
+        let validators: Vec<BlstCrypto> =
+            (0..4).map(|_| BlstCrypto::new_random().unwrap()).collect();
+        let signers: Vec<&BlstCrypto> = validators.iter().collect();
+        let mut light_client = LightClient::new(TrustedValidatorSet {
+            height: BlockHeight(1),
+            hash: None,
+            validators: validators
+                .iter()
+                .map(|crypto| (crypto.validator_pubkey().clone(), 100))
+                .collect(),
+        })
+        .unwrap();
+
+        let tx = |identity: &str| -> Transaction { BlobTransaction::new(identity, vec![]).into() };
+        let lane = |i: usize| LaneId(validators.get(i).unwrap().validator_pubkey().clone());
+        let cut_entry = |lane_id: LaneId,
+                         dp: &DataProposal|
+         -> (LaneId, DataProposalHash, LaneBytesSize, PoDA) {
+            (lane_id, dp.hashed(), LaneBytesSize(0), PoDA::default())
+        };
+
+        let a1 = DataProposal::new(None, vec![tx("a1@hydentity")]);
+        let b1 = DataProposal::new(None, vec![tx("b1@hydentity")]);
+        let block = commit(
+            &signers,
+            ConsensusProposal {
+                slot: 2,
+                cut: vec![cut_entry(lane(0), &a1), cut_entry(lane(1), &b1)],
+                ..Default::default()
+            },
+            vec![(lane(0), vec![a1.clone()]), (lane(1), vec![b1.clone()])],
+        );
+        light_client.verify(&block).unwrap();
+
+        let a2 = DataProposal::new(Some(a1.hashed()), vec![tx("a2@hydentity")]);
+        let a3 = DataProposal::new(Some(a2.hashed()), vec![tx("a3@hydentity")]);
+        let b2 = DataProposal::new(Some(b1.hashed()), vec![tx("b2@hydentity")]);
+        let next = ConsensusProposal {
+            slot: 3,
+            parent_hash: block.hashed(),
+            cut: vec![cut_entry(lane(0), &a3), cut_entry(lane(1), &b2)],
+            ..Default::default()
+        };
+
+        // A lane of the cut is missing
+        assert!(light_client
+            .verify(&commit(
+                &signers,
+                next.clone(),
+                vec![(lane(0), vec![a2.clone(), a3.clone()])]
+            ))
+            .is_err());
+        // A data proposal of a lane is missing
+        assert!(light_client
+            .verify(&commit(
+                &signers,
+                next.clone(),
+                vec![(lane(0), vec![a3.clone()]), (lane(1), vec![b2.clone()])]
+            ))
+            .is_err());
+
+        let block = commit(
+            &signers,
+            next,
+            vec![(lane(0), vec![a2, a3.clone()]), (lane(1), vec![b2.clone()])],
+        );
+        light_client.verify(&block).unwrap();
+
+        // Lanes whose tip did not move carry no data proposal
+        let next = ConsensusProposal {
+            slot: 4,
+            parent_hash: block.hashed(),
+            cut: vec![cut_entry(lane(0), &a3), cut_entry(lane(1), &b2)],
+            ..Default::default()
+        };
+        light_client
+            .verify(&commit(&signers, next, vec![]))
+            .unwrap();
+    }
+}
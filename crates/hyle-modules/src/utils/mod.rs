@@ -1,5 +1,8 @@
 pub mod da_codec;
+pub mod da_sources;
+pub mod light_client;
 pub mod logger;
 pub mod native_verifier_handler;
 pub mod profiling;
 pub mod static_type_map;
+pub mod tx_watchers;
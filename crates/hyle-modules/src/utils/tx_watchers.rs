@@ -0,0 +1,212 @@
+//! Transactions watched by the connections of the TCP and websocket APIs.
+//!
+//! Watches are bounded: a connection watches at most `MAX_WATCHES_PER_CONNECTION` transactions
+//! at once, a watch expires `WATCH_EXPIRY_BLOCKS` blocks after it was made if the transaction
+//! hasn't reached a final state by then, and the watches of a connection are dropped when it
+//! disconnects.
+
+use std::{collections::HashMap, hash::Hash};
+
+use anyhow::{bail, Result};
+use sdk::{Block, BlockHeight, TxHash, TxStateEvent};
+
+/// Maximum number of transactions a connection can watch at once
+pub const MAX_WATCHES_PER_CONNECTION: usize = 100;
+/// Number of blocks after which the watch of a transaction expires
+pub const WATCH_EXPIRY_BLOCKS: u64 = 200;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchUpdate<A> {
+    /// A state transition of a transaction watched by the connection
+    Event(A, TxStateEvent),
+    /// The watch expired before the transaction reached a final state
+    Expired(A, TxHash),
+}
+
+#[derive(Debug)]
+pub struct TxWatchers<A> {
+    /// Connections watching each transaction, with the height their watch expires at
+    watches: HashMap<TxHash, Vec<(A, BlockHeight)>>,
+    /// Number of transactions each connection watches
+    per_connection: HashMap<A, usize>,
+    /// Height of the last block received
+    last_height: BlockHeight,
+}
+
+impl<A> Default for TxWatchers<A> {
+    fn default() -> Self {
+        Self {
+            watches: HashMap::new(),
+            per_connection: HashMap::new(),
+            last_height: BlockHeight(0),
+        }
+    }
+}
+
+impl<A: Clone + Eq + Hash> TxWatchers<A> {
+    /// Watches the transaction for `addr`, renewing the watch if it already exists
+    pub fn watch(&mut self, addr: A, tx_hash: TxHash) -> Result<()> {
+        let expiry = self.last_height + WATCH_EXPIRY_BLOCKS;
+        if let Some(watch) = self
+            .watches
+            .get_mut(&tx_hash)
+            .and_then(|watchers| watchers.iter_mut().find(|(watcher, _)| *watcher == addr))
+        {
+            watch.1 = expiry;
+            return Ok(());
+        }
+        let count = self.per_connection.entry(addr.clone()).or_default();
+        if *count >= MAX_WATCHES_PER_CONNECTION {
+            bail!("Already watching {MAX_WATCHES_PER_CONNECTION} transactions");
+        }
+        *count += 1;
+        self.watches
+            .entry(tx_hash)
+            .or_default()
+            .push((addr, expiry));
+        Ok(())
+    }
+
+    /// Returns the updates to send for the block, and forgets the watches that ended
+    pub fn on_block(&mut self, block: &Block) -> Vec<WatchUpdate<A>> {
+        self.last_height = block.block_height;
+        let mut updates = vec![];
+        let mut ended = vec![];
+        for event in block.tx_state_events() {
+            let Some(watchers) = self.watches.get(&event.tx_hash) else {
+                continue;
+            };
+            for (addr, _) in watchers {
+                updates.push(WatchUpdate::Event(addr.clone(), event.clone()));
+            }
+            if event.event.is_final() {
+                if let Some(watchers) = self.watches.remove(&event.tx_hash) {
+                    ended.extend(watchers.into_iter().map(|(addr, _)| addr));
+                }
+            }
+        }
+
+        let height = block.block_height;
+        self.watches.retain(|tx_hash, watchers| {
+            watchers.retain(|(addr, expiry)| {
+                if *expiry > height {
+                    return true;
+                }
+                updates.push(WatchUpdate::Expired(addr.clone(), tx_hash.clone()));
+                ended.push(addr.clone());
+                false
+            });
+            !watchers.is_empty()
+        });
+
+        for addr in ended {
+            self.release(&addr);
+        }
+        updates
+    }
+
+    /// Drops the watches of a connection
+    pub fn disconnect(&mut self, addr: &A) {
+        if self.per_connection.remove(addr).is_none() {
+            return;
+        }
+        self.watches.retain(|_, watchers| {
+            watchers.retain(|(watcher, _)| watcher != addr);
+            !watchers.is_empty()
+        });
+    }
+
+    fn release(&mut self, addr: &A) {
+        if let Some(count) = self.per_connection.get_mut(addr) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                self.per_connection.remove(addr);
+            }
+        }
+    }
+
+    /// Number of transactions watched by at least one connection
+    pub fn len(&self) -> usize {
+        self.watches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdk::TransactionStateEvent;
+
+    use super::*;
+
+    fn block(height: u64, events: &[(&TxHash, TransactionStateEvent)]) -> Block {
+        let mut block = Block {
+            block_height: BlockHeight(height),
+            ..Default::default()
+        };
+        for (tx_hash, event) in events {
+            block
+                .transactions_events
+                .entry((*tx_hash).clone())
+                .or_default()
+                .push(event.clone());
+        }
+        block
+    }
+
+    #[test]
+    fn test_watches_are_bounded() {
+        let mut watchers = TxWatchers::<String>::default();
+        let alice = "alice".to_string();
+        for i in 0..MAX_WATCHES_PER_CONNECTION {
+            assert!(watchers
+                .watch(alice.clone(), TxHash(format!("{i}")))
+                .is_ok());
+        }
+        // Renewing a watch is always allowed, watching one more transaction is not
+        assert!(watchers.watch(alice.clone(), TxHash("0".into())).is_ok());
+        assert!(watchers
+            .watch(alice.clone(), TxHash("more".into()))
+            .is_err());
+        assert!(watchers.watch("bob".into(), TxHash("more".into())).is_ok());
+
+        watchers.disconnect(&alice);
+        assert_eq!(watchers.len(), 1);
+        assert!(watchers.watch(alice, TxHash("more".into())).is_ok());
+    }
+
+    #[test]
+    fn test_watches_end() {
+        let mut watchers = TxWatchers::<String>::default();
+        let settled = TxHash("settled".into());
+        let pending = TxHash("pending".into());
+        watchers.watch("alice".into(), settled.clone()).unwrap();
+        watchers.watch("alice".into(), pending.clone()).unwrap();
+
+        let updates = watchers.on_block(&block(
+            1,
+            &[
+                (&settled, TransactionStateEvent::Sequenced),
+                (&settled, TransactionStateEvent::Settled),
+                (&pending, TransactionStateEvent::Sequenced),
+            ],
+        ));
+        assert_eq!(updates.len(), 3);
+        assert!(matches!(
+            updates.first(),
+            Some(WatchUpdate::Event(addr, event)) if addr == "alice" && event.tx_hash == pending
+        ));
+        // Settled transactions are no longer watched
+        assert_eq!(watchers.len(), 1);
+
+        let updates = watchers.on_block(&block(WATCH_EXPIRY_BLOCKS, &[]));
+        assert_eq!(
+            updates,
+            vec![WatchUpdate::Expired("alice".to_string(), pending)]
+        );
+        assert!(watchers.is_empty());
+        assert!(watchers.per_connection.is_empty());
+    }
+}
@@ -1,13 +1,163 @@
+use std::time::Duration;
+
 use crate::tcp::{tcp_client::TcpClient, tcp_server::TcpServer};
+use anyhow::{bail, Result};
 use borsh::{BorshDeserialize, BorshSerialize};
-use sdk::Transaction;
+use sdk::{
+    api::MempoolAdmissionError, Hashed, Transaction, TransactionStateEvent, TxHash, TxStateEvent,
+};
+use tracing::debug;
 
 #[derive(Debug, Clone, BorshSerialize, BorshDeserialize, Eq, PartialEq)]
 pub enum TcpServerMessage {
     NewTx(Transaction),
+    /// Streams the state transitions of the transaction back to this connection,
+    /// until it settles, fails, times out or is dropped.
+    WatchTx(TxHash),
 }
 #[derive(Debug, Clone, BorshSerialize, BorshDeserialize, Eq, PartialEq)]
-pub struct TcpServerResponse;
+pub enum TcpServerResponse {
+    /// The transaction was refused by the mempool admission control
+    Rejected {
+        tx_hash: TxHash,
+        error: MempoolAdmissionError,
+    },
+    /// A state transition of a watched transaction
+    TxEvent(TxStateEvent),
+    /// The watch of the transaction was refused, or expired before it reached a final state
+    WatchEnded { tx_hash: TxHash, reason: String },
+}
 
 pub type TcpApiServer = TcpServer<TcpServerMessage, TcpServerResponse>;
 pub type TcpApiClient = TcpClient<TcpServerMessage, TcpServerResponse>;
+
+impl TcpApiClient {
+    /// Sends the transaction and waits for it to settle, returning its `Settled` transition.
+    /// Fails if the transaction is rejected, reaches any other final state (settled as failed,
+    /// timed out or dropped as duplicate), or has not settled within `timeout`.
+    pub async fn send_and_await_settlement(
+        &mut self,
+        tx: impl Into<Transaction>,
+        timeout: Duration,
+    ) -> Result<TxStateEvent> {
+        let tx: Transaction = tx.into();
+        let tx_hash = tx.hashed();
+
+        // Watch first so that no transition can be missed
+        self.send(TcpServerMessage::WatchTx(tx_hash.clone()))
+            .await?;
+        self.send(TcpServerMessage::NewTx(tx)).await?;
+
+        let wait = async {
+            loop {
+                match self.recv().await {
+                    Some(TcpServerResponse::TxEvent(event)) if event.tx_hash == tx_hash => {
+                        debug!("Tx {} is now {:?}", tx_hash, event.event);
+                        match event.event {
+                            TransactionStateEvent::Settled => return Ok(event),
+                            ref state if state.is_final() => {
+                                bail!("Tx {} did not settle: {:?}", tx_hash, state)
+                            }
+                            _ => {}
+                        }
+                    }
+                    Some(TcpServerResponse::Rejected {
+                        tx_hash: rejected,
+                        error,
+                    }) if rejected == tx_hash => {
+                        bail!("Tx {} was rejected: {}", tx_hash, error);
+                    }
+                    Some(TcpServerResponse::WatchEnded {
+                        tx_hash: watched,
+                        reason,
+                    }) if watched == tx_hash => {
+                        bail!("Stopped watching tx {}: {}", tx_hash, reason);
+                    }
+                    Some(_) => {}
+                    None => bail!("Connection closed while waiting for tx {}", tx_hash),
+                }
+            }
+        };
+        match tokio::time::timeout(timeout, wait).await {
+            Ok(result) => result,
+            Err(_) => bail!("Tx {} did not settle within {:?}", tx_hash, timeout),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdk::{BlobTransaction, BlockHeight, ConsensusProposalHash};
+
+    use super::*;
+    use crate::tcp::TcpEvent;
+
+    /// Sends a transaction through a node answering its watch with `events`,
+    /// and checks what the node received
+    async fn send_with_events(events: Vec<TransactionStateEvent>) -> Result<TxStateEvent> {
+        let mut server = TcpApiServer::start(0, "TcpApiServer").await?;
+        let address = format!("0.0.0.0:{}", server.local_addr()?.port());
+        let mut client = TcpApiClient::connect("client", address).await?;
+
+        let tx: Transaction = BlobTransaction::new("alice@hydentity", vec![]).into();
+        let tx_hash = tx.hashed();
+
+        let node = tokio::spawn(async move {
+            let mut received = vec![];
+            while received.len() < 2 {
+                if let Some(TcpEvent::Message { dest, data }) = server.listen_next().await {
+                    received.push(data);
+                    if received.len() == 2 {
+                        for event in events.iter() {
+                            let event = TxStateEvent {
+                                tx_hash: tx_hash.clone(),
+                                block_hash: ConsensusProposalHash::default(),
+                                block_height: BlockHeight(1),
+                                event: event.clone(),
+                            };
+                            server
+                                .send(dest.clone(), TcpServerResponse::TxEvent(event))
+                                .await
+                                .unwrap();
+                        }
+                    }
+                }
+            }
+            received
+        });
+
+        let settled = client
+            .send_and_await_settlement(tx.clone(), Duration::from_secs(5))
+            .await;
+        assert_eq!(
+            node.await?,
+            vec![
+                TcpServerMessage::WatchTx(tx.hashed()),
+                TcpServerMessage::NewTx(tx)
+            ]
+        );
+        settled
+    }
+
+    #[tokio::test]
+    async fn test_send_and_await_settlement() -> Result<()> {
+        let settled = send_with_events(vec![
+            TransactionStateEvent::Sequenced,
+            TransactionStateEvent::Settled,
+        ])
+        .await?;
+        assert_eq!(settled.event, TransactionStateEvent::Settled);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_and_await_failed_settlement() -> Result<()> {
+        let failed = send_with_events(vec![
+            TransactionStateEvent::Sequenced,
+            TransactionStateEvent::SettledAsFailed,
+        ])
+        .await;
+        assert!(failed.is_err());
+        Ok(())
+    }
+}
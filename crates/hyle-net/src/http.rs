@@ -156,6 +156,28 @@ impl HttpClient {
         Self::parse_response_json(response).await
     }
 
+    /// Like `get`, also returning the value of a response header if it is set
+    pub async fn get_with_header<R>(
+        &self,
+        endpoint: &str,
+        header: &str,
+    ) -> anyhow::Result<(R, Option<String>)>
+    where
+        R: DeserializeOwned,
+    {
+        let do_request = async || {
+            self.request::<String>(endpoint, Method::GET, ContentType::Json, None)
+                .await
+        };
+        let response = self.retry(do_request).await?;
+        let value = response
+            .headers()
+            .get(header)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        Ok((Self::parse_response_json(response).await?, value))
+    }
+
     pub async fn get_str(&self, endpoint: &str) -> anyhow::Result<String> {
         let do_request = async || {
             self.request::<String>(endpoint, Method::GET, ContentType::Text, None)
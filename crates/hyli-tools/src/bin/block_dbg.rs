@@ -171,8 +171,11 @@ async fn main() -> Result<()> {
             .build_module::<SignedDAListener>(DAListenerConf {
                 data_directory: PathBuf::from("data"),
                 da_read_from: "localhost:4141".to_string(),
+                da_fallbacks: vec![],
+                cross_check: false,
                 start_block: Some(BlockHeight(0)),
                 timeout_client_secs: 10,
+                trusted_validators: None,
             })
             .await?;
     } else {
@@ -213,6 +216,7 @@ async fn main() -> Result<()> {
                 contract_name: "oranj".into(),
                 data_directory: dump_folder.clone(),
                 api: build_api_ctx.clone(),
+                token_balances: Default::default(),
             })
             .await?;
 
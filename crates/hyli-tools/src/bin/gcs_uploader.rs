@@ -41,8 +41,11 @@ async fn main() -> Result<()> {
         .build_module::<SignedDAListener>(DAListenerConf {
             data_directory: config.data_directory.clone(),
             da_read_from: config.da_read_from.clone(),
+            da_fallbacks: vec![],
+            cross_check: false,
             start_block: Some(BlockHeight(0)),
             timeout_client_secs: 10,
+            trusted_validators: None,
         })
         .await?;
 
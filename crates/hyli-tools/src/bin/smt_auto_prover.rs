@@ -88,7 +88,10 @@ async fn main() -> Result<()> {
             start_block: None,
             data_directory: config.data_directory.clone(),
             da_read_from: config.da_read_from.clone(),
+            da_fallbacks: vec![],
+            cross_check: false,
             timeout_client_secs: 10,
+            trusted_validators: None,
         })
         .await?;
 
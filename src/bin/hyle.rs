@@ -1,6 +1,9 @@
 use anyhow::{Context, Result};
-use clap::Parser;
-use hyle::{entrypoint::RunPg, utils::conf};
+use clap::{Parser, Subcommand};
+use hyle::{
+    entrypoint::RunPg,
+    utils::{conf, keys},
+};
 use hyle_crypto::BlstCrypto;
 use hyle_modules::{log_error, utils::logger::setup_tracing};
 use std::sync::Arc;
@@ -23,6 +26,20 @@ pub struct Args {
 
     #[clap(long, action)]
     pub pg: bool,
+
+    /// Start from a node state snapshot instead of replaying blocks from genesis
+    #[arg(long)]
+    pub snapshot: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Manage encrypted validator keystores
+    #[command(subcommand)]
+    Keys(keys::KeysCommand),
 }
 
 #[cfg(feature = "dhat")]
@@ -46,10 +63,21 @@ async fn main() -> Result<()> {
     };
 
     let args = Args::parse();
+    if let Some(Command::Keys(command)) = args.command {
+        return keys::run(command);
+    }
+
     let mut config = conf::Conf::new(args.config_file, args.data_directory, args.run_indexer)
         .context("reading config file")?;
 
-    let crypto = Arc::new(BlstCrypto::new(&config.id).context("Could not create crypto")?);
+    // Other validators rely on the key of a validator outside of solo mode: never derive it
+    // from its name. Nodes outside of consensus don't need a secure key.
+    let allow_insecure_fallback =
+        config.consensus.solo || config.p2p.mode != conf::P2pMode::FullValidator;
+    let crypto = Arc::new(
+        BlstCrypto::new_validator(&config.id, allow_insecure_fallback)
+            .context("Could not create crypto")?,
+    );
     let pubkey = Some(crypto.validator_pubkey().clone());
 
     setup_tracing(
@@ -69,6 +97,11 @@ async fn main() -> Result<()> {
         None
     };
 
+    if let Some(snapshot) = args.snapshot {
+        hyle::entrypoint::bootstrap_from_snapshot(&config, std::path::Path::new(&snapshot))
+            .context("bootstrapping from snapshot")?;
+    }
+
     #[cfg(feature = "sp1")]
     {
         hyle_verifiers::sp1_4::init();
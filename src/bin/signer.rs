@@ -0,0 +1,59 @@
+//! Reference remote signer: holds the validator key outside of the node process, and signs
+//! the node requests that pass its slashing-protection rules.
+//!
+//! The key is loaded like the node's (`HYLE_VALIDATOR_SECRET`, `HYLE_VALIDATOR_KEYSTORE` or the keyring),
+//! and nodes use it by setting `HYLE_REMOTE_SIGNER` to its socket. The socket is created in a
+//! directory only accessible by the signer's user, so the node must run as that same user.
+
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use hyle_crypto::{
+    remote_signer::{SignerServer, SlashingProtection},
+    BlstCrypto,
+};
+use hyle_modules::utils::logger::setup_tracing;
+use tracing::info;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// Unix socket to listen on, its directory is created private to the user if needed
+    #[arg(long, default_value = "hyle-signer/signer.sock")]
+    pub listen: PathBuf,
+
+    /// Name of the validator, used to find its key in the keyring
+    #[arg(long, default_value = "node")]
+    pub validator_name: String,
+
+    /// File recording the signed proposals, so they are never signed twice across restarts
+    #[arg(long, default_value = "slashing_protection.bin")]
+    pub slashing_protection: PathBuf,
+
+    /// Log format, as in the node configuration
+    #[arg(long, default_value = "full")]
+    pub log_format: String,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    if std::env::var("HYLE_REMOTE_SIGNER").is_ok() {
+        bail!("HYLE_REMOTE_SIGNER must not be set for the signer itself");
+    }
+    let crypto = BlstCrypto::new_validator(&args.validator_name, false)
+        .context("Could not load validator key")?;
+    let protection = SlashingProtection::load(&args.slashing_protection)?;
+    setup_tracing(
+        &args.log_format,
+        format!("signer({})", crypto.validator_pubkey()),
+    )?;
+
+    info!(
+        "Signing as {} on {}",
+        crypto.validator_pubkey(),
+        args.listen.display()
+    );
+    Arc::new(SignerServer::new(crypto.signer().clone(), protection)).listen(&args.listen)
+}
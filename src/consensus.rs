@@ -7,21 +7,24 @@ use crate::p2p::network::HeaderSigner;
 use crate::{
     bus::command_response::Query,
     genesis::GenesisEvent,
-    mempool::QueryNewCut,
+    mempool::{api::RestApiMessage, QueryNewCut},
     model::{Cut, Hashed, ValidatorPublicKey},
     p2p::{
-        network::{MsgWithHeader, OutboundMessage},
+        network::{MsgHeader, MsgWithHeader, OutboundMessage},
         P2PCommand,
     },
+    state_sync::StateSyncEvent,
     utils::conf::SharedConf,
 };
 use anyhow::{anyhow, bail, Context, Error, Result};
 use borsh::{BorshDeserialize, BorshSerialize};
+use equivocation::{Equivocation, EquivocationDetector};
 use hyle_crypto::BlstCrypto;
 use hyle_crypto::SharedBlstCrypto;
 use hyle_model::utils::TimestampMs;
 use hyle_modules::{log_error, module_bus_client, module_handle_messages, modules::Module};
 use hyle_net::clock::TimestampMsClock;
+use leader_election::LeaderElection;
 use metrics::ConsensusMetrics;
 use role_follower::FollowerState;
 use role_leader::LeaderState;
@@ -36,6 +39,8 @@ use tokio::time::interval;
 use tracing::{debug, info, trace};
 
 pub mod api;
+pub mod equivocation;
+pub mod leader_election;
 pub mod metrics;
 pub mod module;
 mod network;
@@ -75,6 +80,9 @@ pub struct QueryConsensusInfo {}
 #[derive(Clone)]
 pub struct QueryConsensusStakingState {}
 
+#[derive(Clone)]
+pub struct QueryConsensusEquivocations {}
+
 module_bus_client! {
 struct ConsensusBusClient {
 sender(OutboundMessage),
@@ -82,12 +90,15 @@ sender(ConsensusEvent),
 sender(ConsensusCommand),
 sender(P2PCommand),
 sender(Query<QueryNewCut, Cut>),
+sender(RestApiMessage),
 receiver(ConsensusCommand),
 receiver(GenesisEvent),
 receiver(NodeStateEvent),
+receiver(StateSyncEvent),
 receiver(MsgWithHeader<ConsensusNetMessage>),
 receiver(Query<QueryConsensusInfo, ConsensusInfo>),
 receiver(Query<QueryConsensusStakingState, Staking>),
+receiver(Query<QueryConsensusEquivocations, Vec<Equivocation>>),
 }
 }
 
@@ -134,6 +145,43 @@ pub struct ConsensusStore {
     bft_round_state: BFTRoundState,
     /// Validators that asked to be part of consensus
     validator_candidates: Vec<SignedByValidator<ValidatorCandidacy>>,
+    /// Conflicting messages signed by validators
+    equivocations: EquivocationDetector,
+}
+
+impl ConsensusStore {
+    /// Store of a validator resuming from a snapshot of the state at `height`,
+    /// as if all blocks up to it had been processed.
+    pub fn resuming_from(
+        height: BlockHeight,
+        block_hash: ConsensusProposalHash,
+        staking: Staking,
+    ) -> Self {
+        let mut store = Self::default();
+        store
+            .bft_round_state
+            .resume_from(height, block_hash, staking);
+        store
+    }
+}
+
+impl BFTRoundState {
+    fn resume_from(
+        &mut self,
+        height: BlockHeight,
+        block_hash: ConsensusProposalHash,
+        staking: Staking,
+    ) {
+        self.staking = staking;
+        self.joining.staking_updated_to = height.0;
+        self.slot = height.0 + 1;
+        self.view = 0;
+        self.parent_hash = block_hash;
+        self.current_proposal = ConsensusProposal {
+            slot: height.0,
+            ..Default::default()
+        };
+    }
 }
 
 pub struct Consensus {
@@ -144,6 +192,7 @@ pub struct Consensus {
     #[allow(dead_code)]
     config: SharedConf,
     crypto: SharedBlstCrypto,
+    leader_election: LeaderElection,
 }
 
 impl Deref for Consensus {
@@ -175,21 +224,14 @@ macro_rules! with_metric {
 
 impl Consensus {
     fn round_leader(&self) -> Result<ValidatorPublicKey> {
-        // Find out who the next leader will be.
-        // For now, this is round-robin on slot + view for simplicity.
-        // (we remove 1 for backwards compatibility of the tests when making the change)
-        let index = (self.bft_round_state.slot as usize + self.bft_round_state.view as usize)
-            .wrapping_sub(1)
-            % self.bft_round_state.staking.bonded().len();
-
-        // Get the next leader based on the random index
-        Ok(self
-            .bft_round_state
-            .staking
-            .bonded()
-            .get(index)
-            .context("No next leader found")?
-            .clone())
+        self.leader_election
+            .leader(
+                &self.bft_round_state.staking,
+                self.bft_round_state.parent_hash.0.as_bytes(),
+                self.bft_round_state.slot,
+                self.bft_round_state.view,
+            )
+            .context("No next leader found")
     }
     fn next_view_leader(&mut self) -> Result<ValidatorPublicKey> {
         self.bft_round_state.view += 1;
@@ -219,12 +261,13 @@ impl Consensus {
                 self.bft_round_state.parent_timestamp =
                     self.bft_round_state.current_proposal.timestamp.clone();
                 self.bft_round_state.parent_cut = self.bft_round_state.current_proposal.cut.clone();
+                let slot = self.bft_round_state.slot;
+                self.equivocations.prune(slot);
 
                 // Store the last commited QC to avoid issues when parsing Commit messages before Prepare
                 self.bft_round_state.follower.buffered_quorum_certificate = match ticket {
                     Ticket::CommitQC(qc) => Some(qc),
-                    Ticket::ForcedCommitQc => None,
-                    _ => unreachable!(),
+                    _ => None,
                 };
                 for action in
                     std::mem::take(&mut self.bft_round_state.current_proposal.staking_actions)
@@ -248,6 +291,14 @@ impl Consensus {
                             .staking
                             .pay_for_dadi(lane_id, cumul_size)
                             .map_err(|e| anyhow::anyhow!(e))?,
+                        ConsensusStakingAction::Unbond { validator } => {
+                            debug!("👋 Validator unbonded: {}", validator);
+                            self.store
+                                .bft_round_state
+                                .staking
+                                .unbond(&validator)
+                                .map_err(|e| anyhow::anyhow!(e))?;
+                        }
                     }
                 }
                 self.store
@@ -416,16 +467,11 @@ impl Consensus {
     fn handle_net_message(&mut self, msg: MsgWithHeader<ConsensusNetMessage>) -> Result<(), Error> {
         let MsgWithHeader::<ConsensusNetMessage> {
             msg: net_message,
-            header:
-                SignedByValidator {
-                    signature:
-                        ValidatorSignature {
-                            validator: sender, ..
-                        },
-                    ..
-                },
-            ..
+            header,
         } = msg;
+        let sender = header.signature.validator.clone();
+
+        self.detect_equivocation(&header, &net_message);
 
         match net_message {
             ConsensusNetMessage::Prepare(consensus_proposal, ticket, view) => {
@@ -493,6 +539,89 @@ impl Consensus {
         }
     }
 
+    /// Records the signed messages that can conflict, and handles the conflicts found
+    fn detect_equivocation(
+        &mut self,
+        header: &SignedByValidator<MsgHeader>,
+        net_message: &ConsensusNetMessage,
+    ) {
+        let round = (self.bft_round_state.slot, self.bft_round_state.view);
+        // Only record the messages of bonded validators for recent rounds, anyone else could
+        // make us keep an unbounded number of them.
+        let signer = match net_message {
+            ConsensusNetMessage::Prepare(consensus_proposal, _, view) => {
+                if !equivocation::is_recent((consensus_proposal.slot, *view), round) {
+                    return;
+                }
+                &header.signature.validator
+            }
+            ConsensusNetMessage::PrepareVote(prepare_vote) => &prepare_vote.signature.validator,
+            ConsensusNetMessage::ConfirmAck(confirm_ack) => &confirm_ack.signature.validator,
+            ConsensusNetMessage::Timeout((timeout, _)) => {
+                if !equivocation::is_recent((timeout.msg.0, timeout.msg.1), round) {
+                    return;
+                }
+                &timeout.signature.validator
+            }
+            _ => return,
+        };
+        if !self.bft_round_state.staking.is_bonded(signer) {
+            return;
+        }
+
+        let equivocation = match net_message {
+            ConsensusNetMessage::Prepare(consensus_proposal, ticket, view) => {
+                match equivocation::signed_prepare(header, consensus_proposal, ticket, *view) {
+                    Ok(prepare) => self.equivocations.on_prepare(prepare),
+                    Err(e) => {
+                        debug!("Could not record Prepare message: {e:#}");
+                        None
+                    }
+                }
+            }
+            ConsensusNetMessage::PrepareVote(prepare_vote) => {
+                self.equivocations.on_prepare_vote(prepare_vote)
+            }
+            ConsensusNetMessage::ConfirmAck(confirm_ack) => {
+                self.equivocations.on_confirm_ack(confirm_ack)
+            }
+            ConsensusNetMessage::Timeout((timeout, _)) => self
+                .equivocations
+                .on_timeout(SignedConsensusMessage::Timeout(timeout.clone())),
+            _ => None,
+        };
+        // Only messages signing their slot and view make verifiable evidence, conflicting
+        // votes stay local alerts
+        if let Some(Equivocation::DoubleSign(evidence)) = equivocation {
+            if self.config.consensus.submit_slashing_evidence {
+                let _ = log_error!(
+                    self.submit_slashing_evidence(evidence),
+                    "Submitting slashing evidence"
+                );
+            }
+        }
+    }
+
+    /// Sends a transaction slashing the validator of the evidence to the mempool.
+    /// Like any staking transaction, it settles once proven.
+    fn submit_slashing_evidence(&mut self, evidence: DoubleSignEvidence) -> Result<()> {
+        info!(
+            "⚔️ Submitting slashing evidence against {} for slot {} view {}",
+            evidence.offender(),
+            evidence.slot(),
+            evidence.view()
+        );
+        let tx = BlobTransaction::new(
+            format!("{}@staking", self.config.id),
+            vec![StakingAction::Slash {
+                evidence: Box::new(evidence),
+            }
+            .as_blob("staking".into(), None, None)],
+        );
+        self.bus.send(RestApiMessage::NewTx(tx.into()))?;
+        Ok(())
+    }
+
     /// Apply ticket locally, and start new round with it
     fn advance_round(&mut self, ticket: Ticket) -> Result<()> {
         self.apply_ticket(ticket.clone())?;
@@ -606,6 +735,25 @@ impl Consensus {
         Ok(())
     }
 
+    /// Resumes joining from a state synced from peers, as if all blocks up to it had been processed.
+    fn handle_state_sync_event(&mut self, event: StateSyncEvent) {
+        let StateSyncEvent::Synced {
+            height,
+            block_hash,
+            staking,
+            ..
+        } = event;
+        if !matches!(self.bft_round_state.state_tag, StateTag::Joining)
+            || self.store.bft_round_state.joining.staking_updated_to >= height.0
+        {
+            return;
+        }
+        info!("🚪 Resuming from state synced at block {}", height);
+        self.store
+            .bft_round_state
+            .resume_from(height, block_hash, staking);
+    }
+
     async fn handle_node_state_event(&mut self, msg: NodeStateEvent) -> Result<()> {
         match msg {
             NodeStateEvent::NewBlock(block) => {
@@ -771,6 +919,9 @@ impl Consensus {
             listen<NodeStateEvent> event => {
                 let _ = log_error!(self.handle_node_state_event(event).await, "Error while handling data event");
             }
+            listen<StateSyncEvent> event => {
+                self.handle_state_sync_event(event);
+            }
             listen<ConsensusCommand> cmd => {
                 let _ = log_error!(self.handle_command(cmd).await, "Error while handling consensus command");
             }
@@ -788,6 +939,9 @@ impl Consensus {
             command_response<QueryConsensusStakingState, Staking> _ => {
                 Ok(self.bft_round_state.staking.clone())
             }
+            command_response<QueryConsensusEquivocations, Vec<Equivocation>> _ => {
+                Ok(self.equivocations.equivocations().to_vec())
+            }
             _ = timeout_ticker.tick() => {
                 log_error!(self.bus.send(ConsensusCommand::TimeoutTick), "Cannot send message over channel")?;
             }
@@ -889,6 +1043,8 @@ pub mod test {
                 store,
                 config: Arc::new(conf),
                 crypto: Arc::new(crypto),
+                // Scenarios below are written for a known sequence of leaders
+                leader_election: LeaderElection::RoundRobin,
             }
         }
 
@@ -1067,12 +1223,17 @@ pub mod test {
             self.consensus
                 .handle_node_state_event(NodeStateEvent::NewBlock(Box::new(Block {
                     staking_actions: vec![
-                        (staker.name.clone().into(), StakingAction::Stake { amount }),
+                        (
+                            staker.name.clone().into(),
+                            StakingAction::Stake { amount },
+                            BlockHeight(0),
+                        ),
                         (
                             staker.name.clone().into(),
                             StakingAction::Delegate {
                                 validator: staker.pubkey(),
                             },
+                            BlockHeight(0),
                         ),
                     ],
                     ..Default::default()
@@ -1096,12 +1257,17 @@ pub mod test {
             self.consensus
                 .handle_node_state_event(NodeStateEvent::NewBlock(Box::new(Block {
                     staking_actions: vec![
-                        (self.name.clone().into(), StakingAction::Stake { amount }),
+                        (
+                            self.name.clone().into(),
+                            StakingAction::Stake { amount },
+                            BlockHeight(0),
+                        ),
                         (
                             self.name.clone().into(),
                             StakingAction::Delegate {
                                 validator: self.consensus.crypto.validator_pubkey().clone(),
                             },
+                            BlockHeight(0),
                         ),
                     ],
                     ..Default::default()
@@ -1485,6 +1651,89 @@ pub mod test {
         );
     }
 
+    #[test_log::test(tokio::test)]
+    async fn prepare_unbonding_all_validators() {
+        let (mut node1, mut node2): (ConsensusTestCtx, ConsensusTestCtx) = build_nodes!(2).await;
+
+        // All validators unstake and fall below the minimum stake
+        let unstakes = Block {
+            staking_actions: [&node1, &node2]
+                .iter()
+                .map(|node| {
+                    (
+                        hex::encode(node.pubkey().0).into(),
+                        StakingAction::Unstake { amount: 100 },
+                        BlockHeight(0),
+                    )
+                })
+                .collect(),
+            ..Default::default()
+        };
+        for node in [&mut node1, &mut node2] {
+            node.handle_node_state_event(NodeStateEvent::NewBlock(Box::new(unstakes.clone())))
+                .await
+                .expect("Failed to unstake");
+        }
+
+        // The leader doesn't unbond them all
+        node1.start_round().await;
+        let prepare = node1.assert_broadcast("Prepare").await;
+        let ConsensusNetMessage::Prepare(mut cp, ticket, view) = prepare.msg.clone() else {
+            panic!("Expected a Prepare, got {:?}", prepare.msg);
+        };
+        assert!(cp
+            .staking_actions
+            .iter()
+            .all(|action| !matches!(action, ConsensusStakingAction::Unbond { .. })));
+
+        // A leader doing so is rejected by followers
+        cp.staking_actions.extend(
+            [node1.pubkey(), node2.pubkey()]
+                .map(|validator| ConsensusStakingAction::Unbond { validator }),
+        );
+        let prepare = node1
+            .consensus
+            .sign_net_message(ConsensusNetMessage::Prepare(cp, ticket, view))
+            .expect("Error while signing");
+        assert_contains!(
+            node2.handle_msg_err(&prepare).await.to_string(),
+            "unbonds all"
+        );
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn equivocations_of_bonded_validators_only() {
+        let (mut node1, node2): (ConsensusTestCtx, ConsensusTestCtx) = build_nodes!(2).await;
+        let outsider = BlstCrypto::new("outsider").unwrap();
+
+        // Conflicting votes of a bonded validator, and of an unknown key
+        for proposal_hash in ["a", "b"] {
+            node1.consensus.equivocations.on_proposal(
+                ConsensusProposalHash(proposal_hash.into()),
+                1,
+                0,
+            );
+        }
+        for crypto in [&outsider, node2.consensus.crypto.as_ref()] {
+            for proposal_hash in ["a", "b"] {
+                let vote = crypto
+                    .sign((
+                        ConsensusProposalHash(proposal_hash.into()),
+                        PrepareVoteMarker,
+                    ))
+                    .unwrap();
+                let msg = crypto
+                    .sign_msg_with_header(ConsensusNetMessage::PrepareVote(vote))
+                    .unwrap();
+                let _ = node1.consensus.handle_net_message(msg);
+            }
+        }
+
+        let equivocations = node1.consensus.equivocations.equivocations();
+        assert_eq!(equivocations.len(), 1);
+        assert_eq!(equivocations.first().unwrap().validator(), &node2.pubkey());
+    }
+
     #[test_log::test(tokio::test)]
     async fn prepare_timestamp_too_old() {
         let (mut node1, mut node2, mut node3, mut node4): (
@@ -2282,6 +2531,170 @@ pub mod test {
         }
     }
 
+    #[test_log::test(tokio::test)]
+    async fn test_stake_weighted_round_leader() {
+        let (mut node1, mut node2, mut node3, mut node4): (
+            ConsensusTestCtx,
+            ConsensusTestCtx,
+            ConsensusTestCtx,
+            ConsensusTestCtx,
+        ) = build_nodes!(4).await;
+        let mut nodes = [&mut node1, &mut node2, &mut node3, &mut node4];
+        for node in nodes.iter_mut() {
+            node.consensus.leader_election = LeaderElection::StakeWeighted;
+        }
+
+        let mut leaders = std::collections::BTreeSet::new();
+        for seed in 0..32u8 {
+            for view in 0..2 {
+                for node in nodes.iter_mut() {
+                    node.consensus.bft_round_state.parent_hash =
+                        ConsensusProposalHash(format!("parent-{seed}"));
+                    node.consensus.bft_round_state.view = view;
+                }
+                let round_leaders: std::collections::BTreeSet<_> = nodes
+                    .iter()
+                    .map(|node| node.consensus.round_leader().unwrap())
+                    .collect();
+                // All nodes agree on the leader
+                assert_eq!(round_leaders.len(), 1);
+                leaders.extend(round_leaders);
+            }
+        }
+        // ... which depends on the last committed proposal
+        assert_eq!(leaders.len(), 4);
+    }
+
+    #[allow(clippy::indexing_slicing)]
+    #[test_log::test(tokio::test)]
+    async fn test_commit_qcs_of_a_proposal_elect_the_same_leader() {
+        let (node1, node2, node3, node4): (
+            ConsensusTestCtx,
+            ConsensusTestCtx,
+            ConsensusTestCtx,
+            ConsensusTestCtx,
+        ) = build_nodes!(4).await;
+        let mut nodes = [node1, node2, node3, node4];
+
+        let proposal = ConsensusProposal {
+            slot: 1,
+            ..Default::default()
+        };
+        let acks: Vec<_> = nodes
+            .iter()
+            .map(|node| {
+                node.consensus
+                    .crypto
+                    .sign((proposal.hashed(), ConfirmAckMarker))
+                    .unwrap()
+            })
+            .collect();
+        let commit_qc = |signers: &[usize]| {
+            let acks: Vec<_> = signers.iter().map(|i| &acks[*i]).collect();
+            let aggregate =
+                BlstCrypto::aggregate((proposal.hashed(), ConfirmAckMarker), &acks).unwrap();
+            QuorumCertificate(aggregate.signature, ConfirmAckMarker)
+        };
+        // The leader received all acks, and may aggregate any 2f+1 of them
+        let commit_qcs = [commit_qc(&[0, 1, 2]), commit_qc(&[1, 2, 3])];
+        assert_ne!(commit_qcs[0], commit_qcs[1]);
+
+        let mut leaders = vec![];
+        for (node, commit_qc) in nodes.iter_mut().zip(commit_qcs.iter().cycle()) {
+            node.consensus.leader_election = LeaderElection::StakeWeighted;
+            node.consensus.bft_round_state.current_proposal = proposal.clone();
+            node.consensus
+                .verify_commit_quorum_certificate_against_current_proposal(commit_qc)
+                .unwrap();
+            node.consensus
+                .advance_round(Ticket::CommitQC(commit_qc.clone()))
+                .unwrap();
+            leaders.push(node.consensus.round_leader().unwrap());
+        }
+        assert!(leaders.iter().all(|leader| *leader == leaders[0]));
+    }
+
+    #[allow(clippy::indexing_slicing)]
+    #[test_log::test(tokio::test)]
+    async fn stake_weighted_commits_with_joining_node() {
+        let (node1, node2, node3, node4): (
+            ConsensusTestCtx,
+            ConsensusTestCtx,
+            ConsensusTestCtx,
+            ConsensusTestCtx,
+        ) = build_nodes!(4).await;
+        let mut nodes = [node1, node2, node3, node4];
+        let mut joining = ConsensusTestCtx::new_node("joining").await;
+        joining.setup_for_joining(&nodes.iter().collect::<Vec<_>>());
+
+        for node in nodes.iter_mut().chain(std::iter::once(&mut joining)) {
+            node.consensus.leader_election = LeaderElection::StakeWeighted;
+        }
+        for node in nodes.iter_mut() {
+            if node.consensus.round_leader().unwrap() == node.pubkey() {
+                node.consensus.bft_round_state.state_tag = StateTag::Leader;
+                node.consensus.bft_round_state.leader.pending_ticket = Some(Ticket::Genesis);
+            } else {
+                node.consensus.bft_round_state.state_tag = StateTag::Follower;
+                node.consensus.bft_round_state.leader.pending_ticket = None;
+            }
+        }
+
+        let mut leaders = std::collections::BTreeSet::new();
+        for slot in 1..=12 {
+            let leader = nodes[0].consensus.round_leader().unwrap();
+            for node in nodes.iter() {
+                assert_eq!(node.consensus.round_leader().unwrap(), leader);
+            }
+            leaders.insert(leader.clone());
+            let l = nodes
+                .iter()
+                .position(|node| node.pubkey() == leader)
+                .unwrap();
+            let followers: Vec<usize> = (0..nodes.len()).filter(|i| *i != l).collect();
+
+            nodes[l].start_round().await;
+            let prepare = nodes[l].assert_broadcast("Leader - Prepare").await;
+            let ConsensusNetMessage::Prepare(cp, ..) = &prepare.msg else {
+                panic!("Expected a Prepare, got {:?}", prepare.msg);
+            };
+            assert_eq!(cp.slot, slot);
+            let cp = cp.clone();
+            for &f in followers.iter() {
+                nodes[f].handle_msg(&prepare, "Prepare").await;
+                let vote = nodes[f]
+                    .assert_send(&leader, "Follower - PrepareVote")
+                    .await;
+                nodes[l].handle_msg(&vote, "PrepareVote").await;
+            }
+            let confirm = nodes[l].assert_broadcast("Leader - Confirm").await;
+            for &f in followers.iter() {
+                nodes[f].handle_msg(&confirm, "Confirm").await;
+                let ack = nodes[f].assert_send(&leader, "Follower - ConfirmAck").await;
+                nodes[l].handle_msg(&ack, "ConfirmAck").await;
+            }
+            let commit = nodes[l].assert_broadcast("Leader - Commit").await;
+            for &f in followers.iter() {
+                nodes[f].handle_msg(&commit, "Commit").await;
+            }
+
+            // A node catching up from the committed blocks agrees on the next leader
+            joining
+                .handle_node_state_event(NodeStateEvent::NewBlock(Box::new(Block {
+                    block_height: BlockHeight(slot),
+                    hash: cp.hashed(),
+                    ..Default::default()
+                })))
+                .await
+                .unwrap();
+            assert_eq!(
+                joining.consensus.round_leader().unwrap(),
+                nodes[0].consensus.round_leader().unwrap()
+            );
+        }
+        assert!(leaders.len() > 1, "The same leader led all slots");
+    }
+
     #[test_log::test(tokio::test(flavor = "multi_thread", worker_threads = 2))]
     async fn test_consensus_starts_after_genesis_is_processed() {
         let mut node_builder = NodeIntegrationCtxBuilder::new().await;
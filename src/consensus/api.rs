@@ -1,7 +1,7 @@
 use anyhow::anyhow;
 use axum::{debug_handler, extract::State, http::StatusCode, response::IntoResponse, Json, Router};
 use client_sdk::contract_indexer::AppError;
-use hyle_model::api::APIStaking;
+use hyle_model::api::{APIEquivocation, APIStaking};
 use hyle_modules::{bus::SharedMessageBus, modules::signal::ShutdownModule};
 use staking::state::Staking;
 use tracing::error;
@@ -17,12 +17,16 @@ use crate::{
     model::{ConsensusInfo, SharedRunContext},
 };
 
-use super::{QueryConsensusInfo, QueryConsensusStakingState};
+use super::{
+    equivocation::Equivocation, QueryConsensusEquivocations, QueryConsensusInfo,
+    QueryConsensusStakingState,
+};
 
 bus_client! {
 struct RestBusClient {
     sender(Query<QueryConsensusInfo, ConsensusInfo>),
     sender(Query<QueryConsensusStakingState, Staking>),
+    sender(Query<QueryConsensusEquivocations, Vec<Equivocation>>),
     receiver(ShutdownModule),
 }
 }
@@ -42,6 +46,7 @@ pub async fn api(bus: &SharedMessageBus, ctx: &SharedRunContext) -> Router<()> {
     let (router, api) = OpenApiRouter::with_openapi(ConsensusAPI::openapi())
         .routes(routes!(get_consensus_state))
         .routes(routes!(get_consensus_staking_state))
+        .routes(routes!(get_consensus_equivocations))
         .split_for_parts();
 
     if let Ok(mut o) = ctx.api.openapi.lock() {
@@ -112,6 +117,40 @@ pub async fn get_consensus_staking_state(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/equivocations",
+    tag = "Consensus",
+    responses(
+        (status = OK, body = [APIEquivocation])
+    )
+)]
+#[debug_handler]
+pub async fn get_consensus_equivocations(
+    State(mut state): State<RouterState>,
+) -> Result<impl IntoResponse, AppError> {
+    match state
+        .bus
+        .shutdown_aware_request::<()>(QueryConsensusEquivocations {})
+        .await
+    {
+        Ok(equivocations) => Ok(Json(
+            equivocations
+                .iter()
+                .map(APIEquivocation::from)
+                .collect::<Vec<_>>(),
+        )),
+        Err(err) => {
+            error!("{:?}", err);
+
+            Err(AppError(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                anyhow!("Error while getting equivocations: {err}"),
+            ))
+        }
+    }
+}
+
 impl Clone for RouterState {
     fn clone(&self) -> Self {
         use hyle_modules::utils::static_type_map::Pick;
@@ -126,6 +165,10 @@ impl Clone for RouterState {
                     &self.bus,
                 )
                 .clone(),
+                Pick::<tokio::sync::broadcast::Sender<Query<QueryConsensusEquivocations, Vec<Equivocation>>>>::get(
+                    &self.bus,
+                )
+                .clone(),
                 Pick::<tokio::sync::broadcast::Receiver<ShutdownModule>>::get(&self.bus).resubscribe()
             )
         }
@@ -0,0 +1,478 @@
+//! Detection of validators signing conflicting consensus messages.
+//!
+//! Prepare and timeout messages sign their slot and view: a validator signing two of them for
+//! the same slot and view is provable to anyone, and the evidence can be submitted to the
+//! staking contract for slashing.
+//! Votes only sign a proposal hash: they are attributed to the round their proposal was seen
+//! in, and votes on proposals that weren't seen are ignored. Conflicting votes are only local
+//! alerts, as their round can't be proven.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use borsh::{BorshDeserialize, BorshSerialize};
+use hyle_crypto::BlstCrypto;
+use hyle_model::{
+    api::APIEquivocation, ConsensusProposal, ConsensusProposalHash, DoubleSignEvidence,
+    SignedByValidator, SignedConsensusMessage, Slot, ValidatorPublicKey, View,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use super::{ConfirmAck, PrepareVote, Ticket};
+use crate::p2p::network::MsgHeader;
+
+/// Number of slots for which signed messages are kept to detect conflicts
+pub const EQUIVOCATION_WINDOW: Slot = 10;
+
+/// Maximum number of conflicts kept, the oldest ones being forgotten first
+pub const MAX_EQUIVOCATIONS: usize = 100;
+
+/// Whether a message for `round` is close enough to the current round to be recorded.
+/// Only slots within the window around the current one are, and views at most the window
+/// ahead of the current one.
+pub fn is_recent(round: (Slot, View), current_round: (Slot, View)) -> bool {
+    round.0.abs_diff(current_round.0) <= EQUIVOCATION_WINDOW
+        && round.1 <= current_round.1 + EQUIVOCATION_WINDOW
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, BorshSerialize, BorshDeserialize, PartialEq, Eq)]
+pub enum Equivocation {
+    /// Two proposals signed by a leader, or two timeouts, for the same slot and view
+    DoubleSign(DoubleSignEvidence),
+    /// Prepare votes for two different proposals of the same slot and view
+    ConflictingPrepareVotes {
+        slot: Slot,
+        view: View,
+        first: PrepareVote,
+        second: PrepareVote,
+    },
+    /// Commit votes for two different proposals of the same slot and view
+    ConflictingConfirmAcks {
+        slot: Slot,
+        view: View,
+        first: ConfirmAck,
+        second: ConfirmAck,
+    },
+}
+
+impl Equivocation {
+    pub fn validator(&self) -> &ValidatorPublicKey {
+        match self {
+            Equivocation::DoubleSign(evidence) => evidence.offender(),
+            Equivocation::ConflictingPrepareVotes { first, .. } => &first.signature.validator,
+            Equivocation::ConflictingConfirmAcks { first, .. } => &first.signature.validator,
+        }
+    }
+
+    pub fn round(&self) -> (Slot, View) {
+        match self {
+            Equivocation::DoubleSign(evidence) => (evidence.slot(), evidence.view()),
+            Equivocation::ConflictingPrepareVotes { slot, view, .. }
+            | Equivocation::ConflictingConfirmAcks { slot, view, .. } => (*slot, *view),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Equivocation::DoubleSign(evidence) => match evidence.first {
+                SignedConsensusMessage::Prepare { .. } => "Prepare",
+                SignedConsensusMessage::Timeout(_) => "Timeout",
+            },
+            Equivocation::ConflictingPrepareVotes { .. } => "PrepareVote",
+            Equivocation::ConflictingConfirmAcks { .. } => "ConfirmAck",
+        }
+    }
+}
+
+impl From<&Equivocation> for APIEquivocation {
+    fn from(equivocation: &Equivocation) -> Self {
+        let (slot, view) = equivocation.round();
+        let evidence = match equivocation {
+            Equivocation::DoubleSign(evidence) => borsh::to_vec(evidence),
+            Equivocation::ConflictingPrepareVotes { first, second, .. } => {
+                borsh::to_vec(&(first, second))
+            }
+            Equivocation::ConflictingConfirmAcks { first, second, .. } => {
+                borsh::to_vec(&(first, second))
+            }
+        };
+        APIEquivocation {
+            validator: equivocation.validator().clone(),
+            slot,
+            view,
+            kind: equivocation.kind().to_string(),
+            slashable: matches!(equivocation, Equivocation::DoubleSign(_)),
+            evidence: hex::encode(evidence.unwrap_or_default()),
+        }
+    }
+}
+
+/// Builds the signed form of a received Prepare message, from the header it was signed with
+pub fn signed_prepare(
+    header: &SignedByValidator<MsgHeader>,
+    consensus_proposal: &ConsensusProposal,
+    ticket: &Ticket,
+    view: View,
+) -> Result<SignedConsensusMessage> {
+    Ok(SignedConsensusMessage::Prepare {
+        proposal: Box::new(consensus_proposal.clone()),
+        ticket: borsh::to_vec(ticket)?,
+        view,
+        timestamp: header.msg.timestamp,
+        signature: header.signature.clone(),
+    })
+}
+
+/// Message signed by each validator for a round
+type SignedByRound = BTreeMap<(ValidatorPublicKey, Slot, View), SignedConsensusMessage>;
+
+/// Votes of each validator for a round
+type VotesByRound<T> =
+    BTreeMap<(ValidatorPublicKey, Slot, View), SignedByValidator<(ConsensusProposalHash, T)>>;
+
+/// Keeps the consensus messages signed in recent rounds, and the conflicts found among them
+#[derive(Debug, Default, Clone, BorshSerialize, BorshDeserialize)]
+pub struct EquivocationDetector {
+    /// Proposal signed by each leader for a round
+    prepares: SignedByRound,
+    /// Round each proposal was seen in, to know the round of the votes on it
+    proposal_rounds: BTreeMap<ConsensusProposalHash, (Slot, View)>,
+    prepare_votes: BTreeMap<(ValidatorPublicKey, Slot, View), PrepareVote>,
+    confirm_acks: BTreeMap<(ValidatorPublicKey, Slot, View), ConfirmAck>,
+    /// Timeout signed by each validator for a round
+    timeouts: SignedByRound,
+    /// Last conflicts found, at most `MAX_EQUIVOCATIONS`
+    equivocations: Vec<Equivocation>,
+}
+
+impl EquivocationDetector {
+    pub fn equivocations(&self) -> &[Equivocation] {
+        &self.equivocations
+    }
+
+    /// Records the round of a proposal, to attribute the votes on it
+    pub fn on_proposal(&mut self, proposal_hash: ConsensusProposalHash, slot: Slot, view: View) {
+        self.proposal_rounds
+            .entry(proposal_hash)
+            .or_insert((slot, view));
+    }
+
+    /// Records a Prepare message, returning the evidence if its leader already signed another
+    /// proposal for that round
+    pub fn on_prepare(&mut self, prepare: SignedConsensusMessage) -> Option<Equivocation> {
+        self.on_proposal(prepare.signed_hash(), prepare.slot(), prepare.view());
+        let evidence = Self::double_sign(&mut self.prepares, prepare)?;
+        self.record(Equivocation::DoubleSign(evidence))
+    }
+
+    /// Records a timeout, returning the evidence if its validator already signed a timeout
+    /// with another parent for that round
+    pub fn on_timeout(&mut self, timeout: SignedConsensusMessage) -> Option<Equivocation> {
+        let evidence = Self::double_sign(&mut self.timeouts, timeout)?;
+        self.record(Equivocation::DoubleSign(evidence))
+    }
+
+    /// Records a PrepareVote, returning the conflict if its validator already voted for
+    /// another proposal of the round the vote is for. Votes on unknown proposals are ignored.
+    pub fn on_prepare_vote(&mut self, vote: &PrepareVote) -> Option<Equivocation> {
+        let (slot, view) = self.vote_round(&vote.msg.0)?;
+        let key = (vote.signature.validator.clone(), slot, view);
+        let first = Self::conflicting_vote(&mut self.prepare_votes, key, vote)?;
+        self.record(Equivocation::ConflictingPrepareVotes {
+            slot,
+            view,
+            first,
+            second: vote.clone(),
+        })
+    }
+
+    /// Records a ConfirmAck, returning the conflict if its validator already acknowledged
+    /// another proposal of the round the vote is for. Votes on unknown proposals are ignored.
+    pub fn on_confirm_ack(&mut self, ack: &ConfirmAck) -> Option<Equivocation> {
+        let (slot, view) = self.vote_round(&ack.msg.0)?;
+        let key = (ack.signature.validator.clone(), slot, view);
+        let first = Self::conflicting_vote(&mut self.confirm_acks, key, ack)?;
+        self.record(Equivocation::ConflictingConfirmAcks {
+            slot,
+            view,
+            first,
+            second: ack.clone(),
+        })
+    }
+
+    /// Forgets the messages of rounds too old to still be received
+    pub fn prune(&mut self, slot: Slot) {
+        let oldest = slot.saturating_sub(EQUIVOCATION_WINDOW);
+        self.prepares.retain(|(_, s, _), _| *s >= oldest);
+        self.proposal_rounds.retain(|_, (s, _)| *s >= oldest);
+        self.prepare_votes.retain(|(_, s, _), _| *s >= oldest);
+        self.confirm_acks.retain(|(_, s, _), _| *s >= oldest);
+        self.timeouts.retain(|(_, s, _), _| *s >= oldest);
+    }
+
+    /// Votes are for the round their proposal was seen in, None if it wasn't
+    fn vote_round(&self, proposal_hash: &ConsensusProposalHash) -> Option<(Slot, View)> {
+        self.proposal_rounds.get(proposal_hash).copied()
+    }
+
+    /// Records a message signing its slot and view, returning the evidence if its validator
+    /// already signed another hash for that round
+    fn double_sign(
+        signed: &mut SignedByRound,
+        message: SignedConsensusMessage,
+    ) -> Option<DoubleSignEvidence> {
+        let (slot, view) = (message.slot(), message.view());
+        let key = (message.validator().clone(), slot, view);
+        let Some(first) = signed.get(&key) else {
+            signed.insert(key, message);
+            return None;
+        };
+        if first.signed_hash() == message.signed_hash() {
+            return None;
+        }
+
+        let evidence = DoubleSignEvidence {
+            first: first.clone(),
+            second: message,
+        };
+        if let Err(e) = evidence.verify() {
+            debug!("Ignoring conflicting messages for slot {slot} view {view}: {e}");
+            // Only keep a message that was really signed by its validator
+            if evidence.second.verify_signature().is_ok() {
+                signed.insert(key, evidence.second);
+            }
+            return None;
+        }
+        Some(evidence)
+    }
+
+    /// Records a vote, returning the first vote of the validator for the round if it is for
+    /// another proposal and both are correctly signed
+    fn conflicting_vote<T>(
+        votes: &mut VotesByRound<T>,
+        key: (ValidatorPublicKey, Slot, View),
+        vote: &SignedByValidator<(ConsensusProposalHash, T)>,
+    ) -> Option<SignedByValidator<(ConsensusProposalHash, T)>>
+    where
+        T: BorshSerialize + Clone,
+    {
+        let Some(first) = votes.get(&key) else {
+            votes.insert(key, vote.clone());
+            return None;
+        };
+        if first.msg.0 == vote.msg.0 || !BlstCrypto::verify(vote).unwrap_or(false) {
+            return None;
+        }
+        if !BlstCrypto::verify(first).unwrap_or(false) {
+            votes.insert(key, vote.clone());
+            return None;
+        }
+        Some(first.clone())
+    }
+
+    fn record(&mut self, equivocation: Equivocation) -> Option<Equivocation> {
+        let validator = equivocation.validator();
+        let (slot, view) = equivocation.round();
+        if self.equivocations.iter().any(|known| {
+            known.kind() == equivocation.kind()
+                && known.validator() == validator
+                && known.round() == (slot, view)
+        }) {
+            return None;
+        }
+        warn!(
+            "🚨 Validator {} signed conflicting {} messages for slot {} view {}",
+            validator,
+            equivocation.kind(),
+            slot,
+            view
+        );
+        if self.equivocations.len() >= MAX_EQUIVOCATIONS {
+            self.equivocations.remove(0);
+        }
+        self.equivocations.push(equivocation.clone());
+        Some(equivocation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyle_model::{utils::TimestampMs, ConfirmAckMarker, ConsensusTimeoutMarker, Hashed};
+
+    use super::*;
+    use crate::{
+        consensus::{ConsensusNetMessage, PrepareVoteMarker},
+        p2p::network::HeaderSigner,
+    };
+
+    fn proposal(slot: Slot, timestamp: u128) -> ConsensusProposal {
+        ConsensusProposal {
+            slot,
+            timestamp: TimestampMs(timestamp),
+            ..Default::default()
+        }
+    }
+
+    fn prepare(
+        crypto: &BlstCrypto,
+        proposal: &ConsensusProposal,
+        view: View,
+    ) -> SignedConsensusMessage {
+        let msg = crypto
+            .sign_msg_with_header(ConsensusNetMessage::Prepare(
+                proposal.clone(),
+                Ticket::Genesis,
+                view,
+            ))
+            .unwrap();
+        signed_prepare(&msg.header, proposal, &Ticket::Genesis, view).unwrap()
+    }
+
+    #[test]
+    fn test_signed_prepare_matches_header() {
+        let crypto = BlstCrypto::new("leader").unwrap();
+        assert!(prepare(&crypto, &proposal(3, 1), 2)
+            .verify_signature()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_conflicting_prepares() {
+        let leader = BlstCrypto::new("leader").unwrap();
+        let mut detector = EquivocationDetector::default();
+
+        assert_eq!(
+            detector.on_prepare(prepare(&leader, &proposal(3, 1), 0)),
+            None
+        );
+        // Same proposal again, or another view
+        assert_eq!(
+            detector.on_prepare(prepare(&leader, &proposal(3, 1), 0)),
+            None
+        );
+        assert_eq!(
+            detector.on_prepare(prepare(&leader, &proposal(3, 2), 1)),
+            None
+        );
+
+        let equivocation = detector
+            .on_prepare(prepare(&leader, &proposal(3, 3), 0))
+            .unwrap();
+        let Equivocation::DoubleSign(evidence) = &equivocation else {
+            panic!("Expected a double sign, got {equivocation:?}");
+        };
+        assert_eq!(evidence.verify(), Ok(()));
+        assert_eq!(evidence.offender(), leader.validator_pubkey());
+        assert_eq!(equivocation.round(), (3, 0));
+
+        // Recorded once
+        assert_eq!(
+            detector.on_prepare(prepare(&leader, &proposal(3, 4), 0)),
+            None
+        );
+        assert_eq!(detector.equivocations(), &[equivocation]);
+    }
+
+    #[test]
+    fn test_conflicting_votes() {
+        let validator = BlstCrypto::new("validator").unwrap();
+        let mut detector = EquivocationDetector::default();
+        let a = proposal(3, 1).hashed();
+        let b = proposal(3, 2).hashed();
+        detector.on_proposal(a.clone(), 3, 0);
+        detector.on_proposal(b.clone(), 3, 1);
+
+        // Votes for proposals of different views
+        let vote_a = validator.sign((a.clone(), PrepareVoteMarker)).unwrap();
+        let vote_b = validator.sign((b, PrepareVoteMarker)).unwrap();
+        assert_eq!(detector.on_prepare_vote(&vote_a), None);
+        assert_eq!(detector.on_prepare_vote(&vote_b), None);
+
+        // Votes for an unknown proposal have no known round
+        let c = proposal(3, 3).hashed();
+        let vote_c = validator.sign((c.clone(), PrepareVoteMarker)).unwrap();
+        assert_eq!(detector.on_prepare_vote(&vote_c), None);
+
+        // Once it is seen in the round of the first one, they conflict
+        detector.on_proposal(c.clone(), 3, 0);
+        let equivocation = detector.on_prepare_vote(&vote_c).unwrap();
+        assert!(matches!(
+            equivocation,
+            Equivocation::ConflictingPrepareVotes {
+                slot: 3,
+                view: 0,
+                ..
+            }
+        ));
+        // ... which is not slashable
+        assert!(!APIEquivocation::from(&equivocation).slashable);
+
+        let ack_a = validator.sign((a, ConfirmAckMarker)).unwrap();
+        let ack_c = validator.sign((c, ConfirmAckMarker)).unwrap();
+        assert_eq!(detector.on_confirm_ack(&ack_a), None);
+        // A forged vote isn't evidence
+        let mut forged = ack_c.clone();
+        forged.signature.signature = ack_a.signature.signature.clone();
+        assert_eq!(detector.on_confirm_ack(&forged), None);
+        assert!(detector.on_confirm_ack(&ack_c).is_some());
+        assert_eq!(detector.equivocations().len(), 2);
+
+        detector.prune(3 + EQUIVOCATION_WINDOW + 1);
+        assert!(detector.prepare_votes.is_empty());
+        assert_eq!(detector.equivocations().len(), 2);
+    }
+
+    #[test]
+    fn test_conflicting_timeouts() {
+        let validator = BlstCrypto::new("validator").unwrap();
+        let mut detector = EquivocationDetector::default();
+        let timeout = |parent: &str| {
+            SignedConsensusMessage::Timeout(
+                validator
+                    .sign((
+                        3,
+                        1,
+                        ConsensusProposalHash(parent.to_string()),
+                        ConsensusTimeoutMarker,
+                    ))
+                    .unwrap(),
+            )
+        };
+
+        assert_eq!(detector.on_timeout(timeout("a")), None);
+        assert_eq!(detector.on_timeout(timeout("a")), None);
+        let equivocation = detector.on_timeout(timeout("b")).unwrap();
+        let Equivocation::DoubleSign(evidence) = &equivocation else {
+            panic!("Expected a double sign, got {equivocation:?}");
+        };
+        assert_eq!(evidence.verify(), Ok(()));
+        assert_eq!(equivocation.round(), (3, 1));
+        assert_eq!(equivocation.kind(), "Timeout");
+    }
+
+    #[test]
+    fn test_recent_rounds() {
+        assert!(is_recent((15, 0), (20, 2)));
+        assert!(is_recent((30, 12), (20, 2)));
+        assert!(!is_recent((9, 0), (20, 2)));
+        assert!(!is_recent((31, 0), (20, 2)));
+        assert!(!is_recent((20, 13), (20, 2)));
+    }
+
+    #[test]
+    fn test_equivocations_are_capped() {
+        let leader = BlstCrypto::new("leader").unwrap();
+        let mut detector = EquivocationDetector::default();
+
+        for slot in 0..MAX_EQUIVOCATIONS as Slot + 5 {
+            detector.on_prepare(prepare(&leader, &proposal(slot, 1), 0));
+            assert!(detector
+                .on_prepare(prepare(&leader, &proposal(slot, 2), 0))
+                .is_some());
+            detector.prune(slot);
+        }
+        assert_eq!(detector.equivocations().len(), MAX_EQUIVOCATIONS);
+        assert_eq!(detector.equivocations().first().unwrap().round(), (5, 0));
+    }
+}
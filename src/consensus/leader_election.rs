@@ -0,0 +1,193 @@
+//! Selection of the leader of each slot and view.
+
+use hyle_model::{Slot, ValidatorPublicKey, View};
+use sha3::{Digest, Sha3_256};
+use staking::state::Staking;
+
+/// How the leader of a round is chosen among bonded validators.
+/// All validators must use the same rule.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LeaderElection {
+    /// Leaders are drawn with a probability proportional to their stake, seeded by the
+    /// proposal the commit quorum certificate of the previous slot certifies.
+    #[default]
+    StakeWeighted,
+    /// Leaders take turns in bonding order. Predictable, kept for the scenario tests
+    /// that are written against a fixed schedule.
+    RoundRobin,
+}
+
+impl LeaderElection {
+    pub fn leader(
+        &self,
+        staking: &Staking,
+        seed: &[u8],
+        slot: Slot,
+        view: View,
+    ) -> Option<ValidatorPublicKey> {
+        match self {
+            LeaderElection::StakeWeighted => stake_weighted_leader(staking, seed, slot, view)
+                .or_else(|| round_robin_leader(staking, slot, view)),
+            LeaderElection::RoundRobin => round_robin_leader(staking, slot, view),
+        }
+    }
+}
+
+fn round_robin_leader(staking: &Staking, slot: Slot, view: View) -> Option<ValidatorPublicKey> {
+    let bonded = staking.bonded();
+    if bonded.is_empty() {
+        return None;
+    }
+    // (we remove 1 for backwards compatibility of the tests when making the change)
+    let index = (slot as usize + view as usize).wrapping_sub(1) % bonded.len();
+    bonded.get(index).cloned()
+}
+
+/// Draws a bonded validator with a probability proportional to its stake.
+///
+/// The draw is seeded by `seed`, the hash of the proposal certified by the commit quorum
+/// certificate of the previous slot, so that leaders can't be known before that slot is
+/// committed. The aggregate signature of the certificate isn't used: it depends on which
+/// votes the leader aggregated, so validators could disagree on the leader. The hash is the
+/// same for every certificate, and for a forced commit or a node resuming from a block.
+/// Note that the leader of the previous slot chooses the content of its proposal, and so
+/// could try several ones to influence the next leaders.
+///
+/// Returns None if no bonded validator has any stake.
+pub fn stake_weighted_leader(
+    staking: &Staking,
+    seed: &[u8],
+    slot: Slot,
+    view: View,
+) -> Option<ValidatorPublicKey> {
+    let stakes: Vec<(&ValidatorPublicKey, u128)> = staking
+        .bonded()
+        .iter()
+        .map(|v| (v, staking.get_stake(v).unwrap_or(0)))
+        .filter(|(_, stake)| *stake > 0)
+        .collect();
+    let total = stakes
+        .iter()
+        .try_fold(0u128, |total, (_, stake)| total.checked_add(*stake))?;
+    if total == 0 {
+        return None;
+    }
+
+    let mut hasher = Sha3_256::new();
+    hasher.update(b"hyle-leader-election");
+    hasher.update(seed);
+    hasher.update(slot.to_le_bytes());
+    hasher.update(view.to_le_bytes());
+    let digest = hasher.finalize();
+    // The modulo bias is negligible, as stakes are far below 2^128
+    let mut draw = u128::from_le_bytes(*digest.first_chunk::<16>()?) % total;
+
+    for (validator, stake) in stakes {
+        if draw < stake {
+            return Some(validator.clone());
+        }
+        draw -= stake;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use hyle_model::Identity;
+
+    use super::*;
+
+    fn staking_with(stakes: &[u128]) -> (Staking, Vec<ValidatorPublicKey>) {
+        let mut staking = Staking::new();
+        let mut validators = vec![];
+        for (i, stake) in stakes.iter().enumerate() {
+            let validator = ValidatorPublicKey(vec![i as u8 + 1; 48]);
+            let staker = Identity::new(format!("staker{i}"));
+            staking.stake(staker.clone(), *stake).unwrap();
+            staking.delegate_to(staker, validator.clone()).unwrap();
+            staking.bond(validator.clone()).unwrap();
+            validators.push(validator);
+        }
+        (staking, validators)
+    }
+
+    /// Share of the rounds led by each validator, over many commits and views
+    fn leader_shares(staking: &Staking, validators: &[ValidatorPublicKey]) -> Vec<f64> {
+        let rounds: u64 = 20_000;
+        let mut led = BTreeMap::<ValidatorPublicKey, u64>::new();
+        for slot in 0..rounds {
+            let seed = Sha3_256::digest(slot.to_le_bytes());
+            let leader = stake_weighted_leader(staking, &seed, slot, slot % 3).unwrap();
+            *led.entry(leader).or_default() += 1;
+        }
+        validators
+            .iter()
+            .map(|v| led.get(v).copied().unwrap_or(0) as f64 / rounds as f64)
+            .collect()
+    }
+
+    fn assert_fair(stakes: &[u128]) {
+        let (staking, validators) = staking_with(stakes);
+        let total: u128 = stakes.iter().sum();
+        for (share, stake) in leader_shares(&staking, &validators).iter().zip(stakes) {
+            let expected = *stake as f64 / total as f64;
+            assert!(
+                (share - expected).abs() < 0.02,
+                "stakes {stakes:?}: led {share} of the rounds, expected {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_fair_with_equal_stakes() {
+        assert_fair(&[100, 100, 100, 100]);
+    }
+
+    #[test]
+    fn test_fair_with_uneven_stakes() {
+        assert_fair(&[100, 200, 300, 400]);
+        assert_fair(&[1000, 100, 100, 100, 100]);
+        assert_fair(&[32, 32, 10_000]);
+    }
+
+    #[test]
+    fn test_deterministic_and_seeded() {
+        let (staking, validators) = staking_with(&[100, 100, 100, 100]);
+
+        let leader = stake_weighted_leader(&staking, b"seed", 5, 0);
+        assert_eq!(leader, stake_weighted_leader(&staking, b"seed", 5, 0));
+
+        // Other seeds or views give other leaders
+        let leaders: std::collections::BTreeSet<_> = (0..64u8)
+            .filter_map(|i| stake_weighted_leader(&staking, &[i], 5, 0))
+            .collect();
+        assert_eq!(leaders.len(), validators.len());
+        let views: std::collections::BTreeSet<_> = (0..64)
+            .filter_map(|view| stake_weighted_leader(&staking, b"seed", 5, view))
+            .collect();
+        assert_eq!(views.len(), validators.len());
+    }
+
+    #[test]
+    fn test_only_bonded_validators_lead() {
+        let (mut staking, validators) = staking_with(&[100, 100]);
+        // Delegated to, but not bonded
+        let candidate = ValidatorPublicKey(vec![42; 48]);
+        staking.stake(Identity::new("candidate"), 1000).unwrap();
+        staking
+            .delegate_to(Identity::new("candidate"), candidate)
+            .unwrap();
+        for slot in 0..1000 {
+            let leader = stake_weighted_leader(&staking, b"seed", slot, 0).unwrap();
+            assert!(validators.contains(&leader));
+        }
+
+        assert_eq!(stake_weighted_leader(&Staking::new(), b"seed", 1, 0), None);
+        assert_eq!(
+            LeaderElection::StakeWeighted.leader(&Staking::new(), b"seed", 1, 0),
+            None
+        );
+    }
+}
@@ -4,8 +4,8 @@ use hyle_modules::{bus::SharedMessageBus, log_error, modules::Module};
 use crate::model::SharedRunContext;
 
 use super::{
-    api, consensus_bus_client::ConsensusBusClient, metrics::ConsensusMetrics, Consensus,
-    ConsensusStore,
+    api, consensus_bus_client::ConsensusBusClient, leader_election::LeaderElection,
+    metrics::ConsensusMetrics, Consensus, ConsensusStore,
 };
 
 impl Module for Consensus {
@@ -31,6 +31,7 @@ impl Module for Consensus {
             store,
             config: ctx.config.clone(),
             crypto: ctx.crypto.clone(),
+            leader_election: LeaderElection::default(),
         })
     }
 
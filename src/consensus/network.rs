@@ -4,6 +4,7 @@ use sha3::{Digest, Sha3_256};
 use std::{fmt::Display, ops::Deref};
 use strum_macros::IntoStaticStr;
 
+use hyle_crypto::signer::SigningKind;
 use hyle_model::*;
 
 use crate::p2p::network::{HeaderSignableData, IntoHeaderSignableData};
@@ -73,20 +74,7 @@ impl From<PrepareVote> for ConsensusNetMessage {
     }
 }
 
-#[derive(
-    Debug,
-    Serialize,
-    Deserialize,
-    Clone,
-    BorshSerialize,
-    BorshDeserialize,
-    PartialEq,
-    Eq,
-    Hash,
-    Ord,
-    PartialOrd,
-)]
-pub struct ConfirmAckMarker;
+pub use hyle_model::ConfirmAckMarker;
 pub type ConfirmAck = SignedByValidator<(ConsensusProposalHash, ConfirmAckMarker)>;
 
 impl From<ConfirmAck> for ConsensusNetMessage {
@@ -95,20 +83,7 @@ impl From<ConfirmAck> for ConsensusNetMessage {
     }
 }
 
-#[derive(
-    Debug,
-    Serialize,
-    Deserialize,
-    Clone,
-    BorshSerialize,
-    BorshDeserialize,
-    PartialEq,
-    Eq,
-    Hash,
-    Ord,
-    PartialOrd,
-)]
-pub struct ConsensusTimeoutMarker;
+pub use hyle_model::ConsensusTimeoutMarker;
 
 /// This first message will be used in the TC to generate a proof of timeout
 /// Here we add the parent hash purely to avoid replay attacks
@@ -311,6 +286,18 @@ impl IntoHeaderSignableData for ConsensusNetMessage {
             .unwrap_or_default(),
         })
     }
+
+    fn signing_kind(&self, timestamp: u128) -> SigningKind {
+        match self {
+            ConsensusNetMessage::Prepare(cp, ticket, view) => SigningKind::Prepare {
+                proposal: Box::new(cp.clone()),
+                ticket: borsh::to_vec(ticket).unwrap_or_default(),
+                view: *view,
+                timestamp,
+            },
+            _ => SigningKind::Generic,
+        }
+    }
 }
 
 #[derive(
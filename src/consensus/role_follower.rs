@@ -1,6 +1,6 @@
 use anyhow::{bail, Context, Result};
 use borsh::{BorshDeserialize, BorshSerialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use tracing::{debug, info, trace, warn};
 
 use super::*;
@@ -11,7 +11,7 @@ use crate::{
     p2p::P2PCommand,
     utils::conf::TimestampCheck,
 };
-use hyle_crypto::BlstCrypto;
+use hyle_crypto::{signer::SigningKind, BlstCrypto};
 use hyle_model::{
     utils::TimestampMs, AggregateSignature, ConsensusProposal, ConsensusProposalHash,
     ConsensusStakingAction, Cut, LaneBytesSize, LaneId, SignedByValidator, ValidatorCandidacy,
@@ -188,7 +188,15 @@ impl Consensus {
             );
             self.send_net_message(
                 round_leader,
-                self.crypto.sign((cp_hash, PrepareVoteMarker))?.into(),
+                self.crypto
+                    .sign_with_kind(
+                        (cp_hash, PrepareVoteMarker),
+                        SigningKind::PrepareVote {
+                            proposal: Box::new(self.bft_round_state.current_proposal.clone()),
+                            view: self.bft_round_state.view,
+                        },
+                    )?
+                    .into(),
             )?;
         } else {
             info!(
@@ -255,7 +263,13 @@ impl Consensus {
             self.send_net_message(
                 self.round_leader()?,
                 self.crypto
-                    .sign((proposal_hash_hint, ConfirmAckMarker))?
+                    .sign_with_kind(
+                        (proposal_hash_hint, ConfirmAckMarker),
+                        SigningKind::ConfirmAck {
+                            proposal: Box::new(self.bft_round_state.current_proposal.clone()),
+                            view: self.bft_round_state.view,
+                        },
+                    )?
                     .into(),
             )?;
         } else {
@@ -464,7 +478,12 @@ impl Consensus {
             .current_proposal
             .staking_actions
             .iter()
-            .filter(|sa| matches!(sa, ConsensusStakingAction::Bond { .. }))
+            .filter(|sa| {
+                matches!(
+                    sa,
+                    ConsensusStakingAction::Bond { .. } | ConsensusStakingAction::Unbond { .. }
+                )
+            })
             .count()
             > 0
     }
@@ -765,6 +784,7 @@ impl Consensus {
     }
 
     fn verify_staking_actions(&mut self, proposal: &ConsensusProposal) -> Result<()> {
+        let mut unbonded = BTreeSet::new();
         for action in &proposal.staking_actions {
             match action {
                 ConsensusStakingAction::Bond { candidate } => {
@@ -774,8 +794,31 @@ impl Consensus {
                     lane_id,
                     cumul_size,
                 } => Self::verify_dadi_fees(&proposal.cut, lane_id, cumul_size)?,
+                ConsensusStakingAction::Unbond { validator } => {
+                    self.verify_validator_to_unbond(validator)?;
+                    if !unbonded.insert(validator) {
+                        bail!("Validator {validator} is unbonded twice");
+                    }
+                }
             }
         }
+        // As checked by the leader, unbonding all validators would halt the chain
+        if !unbonded.is_empty() && unbonded.len() >= self.bft_round_state.staking.bonded().len() {
+            bail!("Proposal unbonds all {} validators", unbonded.len());
+        }
+        Ok(())
+    }
+
+    /// Verify that an unbonded validator was slashed or doesn't have enough stake anymore
+    fn verify_validator_to_unbond(&self, validator: &ValidatorPublicKey) -> Result<()> {
+        if !self
+            .bft_round_state
+            .staking
+            .validators_to_unbond()
+            .contains(validator)
+        {
+            bail!("Validator {validator} can't be unbonded");
+        }
         Ok(())
     }
 
@@ -8,6 +8,7 @@ use crate::{
     mempool::QueryNewCut,
     model::{Hashed, ValidatorPublicKey},
 };
+use hyle_crypto::signer::SigningKind;
 use hyle_model::{utils::TimestampMs, ConsensusProposal, ConsensusStakingAction};
 use staking::state::MIN_STAKE;
 use tokio::sync::broadcast;
@@ -152,6 +153,17 @@ impl Consensus {
                 .map(|v| v.into())
                 .collect();
 
+            // Slashed validators, or those without enough stake, leave the consensus.
+            // Never unbond all validators, which would halt the chain.
+            let validators_to_unbond = self.bft_round_state.staking.validators_to_unbond();
+            if validators_to_unbond.len() < self.bft_round_state.staking.bonded().len() {
+                staking_actions.extend(
+                    validators_to_unbond
+                        .into_iter()
+                        .map(|validator| ConsensusStakingAction::Unbond { validator }),
+                );
+            }
+
             for tx in cut.iter() {
                 debug!("📦 Lane {} cumulated size: {}", tx.0, tx.2);
                 staking_actions.push(ConsensusStakingAction::PayFeesForDaDi {
@@ -181,6 +193,14 @@ impl Consensus {
 
         self.metrics.start_new_round(self.bft_round_state.slot);
 
+        // Votes on our proposal are for this round
+        let (slot, view) = (self.bft_round_state.slot, self.bft_round_state.view);
+        self.store.equivocations.on_proposal(
+            self.bft_round_state.current_proposal.hashed(),
+            slot,
+            view,
+        );
+
         // Verifies that to-be-built block is large enough (?)
 
         // Broadcasts Prepare message to all validators
@@ -267,9 +287,14 @@ impl Consensus {
 
             let proposal_hash_hint = self.bft_round_state.current_proposal.hashed();
             // Aggregates them into a *Prepare* Quorum Certificate
-            let prepvote_signed_aggregation = self
-                .crypto
-                .sign_aggregate((proposal_hash_hint.clone(), PrepareVoteMarker), aggregates)?;
+            let prepvote_signed_aggregation = self.crypto.sign_aggregate_with_kind(
+                (proposal_hash_hint.clone(), PrepareVoteMarker),
+                SigningKind::PrepareVote {
+                    proposal: Box::new(self.bft_round_state.current_proposal.clone()),
+                    view: self.bft_round_state.view,
+                },
+                aggregates,
+            )?;
 
             // Process the Confirm message locally, then send it to peers.
             self.bft_round_state.leader.step = Step::ConfirmAck;
@@ -368,11 +393,13 @@ impl Consensus {
                 &self.bft_round_state.leader.confirm_ack.iter().collect();
 
             // Aggregates them into a *Commit* Quorum Certificate
-            let commit_signed_aggregation = self.crypto.sign_aggregate(
-                (
-                    self.bft_round_state.current_proposal.hashed(),
-                    ConfirmAckMarker,
-                ),
+            let proposal_hash = self.bft_round_state.current_proposal.hashed();
+            let commit_signed_aggregation = self.crypto.sign_aggregate_with_kind(
+                (proposal_hash, ConfirmAckMarker),
+                SigningKind::ConfirmAck {
+                    proposal: Box::new(self.bft_round_state.current_proposal.clone()),
+                    view: self.bft_round_state.view,
+                },
                 aggregates,
             )?;
 
@@ -5,6 +5,7 @@ use tracing::{debug, info, trace, warn};
 
 use super::*;
 use crate::model::{Slot, ValidatorPublicKey, View};
+use hyle_crypto::signer::SigningKind;
 use hyle_model::{utils::TimestampMs, ConsensusProposalHash, Hashed, Signed, SignedByValidator};
 use hyle_net::clock::TimestampMsClock;
 
@@ -383,13 +384,14 @@ impl Consensus {
                         Result::Ok((
                             QuorumCertificate(
                                 self.crypto
-                                    .sign_aggregate(
+                                    .sign_aggregate_with_kind(
                                         (
                                             self.bft_round_state.slot,
                                             self.bft_round_state.view,
                                             self.bft_round_state.parent_hash.clone(),
                                             ConsensusTimeoutMarker,
                                         ),
+                                        self.timeout_signing_kind(),
                                         signed_messages.as_slice(),
                                     )?
                                     .signature,
@@ -418,13 +420,14 @@ impl Consensus {
                         Result::Ok((
                             QuorumCertificate(
                                 self.crypto
-                                    .sign_aggregate(
+                                    .sign_aggregate_with_kind(
                                         (
                                             self.bft_round_state.slot,
                                             self.bft_round_state.view,
                                             self.bft_round_state.parent_hash.clone(),
                                             ConsensusTimeoutMarker,
                                         ),
+                                        self.timeout_signing_kind(),
                                         signed_nil_messages.as_slice(),
                                     )?
                                     .signature,
@@ -464,13 +467,32 @@ impl Consensus {
         Ok(())
     }
 
+    /// Kind of the timeout of the current slot and view, for the signer to check
+    fn timeout_signing_kind(&self) -> SigningKind {
+        SigningKind::Timeout {
+            slot: self.bft_round_state.slot,
+            view: self.bft_round_state.view,
+            parent_hash: self.bft_round_state.parent_hash.clone(),
+        }
+    }
+
+    fn sign_timeout(
+        &self,
+    ) -> Result<SignedByValidator<(Slot, View, ConsensusProposalHash, ConsensusTimeoutMarker)>>
+    {
+        self.crypto.sign_with_kind(
+            (
+                self.bft_round_state.slot,
+                self.bft_round_state.view,
+                self.bft_round_state.parent_hash.clone(),
+                ConsensusTimeoutMarker,
+            ),
+            self.timeout_signing_kind(),
+        )
+    }
+
     fn get_timeout_message(&self) -> Result<ConsensusTimeout> {
-        let signed_timeout_metadata = self.crypto.sign((
-            self.bft_round_state.slot,
-            self.bft_round_state.view,
-            self.bft_round_state.parent_hash.clone(),
-            ConsensusTimeoutMarker,
-        ))?;
+        let signed_timeout_metadata = self.sign_timeout()?;
         tracing::debug!(
             "Sending timeout message for slot {} and view {}.\nHighest seen {:?}",
             self.bft_round_state.slot,
@@ -491,12 +513,7 @@ impl Consensus {
                 }
                 _ => (
                     signed_timeout_metadata,
-                    TimeoutKind::NilProposal(self.crypto.sign((
-                        self.bft_round_state.slot,
-                        self.bft_round_state.view,
-                        self.bft_round_state.parent_hash.clone(),
-                        ConsensusTimeoutMarker,
-                    ))?),
+                    TimeoutKind::NilProposal(self.sign_timeout()?),
                 ),
             },
         )
@@ -1,12 +1,12 @@
 //! Minimal block storage layer for data availability.
 
 mod blocks_fjall;
+#[cfg(test)]
 mod blocks_memory;
 mod module;
+pub mod storage;
 
-// Pick one of the two implementations
-use blocks_fjall::Blocks;
-//use blocks_memory::Blocks;
+use storage::BlocksStorage;
 
 use hyle_modules::{
     log_error, module_bus_client, module_handle_messages,
@@ -29,6 +29,7 @@ use crate::{
     genesis::GenesisEvent,
     model::*,
     p2p::network::{OutboundMessage, PeerEvent},
+    state_sync::StateSyncEvent,
     utils::conf::SharedConf,
 };
 use anyhow::{Context, Error, Result};
@@ -48,25 +49,40 @@ struct DABusClient {
     receiver(MempoolStatusEvent),
     receiver(GenesisEvent),
     receiver(PeerEvent),
+    receiver(StateSyncEvent),
 }
 }
 
 type DaTcpServer =
     hyle_net::tcp::tcp_server::TcpServer<DataAvailabilityRequest, DataAvailabilityEvent>;
 
+pub struct DataAvailabilityCtx {
+    pub common: SharedRunContext,
+    /// Storage overriding the backend selected in the configuration, only meant for tests
+    pub blocks: Option<Box<dyn BlocksStorage>>,
+}
+
 #[derive(Debug)]
 pub struct DataAvailability {
     config: SharedConf,
     bus: DABusClient,
-    pub blocks: Blocks,
+    pub blocks: Box<dyn BlocksStorage>,
 
     buffered_signed_blocks: BTreeSet<SignedBlock>,
 
     need_catchup: bool,
     catchup_task: Option<tokio::task::JoinHandle<()>>,
     catchup_height: Option<BlockHeight>,
+
+    /// Height and hash of the last block included in the node state snapshot we started from, if any
+    snapshot_tip: Option<(BlockHeight, ConsensusProposalHash)>,
+    /// Catchup is delayed until the state is synced from peers, so it starts after the synced block
+    awaiting_state_sync: bool,
 }
 
+/// File written when bootstrapping from a snapshot, holding the snapshot tip
+pub const SNAPSHOT_TIP_FILE: &str = "da_snapshot_tip.bin";
+
 impl DataAvailability {
     pub async fn start(&mut self) -> Result<()> {
         info!(
@@ -110,13 +126,24 @@ impl DataAvailability {
                 if let GenesisEvent::GenesisBlock(signed_block) = cmd {
                     debug!("🌱  Genesis block received with validators {:?}", signed_block.consensus_proposal.staking_actions.clone());
                     let _= log_error!(self.handle_signed_block(signed_block, &mut server).await.context("Handling genesis block"), "Handling GenesisBlock Event");
+                } else if self.awaiting_state_sync {
+                    info!("📡  Waiting for state sync before catching up on blocks");
                 } else {
                     // TODO: I think this is technically a data race with p2p ?
                     self.need_catchup = true;
                     // This also triggers when restarting from serialized state, which seems fine.
                 }
             }
+            listen<StateSyncEvent> StateSyncEvent::Synced { height, block_hash, .. } => {
+                _ = log_error!(self.handle_state_synced(height, block_hash), "Handling StateSyncEvent");
+            }
             listen<PeerEvent> msg => {
+                // Peers met while waiting for state sync are used once it completes
+                if self.awaiting_state_sync {
+                    let PeerEvent::NewPeer { da_address, .. } = msg;
+                    peers.push(da_address);
+                    continue;
+                }
                 if !self.need_catchup || self.catchup_task.as_ref().is_some_and(|t| !t.is_finished()) {
                     continue;
                 }
@@ -168,7 +195,7 @@ impl DataAvailability {
 
             Some(tcp_event) = server.listen_next() => {
                 if let TcpEvent::Message { dest, data } = tcp_event {
-                    _ = self.start_streaming_to_peer(data.0, &mut catchup_joinset, &dest).await;
+                    _ = log_error!(self.start_streaming_to_peer(data.0, &mut catchup_joinset, &dest, &mut server).await, "Starting stream to peer");
                 }
             }
 
@@ -282,6 +309,19 @@ impl DataAvailability {
             );
             return None;
         }
+        // if new block was already pruned, ignore it
+        if self
+            .blocks
+            .first_height()
+            .is_some_and(|floor| block.height() < floor)
+        {
+            debug!(
+                "Block {} {} is below the retained floor, ignoring",
+                block.height(),
+                hash
+            );
+            return None;
+        }
         // if new block is not the next block in the chain, buffer
         if !self.blocks.is_empty() {
             if !self.blocks.contains(block.parent_hash()) {
@@ -295,6 +335,18 @@ impl DataAvailability {
                 self.buffered_signed_blocks.insert(block);
                 return None;
             }
+        // if we bootstrapped from a snapshot, the first block must extend the snapshot tip
+        } else if let Some((tip_height, tip_hash)) = &self.snapshot_tip {
+            if block.height() != *tip_height + 1 || block.parent_hash() != tip_hash {
+                trace!(
+                    "Received block with height {} but block following snapshot tip {} is missing",
+                    block.height(),
+                    tip_height
+                );
+                trace!("Buffering block {}", block.hashed());
+                self.buffered_signed_blocks.insert(block);
+                return None;
+            }
         // if genesis block is missing, buffer
         } else if block.height() != BlockHeight(0) {
             trace!(
@@ -315,6 +367,10 @@ impl DataAvailability {
         );
         let highest_processed_height = self.pop_buffer(hash, tcp_server).await;
         _ = log_error!(self.blocks.persist(), "Persisting blocks");
+        _ = log_error!(
+            self.blocks.apply_retention(&self.config.da_storage),
+            "Pruning blocks"
+        );
 
         Some(highest_processed_height.unwrap_or(block_height))
     }
@@ -425,7 +481,27 @@ impl DataAvailability {
         start_height: BlockHeight,
         catchup_joinset: &mut JoinSet<(Vec<ConsensusProposalHash>, String, usize)>,
         peer_ip: &str,
+        tcp_server: &mut DaTcpServer,
     ) -> Result<()> {
+        // Let the peer know it won't get the blocks it asked for
+        let start_height = match self.blocks.first_height() {
+            Some(first_available) if start_height < first_available => {
+                warn!(
+                    "Peer {} asked for blocks from height {} but blocks below {} were pruned",
+                    peer_ip, start_height, first_available
+                );
+                tcp_server.try_send(
+                    peer_ip.to_string(),
+                    DataAvailabilityEvent::BlocksPruned {
+                        requested: start_height,
+                        first_available,
+                    },
+                )?;
+                first_available
+            }
+            _ => start_height,
+        };
+
         // Finally, stream past blocks as required.
         // We'll create a copy of the range so we don't stream everything.
         // We will safely stream everything as any new block will be sent
@@ -451,6 +527,27 @@ impl DataAvailability {
         Ok(())
     }
 
+    /// Starts catching up on blocks following the state synced from peers.
+    fn handle_state_synced(
+        &mut self,
+        height: BlockHeight,
+        block_hash: ConsensusProposalHash,
+    ) -> Result<()> {
+        if !self.awaiting_state_sync {
+            return Ok(());
+        }
+        self.awaiting_state_sync = false;
+        self.need_catchup = true;
+        if !self.blocks.is_empty() {
+            return Ok(());
+        }
+        let tip = (height, block_hash);
+        Self::save_on_disk(&self.config.data_directory.join(SNAPSHOT_TIP_FILE), &tip)
+            .context("Saving snapshot tip")?;
+        self.snapshot_tip = Some(tip);
+        Ok(())
+    }
+
     async fn ask_for_catchup_blocks(
         &mut self,
         ip: String,
@@ -461,6 +558,7 @@ impl DataAvailability {
             .blocks
             .last()
             .map(|block| block.height() + 1)
+            .or(self.snapshot_tip.as_ref().map(|(height, _)| *height + 1))
             .unwrap_or(BlockHeight(0));
 
         let mut client = DataAvailabilityClient::connect("block_catcher".to_string(), ip)
@@ -508,6 +606,13 @@ impl DataAvailability {
                                 // Reset the timeout ONLY when a block is received
                                 deadline = Instant::now() + timeout_duration;
                             }
+                            Some(DataAvailabilityEvent::BlocksPruned { requested, first_available }) => {
+                                warn!(
+                                    "Peer pruned blocks {}..{}, cannot catch up from it",
+                                    requested, first_available
+                                );
+                                break;
+                            }
                             Some(_) => {
                                 tracing::trace!("Dropped received message in catchup task");
                             }
@@ -534,13 +639,19 @@ pub mod tests {
         consensus::CommittedConsensusProposal,
         model::*,
         node_state::module::{NodeStateBusClient, NodeStateEvent},
-        utils::{conf::Conf, integration_test::find_available_port},
+        utils::{
+            conf::{Conf, DaStorageConf},
+            integration_test::find_available_port,
+        },
     };
+    use hyle_model::utils::TimestampMs;
     use hyle_modules::log_error;
     use hyle_modules::utils::da_codec::{DataAvailabilityClient, DataAvailabilityServer};
 
+    use super::blocks_fjall::FjallBlocks;
+    use super::blocks_memory::MemoryBlocks;
     use super::codec::DataAvailabilityEvent;
-    use super::Blocks;
+    use super::storage::BlocksStorage;
     use super::{module_bus_client, DaTcpServer};
     use anyhow::Result;
     use staking::state::Staking;
@@ -556,7 +667,7 @@ pub mod tests {
         pub async fn new(shared_bus: crate::bus::SharedMessageBus) -> Self {
             let path = tempfile::tempdir().unwrap().keep();
             let tmpdir = path;
-            let blocks = Blocks::new(&tmpdir).unwrap();
+            let blocks = Box::new(FjallBlocks::new(&tmpdir).unwrap());
 
             let bus = super::DABusClient::new_from_bus(shared_bus.new_handle()).await;
             let node_state_bus = NodeStateBusClient::new_from_bus(shared_bus).await;
@@ -575,6 +686,8 @@ pub mod tests {
                 need_catchup: false,
                 catchup_task: None,
                 catchup_height: None,
+                snapshot_tip: None,
+                awaiting_state_sync: false,
             };
 
             DataAvailabilityTestCtx {
@@ -607,7 +720,7 @@ pub mod tests {
     #[test_log::test]
     fn test_blocks() -> Result<()> {
         let tmpdir = tempfile::tempdir().unwrap().keep();
-        let mut blocks = Blocks::new(&tmpdir).unwrap();
+        let mut blocks = FjallBlocks::new(&tmpdir).unwrap();
         let block = SignedBlock::default();
         blocks.put(block.clone())?;
         assert!(blocks.last().unwrap().height() == block.height());
@@ -617,10 +730,78 @@ pub mod tests {
         Ok(())
     }
 
+    fn make_chain(len: u64) -> Vec<SignedBlock> {
+        let mut block = SignedBlock::default();
+        let mut blocks = vec![];
+        for i in 1..=len {
+            blocks.push(block.clone());
+            block.consensus_proposal.parent_hash = block.hashed();
+            block.consensus_proposal.slot = i;
+            block.consensus_proposal.timestamp = TimestampMs(i as u128 * 1000);
+        }
+        blocks
+    }
+
+    fn check_keep_last_blocks(blocks: &mut dyn BlocksStorage) -> Result<()> {
+        for block in make_chain(10) {
+            blocks.put(block)?;
+        }
+        let conf = DaStorageConf {
+            keep_last_blocks: 4,
+            ..Default::default()
+        };
+        assert_eq!(blocks.apply_retention(&conf)?, Some(BlockHeight(6)));
+        assert_eq!(blocks.first_height(), Some(BlockHeight(6)));
+        assert_eq!(blocks.last().unwrap().height(), BlockHeight(9));
+        assert_eq!(blocks.get_hash_by_height(BlockHeight(5))?, None);
+        assert!(blocks.get_hash_by_height(BlockHeight(6))?.is_some());
+        assert_eq!(
+            blocks.range(BlockHeight(0), BlockHeight(10)).count(),
+            4,
+            "Only retained blocks are returned"
+        );
+        // Nothing left to prune
+        assert_eq!(blocks.apply_retention(&conf)?, None);
+        Ok(())
+    }
+
+    #[test_log::test]
+    fn test_keep_last_blocks_fjall() -> Result<()> {
+        let tmpdir = tempfile::tempdir().unwrap().keep();
+        check_keep_last_blocks(&mut FjallBlocks::new(&tmpdir)?)
+    }
+
+    #[test_log::test]
+    fn test_keep_last_blocks_memory() -> Result<()> {
+        let tmpdir = tempfile::tempdir().unwrap().keep();
+        check_keep_last_blocks(&mut MemoryBlocks::new(&tmpdir)?)
+    }
+
+    #[test_log::test]
+    fn test_keep_blocks_newer_than() -> Result<()> {
+        let tmpdir = tempfile::tempdir().unwrap().keep();
+        let mut blocks = FjallBlocks::new(&tmpdir)?;
+        for block in make_chain(10) {
+            blocks.put(block)?;
+        }
+        // Latest block has timestamp 9s, keep blocks from 6s onwards
+        let conf = DaStorageConf {
+            keep_blocks_newer_than_secs: 3,
+            ..Default::default()
+        };
+        assert_eq!(blocks.apply_retention(&conf)?, Some(BlockHeight(6)));
+        assert_eq!(blocks.first_height(), Some(BlockHeight(6)));
+
+        // Disabled rules don't prune anything
+        let conf = DaStorageConf::default();
+        assert_eq!(blocks.apply_retention(&conf)?, None);
+        Ok(())
+    }
+
     #[test_log::test(tokio::test)]
     async fn test_pop_buffer_large() {
         let tmpdir = tempfile::tempdir().unwrap().keep();
-        let blocks = Blocks::new(&tmpdir).unwrap();
+        let blocks = Box::new(FjallBlocks::new(&tmpdir).unwrap());
 
         let mut server = DataAvailabilityServer::start(7898, "DaServer")
             .await
@@ -638,6 +819,8 @@ pub mod tests {
             need_catchup: false,
             catchup_task: None,
             catchup_height: None,
+            snapshot_tip: None,
+            awaiting_state_sync: false,
         };
         let mut block = SignedBlock::default();
         let mut blocks = vec![];
@@ -659,6 +842,47 @@ pub mod tests {
         }
     }
 
+    #[test_log::test(tokio::test)]
+    async fn test_start_from_snapshot_tip() {
+        let tmpdir = tempfile::tempdir().unwrap().keep();
+        let blocks = Box::new(FjallBlocks::new(&tmpdir).unwrap());
+
+        let mut server = DataAvailabilityServer::start(find_available_port().await, "DaServer")
+            .await
+            .unwrap();
+
+        let chain = make_chain(10);
+        let tip = &chain[4];
+
+        let bus = super::DABusClient::new_from_bus(crate::bus::SharedMessageBus::new(
+            crate::bus::metrics::BusMetrics::global("global".to_string()),
+        ))
+        .await;
+        let mut da = super::DataAvailability {
+            config: Default::default(),
+            bus,
+            blocks,
+            buffered_signed_blocks: Default::default(),
+            need_catchup: false,
+            catchup_task: None,
+            catchup_height: None,
+            snapshot_tip: Some((tip.height(), tip.hashed())),
+            awaiting_state_sync: false,
+        };
+
+        // Blocks not following the snapshot tip are buffered
+        assert_eq!(
+            da.handle_signed_block(chain[6].clone(), &mut server).await,
+            None
+        );
+        assert_eq!(
+            da.handle_signed_block(chain[5].clone(), &mut server).await,
+            Some(BlockHeight(6))
+        );
+        assert_eq!(da.blocks.first_height(), Some(BlockHeight(5)));
+        assert_eq!(da.blocks.last().unwrap().height(), BlockHeight(6));
+    }
+
     module_bus_client! {
     #[derive(Debug)]
     struct TestBusClient {
@@ -669,7 +893,7 @@ pub mod tests {
     #[test_log::test(tokio::test)]
     async fn test_da_streaming() {
         let tmpdir = tempfile::tempdir().unwrap().keep();
-        let blocks = Blocks::new(&tmpdir).unwrap();
+        let blocks = Box::new(FjallBlocks::new(&tmpdir).unwrap());
 
         let global_bus = crate::bus::SharedMessageBus::new(
             crate::bus::metrics::BusMetrics::global("global".to_string()),
@@ -688,6 +912,8 @@ pub mod tests {
             need_catchup: false,
             catchup_task: None,
             catchup_height: None,
+            snapshot_tip: None,
+            awaiting_state_sync: false,
         };
 
         let mut block = SignedBlock::default();
@@ -785,6 +1011,74 @@ pub mod tests {
             assert!(heights_received.contains(&i));
         }
     }
+    #[test_log::test(tokio::test)]
+    async fn test_da_streaming_reports_pruned_blocks() {
+        let tmpdir = tempfile::tempdir().unwrap().keep();
+        let mut blocks = FjallBlocks::new(&tmpdir).unwrap();
+        for block in make_chain(10) {
+            blocks.put(block).unwrap();
+        }
+
+        let global_bus = crate::bus::SharedMessageBus::new(
+            crate::bus::metrics::BusMetrics::global("global".to_string()),
+        );
+        let bus = super::DABusClient::new_from_bus(global_bus.new_handle()).await;
+
+        let mut config: Conf = Conf::new(vec![], None, None).unwrap();
+        config.da_server_port = find_available_port().await;
+        config.da_public_address = format!("127.0.0.1:{}", config.da_server_port);
+        config.da_storage.keep_last_blocks = 4;
+        blocks.apply_retention(&config.da_storage).unwrap();
+
+        let mut da = super::DataAvailability {
+            config: config.clone().into(),
+            bus,
+            blocks: Box::new(blocks),
+            buffered_signed_blocks: Default::default(),
+            need_catchup: false,
+            catchup_task: None,
+            catchup_height: None,
+            snapshot_tip: None,
+            awaiting_state_sync: false,
+        };
+
+        tokio::spawn(async move {
+            da.start().await.unwrap();
+        });
+
+        // wait until it's up
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let mut client =
+            DataAvailabilityClient::connect("client_id", config.da_public_address.clone())
+                .await
+                .unwrap();
+
+        client
+            .send(DataAvailabilityRequest(BlockHeight(2)))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            client.recv().await,
+            Some(DataAvailabilityEvent::BlocksPruned {
+                requested: BlockHeight(2),
+                first_available: BlockHeight(6),
+            })
+        );
+
+        let mut heights_received = vec![];
+        while let Some(event) = client.recv().await {
+            if let DataAvailabilityEvent::SignedBlock(block) = event {
+                heights_received.push(block.height().0);
+            }
+            if heights_received.len() == 4 {
+                break;
+            }
+        }
+        assert_eq!(heights_received, vec![6, 7, 8, 9]);
+    }
+
     #[test_log::test(tokio::test)]
     async fn test_da_catchup() {
         let sender_global_bus = crate::bus::SharedMessageBus::new(
@@ -10,6 +10,8 @@ use crate::{
     model::{BlockHeight, Hashed, SignedBlock},
 };
 
+use super::storage::BlocksStorage;
+
 struct FjallHashKey(ConsensusProposalHash);
 struct FjallHeightKey([u8; 8]);
 struct FjallValue(Vec<u8>);
@@ -47,19 +49,23 @@ impl AsRef<[u8]> for FjallValue {
     }
 }
 
-pub struct Blocks {
+pub struct FjallBlocks {
     db: Keyspace,
     by_hash: PartitionHandle,
     by_height: PartitionHandle,
 }
 
-impl Blocks {
+impl FjallBlocks {
     fn decode_block(item: Slice) -> Result<SignedBlock> {
         borsh::from_slice(&item).map_err(Into::into)
     }
     fn decode_block_hash(item: Slice) -> Result<ConsensusProposalHash> {
         borsh::from_slice(&item).map_err(Into::into)
     }
+    fn decode_height(item: &Slice) -> Result<BlockHeight> {
+        let bytes: [u8; 8] = item.as_ref().try_into()?;
+        Ok(BlockHeight(u64::from_be_bytes(bytes)))
+    }
 
     pub fn new(path: &Path) -> Result<Self> {
         let db = Config::new(path)
@@ -83,24 +89,26 @@ impl Blocks {
 
         info!("{} block(s) available", by_hash.len()?);
 
-        Ok(Blocks {
+        Ok(FjallBlocks {
             db,
             by_hash,
             by_height,
         })
     }
+}
 
-    pub fn is_empty(&self) -> bool {
+impl BlocksStorage for FjallBlocks {
+    fn is_empty(&self) -> bool {
         self.by_hash.is_empty().unwrap_or(true)
     }
 
-    pub fn persist(&self) -> Result<()> {
+    fn persist(&self) -> Result<()> {
         self.db
             .persist(fjall::PersistMode::Buffer)
             .map_err(Into::into)
     }
 
-    pub fn put(&mut self, block: SignedBlock) -> Result<()> {
+    fn put(&mut self, block: SignedBlock) -> Result<()> {
         let block_hash = block.hashed();
         if self.contains(&block_hash) {
             return Ok(());
@@ -117,18 +125,18 @@ impl Blocks {
         Ok(())
     }
 
-    pub fn get(&self, block_hash: &ConsensusProposalHash) -> Result<Option<SignedBlock>> {
+    fn get(&self, block_hash: &ConsensusProposalHash) -> Result<Option<SignedBlock>> {
         let item = self.by_hash.get(FjallHashKey(block_hash.clone()))?;
         item.map(Self::decode_block).transpose()
     }
 
-    pub fn contains(&mut self, block: &ConsensusProposalHash) -> bool {
+    fn contains(&mut self, block: &ConsensusProposalHash) -> bool {
         self.by_hash
             .contains_key(FjallHashKey(block.clone()))
             .unwrap_or(false)
     }
 
-    pub fn last(&self) -> Option<SignedBlock> {
+    fn last(&self) -> Option<SignedBlock> {
         match self.by_height.last_key_value() {
             Ok(Some((_, v))) => {
                 let Ok(hash) = Self::decode_block_hash(v) else {
@@ -144,27 +152,55 @@ impl Blocks {
         }
     }
 
-    pub fn last_block_hash(&self) -> Option<ConsensusProposalHash> {
-        self.last().map(|b| b.hashed())
+    fn first_height(&self) -> Option<BlockHeight> {
+        match self.by_height.first_key_value() {
+            Ok(Some((k, _))) => Self::decode_height(&k).ok(),
+            Ok(None) => None,
+            Err(e) => {
+                error!("Error getting first block: {:?}", e);
+                None
+            }
+        }
     }
 
-    pub fn range(
+    fn get_hash_by_height(&self, height: BlockHeight) -> Result<Option<ConsensusProposalHash>> {
+        let item = self.by_height.get(FjallHeightKey::new(height))?;
+        item.map(Self::decode_block_hash).transpose()
+    }
+
+    fn range(
         &mut self,
         min: BlockHeight,
         max: BlockHeight,
-    ) -> impl Iterator<Item = Result<ConsensusProposalHash>> {
-        self.by_height
-            .range(FjallHeightKey::new(min)..FjallHeightKey::new(max))
-            .map_while(|maybe_item| match maybe_item {
-                Ok((_, v)) => Some(Self::decode_block_hash(v)),
-                Err(_) => None,
-            })
+    ) -> Box<dyn Iterator<Item = Result<ConsensusProposalHash>> + '_> {
+        Box::new(
+            self.by_height
+                .range(FjallHeightKey::new(min)..FjallHeightKey::new(max))
+                .map_while(|maybe_item| match maybe_item {
+                    Ok((_, v)) => Some(Self::decode_block_hash(v)),
+                    Err(_) => None,
+                }),
+        )
+    }
+
+    fn prune_below(&mut self, height: BlockHeight) -> Result<u64> {
+        let mut batch = self.db.batch();
+        let mut pruned = 0;
+        for item in self.by_height.range(..FjallHeightKey::new(height)) {
+            let (k, v) = item?;
+            let hash = Self::decode_block_hash(v)?;
+            batch.remove(&self.by_hash, FjallHashKey(hash).as_ref());
+            batch.remove(&self.by_height, k);
+            pruned += 1;
+        }
+        batch.commit()?;
+        Ok(pruned)
     }
 }
 
-impl Debug for Blocks {
+impl Debug for FjallBlocks {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Blocks")
+        f.debug_struct("FjallBlocks")
             .field("len", &self.by_height.len())
             .finish()
     }
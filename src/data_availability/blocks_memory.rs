@@ -1,4 +1,3 @@
-#![allow(unused)]
 use std::path::Path;
 
 use crate::{
@@ -7,29 +6,41 @@ use crate::{
 };
 use anyhow::Result;
 use indexmap::IndexMap;
-use tracing::{info, trace};
+use tracing::trace;
+
+use super::storage::BlocksStorage;
 
 #[derive(Debug)]
-pub struct Blocks {
+pub struct MemoryBlocks {
     data: IndexMap<ConsensusProposalHash, SignedBlock>,
 }
 
-impl Blocks {
+impl MemoryBlocks {
     pub fn new(_: &Path) -> Result<Self> {
         Ok(Self {
             data: IndexMap::new(),
         })
     }
 
-    pub fn is_empty(&self) -> bool {
+    /// Index of the first block with a height greater or equal to the given one.
+    /// Items are in order but we don't know where they are. Binary search.
+    fn position(&self, height: BlockHeight) -> usize {
+        self.data
+            .binary_search_by(|_, block| block.height().cmp(&height))
+            .unwrap_or_else(|idx| idx)
+    }
+}
+
+impl BlocksStorage for MemoryBlocks {
+    fn is_empty(&self) -> bool {
         self.data.is_empty()
     }
 
-    pub fn persist(&self) -> Result<()> {
+    fn persist(&self) -> Result<()> {
         Ok(())
     }
 
-    pub fn put(&mut self, data: SignedBlock) -> Result<()> {
+    fn put(&mut self, data: SignedBlock) -> Result<()> {
         let block_hash = data.hashed();
         if self.contains(&block_hash) {
             return Ok(());
@@ -39,43 +50,46 @@ impl Blocks {
         Ok(())
     }
 
-    pub fn get(&self, block_hash: &ConsensusProposalHash) -> Result<Option<SignedBlock>> {
+    fn get(&self, block_hash: &ConsensusProposalHash) -> Result<Option<SignedBlock>> {
         Ok(self.data.get(block_hash).cloned())
     }
 
-    pub fn contains(&mut self, block_hash: &ConsensusProposalHash) -> bool {
+    fn contains(&mut self, block_hash: &ConsensusProposalHash) -> bool {
         self.data.contains_key(block_hash)
     }
 
-    pub fn last(&self) -> Option<SignedBlock> {
+    fn get_hash_by_height(&self, height: BlockHeight) -> Result<Option<ConsensusProposalHash>> {
+        Ok(self
+            .data
+            .get_index(self.position(height))
+            .filter(|(_, block)| block.height() == height)
+            .map(|(hash, _)| hash.clone()))
+    }
+
+    fn last(&self) -> Option<SignedBlock> {
         self.data.last().map(|(_, block)| block.clone())
     }
 
-    pub fn last_block_hash(&self) -> Option<ConsensusProposalHash> {
-        self.last().map(|b| b.hashed())
+    fn first_height(&self) -> Option<BlockHeight> {
+        self.data.first().map(|(_, block)| block.height())
     }
 
-    pub fn range(
-        &self,
+    fn range(
+        &mut self,
         min: BlockHeight,
         max: BlockHeight,
     ) -> Box<dyn Iterator<Item = Result<ConsensusProposalHash>> + '_> {
-        // Items are in order but we don't know where they are. Binary search.
-        let Ok(min) = self
-            .data
-            .binary_search_by(|_, block| block.height().0.cmp(&min.0))
-        else {
+        let min = self.position(min);
+        let max = self.position(max);
+        let Some(iter) = self.data.get_range(min..max.max(min)) else {
             return Box::new(::std::iter::empty());
         };
-        let Ok(max) = self
-            .data
-            .binary_search_by(|_, block| block.height().0.cmp(&(max.0 - 1)))
-        else {
-            return Box::new(::std::iter::empty());
-        };
-        let Some(iter) = self.data.get_range(min..max + 1) else {
-            return Box::new(::std::iter::empty());
-        };
-        Box::new(iter.values().map(|block| Ok(block.hashed().clone())))
+        Box::new(iter.keys().map(|hash| Ok(hash.clone())))
+    }
+
+    fn prune_below(&mut self, height: BlockHeight) -> Result<u64> {
+        let idx = self.position(height);
+        self.data.drain(..idx);
+        Ok(idx as u64)
     }
 }
@@ -2,24 +2,49 @@ use std::collections::BTreeSet;
 
 use hyle_modules::{bus::SharedMessageBus, modules::Module};
 
-use crate::model::SharedRunContext;
+use crate::{
+    model::{BlockHeight, ConsensusProposalHash},
+    state_sync::should_sync_state,
+};
 
-use super::{blocks_fjall::Blocks, d_a_bus_client::DABusClient, DataAvailability};
+use super::{
+    d_a_bus_client::DABusClient,
+    storage::{open_blocks_storage, BlocksStorage},
+    DataAvailability, DataAvailabilityCtx, SNAPSHOT_TIP_FILE,
+};
 
 impl Module for DataAvailability {
-    type Context = SharedRunContext;
+    type Context = DataAvailabilityCtx;
 
     async fn build(bus: SharedMessageBus, ctx: Self::Context) -> anyhow::Result<Self> {
+        let DataAvailabilityCtx {
+            common: ctx,
+            blocks,
+        } = ctx;
         let bus = DABusClient::new_from_bus(bus.new_handle()).await;
+        let snapshot_tip: Option<(BlockHeight, ConsensusProposalHash)> =
+            Self::load_from_disk(&ctx.config.data_directory.join(SNAPSHOT_TIP_FILE));
+
+        let blocks: Box<dyn BlocksStorage> = match blocks {
+            Some(blocks) => blocks,
+            None => open_blocks_storage(
+                &ctx.config.da_storage,
+                &ctx.config.data_directory.join("data_availability.db"),
+            )?,
+        };
 
         Ok(DataAvailability {
             config: ctx.config.clone(),
             bus,
-            blocks: Blocks::new(&ctx.config.data_directory.join("data_availability.db"))?,
+            awaiting_state_sync: should_sync_state(&ctx.config)
+                && blocks.is_empty()
+                && snapshot_tip.is_none(),
+            blocks,
             buffered_signed_blocks: BTreeSet::new(),
             need_catchup: false,
             catchup_task: None,
             catchup_height: None,
+            snapshot_tip,
         })
     }
 
@@ -0,0 +1,149 @@
+use std::{collections::BTreeMap, fmt::Debug, path::Path, sync::Mutex, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use tracing::info;
+
+use crate::{
+    model::{BlockHeight, ConsensusProposalHash, Hashed, SignedBlock},
+    utils::conf::{DaStorageBackend, DaStorageConf},
+};
+
+use super::blocks_fjall::FjallBlocks;
+
+/// Storage backend for the blocks served by the DataAvailability module.
+///
+/// Blocks are persisted in a fjall keyspace by default. Other backends are registered with
+/// [`register_blocks_storage`], and selected in the configuration by their name.
+///
+/// Blocks are stored by hash and indexed by height. Backends may drop old blocks
+/// through [`BlocksStorage::prune_below`], in which case they must keep reporting
+/// the lowest retained height through [`BlocksStorage::first_height`].
+pub trait BlocksStorage: Debug + Send + Sync {
+    fn is_empty(&self) -> bool;
+    fn persist(&self) -> Result<()>;
+
+    fn put(&mut self, block: SignedBlock) -> Result<()>;
+    fn get(&self, block_hash: &ConsensusProposalHash) -> Result<Option<SignedBlock>>;
+    fn contains(&mut self, block_hash: &ConsensusProposalHash) -> bool;
+
+    /// Hash of the block stored at the given height, if retained
+    fn get_hash_by_height(&self, height: BlockHeight) -> Result<Option<ConsensusProposalHash>>;
+    fn last(&self) -> Option<SignedBlock>;
+    /// Lowest height still stored, None if storage is empty
+    fn first_height(&self) -> Option<BlockHeight>;
+
+    /// Hashes of the blocks in [min, max), ordered by height
+    fn range(
+        &mut self,
+        min: BlockHeight,
+        max: BlockHeight,
+    ) -> Box<dyn Iterator<Item = Result<ConsensusProposalHash>> + '_>;
+
+    /// Removes all blocks strictly below the given height, returns the number of removed blocks
+    fn prune_below(&mut self, height: BlockHeight) -> Result<u64>;
+
+    fn last_block_hash(&self) -> Option<ConsensusProposalHash> {
+        self.last().map(|b| b.hashed())
+    }
+
+    /// Prunes blocks that fall out of the retention policy.
+    /// The latest block is always kept so the chain can be extended.
+    /// Returns the new retained floor if anything was pruned.
+    fn apply_retention(&mut self, conf: &DaStorageConf) -> Result<Option<BlockHeight>> {
+        let (Some(first), Some(last)) = (self.first_height(), self.last()) else {
+            return Ok(None);
+        };
+        let mut floor = first;
+
+        if conf.keep_last_blocks > 0 {
+            let height = BlockHeight((last.height().0 + 1).saturating_sub(conf.keep_last_blocks));
+            floor = floor.max(height);
+        }
+
+        if conf.keep_blocks_newer_than_secs > 0 {
+            // Age is measured against the latest block rather than the wall clock,
+            // so a node replaying old blocks does not prune everything it receives.
+            let max_age = Duration::from_secs(conf.keep_blocks_newer_than_secs).as_millis();
+            let cutoff = last.consensus_proposal.timestamp.0.saturating_sub(max_age);
+            let mut height = floor;
+            while height < last.height() {
+                let Some(hash) = self.get_hash_by_height(height)? else {
+                    height = height + 1;
+                    continue;
+                };
+                match self.get(&hash)? {
+                    Some(block) if block.consensus_proposal.timestamp.0 < cutoff => {
+                        height = height + 1;
+                    }
+                    _ => break,
+                }
+            }
+            floor = floor.max(height);
+        }
+
+        if floor <= first {
+            return Ok(None);
+        }
+
+        let pruned = self.prune_below(floor)?;
+        info!(
+            "🧹 Pruned {} block(s) below height {} from DA storage",
+            pruned, floor
+        );
+        Ok(Some(floor))
+    }
+}
+
+/// Opens a blocks storage backend at the given path
+pub type BlocksStorageFactory = fn(&Path) -> Result<Box<dyn BlocksStorage>>;
+
+static CUSTOM_BACKENDS: Mutex<BTreeMap<String, BlocksStorageFactory>> = Mutex::new(BTreeMap::new());
+
+/// Registers a blocks storage backend, that the configuration selects with
+/// `backend = { Custom = "<name>" }`. Must be called before the DataAvailability module is built.
+pub fn register_blocks_storage(name: &str, factory: BlocksStorageFactory) {
+    if let Ok(mut backends) = CUSTOM_BACKENDS.lock() {
+        backends.insert(name.to_string(), factory);
+    }
+}
+
+/// Opens the blocks storage backend selected in the configuration.
+pub fn open_blocks_storage(conf: &DaStorageConf, path: &Path) -> Result<Box<dyn BlocksStorage>> {
+    match &conf.backend {
+        DaStorageBackend::Fjall => Ok(Box::new(FjallBlocks::new(path)?)),
+        DaStorageBackend::Custom(name) => {
+            let factory = CUSTOM_BACKENDS
+                .lock()
+                .map_err(|_| anyhow!("DA storage backends registry is poisoned"))?
+                .get(name)
+                .copied()
+                .with_context(|| format!("Unknown DA storage backend {name}, it must be registered with register_blocks_storage"))?;
+            factory(path).with_context(|| format!("Opening DA storage backend {name}"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_availability::blocks_memory::MemoryBlocks;
+
+    #[test]
+    fn test_open_configured_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let conf = |backend| DaStorageConf {
+            backend,
+            ..Default::default()
+        };
+
+        let custom = conf(DaStorageBackend::Custom("test-memory".to_string()));
+        assert!(open_blocks_storage(&custom, dir.path()).is_err());
+
+        register_blocks_storage("test-memory", |path| Ok(Box::new(MemoryBlocks::new(path)?)));
+        let blocks = open_blocks_storage(&custom, dir.path()).unwrap();
+        assert!(blocks.is_empty());
+
+        let fjall = open_blocks_storage(&conf(DaStorageBackend::Fjall), &dir.path().join("db"));
+        assert!(fjall.unwrap().is_empty());
+    }
+}
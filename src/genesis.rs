@@ -1,5 +1,9 @@
 use std::collections::{BTreeMap, HashMap};
 
+mod file;
+
+pub use file::{GenesisAllocation, GenesisContract, GenesisFile};
+
 use crate::{model::*, p2p::network::PeerEvent, utils::conf::SharedConf};
 use anyhow::{Error, Result};
 use client_sdk::{
@@ -48,6 +52,12 @@ struct GenesisBusClient {
 }
 
 type PeerPublicKeyMap = BTreeMap<String, ValidatorPublicKey>;
+/// Program ids of the genesis contracts, their registration transactions and their executor
+type GenesisContractsTxs = (
+    BTreeMap<ContractName, ProgramId>,
+    Vec<Transaction>,
+    TxExecutor<States>,
+);
 
 pub struct Genesis {
     config: SharedConf,
@@ -107,8 +117,16 @@ impl Genesis {
 
     pub async fn do_genesis(&mut self) -> Result<()> {
         let single_node = self.config.consensus.solo;
+        let genesis_file = GenesisFile::from_conf(&self.config.genesis)?;
+        let stakers = genesis_file.stakers(&self.config.genesis);
         // Unless we're in single node mode, we must be a genesis staker to start the network.
-        if !single_node && !self.config.genesis.stakers.contains_key(&self.config.id) {
+        if single_node && !stakers.contains_key(&self.config.id) {
+            anyhow::bail!(
+                "Single node {} is not a staker of the genesis file",
+                self.config.id
+            );
+        }
+        if !single_node && !stakers.contains_key(&self.config.id) {
             info!("📡 Not a genesis staker, need to catchup from peers.");
             _ = self.bus.send(GenesisEvent::NoGenesis {});
             return Ok(());
@@ -124,18 +142,18 @@ impl Genesis {
 
         // Wait until we've connected with all other genesis peers.
         // (We've already checked we're part of the stakers, so if we're alone carry on).
-        if !single_node && self.config.genesis.stakers.len() > 1 {
+        if !single_node && stakers.len() > 1 {
             info!("🌱 Waiting on other genesis peers to join");
             handle_messages! {
                 on_bus self.bus,
                 listen<PeerEvent> msg => {
                     match msg {
                         PeerEvent::NewPeer { name, pubkey, height, .. } => {
-                            if !self.config.genesis.stakers.contains_key(&name) {
+                            if !stakers.contains_key(&name) {
                                 continue;
                             }
 
-                            if self.config.genesis.stakers.contains_key(&name) && height.0 > 0 {
+                            if stakers.contains_key(&name) && height.0 > 0 {
                                 info!("🌱 Peer {}({}) has height {}, skipping genesis", &name, &pubkey, height.0);
                                 _ = self.bus.send(GenesisEvent::NoGenesis {});
                                 return Ok(());
@@ -146,11 +164,11 @@ impl Genesis {
                             self.peer_pubkey.insert(name.clone(), pubkey.clone());
 
                             // Once we know everyone in the initial quorum, craft & process the genesis block.
-                            if self.peer_pubkey.len() == self.config.genesis.stakers.len() {
+                            if self.peer_pubkey.len() == stakers.len() {
                                 info!("🌱 All genesis peers joined, creating genesis block");
                                 break;
                             } else {
-                                info!("🌱 Waiting for {} more peers to join genesis", self.config.genesis.stakers.len() - self.peer_pubkey.len());
+                                info!("🌱 Waiting for {} more peers to join genesis", stakers.len() - self.peer_pubkey.len());
                             }
                         }
                     }
@@ -162,7 +180,7 @@ impl Genesis {
         initial_validators.sort();
 
         let genesis_txs = match self
-            .generate_genesis_txs(&self.peer_pubkey, &self.config.genesis.stakers)
+            .generate_genesis_txs(&self.peer_pubkey, &stakers, &genesis_file)
             .await
         {
             Ok(t) => t,
@@ -173,6 +191,7 @@ impl Genesis {
         };
 
         let signed_block = self.make_genesis_block(genesis_txs, initial_validators);
+        info!("🌱 Genesis block hash {}", signed_block.hashed());
 
         // At this point, we can setup the genesis block.
         _ = self.bus.send(GenesisEvent::GenesisBlock(signed_block));
@@ -184,8 +203,10 @@ impl Genesis {
         &self,
         peer_pubkey: &PeerPublicKeyMap,
         genesis_stake: &HashMap<String, u64>,
+        genesis_file: &GenesisFile,
     ) -> Result<Vec<Transaction>> {
-        let (contract_program_ids, mut genesis_txs, mut tx_executor) = self.genesis_contracts_txs();
+        let (contract_program_ids, mut genesis_txs, mut tx_executor) =
+            self.genesis_contracts_txs(&genesis_file.contracts)?;
 
         let register_txs = self
             .generate_register_txs(peer_pubkey, &mut tx_executor)
@@ -198,6 +219,9 @@ impl Genesis {
         let stake_txs =
             Self::generate_stake_txs(peer_pubkey, &mut tx_executor, genesis_stake).await?;
 
+        let allocation_txs =
+            Self::generate_allocation_txs(&mut tx_executor, &genesis_file.allocations)?;
+
         let token_txs = if self.config.genesis.keep_tokens_in_faucet {
             vec![]
         } else {
@@ -208,6 +232,7 @@ impl Genesis {
             .into_iter()
             .chain(faucet_txs.into_iter())
             .chain(stake_txs.into_iter())
+            .chain(allocation_txs.into_iter())
             .chain(token_txs.into_iter());
 
         for ProofTxBuilder {
@@ -402,6 +427,41 @@ impl Genesis {
         Ok(txs)
     }
 
+    fn generate_allocation_txs(
+        tx_executor: &mut TxExecutor<States>,
+        allocations: &[GenesisAllocation],
+    ) -> Result<Vec<ProofTxBuilder>> {
+        let mut txs = vec![];
+        for allocation in allocations {
+            info!(
+                "🌱  Allocating {} hyllar to {}",
+                allocation.amount, allocation.identity
+            );
+
+            let mut transaction = ProvableBlobTx::new(Identity::new(FAUCET_ID));
+
+            // Verify identity
+            verify_identity(
+                &mut transaction,
+                ContractName::new("hydentity"),
+                &tx_executor.hydentity,
+                "password".to_string(),
+            )?;
+
+            // Transfer
+            transfer(
+                &mut transaction,
+                ContractName::new("hyllar"),
+                allocation.identity.0.clone(),
+                allocation.amount as u128,
+            )?;
+
+            txs.push(tx_executor.process(transaction)?);
+        }
+
+        Ok(txs)
+    }
+
     // Needs to run last
     fn generate_token_txs(tx_executor: &mut TxExecutor<States>) -> Result<Vec<ProofTxBuilder>> {
         let mut txs: Vec<ProofTxBuilder> = vec![];
@@ -503,11 +563,8 @@ impl Genesis {
 
     fn genesis_contracts_txs(
         &self,
-    ) -> (
-        BTreeMap<ContractName, ProgramId>,
-        Vec<Transaction>,
-        TxExecutor<States>,
-    ) {
+        extra_contracts: &[GenesisContract],
+    ) -> Result<GenesisContractsTxs> {
         let staking_program_id = hyle_contracts::STAKING_ID.to_vec();
         let hyllar_program_id = hyle_contracts::HYLLAR_ID.to_vec();
         let smt_token_program_id = hyle_contracts::SMT_TOKEN_ID.to_vec();
@@ -647,9 +704,27 @@ impl Genesis {
         )
         .expect("register risc0-recursion");
 
+        for contract in extra_contracts {
+            if map.contains_key(&contract.name) {
+                anyhow::bail!("Genesis contract {} is a builtin contract", contract.name);
+            }
+            info!("🌱 Registering contract {}", contract.name);
+            let program_id = contract.program_id()?;
+            register_hyle_contract(
+                &mut register_tx,
+                contract.name.clone(),
+                contract.verifier.clone(),
+                program_id.clone(),
+                contract.state_commitment()?,
+                contract.timeout_window(),
+                None,
+            )?;
+            map.insert(contract.name.clone(), program_id);
+        }
+
         let genesis_tx: BlobTransaction = register_tx.into();
 
-        (map, vec![genesis_tx.into()], ctx)
+        Ok((map, vec![genesis_tx.into()], ctx))
     }
 
     fn make_genesis_block(
@@ -781,6 +856,67 @@ mod tests {
         }
     }
 
+    #[test_log::test(tokio::test)]
+    async fn test_genesis_from_file() {
+        let tmpdir = tempfile::Builder::new().tempdir().unwrap();
+        let genesis_path = tmpdir.path().join("genesis.toml");
+        std::fs::write(
+            &genesis_path,
+            r#"
+            [stakers]
+            single-node = 500
+
+            [[contracts]]
+            name = "my-token"
+            verifier = "risc0-1"
+            program_id = "0a1b"
+            timeout_window = 10
+
+            [[allocations]]
+            identity = "alice@hydentity"
+            amount = 5000
+            "#,
+        )
+        .unwrap();
+
+        let mut config =
+            Conf::new(vec![], tmpdir.path().to_str().map(|s| s.to_owned()), None).unwrap();
+        config.id = "single-node".to_string();
+        config.consensus.solo = true;
+        config.genesis.file = Some(genesis_path);
+
+        let block = |config: Conf| async move {
+            let (mut genesis, mut bus) = new(config).await;
+            genesis.start().await.unwrap();
+            match bus.try_recv().expect("recv") {
+                GenesisEvent::GenesisBlock(signed_block) => signed_block,
+                GenesisEvent::NoGenesis => panic!("expected a genesis block"),
+            }
+        };
+        let signed_block = block(config.clone()).await;
+
+        let txs = signed_block
+            .iter_txs_with_id()
+            .map(|(_, _, tx)| tx)
+            .collect::<Vec<_>>();
+        let TransactionData::Blob(register_tx) = &txs.first().unwrap().transaction_data else {
+            panic!("expected the register transaction first");
+        };
+        assert!(register_tx.blobs.iter().any(|blob| {
+            StructuredBlobData::<RegisterContractAction>::try_from(blob.data.clone())
+                .is_ok_and(|data| data.parameters.contract_name == "my-token".into())
+        }));
+        assert!(txs.iter().any(|tx| matches!(
+            &tx.transaction_data,
+            TransactionData::Blob(blob_tx)
+                if blob_tx.blobs.iter().any(|blob| blob.contract_name == "hyllar".into())
+                    && blob_tx.identity == FAUCET_ID.into()
+        )));
+
+        // Every validator derives the same block from the same file
+        assert_eq!(block(config).await.hashed(), signed_block.hashed());
+    }
+
     #[test_log::test(tokio::test)]
     async fn test_genesis_as_leader() {
         let tmpdir = tempfile::Builder::new().tempdir().unwrap();
@@ -0,0 +1,232 @@
+//! Declarative description of the genesis block, loaded from `genesis.file`.
+//!
+//! ```toml
+//! [stakers]
+//! node-1 = 1000
+//! node-2 = 1000
+//!
+//! [[contracts]]
+//! name = "my-token"
+//! verifier = "risc0-1"
+//! program_id = "0a1b..."
+//! state_commitment = "00"
+//! timeout_window = 100
+//!
+//! [[allocations]]
+//! identity = "alice@hydentity"
+//! amount = 5000
+//! ```
+//!
+//! Every genesis validator must load the same file: the genesis transactions are derived
+//! from it in file order, so identical files produce identical genesis blocks.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use hyle_contract_sdk::{ContractName, Identity, ProgramId, StateCommitment};
+use serde::{Deserialize, Serialize};
+
+use crate::{model::*, utils::conf::GenesisConf};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenesisFile {
+    /// Contracts registered in the genesis block, on top of the builtin ones
+    #[serde(default)]
+    pub contracts: Vec<GenesisContract>,
+    /// Hyllar transferred from the faucet to identities
+    #[serde(default)]
+    pub allocations: Vec<GenesisAllocation>,
+    /// Initial bonded stakers and their stakes, replacing `genesis.stakers` if not empty
+    #[serde(default)]
+    pub stakers: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenesisContract {
+    pub name: ContractName,
+    pub verifier: Verifier,
+    /// Hex encoded program id
+    pub program_id: String,
+    /// Hex encoded initial state commitment
+    #[serde(default)]
+    pub state_commitment: String,
+    /// Number of blocks before unsettled transactions time out, 0 for no timeout.
+    /// The network default applies if unset.
+    pub timeout_window: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GenesisAllocation {
+    pub identity: Identity,
+    pub amount: u64,
+}
+
+impl GenesisContract {
+    pub fn program_id(&self) -> Result<ProgramId> {
+        Ok(ProgramId(hex::decode(&self.program_id).with_context(
+            || format!("Invalid program id for contract {}", self.name),
+        )?))
+    }
+
+    pub fn state_commitment(&self) -> Result<StateCommitment> {
+        Ok(StateCommitment(
+            hex::decode(&self.state_commitment)
+                .with_context(|| format!("Invalid state commitment for contract {}", self.name))?,
+        ))
+    }
+
+    pub fn timeout_window(&self) -> Option<TimeoutWindow> {
+        self.timeout_window.map(|blocks| match blocks {
+            0 => TimeoutWindow::NoTimeout,
+            blocks => TimeoutWindow::Timeout(BlockHeight(blocks)),
+        })
+    }
+}
+
+impl GenesisFile {
+    /// Loads the genesis file configured, if any.
+    pub fn from_conf(conf: &GenesisConf) -> Result<Self> {
+        match &conf.file {
+            Some(path) => {
+                Self::load(path).with_context(|| format!("Loading genesis file {}", path.display()))
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Parses a TOML file, or a JSON file if it has a `.json` extension, and validates it.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let file: Self = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&content)?,
+            _ => toml::from_str(&content)?,
+        };
+        file.validate()?;
+        Ok(file)
+    }
+
+    /// Stakers of the genesis, from the file or from the configuration.
+    pub fn stakers(&self, conf: &GenesisConf) -> HashMap<String, u64> {
+        match self.stakers.is_empty() {
+            true => conf.stakers.clone(),
+            false => self.stakers.clone().into_iter().collect(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let mut names = BTreeSet::new();
+        for contract in &self.contracts {
+            if contract.name.0.is_empty() {
+                bail!("Genesis contract with an empty name");
+            }
+            if !names.insert(&contract.name) {
+                bail!("Genesis contract {} is registered twice", contract.name);
+            }
+            if contract.verifier.0.is_empty() {
+                bail!("Genesis contract {} has no verifier", contract.name);
+            }
+            if contract.program_id()?.0.is_empty() {
+                bail!("Genesis contract {} has no program id", contract.name);
+            }
+            contract.state_commitment()?;
+        }
+
+        let mut total: u128 = 0;
+        for allocation in &self.allocations {
+            if !allocation.identity.0.contains('@') {
+                bail!(
+                    "Genesis allocation to {} is not a valid identity",
+                    allocation.identity
+                );
+            }
+            if allocation.amount == 0 {
+                bail!("Genesis allocation to {} is empty", allocation.identity);
+            }
+            total += allocation.amount as u128;
+        }
+        if total > hyllar::TOTAL_SUPPLY {
+            bail!(
+                "Genesis allocations total {total}, more than the supply of {}",
+                hyllar::TOTAL_SUPPLY
+            );
+        }
+
+        if let Some((staker, _)) = self.stakers.iter().find(|(_, stake)| **stake == 0) {
+            bail!("Genesis staker {staker} has no stake");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GENESIS: &str = r#"
+        [stakers]
+        node-1 = 1000
+
+        [[contracts]]
+        name = "my-token"
+        verifier = "risc0-1"
+        program_id = "0a1b"
+        timeout_window = 0
+
+        [[allocations]]
+        identity = "alice@hydentity"
+        amount = 5000
+    "#;
+
+    #[test]
+    fn test_parse_genesis_file() {
+        let file: GenesisFile = toml::from_str(GENESIS).unwrap();
+        file.validate().unwrap();
+
+        let contract = file.contracts.first().unwrap();
+        assert_eq!(contract.program_id().unwrap(), ProgramId(vec![10, 27]));
+        assert_eq!(
+            contract.state_commitment().unwrap(),
+            StateCommitment::default()
+        );
+        assert_eq!(contract.timeout_window(), Some(TimeoutWindow::NoTimeout));
+        assert_eq!(
+            file.stakers(&GenesisConf::default()),
+            [("node-1".to_string(), 1000)].into_iter().collect()
+        );
+
+        let json = serde_json::to_string(&file).unwrap();
+        assert_eq!(serde_json::from_str::<GenesisFile>(&json).unwrap(), file);
+    }
+
+    #[test]
+    fn test_invalid_genesis_file() {
+        let file: GenesisFile = toml::from_str(GENESIS).unwrap();
+
+        let mut duplicate = file.clone();
+        duplicate.contracts.extend(file.contracts.clone());
+        assert!(duplicate.validate().is_err());
+
+        let mut bad_program_id = file.clone();
+        bad_program_id.contracts.first_mut().unwrap().program_id = "not hex".into();
+        assert!(bad_program_id.validate().is_err());
+
+        let mut too_much = file.clone();
+        too_much.allocations.push(GenesisAllocation {
+            identity: "bob@hydentity".into(),
+            amount: u64::MAX,
+        });
+        too_much.allocations.push(GenesisAllocation {
+            identity: "carol@hydentity".into(),
+            amount: u64::MAX,
+        });
+        assert!(too_much.validate().is_err());
+
+        assert!(toml::from_str::<GenesisFile>("unknown = 1").is_err());
+    }
+}
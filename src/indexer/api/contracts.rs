@@ -1,13 +1,16 @@
-use super::{IndexerApiState, TxHashDb};
+use super::{
+    decode_cursor, encode_cursor, next_cursor_headers, page_size, IndexerApiState, TxHashDb,
+};
 use api::{APIContract, APIContractState};
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
     Json,
 };
 
 use crate::model::*;
 use hyle_modules::log_error;
+use sqlx::{Postgres, QueryBuilder};
 
 #[derive(sqlx::FromRow, Debug)]
 pub struct ContractDb {
@@ -23,6 +26,12 @@ pub struct ContractDb {
     #[sqlx(try_from = "i64")]
     pub unsettled_tx: u64, // Total number of unsettled transactions
     pub earliest_unsettled: Option<i64>, // Block height of the earliest unsettled transaction
+    pub pending_program_id: Option<Vec<u8>>, // Scheduled program id change
+    pub pending_program_id_activation: Option<i64>, // Block height the change activates at
+    pub min_program_id_update_delay: Option<i64>, // Minimum notice of program id changes, in blocks
+    pub owner: Option<String>, // Identity managing the contract through the hyle TLD
+    pub name_service: Option<sqlx::types::Json<NameServiceConfig>>, // Lease terms of a name service TLD
+    pub expiry_height: Option<i64>, // End of the lease of a leased name
 }
 
 impl From<ContractDb> for APIContract {
@@ -36,6 +45,19 @@ impl From<ContractDb> for APIContract {
             total_tx: val.total_tx,
             unsettled_tx: val.unsettled_tx,
             earliest_unsettled: val.earliest_unsettled.map(|a| BlockHeight(a as u64)),
+            pending_program_id: val
+                .pending_program_id
+                .zip(val.pending_program_id_activation)
+                .map(|(program_id, activation_height)| PendingProgramIdUpdate {
+                    program_id: ProgramId(program_id),
+                    activation_height: BlockHeight(activation_height as u64),
+                }),
+            min_program_id_update_delay: val
+                .min_program_id_update_delay
+                .map(|delay| BlockHeight(delay as u64)),
+            owner: val.owner.map(Identity),
+            name_service: val.name_service.map(|config| config.0),
+            expiry_height: val.expiry_height.map(|height| BlockHeight(height as u64)),
         }
     }
 }
@@ -57,20 +79,52 @@ impl From<ContractStateDb> for APIContractState {
         }
     }
 }
+#[derive(Debug, serde::Deserialize)]
+pub struct ContractPagination {
+    pub nb_results: Option<i64>,
+    /// Cursor returned in the `x-next-cursor` header of the previous page
+    pub cursor: Option<String>,
+    /// Only list the contracts owned by this identity
+    pub owner: Option<String>,
+}
+
 #[utoipa::path(
     get,
     tag = "Indexer",
     path = "/contracts",
+    params(
+        ("nb_results" = Option<i64>, Query, description = "Page size, at most 100. All contracts are returned if neither this nor a cursor is set"),
+        ("cursor" = Option<String>, Query, description = "Cursor returned by the previous page"),
+        ("owner" = Option<String>, Query, description = "Only list the contracts owned by this identity"),
+    ),
     responses(
-        (status = OK, body = [APIContract])
+        (status = OK, body = [APIContract], headers(
+            ("x-next-cursor" = String, description = "Cursor of the next page, if any")
+        ))
     )
 )]
 pub async fn list_contracts(
+    Query(pagination): Query<ContractPagination>,
     State(state): State<IndexerApiState>,
-) -> Result<Json<Vec<APIContract>>, StatusCode> {
-    let contract = log_error!(
-        sqlx::query_as::<_, ContractDb>(
-            r#"
+) -> Result<(HeaderMap, Json<Vec<APIContract>>), StatusCode> {
+    // Contracts are listed by name, which is the sort key of the cursor
+    let cursor = pagination
+        .cursor
+        .as_deref()
+        .map(|cursor| {
+            decode_cursor(cursor)
+                .map(|[contract_name]| contract_name)
+                .ok_or(StatusCode::BAD_REQUEST)
+        })
+        .transpose()?;
+    let nb_results = match (pagination.nb_results, &cursor) {
+        (Some(nb_results), _) => Some(page_size(Some(nb_results))),
+        (None, Some(_)) => Some(10),
+        (None, None) => None,
+    };
+
+    let mut query = QueryBuilder::<Postgres>::new(
+        r#"
         SELECT
           c.*,
           COUNT(t.*)                             			          AS total_tx,
@@ -94,17 +148,41 @@ pub async fn list_contracts(
         LEFT JOIN transactions AS t
           ON t.parent_dp_hash = tx_c.parent_dp_hash
          AND t.tx_hash       = tx_c.tx_hash
-        GROUP BY c.contract_name;
-"#
-        )
-        .fetch_all(&state.db)
-        .await
-        .map(|db| db.into_iter().map(Into::<APIContract>::into).collect()),
+        WHERE TRUE"#,
+    );
+    if let Some(cursor) = cursor {
+        query.push(" AND c.contract_name > ").push_bind(cursor);
+    }
+    if let Some(owner) = pagination.owner {
+        query.push(" AND c.owner = ").push_bind(owner);
+    }
+    query.push(" GROUP BY c.contract_name ORDER BY c.contract_name");
+    if let Some(nb_results) = nb_results {
+        // One more row to know whether there is a next page
+        query.push(" LIMIT ").push_bind(nb_results + 1);
+    }
+
+    let mut contracts: Vec<APIContract> = log_error!(
+        query
+            .build_query_as::<ContractDb>()
+            .fetch_all(&state.db)
+            .await
+            .map(|db| db.into_iter().map(Into::<APIContract>::into).collect()),
         "Failed to fetch contracts"
     )
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    Ok(Json(contract))
+    let mut next_cursor = None;
+    if let Some(nb_results) = nb_results {
+        if contracts.len() as i64 > nb_results {
+            contracts.truncate(nb_results.max(0) as usize);
+            next_cursor = contracts
+                .last()
+                .map(|contract| encode_cursor(&[&contract.contract_name]));
+        }
+    }
+
+    Ok((next_cursor_headers(next_cursor), Json(contracts)))
 }
 
 #[utoipa::path(
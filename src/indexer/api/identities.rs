@@ -0,0 +1,223 @@
+use super::{
+    next_cursor_headers, page_size, tx_page, BlockPagination, IndexerApiState, TransactionDb,
+    TxCursor, TX_CURSOR_COLUMNS, TX_CURSOR_ORDER,
+};
+use api::{APIIdentity, APIIdentityBalance, APIIdentityContract, APITransaction};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use sqlx::{FromRow, Postgres, QueryBuilder};
+
+use crate::model::*;
+use hyle_modules::log_error;
+
+#[derive(sqlx::FromRow, Debug)]
+pub struct IdentityDb {
+    #[sqlx(try_from = "i64")]
+    pub total_tx: u64, // Total number of blob transactions sent by the identity
+    #[sqlx(try_from = "i64")]
+    pub settled_tx: u64, // Number of successfully settled transactions
+    #[sqlx(try_from = "i64")]
+    pub contracts: u64, // Number of contracts the identity interacted with
+    pub last_block_height: Option<i32>, // Block height of the latest transaction
+}
+
+#[derive(sqlx::FromRow, Debug)]
+pub struct IdentityContractDb {
+    pub contract_name: String, // Contract name
+    #[sqlx(try_from = "i64")]
+    pub total_tx: u64, // Number of transactions with blobs for the contract
+    pub last_block_height: Option<i32>, // Block height of the latest of those transactions
+}
+
+impl From<IdentityContractDb> for APIIdentityContract {
+    fn from(val: IdentityContractDb) -> Self {
+        APIIdentityContract {
+            contract_name: val.contract_name,
+            total_tx: val.total_tx,
+            last_block_height: val.last_block_height.map(|h| BlockHeight(h as u64)),
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    tag = "Indexer",
+    params(
+        ("identity" = String, Path, description = "Identity"),
+    ),
+    path = "/identity/{identity}",
+    responses(
+        (status = OK, body = APIIdentity)
+    )
+)]
+pub async fn get_identity(
+    Path(identity): Path<String>,
+    State(state): State<IndexerApiState>,
+) -> Result<Json<APIIdentity>, StatusCode> {
+    let summary = log_error!(
+        sqlx::query_as::<_, IdentityDb>(
+            r#"
+        SELECT
+          COUNT(*)                                                  AS total_tx,
+          COUNT(*) FILTER (WHERE t.transaction_status = 'success')  AS settled_tx,
+          MAX(t.block_height)                                       AS last_block_height,
+          (
+            SELECT COUNT(DISTINCT b.contract_name)
+            FROM transactions t2
+            JOIN blobs b ON b.parent_dp_hash = t2.parent_dp_hash AND b.tx_hash = t2.tx_hash
+            WHERE t2.identity = $1
+          ) AS contracts
+        FROM transactions t
+        WHERE t.identity = $1
+        "#,
+        )
+        .bind(identity.clone())
+        .fetch_one(&state.db)
+        .await,
+        "Failed to fetch identity"
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if summary.total_tx == 0 {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    Ok(Json(APIIdentity {
+        identity,
+        total_tx: summary.total_tx,
+        settled_tx: summary.settled_tx,
+        contracts: summary.contracts,
+        last_block_height: summary.last_block_height.map(|h| BlockHeight(h as u64)),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    tag = "Indexer",
+    params(
+        ("identity" = String, Path, description = "Identity"),
+        ("nb_results" = Option<i64>, Query, description = "Page size, at most 100"),
+        ("cursor" = Option<String>, Query, description = "Cursor returned by the previous page"),
+    ),
+    path = "/identity/{identity}/transactions",
+    responses(
+        (status = OK, body = [APITransaction], headers(
+            ("x-next-cursor" = String, description = "Cursor of the next page, if any")
+        ))
+    )
+)]
+pub async fn get_identity_transactions(
+    Path(identity): Path<String>,
+    Query(pagination): Query<BlockPagination>,
+    State(state): State<IndexerApiState>,
+) -> Result<(HeaderMap, Json<Vec<APITransaction>>), StatusCode> {
+    let nb_results = page_size(pagination.nb_results);
+    let cursor = TxCursor::from_param(&pagination.cursor)?;
+
+    let mut query = QueryBuilder::<Postgres>::new(format!(
+        r#"
+        SELECT t.*, b.timestamp, {TX_CURSOR_COLUMNS}
+        FROM transactions t
+        LEFT JOIN blocks b ON t.block_hash = b.hash
+        WHERE t.identity = "#
+    ));
+    query.push_bind(identity);
+    if let Some(cursor) = cursor {
+        cursor.push_filter(&mut query);
+    }
+    query
+        .push(TX_CURSOR_ORDER)
+        .push(" LIMIT ")
+        // One more row to know whether there is a next page
+        .push_bind(nb_results + 1);
+
+    let rows = log_error!(
+        query.build().fetch_all(&state.db).await,
+        "Failed to fetch identity transactions"
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let (transactions, next_cursor) = log_error!(
+        tx_page(&rows, nb_results, |row| TransactionDb::from_row(row)
+            .map(APITransaction::from)),
+        "Failed to parse identity transactions"
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok((next_cursor_headers(next_cursor), Json(transactions)))
+}
+
+#[utoipa::path(
+    get,
+    tag = "Indexer",
+    params(
+        ("identity" = String, Path, description = "Identity"),
+    ),
+    path = "/identity/{identity}/contracts",
+    responses(
+        (status = OK, body = [APIIdentityContract])
+    )
+)]
+pub async fn get_identity_contracts(
+    Path(identity): Path<String>,
+    State(state): State<IndexerApiState>,
+) -> Result<Json<Vec<APIIdentityContract>>, StatusCode> {
+    let contracts = log_error!(
+        sqlx::query_as::<_, IdentityContractDb>(
+            r#"
+        SELECT
+          b.contract_name,
+          COUNT(DISTINCT (t.parent_dp_hash, t.tx_hash)) AS total_tx,
+          MAX(t.block_height)                           AS last_block_height
+        FROM transactions t
+        JOIN blobs b ON b.parent_dp_hash = t.parent_dp_hash AND b.tx_hash = t.tx_hash
+        WHERE t.identity = $1
+        GROUP BY b.contract_name
+        ORDER BY b.contract_name
+        "#,
+        )
+        .bind(identity)
+        .fetch_all(&state.db)
+        .await
+        .map(|db| db
+            .into_iter()
+            .map(Into::<APIIdentityContract>::into)
+            .collect()),
+        "Failed to fetch identity contracts"
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(contracts))
+}
+
+#[utoipa::path(
+    get,
+    tag = "Indexer",
+    params(
+        ("identity" = String, Path, description = "Identity"),
+    ),
+    path = "/identity/{identity}/balances",
+    responses(
+        (status = OK, body = [APIIdentityBalance])
+    )
+)]
+pub async fn get_identity_balances(
+    Path(identity): Path<String>,
+    State(state): State<IndexerApiState>,
+) -> Json<Vec<APIIdentityBalance>> {
+    // Balances are read from the states of the indexed token contracts
+    let balances = state
+        .token_balances
+        .of(&identity.into())
+        .await
+        .into_iter()
+        .map(|(contract_name, balance)| APIIdentityBalance {
+            contract_name,
+            balance,
+        })
+        .collect();
+    Json(balances)
+}
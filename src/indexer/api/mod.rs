@@ -1,10 +1,131 @@
 use super::IndexerApiState;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use hyle_model::api::NEXT_CURSOR_HEADER;
+use sqlx::{postgres::PgRow, Postgres, QueryBuilder, Row};
 use utoipa::OpenApi;
 
 #[derive(Debug, serde::Deserialize)]
 pub struct BlockPagination {
     pub start_block: Option<i64>,
     pub nb_results: Option<i64>,
+    /// Cursor returned in the `x-next-cursor` header of the previous page
+    pub cursor: Option<String>,
+}
+
+/// Largest page returned by the paginated listings
+pub(super) const MAX_PAGE_SIZE: i64 = 100;
+
+/// Requested page size, 10 by default, clamped to 1..=MAX_PAGE_SIZE
+pub(super) fn page_size(nb_results: Option<i64>) -> i64 {
+    nb_results.unwrap_or(10).clamp(1, MAX_PAGE_SIZE)
+}
+
+/// Columns to select so that `TxCursor::from_row` can build the cursor of a row
+pub(super) const TX_CURSOR_COLUMNS: &str = "COALESCE(t.block_height, 2147483647) AS cursor_height, COALESCE(t.index, 2147483647) AS cursor_index";
+/// Ordering of the transaction listings, matching `TxCursor`
+pub(super) const TX_CURSOR_ORDER: &str = " ORDER BY COALESCE(t.block_height, 2147483647) DESC, COALESCE(t.index, 2147483647) DESC, t.tx_hash DESC, t.parent_dp_hash DESC";
+
+/// Cursor of every paginated listing: the hex encoded `:`-separated sort key of the last row of
+/// the page, returned in the `x-next-cursor` header. Only the last field may contain a `:`.
+pub(super) fn encode_cursor(key: &[&str]) -> String {
+    hex::encode(key.join(":"))
+}
+
+pub(super) fn decode_cursor<const N: usize>(cursor: &str) -> Option<[String; N]> {
+    let decoded = String::from_utf8(hex::decode(cursor).ok()?).ok()?;
+    decoded
+        .splitn(N, ':')
+        .map(str::to_string)
+        .collect::<Vec<_>>()
+        .try_into()
+        .ok()
+}
+
+/// Opaque position in a listing of transactions ordered by descending (block_height, index).
+/// Transactions that are not in a block yet come first, with a height and index of `i32::MAX`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxCursor {
+    pub block_height: i64,
+    pub index: i64,
+    pub tx_hash: String,
+    pub parent_dp_hash: String,
+}
+
+impl TxCursor {
+    pub fn encode(&self) -> String {
+        encode_cursor(&[
+            &self.block_height.to_string(),
+            &self.index.to_string(),
+            &self.tx_hash,
+            &self.parent_dp_hash,
+        ])
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let [block_height, index, tx_hash, parent_dp_hash] = decode_cursor(cursor)?;
+        Some(TxCursor {
+            block_height: block_height.parse().ok()?,
+            index: index.parse().ok()?,
+            tx_hash,
+            parent_dp_hash,
+        })
+    }
+
+    /// Decodes the cursor query parameter, answering with a bad request if it is malformed
+    pub(super) fn from_param(cursor: &Option<String>) -> Result<Option<Self>, StatusCode> {
+        cursor
+            .as_deref()
+            .map(|cursor| Self::decode(cursor).ok_or(StatusCode::BAD_REQUEST))
+            .transpose()
+    }
+
+    pub(super) fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
+        Ok(TxCursor {
+            block_height: row.try_get::<i32, _>("cursor_height")?.into(),
+            index: row.try_get::<i32, _>("cursor_index")?.into(),
+            tx_hash: row.try_get("tx_hash")?,
+            parent_dp_hash: row.try_get("parent_dp_hash")?,
+        })
+    }
+
+    /// Restricts the query on `transactions t` to the rows after the cursor
+    pub(super) fn push_filter(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        query
+            .push(" AND (COALESCE(t.block_height, 2147483647), COALESCE(t.index, 2147483647), t.tx_hash, t.parent_dp_hash) < (")
+            .push_bind(self.block_height)
+            .push(", ")
+            .push_bind(self.index)
+            .push(", ")
+            .push_bind(self.tx_hash.clone())
+            .push(", ")
+            .push_bind(self.parent_dp_hash.clone())
+            .push(")");
+    }
+}
+
+/// Parses the rows of a page fetched with one extra row, and returns the cursor
+/// of the last row kept if there is a next page.
+pub(super) fn tx_page<T>(
+    rows: &[PgRow],
+    nb_results: i64,
+    parse: impl Fn(&PgRow) -> Result<T, sqlx::Error>,
+) -> Result<(Vec<T>, Option<String>), sqlx::Error> {
+    let page = rows.iter().take(nb_results.max(0) as usize);
+    let items = page.clone().map(parse).collect::<Result<Vec<_>, _>>()?;
+    let next_cursor = match rows.len() as i64 > nb_results {
+        true => page.last().map(TxCursor::from_row).transpose()?,
+        false => None,
+    };
+    Ok((items, next_cursor.map(|cursor| cursor.encode())))
+}
+
+/// Headers of a paginated listing
+pub(super) fn next_cursor_headers(next_cursor: Option<String>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(value) = next_cursor.and_then(|cursor| HeaderValue::from_str(&cursor).ok()) {
+        headers.insert(NEXT_CURSOR_HEADER, value);
+    }
+    headers
 }
 
 #[derive(OpenApi)]
@@ -14,13 +135,17 @@ pub(super) struct IndexerAPI;
 mod blobs;
 mod blocks;
 mod contracts;
+mod identities;
 mod proofs;
+mod search;
 mod stats;
 mod transactions;
 
 pub use blobs::*;
 pub use blocks::*;
 pub use contracts::*;
+pub use identities::*;
 pub use proofs::*;
+pub use search::*;
 pub use stats::*;
 pub use transactions::*;
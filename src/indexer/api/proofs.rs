@@ -1,8 +1,8 @@
-use super::{BlockPagination, IndexerApiState, TransactionDb};
-use api::APITransaction;
+use super::{transactions::list_transactions, BlockPagination, IndexerApiState, TransactionDb};
+use api::{APITransaction, TransactionTypeDb};
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     Json,
 };
 use hyle_model::{api::APIProofDetails, utils::TimestampMs};
@@ -16,49 +16,22 @@ use hyle_modules::log_error;
     get,
     tag = "Indexer",
     path = "/proofs",
+    params(
+        ("start_block" = Option<i64>, Query, description = "Highest block height to list from"),
+        ("nb_results" = Option<i64>, Query, description = "Page size, at most 100"),
+        ("cursor" = Option<String>, Query, description = "Cursor returned by the previous page"),
+    ),
     responses(
-        (status = OK, body = [APITransaction])
+        (status = OK, body = [APITransaction], headers(
+            ("x-next-cursor" = String, description = "Cursor of the next page, if any")
+        ))
     )
 )]
 pub async fn get_proofs(
     Query(pagination): Query<BlockPagination>,
     State(state): State<IndexerApiState>,
-) -> Result<Json<Vec<APITransaction>>, StatusCode> {
-    let transactions = log_error!(
-        match pagination.start_block {
-            Some(start_block) => sqlx::query_as::<_, TransactionDb>(
-                r#"
-            SELECT t.*, b.timestamp
-            FROM transactions t
-            LEFT JOIN blocks b ON t.block_hash = b.hash
-            WHERE b.height <= $1 and b.height > $2 AND t.transaction_type = 'proof_transaction'
-            ORDER BY b.height DESC, t.index DESC
-            LIMIT $3
-            "#,
-            )
-            .bind(start_block)
-            .bind(start_block - pagination.nb_results.unwrap_or(10)) // Fine if this goes negative
-            .bind(pagination.nb_results.unwrap_or(10)),
-            None => sqlx::query_as::<_, TransactionDb>(
-                r#"
-            SELECT t.*, b.timestamp
-            FROM transactions t
-            LEFT JOIN blocks b ON t.block_hash = b.hash
-            WHERE t.transaction_type = 'proof_transaction'
-            ORDER BY b.height DESC, t.index DESC
-            LIMIT $1
-            "#,
-            )
-            .bind(pagination.nb_results.unwrap_or(10)),
-        }
-        .fetch_all(&state.db)
-        .await
-        .map(|db| db.into_iter().map(Into::<APITransaction>::into).collect()),
-        "Failed to fetch proofs"
-    )
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    Ok(Json(transactions))
+) -> Result<(HeaderMap, Json<Vec<APITransaction>>), StatusCode> {
+    list_transactions(&state, &pagination, TransactionTypeDb::ProofTransaction).await
 }
 
 #[utoipa::path(
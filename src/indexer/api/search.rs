@@ -0,0 +1,136 @@
+use super::{
+    next_cursor_headers, page_size, tx_page, IndexerApiState, TxCursor, TX_CURSOR_COLUMNS,
+    TX_CURSOR_ORDER,
+};
+use api::{APITransaction, TransactionStatusDb, TransactionTypeDb};
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use sqlx::types::chrono::{DateTime, NaiveDateTime};
+use sqlx::{FromRow, Postgres, QueryBuilder};
+
+use super::TransactionDb;
+use crate::model::*;
+use hyle_modules::log_error;
+
+#[derive(Debug, serde::Deserialize)]
+pub struct TransactionSearch {
+    pub identity: Option<String>,
+    pub status: Option<TransactionStatusDb>,
+    pub transaction_type: Option<TransactionTypeDb>,
+    /// Inclusive block range
+    pub from_height: Option<i64>,
+    pub to_height: Option<i64>,
+    /// Inclusive range of block timestamps, in milliseconds
+    pub from_timestamp: Option<i64>,
+    pub to_timestamp: Option<i64>,
+    /// Words that must all appear in the data of one of the blobs
+    pub blob_text: Option<String>,
+    /// Cursor returned in the `x-next-cursor` header of the previous page
+    pub cursor: Option<String>,
+    pub nb_results: Option<i64>,
+}
+
+fn timestamp_ms(ms: i64) -> Result<NaiveDateTime, StatusCode> {
+    DateTime::from_timestamp_millis(ms)
+        .map(|t| t.naive_utc())
+        .ok_or(StatusCode::BAD_REQUEST)
+}
+
+#[utoipa::path(
+    get,
+    tag = "Indexer",
+    path = "/search",
+    params(
+        ("identity" = Option<String>, Query, description = "Identity of the sender"),
+        ("status" = Option<TransactionStatusDb>, Query, description = "Transaction status"),
+        ("transaction_type" = Option<TransactionTypeDb>, Query, description = "Transaction type"),
+        ("from_height" = Option<i64>, Query, description = "Lowest block height, inclusive"),
+        ("to_height" = Option<i64>, Query, description = "Highest block height, inclusive"),
+        ("from_timestamp" = Option<i64>, Query, description = "Earliest block timestamp in ms, inclusive"),
+        ("to_timestamp" = Option<i64>, Query, description = "Latest block timestamp in ms, inclusive"),
+        ("blob_text" = Option<String>, Query, description = "Words that must all appear in the data of one of the blobs"),
+        ("cursor" = Option<String>, Query, description = "Cursor returned by the previous page"),
+        ("nb_results" = Option<i64>, Query, description = "Page size, at most 100"),
+    ),
+    responses(
+        (status = OK, body = [APITransaction], headers(
+            ("x-next-cursor" = String, description = "Cursor of the next page, if any")
+        ))
+    )
+)]
+pub async fn search_transactions(
+    Query(search): Query<TransactionSearch>,
+    State(state): State<IndexerApiState>,
+) -> Result<(HeaderMap, Json<Vec<APITransaction>>), StatusCode> {
+    let nb_results = page_size(search.nb_results);
+    let cursor = TxCursor::from_param(&search.cursor)?;
+
+    let mut query = QueryBuilder::<Postgres>::new(format!(
+        r#"
+        SELECT t.*, b.timestamp, {TX_CURSOR_COLUMNS}
+        FROM transactions t
+        LEFT JOIN blocks b ON t.block_hash = b.hash
+        WHERE TRUE"#
+    ));
+    if let Some(identity) = search.identity {
+        query.push(" AND t.identity = ").push_bind(identity);
+    }
+    if let Some(status) = search.status {
+        query.push(" AND t.transaction_status = ").push_bind(status);
+    }
+    if let Some(transaction_type) = search.transaction_type {
+        query
+            .push(" AND t.transaction_type = ")
+            .push_bind(transaction_type);
+    }
+    if let Some(from_height) = search.from_height {
+        query.push(" AND t.block_height >= ").push_bind(from_height);
+    }
+    if let Some(to_height) = search.to_height {
+        query.push(" AND t.block_height <= ").push_bind(to_height);
+    }
+    if let Some(from_timestamp) = search.from_timestamp {
+        query
+            .push(" AND b.timestamp >= ")
+            .push_bind(timestamp_ms(from_timestamp)?);
+    }
+    if let Some(to_timestamp) = search.to_timestamp {
+        query
+            .push(" AND b.timestamp <= ")
+            .push_bind(timestamp_ms(to_timestamp)?);
+    }
+    if let Some(blob_text) = search.blob_text {
+        query
+            .push(
+                " AND EXISTS (SELECT 1 FROM blobs bl WHERE bl.parent_dp_hash = t.parent_dp_hash AND bl.tx_hash = t.tx_hash AND bl.data_search @@ plainto_tsquery('simple', ",
+            )
+            .push_bind(blob_text)
+            .push("))");
+    }
+    if let Some(cursor) = cursor {
+        cursor.push_filter(&mut query);
+    }
+    query
+        .push(TX_CURSOR_ORDER)
+        .push(" LIMIT ")
+        // One more row to know whether there is a next page
+        .push_bind(nb_results + 1);
+
+    let rows = log_error!(
+        query.build().fetch_all(&state.db).await,
+        "Failed to search transactions"
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let (transactions, next_cursor) = log_error!(
+        tx_page(&rows, nb_results, |row| TransactionDb::from_row(row)
+            .map(APITransaction::from)),
+        "Failed to parse searched transactions"
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok((next_cursor_headers(next_cursor), Json(transactions)))
+}
@@ -1,13 +1,16 @@
 use std::num::TryFromIntError;
 
-use super::{BlockPagination, IndexerApiState};
+use super::{
+    next_cursor_headers, page_size, tx_page, BlockPagination, IndexerApiState, TxCursor,
+    TX_CURSOR_COLUMNS, TX_CURSOR_ORDER,
+};
 use api::{
     APITransaction, APITransactionEvents, BlobWithStatus, TransactionStatusDb, TransactionTypeDb,
     TransactionWithBlobs,
 };
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     Json,
 };
 use hyle_model::utils::TimestampMs;
@@ -15,7 +18,7 @@ use sqlx::postgres::PgRow;
 use sqlx::types::chrono::NaiveDateTime;
 use sqlx::FromRow;
 use sqlx::Row;
-use sqlx::{prelude::Type, Postgres};
+use sqlx::{prelude::Type, Postgres, QueryBuilder};
 
 use crate::model::*;
 use hyle_modules::log_error;
@@ -209,53 +212,76 @@ impl From<TransactionDb> for APITransaction {
     }
 }
 
+/// Lists the transactions of a type, most recent first. Pages are delimited by
+/// `start_block` and `nb_results`, or by the cursor returned with the previous page.
+pub(super) async fn list_transactions(
+    state: &IndexerApiState,
+    pagination: &BlockPagination,
+    transaction_type: TransactionTypeDb,
+) -> Result<(HeaderMap, Json<Vec<APITransaction>>), StatusCode> {
+    let nb_results = page_size(pagination.nb_results);
+    let cursor = TxCursor::from_param(&pagination.cursor)?;
+
+    let mut query = QueryBuilder::<Postgres>::new(format!(
+        r#"
+        SELECT t.*, b.timestamp, {TX_CURSOR_COLUMNS}
+        FROM transactions t
+        LEFT JOIN blocks b ON t.block_hash = b.hash
+        WHERE t.transaction_type = "#
+    ));
+    query.push_bind(transaction_type);
+    if let Some(start_block) = pagination.start_block {
+        query
+            .push(" AND b.height <= ")
+            .push_bind(start_block)
+            .push(" AND b.height > ")
+            .push_bind(start_block - nb_results); // Fine if this goes negative
+    }
+    if let Some(cursor) = cursor {
+        cursor.push_filter(&mut query);
+    }
+    query
+        .push(TX_CURSOR_ORDER)
+        .push(" LIMIT ")
+        // One more row to know whether there is a next page
+        .push_bind(nb_results + 1);
+
+    let rows = log_error!(
+        query.build().fetch_all(&state.db).await,
+        "Failed to fetch transactions"
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let (transactions, next_cursor) = log_error!(
+        tx_page(&rows, nb_results, |row| TransactionDb::from_row(row)
+            .map(APITransaction::from)),
+        "Failed to parse transactions"
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok((next_cursor_headers(next_cursor), Json(transactions)))
+}
+
 #[utoipa::path(
     get,
     tag = "Indexer",
     path = "/transactions",
+    params(
+        ("start_block" = Option<i64>, Query, description = "Highest block height to list from"),
+        ("nb_results" = Option<i64>, Query, description = "Page size, at most 100"),
+        ("cursor" = Option<String>, Query, description = "Cursor returned by the previous page"),
+    ),
     responses(
-        (status = OK, body = [APITransaction])
+        (status = OK, body = [APITransaction], headers(
+            ("x-next-cursor" = String, description = "Cursor of the next page, if any")
+        ))
     )
 )]
 pub async fn get_transactions(
     Query(pagination): Query<BlockPagination>,
     State(state): State<IndexerApiState>,
-) -> Result<Json<Vec<APITransaction>>, StatusCode> {
-    let transactions = log_error!(
-        match pagination.start_block {
-            Some(start_block) => sqlx::query_as::<_, TransactionDb>(
-                r#"
-            SELECT t.*, b.timestamp
-            FROM transactions t
-            LEFT JOIN blocks b ON t.block_hash = b.hash
-            WHERE b.height <= $1 and b.height > $2 AND t.transaction_type = 'blob_transaction'
-            ORDER BY b.height DESC, t.index DESC
-            LIMIT $3
-            "#,
-            )
-            .bind(start_block)
-            .bind(start_block - pagination.nb_results.unwrap_or(10)) // Fine if this goes negative
-            .bind(pagination.nb_results.unwrap_or(10)),
-            None => sqlx::query_as::<_, TransactionDb>(
-                r#"
-            SELECT t.*, b.timestamp
-            FROM transactions t
-            LEFT JOIN blocks b ON t.block_hash = b.hash
-            WHERE t.transaction_type = 'blob_transaction'
-            ORDER BY b.height DESC, t.index DESC
-            LIMIT $1
-            "#,
-            )
-            .bind(pagination.nb_results.unwrap_or(10)),
-        }
-        .fetch_all(&state.db)
-        .await
-        .map(|db| db.into_iter().map(Into::<APITransaction>::into).collect()),
-        "Failed to fetch transactions"
-    )
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    Ok(Json(transactions))
+) -> Result<(HeaderMap, Json<Vec<APITransaction>>), StatusCode> {
+    list_transactions(&state, &pagination, TransactionTypeDb::BlobTransaction).await
 }
 
 #[utoipa::path(
@@ -482,23 +508,39 @@ ORDER BY
     tag = "Indexer",
     params(
         ("contract_name" = String, Path, description = "Contract name"),
+        ("nb_results" = Option<i64>, Query, description = "Page size, at most 100. All transactions are returned if neither this nor a cursor is set"),
+        ("cursor" = Option<String>, Query, description = "Cursor returned by the previous page"),
     ),
     path = "/blob_transactions/contract/{contract_name}",
     responses(
-        (status = OK, body = [TransactionWithBlobs])
+        (status = OK, body = [TransactionWithBlobs], headers(
+            ("x-next-cursor" = String, description = "Cursor of the next page, if any")
+        ))
     )
 )]
 pub async fn get_blob_transactions_by_contract(
     Path(contract_name): Path<String>,
+    Query(pagination): Query<BlockPagination>,
     State(state): State<IndexerApiState>,
-) -> Result<Json<Vec<TransactionWithBlobs>>, StatusCode> {
-    let rows = log_error!(sqlx::query(
+) -> Result<(HeaderMap, Json<Vec<TransactionWithBlobs>>), StatusCode> {
+    let cursor = TxCursor::from_param(&pagination.cursor)?;
+    // All transactions are returned unless a page is requested
+    let nb_results = match (pagination.nb_results, &cursor) {
+        (Some(nb_results), _) => Some(page_size(Some(nb_results))),
+        (None, Some(_)) => Some(10),
+        (None, None) => None,
+    };
+
+    let mut query = QueryBuilder::<Postgres>::new(
         r#"
         with blobs as (
             SELECT blobs.*, array_remove(ARRAY_AGG(blob_proof_outputs.hyle_output), NULL) AS proof_outputs
             FROM blobs
             LEFT JOIN blob_proof_outputs ON blobs.parent_dp_hash = blob_proof_outputs.blob_parent_dp_hash AND blobs.tx_hash = blob_proof_outputs.blob_tx_hash AND blobs.blob_index = blob_proof_outputs.blob_index
-            WHERE blobs.contract_name = $1
+            WHERE blobs.contract_name = "#,
+    );
+    query.push_bind(contract_name).push(format!(
+        r#"
             GROUP BY blobs.parent_dp_hash, blobs.tx_hash, blobs.blob_index, blobs.identity
         )
         SELECT
@@ -510,64 +552,78 @@ pub async fn get_blob_transactions_by_contract(
             t.transaction_type,
             t.transaction_status,
             t.identity,
-            array_agg(ROW(b.contract_name, b.data, b.proof_outputs)) AS blobs
+            array_agg(ROW(b.contract_name, b.data, b.proof_outputs)) AS blobs,
+            {TX_CURSOR_COLUMNS}
         FROM blobs b
         JOIN transactions t on t.tx_hash = b.tx_hash AND t.parent_dp_hash = b.parent_dp_hash
+        WHERE TRUE"#
+    ));
+    if let Some(cursor) = cursor {
+        cursor.push_filter(&mut query);
+    }
+    query.push(
+        r#"
         GROUP BY
             t.tx_hash,
             t.parent_dp_hash,
             t.block_hash,
+            t.block_height,
             t.index,
             t.version,
             t.transaction_type,
             t.transaction_status,
-            t.identity
-        "#,
+            t.identity"#,
+    );
+    query.push(TX_CURSOR_ORDER);
+    if let Some(nb_results) = nb_results {
+        // One more row to know whether there is a next page
+        query.push(" LIMIT ").push_bind(nb_results + 1);
+    }
+
+    let rows = log_error!(
+        query.build().fetch_all(&state.db).await,
+        "Failed to fetch blob transactions by contract"
     )
-    .bind(contract_name.clone())
-    .fetch_all(&state.db)
-    .await, "Failed to fetch blob transactions by contract")
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let transactions: Result<Vec<TransactionWithBlobs>, _> = rows
-        .into_iter()
-        .map(|row| {
-            let api_tx: TransactionDb = FromRow::from_row(&row)?;
-            let Some(block_hash) = api_tx.block_hash else {
-                return Err(sqlx::Error::RowNotFound);
-            };
-            let blobs: Vec<(String, Vec<u8>, Vec<serde_json::Value>)> = row.try_get("blobs")?;
-            let blobs = blobs
-                .into_iter()
-                .map(|(contract_name, data, proof_outputs)| BlobWithStatus {
-                    contract_name,
-                    data,
-                    proof_outputs,
-                })
-                .collect();
-            let Some(identity) = api_tx.identity else {
-                return Err(sqlx::Error::RowNotFound);
-            };
-
-            Ok(TransactionWithBlobs {
-                tx_hash: api_tx.tx_hash.0,
-                parent_dp_hash: api_tx.parent_dp_hash,
-                block_hash,
-                index: api_tx.index.unwrap_or(0),
-                version: api_tx.version,
-                transaction_type: api_tx.transaction_type,
-                transaction_status: api_tx.transaction_status,
-                timestamp: api_tx
-                    .timestamp
-                    .map(|t| TimestampMs(t.and_utc().timestamp_millis() as u128)),
-                lane_id: api_tx.lane_id.map(|l| l.0),
-                identity,
-                blobs,
+    let transactions = tx_page(&rows, nb_results.unwrap_or(i64::MAX), |row| {
+        let api_tx: TransactionDb = FromRow::from_row(row)?;
+        let Some(block_hash) = api_tx.block_hash else {
+            return Err(sqlx::Error::RowNotFound);
+        };
+        let blobs: Vec<(String, Vec<u8>, Vec<serde_json::Value>)> = row.try_get("blobs")?;
+        let blobs = blobs
+            .into_iter()
+            .map(|(contract_name, data, proof_outputs)| BlobWithStatus {
+                contract_name,
+                data,
+                proof_outputs,
             })
+            .collect();
+        let Some(identity) = api_tx.identity else {
+            return Err(sqlx::Error::RowNotFound);
+        };
+
+        Ok(TransactionWithBlobs {
+            tx_hash: api_tx.tx_hash.0,
+            parent_dp_hash: api_tx.parent_dp_hash,
+            block_hash,
+            index: api_tx.index.unwrap_or(0),
+            version: api_tx.version,
+            transaction_type: api_tx.transaction_type,
+            transaction_status: api_tx.transaction_status,
+            timestamp: api_tx
+                .timestamp
+                .map(|t| TimestampMs(t.and_utc().timestamp_millis() as u128)),
+            lane_id: api_tx.lane_id.map(|l| l.0),
+            identity,
+            blobs,
         })
-        .collect();
+    });
     match transactions {
-        Ok(transactions) => Ok(Json(transactions)),
+        Ok((transactions, next_cursor)) => {
+            Ok((next_cursor_headers(next_cursor), Json(transactions)))
+        }
         Err(e) => {
             tracing::warn!("Failed to parse transactions with blobs: {:?}", e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
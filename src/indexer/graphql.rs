@@ -0,0 +1,668 @@
+//! GraphQL schema over the indexer tables, mounted next to the REST API under `/v1/indexer/graphql`.
+//!
+//! Objects resolve their relations lazily (block → transactions → blobs → proof outputs),
+//! so a page can be built in a single round-trip. Nested lookups go through a [`DataLoader`],
+//! which batches the rows of a list into one query per field instead of one per row.
+//! Every list is paginated, and query depth and complexity are bounded by the indexer configuration.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    hash::Hash,
+    sync::Arc,
+};
+
+use async_graphql::{
+    dataloader::{DataLoader, Loader},
+    ComplexObject, Context, EmptyMutation, EmptySubscription, Error, Object, Result, Schema,
+    SimpleObject,
+};
+use async_graphql_axum::GraphQL;
+use axum::Router;
+use sqlx::PgPool;
+use tracing::error;
+
+use super::{IndexerApiState, IndexerConf};
+
+pub type IndexerSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Largest page a list field can return
+const MAX_PAGE_SIZE: i64 = 100;
+const DEFAULT_PAGE_SIZE: i64 = 10;
+
+pub fn schema(db: PgPool, conf: &IndexerConf) -> IndexerSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(DataLoader::new(IndexerLoader(db.clone()), tokio::spawn))
+        .data(db)
+        .limit_depth(conf.graphql_max_depth)
+        .limit_complexity(conf.graphql_max_complexity)
+        .finish()
+}
+
+pub fn router(db: PgPool, conf: &IndexerConf) -> Router<IndexerApiState> {
+    Router::new().route_service("/graphql", GraphQL::new(schema(db, conf)))
+}
+
+fn page_size(first: Option<i64>) -> i64 {
+    first.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
+
+fn db_error(e: impl Display) -> Error {
+    error!("GraphQL query failed: {e:#}");
+    Error::new("Failed to query the indexer database")
+}
+
+fn loader(ctx: &Context<'_>) -> Result<&DataLoader<IndexerLoader>> {
+    ctx.data::<DataLoader<IndexerLoader>>()
+}
+
+const BLOB_COLUMNS: &str = "tx_hash, parent_dp_hash, blob_index, identity, contract_name, encode(data, 'hex') AS data, verified";
+const BLOCK_COLUMNS: &str = "hash, parent_hash, height, (EXTRACT(EPOCH FROM timestamp) * 1000)::BIGINT AS timestamp, total_txs";
+const CONTRACT_COLUMNS: &str = "contract_name, tx_hash, parent_dp_hash, verifier, encode(program_id, 'hex') AS program_id, encode(state_commitment, 'hex') AS state_commitment, timeout_window, encode(pending_program_id, 'hex') AS pending_program_id, pending_program_id_activation, min_program_id_update_delay, owner, name_service::TEXT AS name_service, expiry_height";
+const PROOF_OUTPUT_COLUMNS: &str = "proof_tx_hash, proof_parent_dp_hash, blob_tx_hash, blob_parent_dp_hash, blob_index, blob_proof_output_index, contract_name, hyle_output, settled";
+const TRANSACTION_COLUMNS: &str = "tx_hash, parent_dp_hash, version, transaction_type::TEXT AS transaction_type, transaction_status::TEXT AS transaction_status, block_hash, block_height, index, lane_id, identity";
+
+#[derive(SimpleObject, sqlx::FromRow, Clone, Debug)]
+#[graphql(complex)]
+pub struct Block {
+    pub hash: String,
+    pub parent_hash: String,
+    pub height: i64,
+    /// Block timestamp in milliseconds
+    pub timestamp: i64,
+    pub total_txs: i64,
+}
+
+#[ComplexObject]
+impl Block {
+    /// Transactions of the block in order, starting after the one at `after_index` if set
+    #[graphql(complexity = "page_size(first) as usize * child_complexity")]
+    async fn transactions(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i64>,
+        after_index: Option<i32>,
+    ) -> Result<Vec<Transaction>> {
+        loader(ctx)?
+            .load_one(BlockTransactions {
+                block_hash: self.hash.clone(),
+                first: page_size(first),
+                after_index: after_index.unwrap_or(-1),
+            })
+            .await
+            .map(Option::unwrap_or_default)
+            .map_err(db_error)
+    }
+}
+
+#[derive(SimpleObject, sqlx::FromRow, Clone, Debug)]
+#[graphql(complex)]
+pub struct Transaction {
+    pub tx_hash: String,
+    pub parent_dp_hash: String,
+    pub version: i32,
+    pub transaction_type: String,
+    pub transaction_status: String,
+    pub block_hash: Option<String>,
+    pub block_height: Option<i32>,
+    pub index: Option<i32>,
+    pub lane_id: Option<String>,
+    pub identity: Option<String>,
+}
+
+#[ComplexObject]
+impl Transaction {
+    async fn block(&self, ctx: &Context<'_>) -> Result<Option<Block>> {
+        let Some(block_hash) = &self.block_hash else {
+            return Ok(None);
+        };
+        loader(ctx)?
+            .load_one(BlockByHash(block_hash.clone()))
+            .await
+            .map_err(db_error)
+    }
+
+    #[graphql(complexity = "page_size(first) as usize * child_complexity")]
+    async fn blobs(&self, ctx: &Context<'_>, first: Option<i64>) -> Result<Vec<Blob>> {
+        loader(ctx)?
+            .load_one(TransactionBlobs {
+                tx_hash: self.tx_hash.clone(),
+                parent_dp_hash: self.parent_dp_hash.clone(),
+                first: page_size(first),
+            })
+            .await
+            .map(Option::unwrap_or_default)
+            .map_err(db_error)
+    }
+
+    /// Outputs proven by this transaction, if it is a proof transaction
+    #[graphql(complexity = "page_size(first) as usize * child_complexity")]
+    async fn proven_outputs(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i64>,
+    ) -> Result<Vec<ProofOutput>> {
+        loader(ctx)?
+            .load_one(ProvenOutputs {
+                proof_tx_hash: self.tx_hash.clone(),
+                proof_parent_dp_hash: self.parent_dp_hash.clone(),
+                first: page_size(first),
+            })
+            .await
+            .map(Option::unwrap_or_default)
+            .map_err(db_error)
+    }
+}
+
+#[derive(SimpleObject, sqlx::FromRow, Clone, Debug)]
+#[graphql(complex)]
+pub struct Blob {
+    pub tx_hash: String,
+    pub parent_dp_hash: String,
+    pub blob_index: i32,
+    pub identity: String,
+    pub contract_name: String,
+    /// Hex encoded blob data
+    pub data: String,
+    pub verified: bool,
+}
+
+#[ComplexObject]
+impl Blob {
+    #[graphql(complexity = "page_size(first) as usize * child_complexity")]
+    async fn proof_outputs(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i64>,
+    ) -> Result<Vec<ProofOutput>> {
+        loader(ctx)?
+            .load_one(BlobProofOutputs {
+                blob_tx_hash: self.tx_hash.clone(),
+                blob_parent_dp_hash: self.parent_dp_hash.clone(),
+                blob_index: self.blob_index,
+                first: page_size(first),
+            })
+            .await
+            .map(Option::unwrap_or_default)
+            .map_err(db_error)
+    }
+
+    async fn contract(&self, ctx: &Context<'_>) -> Result<Option<Contract>> {
+        contract_by_name(ctx, &self.contract_name).await
+    }
+}
+
+#[derive(SimpleObject, sqlx::FromRow, Clone, Debug)]
+pub struct ProofOutput {
+    pub proof_tx_hash: String,
+    pub proof_parent_dp_hash: String,
+    pub blob_tx_hash: String,
+    pub blob_parent_dp_hash: String,
+    pub blob_index: i32,
+    pub blob_proof_output_index: i32,
+    pub contract_name: String,
+    #[sqlx(json)]
+    pub hyle_output: async_graphql::Json<serde_json::Value>,
+    pub settled: bool,
+}
+
+#[derive(SimpleObject, sqlx::FromRow, Clone, Debug)]
+#[graphql(complex)]
+pub struct Contract {
+    pub contract_name: String,
+    /// Registration transaction
+    pub tx_hash: String,
+    pub parent_dp_hash: String,
+    pub verifier: String,
+    /// Hex encoded program id
+    pub program_id: String,
+    /// Hex encoded current state commitment
+    pub state_commitment: String,
+    pub timeout_window: Option<i64>,
+    /// Hex encoded program id scheduled to replace the current one
+    pub pending_program_id: Option<String>,
+    /// Block height the pending program id activates at
+    pub pending_program_id_activation: Option<i64>,
+    /// Minimum notice, in blocks, the owner must give before changing the program id
+    pub min_program_id_update_delay: Option<i64>,
+    /// Identity managing the contract through the hyle TLD
+    pub owner: Option<String>,
+    /// JSON lease terms, when the contract is a TLD in name service mode
+    pub name_service: Option<String>,
+    /// Block height the lease of a leased name ends at
+    pub expiry_height: Option<i64>,
+}
+
+#[ComplexObject]
+impl Contract {
+    /// State commitments of the contract, most recent first
+    #[graphql(complexity = "page_size(first) as usize * child_complexity")]
+    async fn states(&self, ctx: &Context<'_>, first: Option<i64>) -> Result<Vec<ContractState>> {
+        loader(ctx)?
+            .load_one(ContractStates {
+                contract_name: self.contract_name.clone(),
+                first: page_size(first),
+            })
+            .await
+            .map(Option::unwrap_or_default)
+            .map_err(db_error)
+    }
+}
+
+#[derive(SimpleObject, sqlx::FromRow, Clone, Debug)]
+pub struct ContractState {
+    pub contract_name: String,
+    pub block_hash: String,
+    pub block_height: i64,
+    /// Hex encoded state commitment
+    pub state_commitment: String,
+}
+
+async fn contract_by_name(ctx: &Context<'_>, contract_name: &str) -> Result<Option<Contract>> {
+    loader(ctx)?
+        .load_one(ContractByName(contract_name.to_string()))
+        .await
+        .map_err(db_error)
+}
+
+/// Loads the nested fields of the objects for all the rows of a list at once
+pub struct IndexerLoader(PgPool);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct BlockByHash(String);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct ContractByName(String);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct BlockTransactions {
+    block_hash: String,
+    first: i64,
+    after_index: i32,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct TransactionBlobs {
+    tx_hash: String,
+    parent_dp_hash: String,
+    first: i64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct ProvenOutputs {
+    proof_tx_hash: String,
+    proof_parent_dp_hash: String,
+    first: i64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct BlobProofOutputs {
+    blob_tx_hash: String,
+    blob_parent_dp_hash: String,
+    blob_index: i32,
+    first: i64,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct ContractStates {
+    contract_name: String,
+    first: i64,
+}
+
+/// Groups the keys by page, so that each distinct page gets a single query
+fn by_page<K, P: Ord>(keys: &[K], page: impl Fn(&K) -> P) -> BTreeMap<P, Vec<&K>> {
+    let mut pages = BTreeMap::<P, Vec<&K>>::new();
+    for key in keys {
+        pages.entry(page(key)).or_default().push(key);
+    }
+    pages
+}
+
+/// Groups the rows, in order, under the key they were loaded for. Keys without rows get an empty list.
+fn group_rows<K: Clone + Eq + Hash, V>(
+    loaded: &mut HashMap<K, Vec<V>>,
+    keys: &[&K],
+    rows: Vec<V>,
+    key_of: impl Fn(&V) -> K,
+) {
+    for key in keys {
+        loaded.entry((*key).clone()).or_default();
+    }
+    for row in rows {
+        loaded.entry(key_of(&row)).or_default().push(row);
+    }
+}
+
+impl Loader<BlockByHash> for IndexerLoader {
+    type Value = Block;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(&self, keys: &[BlockByHash]) -> Result<HashMap<BlockByHash, Block>, Self::Error> {
+        let hashes: Vec<String> = keys.iter().map(|k| k.0.clone()).collect();
+        let blocks: Vec<Block> = sqlx::query_as(&format!(
+            "SELECT {BLOCK_COLUMNS} FROM blocks WHERE hash = ANY($1)"
+        ))
+        .bind(hashes)
+        .fetch_all(&self.0)
+        .await?;
+        Ok(blocks
+            .into_iter()
+            .map(|block| (BlockByHash(block.hash.clone()), block))
+            .collect())
+    }
+}
+
+impl Loader<ContractByName> for IndexerLoader {
+    type Value = Contract;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(
+        &self,
+        keys: &[ContractByName],
+    ) -> Result<HashMap<ContractByName, Contract>, Self::Error> {
+        let names: Vec<String> = keys.iter().map(|k| k.0.clone()).collect();
+        let contracts: Vec<Contract> = sqlx::query_as(&format!(
+            "SELECT {CONTRACT_COLUMNS} FROM contracts WHERE contract_name = ANY($1)"
+        ))
+        .bind(names)
+        .fetch_all(&self.0)
+        .await?;
+        Ok(contracts
+            .into_iter()
+            .map(|contract| (ContractByName(contract.contract_name.clone()), contract))
+            .collect())
+    }
+}
+
+impl Loader<BlockTransactions> for IndexerLoader {
+    type Value = Vec<Transaction>;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(
+        &self,
+        keys: &[BlockTransactions],
+    ) -> Result<HashMap<BlockTransactions, Vec<Transaction>>, Self::Error> {
+        let mut loaded = HashMap::new();
+        for ((first, after_index), keys) in by_page(keys, |k| (k.first, k.after_index)) {
+            let hashes: Vec<String> = keys.iter().map(|k| k.block_hash.clone()).collect();
+            let transactions: Vec<Transaction> = sqlx::query_as(&format!(
+                r#"
+                SELECT {TRANSACTION_COLUMNS} FROM (
+                    SELECT *, ROW_NUMBER() OVER (PARTITION BY block_hash ORDER BY index ASC) AS nth
+                    FROM transactions
+                    WHERE block_hash = ANY($1) AND index > $2
+                ) t
+                WHERE nth <= $3
+                ORDER BY index ASC
+                "#
+            ))
+            .bind(hashes)
+            .bind(after_index)
+            .bind(first)
+            .fetch_all(&self.0)
+            .await?;
+            group_rows(&mut loaded, &keys, transactions, |tx| BlockTransactions {
+                block_hash: tx.block_hash.clone().unwrap_or_default(),
+                first,
+                after_index,
+            });
+        }
+        Ok(loaded)
+    }
+}
+
+impl Loader<TransactionBlobs> for IndexerLoader {
+    type Value = Vec<Blob>;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(
+        &self,
+        keys: &[TransactionBlobs],
+    ) -> Result<HashMap<TransactionBlobs, Vec<Blob>>, Self::Error> {
+        let mut loaded = HashMap::new();
+        for (first, keys) in by_page(keys, |k| k.first) {
+            let (tx_hashes, parent_dp_hashes): (Vec<String>, Vec<String>) = keys
+                .iter()
+                .map(|k| (k.tx_hash.clone(), k.parent_dp_hash.clone()))
+                .unzip();
+            let blobs: Vec<Blob> = sqlx::query_as(&format!(
+                r#"
+                SELECT {BLOB_COLUMNS} FROM (
+                    SELECT *, ROW_NUMBER() OVER (PARTITION BY tx_hash, parent_dp_hash ORDER BY blob_index ASC) AS nth
+                    FROM blobs
+                    WHERE (tx_hash, parent_dp_hash) IN (SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[]))
+                ) b
+                WHERE nth <= $3
+                ORDER BY blob_index ASC
+                "#
+            ))
+            .bind(tx_hashes)
+            .bind(parent_dp_hashes)
+            .bind(first)
+            .fetch_all(&self.0)
+            .await?;
+            group_rows(&mut loaded, &keys, blobs, |blob| TransactionBlobs {
+                tx_hash: blob.tx_hash.clone(),
+                parent_dp_hash: blob.parent_dp_hash.clone(),
+                first,
+            });
+        }
+        Ok(loaded)
+    }
+}
+
+impl Loader<ProvenOutputs> for IndexerLoader {
+    type Value = Vec<ProofOutput>;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(
+        &self,
+        keys: &[ProvenOutputs],
+    ) -> Result<HashMap<ProvenOutputs, Vec<ProofOutput>>, Self::Error> {
+        let mut loaded = HashMap::new();
+        for (first, keys) in by_page(keys, |k| k.first) {
+            let (tx_hashes, parent_dp_hashes): (Vec<String>, Vec<String>) = keys
+                .iter()
+                .map(|k| (k.proof_tx_hash.clone(), k.proof_parent_dp_hash.clone()))
+                .unzip();
+            let outputs: Vec<ProofOutput> = sqlx::query_as(&format!(
+                r#"
+                SELECT {PROOF_OUTPUT_COLUMNS} FROM (
+                    SELECT *, ROW_NUMBER() OVER (PARTITION BY proof_tx_hash, proof_parent_dp_hash ORDER BY blob_proof_output_index ASC) AS nth
+                    FROM blob_proof_outputs
+                    WHERE (proof_tx_hash, proof_parent_dp_hash) IN (SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[]))
+                ) o
+                WHERE nth <= $3
+                ORDER BY blob_proof_output_index ASC
+                "#
+            ))
+            .bind(tx_hashes)
+            .bind(parent_dp_hashes)
+            .bind(first)
+            .fetch_all(&self.0)
+            .await?;
+            group_rows(&mut loaded, &keys, outputs, |output| ProvenOutputs {
+                proof_tx_hash: output.proof_tx_hash.clone(),
+                proof_parent_dp_hash: output.proof_parent_dp_hash.clone(),
+                first,
+            });
+        }
+        Ok(loaded)
+    }
+}
+
+impl Loader<BlobProofOutputs> for IndexerLoader {
+    type Value = Vec<ProofOutput>;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(
+        &self,
+        keys: &[BlobProofOutputs],
+    ) -> Result<HashMap<BlobProofOutputs, Vec<ProofOutput>>, Self::Error> {
+        let mut loaded = HashMap::new();
+        for (first, keys) in by_page(keys, |k| k.first) {
+            let mut tx_hashes = Vec::with_capacity(keys.len());
+            let mut parent_dp_hashes = Vec::with_capacity(keys.len());
+            let mut blob_indexes = Vec::with_capacity(keys.len());
+            for key in &keys {
+                tx_hashes.push(key.blob_tx_hash.clone());
+                parent_dp_hashes.push(key.blob_parent_dp_hash.clone());
+                blob_indexes.push(key.blob_index);
+            }
+            let outputs: Vec<ProofOutput> = sqlx::query_as(&format!(
+                r#"
+                SELECT {PROOF_OUTPUT_COLUMNS} FROM (
+                    SELECT *, ROW_NUMBER() OVER (PARTITION BY blob_tx_hash, blob_parent_dp_hash, blob_index ORDER BY blob_proof_output_index ASC) AS nth
+                    FROM blob_proof_outputs
+                    WHERE (blob_tx_hash, blob_parent_dp_hash, blob_index) IN (SELECT * FROM UNNEST($1::TEXT[], $2::TEXT[], $3::INT[]))
+                ) o
+                WHERE nth <= $4
+                ORDER BY blob_proof_output_index ASC
+                "#
+            ))
+            .bind(tx_hashes)
+            .bind(parent_dp_hashes)
+            .bind(blob_indexes)
+            .bind(first)
+            .fetch_all(&self.0)
+            .await?;
+            group_rows(&mut loaded, &keys, outputs, |output| BlobProofOutputs {
+                blob_tx_hash: output.blob_tx_hash.clone(),
+                blob_parent_dp_hash: output.blob_parent_dp_hash.clone(),
+                blob_index: output.blob_index,
+                first,
+            });
+        }
+        Ok(loaded)
+    }
+}
+
+impl Loader<ContractStates> for IndexerLoader {
+    type Value = Vec<ContractState>;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(
+        &self,
+        keys: &[ContractStates],
+    ) -> Result<HashMap<ContractStates, Vec<ContractState>>, Self::Error> {
+        let mut loaded = HashMap::new();
+        for (first, keys) in by_page(keys, |k| k.first) {
+            let names: Vec<String> = keys.iter().map(|k| k.contract_name.clone()).collect();
+            let states: Vec<ContractState> = sqlx::query_as(
+                r#"
+                SELECT contract_name, block_hash, block_height, state_commitment FROM (
+                    SELECT cs.contract_name, cs.block_hash, b.height AS block_height,
+                        encode(cs.state_commitment, 'hex') AS state_commitment,
+                        ROW_NUMBER() OVER (PARTITION BY cs.contract_name ORDER BY b.height DESC) AS nth
+                    FROM contract_state cs
+                    JOIN blocks b ON cs.block_hash = b.hash
+                    WHERE cs.contract_name = ANY($1)
+                ) s
+                WHERE nth <= $2
+                ORDER BY block_height DESC
+                "#,
+            )
+            .bind(names)
+            .bind(first)
+            .fetch_all(&self.0)
+            .await?;
+            group_rows(&mut loaded, &keys, states, |state| ContractStates {
+                contract_name: state.contract_name.clone(),
+                first,
+            });
+        }
+        Ok(loaded)
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Block by height or by hash. Without arguments, the last block.
+    async fn block(
+        &self,
+        ctx: &Context<'_>,
+        height: Option<i64>,
+        hash: Option<String>,
+    ) -> Result<Option<Block>> {
+        let db = ctx.data::<PgPool>()?;
+        match (height, hash) {
+            (Some(height), _) => {
+                sqlx::query_as(&format!(
+                    "SELECT {BLOCK_COLUMNS} FROM blocks WHERE height = $1"
+                ))
+                .bind(height)
+                .fetch_optional(db)
+                .await
+            }
+            (None, Some(hash)) => {
+                sqlx::query_as(&format!(
+                    "SELECT {BLOCK_COLUMNS} FROM blocks WHERE hash = $1"
+                ))
+                .bind(hash)
+                .fetch_optional(db)
+                .await
+            }
+            (None, None) => {
+                sqlx::query_as(&format!(
+                    "SELECT {BLOCK_COLUMNS} FROM blocks ORDER BY height DESC LIMIT 1"
+                ))
+                .fetch_optional(db)
+                .await
+            }
+        }
+        .map_err(db_error)
+    }
+
+    /// Blocks by descending height, starting at `start_height` if set
+    #[graphql(complexity = "page_size(first) as usize * child_complexity")]
+    async fn blocks(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i64>,
+        start_height: Option<i64>,
+    ) -> Result<Vec<Block>> {
+        sqlx::query_as(&format!(
+            "SELECT {BLOCK_COLUMNS} FROM blocks WHERE height <= $1 ORDER BY height DESC LIMIT $2"
+        ))
+        .bind(start_height.unwrap_or(i64::MAX))
+        .bind(page_size(first))
+        .fetch_all(ctx.data::<PgPool>()?)
+        .await
+        .map_err(db_error)
+    }
+
+    /// Latest transaction with this hash
+    async fn transaction(&self, ctx: &Context<'_>, tx_hash: String) -> Result<Option<Transaction>> {
+        sqlx::query_as(&format!(
+            "SELECT {TRANSACTION_COLUMNS} FROM transactions WHERE tx_hash = $1 ORDER BY block_height DESC NULLS FIRST, index DESC LIMIT 1"
+        ))
+        .bind(tx_hash)
+        .fetch_optional(ctx.data::<PgPool>()?)
+        .await
+        .map_err(db_error)
+    }
+
+    async fn contract(&self, ctx: &Context<'_>, contract_name: String) -> Result<Option<Contract>> {
+        contract_by_name(ctx, &contract_name).await
+    }
+
+    /// Contracts by name, starting after `after` if set
+    #[graphql(complexity = "page_size(first) as usize * child_complexity")]
+    async fn contracts(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i64>,
+        after: Option<String>,
+    ) -> Result<Vec<Contract>> {
+        sqlx::query_as(&format!(
+            "SELECT {CONTRACT_COLUMNS} FROM contracts WHERE contract_name > $1 ORDER BY contract_name ASC LIMIT $2"
+        ))
+        .bind(after.unwrap_or_default())
+        .bind(page_size(first))
+        .fetch_all(ctx.data::<PgPool>()?)
+        .await
+        .map_err(db_error)
+    }
+}
@@ -529,7 +529,14 @@ impl Indexer {
                 query_builder.push("verifier = EXCLUDED.verifier, ");
                 query_builder.push("program_id = EXCLUDED.program_id, ");
                 query_builder.push("timeout_window = EXCLUDED.timeout_window, ");
-                query_builder.push("state_commitment = EXCLUDED.state_commitment ");
+                query_builder.push("state_commitment = EXCLUDED.state_commitment, ");
+                // A re-registered contract does not inherit a pending program id change
+                query_builder.push("pending_program_id = NULL, ");
+                query_builder.push("pending_program_id_activation = NULL, ");
+                query_builder.push("min_program_id_update_delay = NULL, ");
+                query_builder.push("owner = NULL, ");
+                query_builder.push("name_service = NULL, ");
+                query_builder.push("expiry_height = NULL ");
 
                 query_builder
                     .build()
@@ -1131,6 +1138,13 @@ impl Indexer {
             }
         }
 
+        // Expired names are removed like deleted contracts, unless registered again below
+        for contract_name in block.expired_contracts.iter() {
+            self.handler_store
+                .deleted_contracts
+                .insert(contract_name.clone());
+        }
+
         // After TXes as it refers to those (for now)
         for (tx_hash, contract, _) in block.registered_contracts.values() {
             let verifier = &contract.verifier.0;
@@ -1205,6 +1219,21 @@ impl Indexer {
             );
         }
 
+        // Scheduled program id changes activating in this block are not pending anymore
+        if !block.updated_program_ids.is_empty() {
+            let block_height: i64 = block
+                .block_height
+                .0
+                .try_into()
+                .context("Converting block height to i64")?;
+            self.handler_store.sql_updates.push(
+                sqlx::query::<Postgres>(
+                    "UPDATE contracts SET pending_program_id = NULL, pending_program_id_activation = NULL WHERE pending_program_id_activation <= $1",
+                )
+                .bind(block_height),
+            );
+        }
+
         // Handling updated contract program ids
         for (contract_name, program_id) in block.updated_program_ids {
             let contract_name = contract_name.0;
@@ -1219,7 +1248,84 @@ impl Indexer {
             );
         }
 
-        // Handling updated contract program ids
+        // Handling minimum program id update delays, required by contract owners
+        for (contract_name, min_delay) in block.updated_min_program_id_delays {
+            let min_delay: i64 = min_delay
+                .0
+                .try_into()
+                .context("Converting minimum program id update delay to i64")?;
+            self.handler_store.sql_updates.push(
+                sqlx::query::<Postgres>(
+                    "UPDATE contracts SET min_program_id_update_delay = $1 WHERE contract_name = $2",
+                )
+                .bind(min_delay)
+                .bind(contract_name.0),
+            );
+        }
+
+        // Handling contract owners, set at registration or transferred
+        for (contract_name, owner) in block.updated_owners {
+            self.handler_store.sql_updates.push(
+                sqlx::query::<Postgres>("UPDATE contracts SET owner = $1 WHERE contract_name = $2")
+                    .bind(owner.0)
+                    .bind(contract_name.0),
+            );
+        }
+
+        // Handling name services, configured on TLDs
+        for (contract_name, config) in block.updated_name_services {
+            self.handler_store.sql_updates.push(
+                sqlx::query::<Postgres>(
+                    "UPDATE contracts SET name_service = $1 WHERE contract_name = $2",
+                )
+                .bind(sqlx::types::Json(config))
+                .bind(contract_name.0),
+            );
+        }
+
+        // Handling leased names, registered or renewed
+        for (contract_name, expiry_height) in block.updated_expiries {
+            let expiry_height: i64 = expiry_height
+                .0
+                .try_into()
+                .context("Converting expiry height to i64")?;
+            self.handler_store.sql_updates.push(
+                sqlx::query::<Postgres>(
+                    "UPDATE contracts SET expiry_height = $1 WHERE contract_name = $2",
+                )
+                .bind(expiry_height)
+                .bind(contract_name.0),
+            );
+        }
+
+        // Handling scheduled contract program ids
+        for (contract_name, update) in block.scheduled_program_ids {
+            let activation_height: i64 = update
+                .activation_height
+                .0
+                .try_into()
+                .context("Converting activation height to i64")?;
+            self.handler_store.sql_updates.push(
+                sqlx::query::<Postgres>(
+                    "UPDATE contracts SET pending_program_id = $1, pending_program_id_activation = $2 WHERE contract_name = $3",
+                )
+                .bind(update.program_id.0)
+                .bind(activation_height)
+                .bind(contract_name.0),
+            );
+        }
+
+        // Handling cancelled contract program ids
+        for contract_name in block.cancelled_program_ids {
+            self.handler_store.sql_updates.push(
+                sqlx::query::<Postgres>(
+                    "UPDATE contracts SET pending_program_id = NULL, pending_program_id_activation = NULL WHERE contract_name = $1",
+                )
+                .bind(contract_name.0),
+            );
+        }
+
+        // Handling updated contract timeout windows
         for (contract_name, timeout_window) in block.updated_timeout_windows {
             let contract_name = contract_name.0;
 
@@ -0,0 +1,21 @@
+-- Indexes backing the transaction search endpoint.
+-- Listings are ordered by descending (block_height, index), transactions not in a block yet first.
+CREATE INDEX idx_transactions_search_order
+  ON transactions (
+    (COALESCE(block_height, 2147483647)) DESC,
+    (COALESCE(index, 2147483647))        DESC,
+    tx_hash                              DESC,
+    parent_dp_hash                       DESC
+  );
+
+CREATE INDEX idx_transactions_identity_search_order
+  ON transactions (
+    identity,
+    (COALESCE(block_height, 2147483647)) DESC,
+    (COALESCE(index, 2147483647))        DESC
+  );
+
+CREATE INDEX idx_transactions_status ON transactions (transaction_status);
+CREATE INDEX idx_transactions_type ON transactions (transaction_type);
+CREATE INDEX idx_transactions_block_height ON transactions (block_height);
+CREATE INDEX idx_blocks_timestamp ON blocks (timestamp);
@@ -0,0 +1,14 @@
+-- Relation between identities and the blob transactions they sent, backing the identity views.
+CREATE TABLE txs_identities (
+    identity TEXT NOT NULL,        -- Identity of the sender
+    parent_dp_hash TEXT NOT NULL,  -- Foreign key linking to the parent_dp_hash BlobTransactions
+    tx_hash TEXT NOT NULL,         -- Foreign key linking to the tx_hash BlobTransactions
+    PRIMARY KEY (identity, parent_dp_hash, tx_hash)
+);
+CREATE INDEX idx_txs_identities_tx ON txs_identities (parent_dp_hash, tx_hash);
+
+INSERT INTO txs_identities (identity, parent_dp_hash, tx_hash)
+SELECT identity, parent_dp_hash, tx_hash
+FROM transactions
+WHERE identity IS NOT NULL AND block_hash IS NOT NULL
+ON CONFLICT DO NOTHING;
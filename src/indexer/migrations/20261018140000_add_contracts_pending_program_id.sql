@@ -0,0 +1,3 @@
+-- Program id change scheduled for a contract, applied once the chain reaches the activation height.
+ALTER TABLE contracts ADD COLUMN pending_program_id BYTEA;
+ALTER TABLE contracts ADD COLUMN pending_program_id_activation BIGINT;
@@ -0,0 +1,3 @@
+-- Identity managing a contract registered under the hyle TLD.
+ALTER TABLE contracts ADD COLUMN owner TEXT;
+CREATE INDEX idx_contracts_owner ON contracts(owner);
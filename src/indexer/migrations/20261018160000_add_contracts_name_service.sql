@@ -0,0 +1,4 @@
+-- Lease terms of a TLD in name service mode, and expiry height of the names leased from one.
+ALTER TABLE contracts ADD COLUMN name_service JSONB;
+ALTER TABLE contracts ADD COLUMN expiry_height BIGINT;
+CREATE INDEX idx_contracts_expiry_height ON contracts(expiry_height);
@@ -0,0 +1,5 @@
+-- Full-text search over the blob data, backing the blob_text filter of the transaction search endpoint.
+-- Non-printable bytes are escaped, so the readable parts of binary blobs are indexed as well.
+ALTER TABLE blobs ADD COLUMN data_search TSVECTOR
+  GENERATED ALWAYS AS (to_tsvector('simple', encode(data, 'escape'))) STORED;
+CREATE INDEX idx_blobs_data_search ON blobs USING GIN (data_search);
@@ -0,0 +1,2 @@
+-- The identity views read the identity column of the transactions, indexed for the search endpoint.
+DROP TABLE txs_identities;
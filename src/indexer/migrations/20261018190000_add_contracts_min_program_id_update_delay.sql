@@ -0,0 +1,2 @@
+-- Minimum notice, in blocks, of the program id changes of a contract, required by its owner.
+ALTER TABLE contracts ADD COLUMN min_program_id_update_delay BIGINT;
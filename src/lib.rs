@@ -28,6 +28,7 @@ pub mod node_state;
 pub mod p2p;
 pub mod rest;
 pub mod single_node_consensus;
+pub mod state_sync;
 pub mod tcp_server;
 pub mod utils;
 
@@ -9,11 +9,13 @@ use crate::{
     p2p::network::{
         HeaderSignableData, HeaderSigner, IntoHeaderSignableData, MsgWithHeader, OutboundMessage,
     },
+    state_sync::{LanesTip, StateSyncEvent},
     utils::{
         conf::SharedConf,
         serialize::{arc_rwlock_borsh, BorshableIndexMap},
     },
 };
+use admission::SharedAdmission;
 use anyhow::{bail, Context, Result};
 use api::RestApiMessage;
 use block_construction::BlockUnderConstruction;
@@ -45,11 +47,13 @@ use storage_fjall::LanesStorage;
 use strum_macros::IntoStaticStr;
 use tracing::{debug, info, trace, warn};
 
+pub mod admission;
 pub mod api;
 pub mod block_construction;
 pub mod metrics;
 pub mod module;
 pub mod own_lane;
+pub mod priority;
 pub mod storage;
 pub mod storage_fjall;
 pub mod storage_memory;
@@ -60,6 +64,9 @@ pub mod verify_tx;
 #[derive(Debug, Clone)]
 pub struct QueryNewCut(pub Staking);
 
+#[derive(Debug, Clone)]
+pub struct QueryLanesTip {}
+
 #[derive(Debug, Default, Clone, BorshSerialize, BorshDeserialize)]
 pub struct KnownContracts(pub HashMap<ContractName, (Verifier, ProgramId)>);
 
@@ -90,7 +97,9 @@ struct MempoolBusClient {
     receiver(ConsensusEvent),
     receiver(GenesisEvent),
     receiver(NodeStateEvent),
+    receiver(StateSyncEvent),
     receiver(Query<QueryNewCut, Cut>),
+    receiver(Query<QueryLanesTip, LanesTip>),
 }
 }
 
@@ -99,6 +108,8 @@ type UnaggregatedPoDA = Vec<ValidatorDAG>;
 #[derive(Default, BorshSerialize, BorshDeserialize)]
 pub struct MempoolStore {
     // own_lane.rs
+    #[borsh(skip)]
+    admission: SharedAdmission,
     // TODO: implement serialization, probably with a custom future that yields the unmodified Tx
     // on cancellation
     #[borsh(skip)]
@@ -304,6 +315,34 @@ impl Mempool {
         );
     }
 
+    fn handle_state_sync_event(&mut self, event: StateSyncEvent) {
+        let StateSyncEvent::Synced {
+            staking,
+            lanes_tip,
+            contracts,
+            ..
+        } = event;
+        self.staking = staking;
+        {
+            #[allow(clippy::expect_used, reason = "not held across await")]
+            let mut known_contracts = self.known_contracts.write().expect("logic issue");
+            for contract in contracts {
+                known_contracts.register_contract(
+                    &contract.name,
+                    &contract.verifier,
+                    &contract.program_id,
+                );
+            }
+        }
+        // Lanes we already know about are kept up to date by the mempool sync
+        for (lane_id, (dp_hash, size)) in lanes_tip {
+            if self.lanes.get_lane_hash_tip(&lane_id).is_none() {
+                debug!("Setting synced lane tip {} for lane {}", dp_hash, lane_id);
+                self.lanes.update_lane_tip(lane_id, dp_hash, size);
+            }
+        }
+    }
+
     fn handle_contract_update(&mut self, contract_name: ContractName, program_id: ProgramId) {
         #[allow(clippy::expect_used, reason = "not held across await")]
         let mut known_contracts = self.known_contracts.write().expect("logic issue");
@@ -381,6 +420,7 @@ impl Mempool {
                 );
 
                 self.staking = cpp.staking.clone();
+                self.new_admission_slot();
 
                 let cut = cpp.consensus_proposal.cut.clone();
                 let previous_cut = self
@@ -0,0 +1,297 @@
+//! Admission control of the transactions submitted to the mempool.
+//!
+//! The REST and TCP APIs check the quotas before forwarding a transaction so they can
+//! answer with a typed error, and the mempool enforces them again when the transaction
+//! is added to the pending transactions, as several transactions can be checked concurrently.
+//! A transaction the API accepted can thus still be dropped, which the client only sees by
+//! watching it.
+//! The per-client rate limit counts submissions, so it is only applied by the APIs.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, RwLock},
+};
+
+use hyle_model::{api::MempoolAdmissionError, utils::TimestampMs};
+
+use super::priority::ClientRateLimiter;
+use crate::{model::*, utils::conf::MempoolConf};
+
+pub type SharedAdmission = Arc<RwLock<AdmissionControl>>;
+
+/// Tracks the transactions waiting for dissemination against the configured quotas.
+#[derive(Debug, Default)]
+pub struct AdmissionControl {
+    conf: MempoolConf,
+    pending_txs_per_identity: HashMap<Identity, u32>,
+    pending_bytes: u64,
+    /// Bytes received per contract since the beginning of the slot
+    contract_bytes_in_slot: HashMap<ContractName, u64>,
+    rate_limiter: ClientRateLimiter,
+}
+
+/// Bytes each contract receives from the transaction
+fn contract_bytes(tx: &Transaction) -> Vec<(&ContractName, u64)> {
+    match &tx.transaction_data {
+        TransactionData::Blob(blob_tx) => blob_tx
+            .blobs
+            .iter()
+            .map(|blob| (&blob.contract_name, blob.data.0.len() as u64))
+            .collect(),
+        TransactionData::Proof(proof_tx) => {
+            vec![(&proof_tx.contract_name, proof_tx.proof.0.len() as u64)]
+        }
+        TransactionData::VerifiedProof(proof_tx) => {
+            vec![(&proof_tx.contract_name, proof_tx.proof_size as u64)]
+        }
+    }
+}
+
+fn identity(tx: &Transaction) -> Option<&Identity> {
+    match &tx.transaction_data {
+        TransactionData::Blob(blob_tx) => Some(&blob_tx.identity),
+        _ => None,
+    }
+}
+
+impl AdmissionControl {
+    pub fn new(conf: MempoolConf) -> Self {
+        Self {
+            conf,
+            ..Default::default()
+        }
+    }
+
+    pub fn shared(conf: MempoolConf) -> SharedAdmission {
+        Arc::new(RwLock::new(Self::new(conf)))
+    }
+
+    /// Checks that the transaction fits in the quotas, without accounting for it.
+    pub fn check(&self, tx: &Transaction) -> Result<(), MempoolAdmissionError> {
+        let max_pending_bytes = self.conf.max_pending_bytes;
+        if max_pending_bytes > 0
+            && self.pending_bytes + tx.estimate_size() as u64 > max_pending_bytes
+        {
+            return Err(MempoolAdmissionError::MempoolFull { max_pending_bytes });
+        }
+
+        let max_pending_txs = self.conf.max_pending_txs_per_identity;
+        if let Some(identity) = identity(tx) {
+            let pending = self
+                .pending_txs_per_identity
+                .get(identity)
+                .copied()
+                .unwrap_or(0);
+            if max_pending_txs > 0 && pending >= max_pending_txs {
+                return Err(MempoolAdmissionError::IdentityQuotaExceeded {
+                    identity: identity.clone(),
+                    max_pending_txs,
+                });
+            }
+        }
+
+        let max_bytes_per_slot = self.conf.max_contract_bytes_per_slot;
+        if max_bytes_per_slot > 0 {
+            let mut added: HashMap<&ContractName, u64> = HashMap::new();
+            for (contract_name, bytes) in contract_bytes(tx) {
+                *added.entry(contract_name).or_default() += bytes;
+            }
+            for (contract_name, bytes) in added {
+                let used = self
+                    .contract_bytes_in_slot
+                    .get(contract_name)
+                    .copied()
+                    .unwrap_or(0);
+                if used + bytes > max_bytes_per_slot {
+                    return Err(MempoolAdmissionError::ContractQuotaExceeded {
+                        contract_name: contract_name.clone(),
+                        max_bytes_per_slot,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the quotas and counts a transaction submitted through an API against the rate
+    /// limit of the client that sent it, before it is forwarded to the mempool.
+    pub fn submit(
+        &mut self,
+        tx: &Transaction,
+        client: IpAddr,
+        now: TimestampMs,
+    ) -> Result<(), MempoolAdmissionError> {
+        self.check(tx)?;
+        self.rate_limiter.check(client, now, &self.conf)
+    }
+
+    /// Checks the quotas and accounts for the transaction if it fits.
+    pub fn admit(&mut self, tx: &Transaction) -> Result<(), MempoolAdmissionError> {
+        self.check(tx)?;
+        self.record(tx);
+        Ok(())
+    }
+
+    /// Accounts for a pending transaction regardless of the quotas,
+    /// e.g. when reloading the pending transactions from disk.
+    pub fn record(&mut self, tx: &Transaction) {
+        self.pending_bytes += tx.estimate_size() as u64;
+        if let Some(identity) = identity(tx) {
+            *self
+                .pending_txs_per_identity
+                .entry(identity.clone())
+                .or_default() += 1;
+        }
+        for (contract_name, bytes) in contract_bytes(tx) {
+            *self
+                .contract_bytes_in_slot
+                .entry(contract_name.clone())
+                .or_default() += bytes;
+        }
+    }
+
+    /// The transaction is no longer pending. Bytes used in the slot are kept until the next slot.
+    pub fn release(&mut self, tx: &Transaction) {
+        self.pending_bytes = self.pending_bytes.saturating_sub(tx.estimate_size() as u64);
+        if let Some(identity) = identity(tx) {
+            if let Some(count) = self.pending_txs_per_identity.get_mut(identity) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    self.pending_txs_per_identity.remove(identity);
+                }
+            }
+        }
+    }
+
+    /// Resets the per-contract quotas
+    pub fn new_slot(&mut self) {
+        self.contract_bytes_in_slot.clear();
+    }
+
+    pub fn pending_bytes(&self) -> u64 {
+        self.pending_bytes
+    }
+
+    pub fn pending_identities(&self) -> usize {
+        self.pending_txs_per_identity.len()
+    }
+
+    pub fn contract_bytes_in_slot(&self) -> &HashMap<ContractName, u64> {
+        &self.contract_bytes_in_slot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blob_tx(identity: &str, contract: &str, len: usize) -> Transaction {
+        BlobTransaction::new(
+            identity,
+            vec![Blob {
+                contract_name: contract.into(),
+                data: BlobData(vec![0; len]),
+            }],
+        )
+        .into()
+    }
+
+    #[test]
+    fn test_identity_quota() {
+        let mut admission = AdmissionControl::new(MempoolConf {
+            max_pending_txs_per_identity: 2,
+            ..Default::default()
+        });
+        let first = blob_tx("alice@hydentity", "c1", 1);
+
+        assert!(admission.admit(&first).is_ok());
+        assert!(admission
+            .admit(&blob_tx("alice@hydentity", "c1", 2))
+            .is_ok());
+        assert_eq!(
+            admission.admit(&blob_tx("alice@hydentity", "c1", 3)),
+            Err(MempoolAdmissionError::IdentityQuotaExceeded {
+                identity: "alice@hydentity".into(),
+                max_pending_txs: 2,
+            })
+        );
+        assert!(admission.check(&blob_tx("bob@hydentity", "c1", 3)).is_ok());
+
+        // Once disseminated, the identity can submit again
+        admission.release(&first);
+        assert!(admission
+            .admit(&blob_tx("alice@hydentity", "c1", 3))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut admission = AdmissionControl::new(MempoolConf {
+            max_txs_per_client: 2,
+            rate_limit_window: std::time::Duration::from_secs(3600),
+            ..Default::default()
+        });
+        let client = IpAddr::from([10, 0, 0, 1]);
+
+        assert!(admission
+            .submit(&blob_tx("alice@hydentity", "c1", 1), client, TimestampMs(0))
+            .is_ok());
+        assert!(admission
+            .submit(&blob_tx("bob@hydentity", "c1", 1), client, TimestampMs(1))
+            .is_ok());
+        // Rotating the identity doesn't get around the limit
+        assert_eq!(
+            admission.submit(&blob_tx("carol@hydentity", "c1", 1), client, TimestampMs(2)),
+            Err(MempoolAdmissionError::RateLimited {
+                max_txs: 2,
+                window_ms: 3_600_000,
+            })
+        );
+        assert!(admission
+            .submit(
+                &blob_tx("alice@hydentity", "c1", 1),
+                IpAddr::from([10, 0, 0, 2]),
+                TimestampMs(2)
+            )
+            .is_ok());
+        // Submissions are not pending transactions
+        assert_eq!(admission.pending_identities(), 0);
+    }
+
+    #[test]
+    fn test_contract_quota_and_pending_bytes() {
+        let mut admission = AdmissionControl::new(MempoolConf {
+            max_contract_bytes_per_slot: 100,
+            ..Default::default()
+        });
+        let tx = blob_tx("alice@hydentity", "c1", 60);
+        assert!(admission.admit(&tx).is_ok());
+        admission.release(&tx);
+        // Released transactions still count for the slot
+        assert_eq!(
+            admission.check(&blob_tx("bob@hydentity", "c1", 60)),
+            Err(MempoolAdmissionError::ContractQuotaExceeded {
+                contract_name: "c1".into(),
+                max_bytes_per_slot: 100,
+            })
+        );
+        assert!(admission.check(&blob_tx("bob@hydentity", "c2", 60)).is_ok());
+        admission.new_slot();
+        assert!(admission.check(&blob_tx("bob@hydentity", "c1", 60)).is_ok());
+
+        let tx = blob_tx("alice@hydentity", "c1", 10);
+        let mut admission = AdmissionControl::new(MempoolConf {
+            max_pending_bytes: tx.estimate_size() as u64 + 1,
+            ..Default::default()
+        });
+        assert!(admission.admit(&tx).is_ok());
+        assert!(matches!(
+            admission.check(&tx),
+            Err(MempoolAdmissionError::MempoolFull { .. })
+        ));
+        admission.release(&tx);
+        assert_eq!(admission.pending_bytes(), 0);
+        assert!(admission.check(&tx).is_ok());
+    }
+}
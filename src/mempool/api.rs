@@ -1,12 +1,23 @@
 use anyhow::anyhow;
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json, Router};
+use std::net::IpAddr;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json, Router,
+};
 use borsh::{BorshDeserialize, BorshSerialize};
 use hyle_contract_sdk::TxHash;
-use hyle_model::{api::APIRegisterContract, RegisterContractAction, StructuredBlobData};
+use hyle_model::{
+    api::{APIRegisterContract, MempoolAdmissionError},
+    RegisterContractAction, StructuredBlobData,
+};
 use hyle_modules::{
     bus::SharedMessageBus, modules::SharedBuildApiCtx,
     node_state::contract_registration::validate_contract_registration_metadata,
 };
+use hyle_net::{clock::TimestampMsClock, net::HyleNetSocketAddr};
 use serde::{Deserialize, Serialize};
 use tracing::info;
 use utoipa::OpenApi;
@@ -18,6 +29,8 @@ use crate::{
     rest::AppError,
 };
 
+use super::admission::SharedAdmission;
+
 #[derive(Debug, Serialize, Deserialize, Clone, BorshSerialize, BorshDeserialize)]
 pub enum RestApiMessage {
     NewTx(Transaction),
@@ -31,14 +44,20 @@ struct RestBusClient {
 
 pub struct RouterState {
     bus: RestBusClient,
+    admission: SharedAdmission,
 }
 
 #[derive(OpenApi)]
 struct MempoolAPI;
 
-pub async fn api(bus: &SharedMessageBus, ctx: &SharedBuildApiCtx) -> Router<()> {
+pub async fn api(
+    bus: &SharedMessageBus,
+    ctx: &SharedBuildApiCtx,
+    admission: SharedAdmission,
+) -> Router<()> {
     let state = RouterState {
         bus: RestBusClient::new_from_bus(bus.new_handle()).await,
+        admission,
     };
 
     let (router, api) = OpenApiRouter::with_openapi(MempoolAPI::openapi())
@@ -53,17 +72,42 @@ pub async fn api(bus: &SharedMessageBus, ctx: &SharedBuildApiCtx) -> Router<()>
     router.with_state(state)
 }
 
+fn admission_status(error: &MempoolAdmissionError) -> StatusCode {
+    match error {
+        MempoolAdmissionError::IdentityQuotaExceeded { .. }
+        | MempoolAdmissionError::ContractQuotaExceeded { .. }
+        | MempoolAdmissionError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+        MempoolAdmissionError::MempoolFull { .. } => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+/// Checks the transaction against the admission control and forwards it to the mempool.
+/// A transaction accepted here can still be dropped by the mempool: the quotas are checked
+/// again when it is added to the pending transactions, and proofs are verified there.
 async fn handle_send(
     mut state: RouterState,
+    client: IpAddr,
     payload: TransactionData,
-) -> Result<Json<TxHash>, AppError> {
+) -> Result<Response, AppError> {
     let tx: Transaction = payload.into();
     let tx_hash = tx.hashed();
+
+    #[allow(clippy::expect_used, reason = "not held across await")]
+    let checked =
+        state
+            .admission
+            .write()
+            .expect("logic issue")
+            .submit(&tx, client, TimestampMsClock::now());
+    if let Err(error) = checked {
+        info!("Rejecting tx {}: {}", tx_hash, error);
+        return Ok((admission_status(&error), Json(error)).into_response());
+    }
+
     state
         .bus
         .send(RestApiMessage::NewTx(tx))
-        .map(|_| tx_hash)
-        .map(Json)
+        .map(|_| Json(tx_hash).into_response())
         .map_err(|err| AppError(StatusCode::INTERNAL_SERVER_ERROR, anyhow!(err)))
 }
 
@@ -72,11 +116,14 @@ async fn handle_send(
     path = "/tx/send/blob",
     tag = "Mempool",
     responses(
-        (status = OK, description = "Send blob transaction", body = TxHash)
+        (status = OK, description = "Send blob transaction. The mempool can still drop it, watch it over the TCP API to follow it", body = TxHash),
+        (status = TOO_MANY_REQUESTS, description = "Over a quota or the rate limit of the mempool admission control", body = MempoolAdmissionError),
+        (status = SERVICE_UNAVAILABLE, description = "The mempool is full", body = MempoolAdmissionError)
     )
 )]
 pub async fn send_blob_transaction(
     State(state): State<RouterState>,
+    ConnectInfo(client): ConnectInfo<HyleNetSocketAddr>,
     Json(payload): Json<BlobTransaction>,
 ) -> Result<impl IntoResponse, AppError> {
     info!("Got blob transaction {}", payload.hashed());
@@ -114,7 +161,7 @@ pub async fn send_blob_transaction(
             anyhow!("Too many blobs in transaction"),
         ));
     }
-    handle_send(state, TransactionData::Blob(payload)).await
+    handle_send(state, client.0.ip(), TransactionData::Blob(payload)).await
 }
 
 #[utoipa::path(
@@ -122,15 +169,18 @@ pub async fn send_blob_transaction(
     path = "/tx/send/proof",
     tag = "Mempool",
     responses(
-        (status = OK, description = "Send proof transaction", body = TxHash)
+        (status = OK, description = "Send proof transaction. The mempool can still drop it, watch it over the TCP API to follow it", body = TxHash),
+        (status = TOO_MANY_REQUESTS, description = "Over a quota or the rate limit of the mempool admission control", body = MempoolAdmissionError),
+        (status = SERVICE_UNAVAILABLE, description = "The mempool is full", body = MempoolAdmissionError)
     )
 )]
 pub async fn send_proof_transaction(
     State(state): State<RouterState>,
+    ConnectInfo(client): ConnectInfo<HyleNetSocketAddr>,
     Json(payload): Json<ProofTransaction>,
 ) -> Result<impl IntoResponse, AppError> {
     info!("Got proof transaction {}", payload.hashed());
-    handle_send(state, TransactionData::Proof(payload)).await
+    handle_send(state, client.0.ip(), TransactionData::Proof(payload)).await
 }
 
 #[utoipa::path(
@@ -138,11 +188,14 @@ pub async fn send_proof_transaction(
     path = "/contract/register",
     tag = "Mempool",
     responses(
-        (status = OK, description = "Register contract", body = TxHash)
+        (status = OK, description = "Register contract. The mempool can still drop it, watch it over the TCP API to follow it", body = TxHash),
+        (status = TOO_MANY_REQUESTS, description = "Over a quota or the rate limit of the mempool admission control", body = MempoolAdmissionError),
+        (status = SERVICE_UNAVAILABLE, description = "The mempool is full", body = MempoolAdmissionError)
     )
 )]
 pub async fn register_contract(
     State(state): State<RouterState>,
+    ConnectInfo(client): ConnectInfo<HyleNetSocketAddr>,
     Json(payload): Json<APIRegisterContract>,
 ) -> Result<impl IntoResponse, AppError> {
     let owner = "hyle".into();
@@ -157,7 +210,7 @@ pub async fn register_contract(
 
     let tx = BlobTransaction::from(payload);
 
-    handle_send(state, TransactionData::Blob(tx)).await
+    handle_send(state, client.0.ip(), TransactionData::Blob(tx)).await
 }
 
 impl Clone for RouterState {
@@ -168,6 +221,7 @@ impl Clone for RouterState {
                 Pick::<BusMetrics>::get(&self.bus).clone(),
                 Pick::<tokio::sync::broadcast::Sender<RestApiMessage>>::get(&self.bus).clone(),
             ),
+            admission: self.admission.clone(),
         }
     }
 }
@@ -6,7 +6,7 @@ use opentelemetry::{
 
 use crate::model::ValidatorPublicKey;
 
-use super::QueryNewCut;
+use super::{admission::AdmissionControl, QueryNewCut};
 
 #[derive(Clone)]
 pub struct MempoolMetrics {
@@ -16,6 +16,9 @@ pub struct MempoolMetrics {
     sync_reply: Counter<u64>,
     mempool_sync: Counter<u64>,
     tx_waiting_dissemination: Gauge<u64>,
+    pending_bytes: Gauge<u64>,
+    pending_identities: Gauge<u64>,
+    max_contract_bytes_in_slot: Gauge<u64>,
     new_cut: Counter<u64>,
 
     received_dp: Counter<u64>,
@@ -48,6 +51,15 @@ impl MempoolMetrics {
             tx_waiting_dissemination: my_meter
                 .u64_gauge(format!("{mempool}_tx_waiting_dissemination"))
                 .build(),
+            pending_bytes: my_meter
+                .u64_gauge(format!("{mempool}_pending_bytes"))
+                .build(),
+            pending_identities: my_meter
+                .u64_gauge(format!("{mempool}_pending_identities"))
+                .build(),
+            max_contract_bytes_in_slot: my_meter
+                .u64_gauge(format!("{mempool}_max_contract_bytes_in_slot"))
+                .build(),
             new_cut: my_meter.u64_counter(format!("{mempool}_new_cut")).build(),
 
             received_dp: my_meter
@@ -87,6 +99,22 @@ impl MempoolMetrics {
             .record(nb as u64, &[KeyValue::new("status", "pending")])
     }
 
+    pub fn snapshot_admission(&self, admission: &AdmissionControl) {
+        self.pending_bytes.record(admission.pending_bytes(), &[]);
+        self.pending_identities
+            .record(admission.pending_identities() as u64, &[]);
+        // Any client can name any contract, so don't label by contract
+        self.max_contract_bytes_in_slot.record(
+            admission
+                .contract_bytes_in_slot()
+                .values()
+                .max()
+                .copied()
+                .unwrap_or(0),
+            &[],
+        );
+    }
+
     pub fn add_api_tx(&self, kind: &'static str) {
         self.api_tx.add(
             1,
@@ -2,8 +2,12 @@ use hyle_modules::{log_error, module_handle_messages};
 use std::{collections::BTreeMap, sync::Arc, time::Duration};
 
 use crate::{
-    consensus::ConsensusEvent, model::*, node_state::module::NodeStateEvent,
-    p2p::network::MsgWithHeader, utils::conf::P2pMode,
+    consensus::ConsensusEvent,
+    model::*,
+    node_state::module::NodeStateEvent,
+    p2p::network::MsgWithHeader,
+    state_sync::{LanesTip, StateSyncEvent},
+    utils::conf::P2pMode,
 };
 
 use client_sdk::tcp_client::TcpServerMessage;
@@ -11,7 +15,7 @@ use hyle_model::{DataProposalHash, LaneBytesSize, LaneId, ProgramId, Verifier};
 use hyle_modules::{bus::SharedMessageBus, modules::Module};
 use tracing::warn;
 
-use super::{api::RestApiMessage, MempoolNetMessage, QueryNewCut};
+use super::{api::RestApiMessage, MempoolNetMessage, QueryLanesTip, QueryNewCut};
 
 use crate::model::SharedRunContext;
 
@@ -27,19 +31,32 @@ impl Module for Mempool {
 
     async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
         let metrics = MempoolMetrics::global(ctx.config.id.clone());
-        let api = api::api(&bus, &ctx.api).await;
-        if let Ok(mut guard) = ctx.api.router.lock() {
-            if let Some(router) = guard.take() {
-                guard.replace(router.nest("/v1/", api));
+        // Archive nodes are read-only, they don't accept transactions
+        if ctx.config.p2p.mode != P2pMode::Archive {
+            let api = api::api(&bus, &ctx.api, ctx.admission.clone()).await;
+            if let Ok(mut guard) = ctx.api.router.lock() {
+                if let Some(router) = guard.take() {
+                    guard.replace(router.nest("/v1/", api));
+                }
             }
         }
         let bus = MempoolBusClient::new_from_bus(bus.new_handle()).await;
 
-        let attributes = Self::load_from_disk::<MempoolStore>(
+        let mut attributes = Self::load_from_disk::<MempoolStore>(
             ctx.config.data_directory.join("mempool.bin").as_path(),
         )
         .unwrap_or_default();
 
+        // Account for the pending transactions reloaded from disk
+        attributes.admission = ctx.admission.clone();
+        {
+            #[allow(clippy::expect_used, reason = "not held across await")]
+            let mut admission = attributes.admission.write().expect("logic issue");
+            for tx in attributes.waiting_dissemination_txs.values() {
+                admission.record(tx);
+            }
+        }
+
         let lanes_tip =
             Self::load_from_disk::<BTreeMap<LaneId, (DataProposalHash, LaneBytesSize)>>(
                 ctx.config
@@ -112,7 +129,8 @@ impl Module for Mempool {
             listen<NodeStateEvent> cmd => {
                 let NodeStateEvent::NewBlock(block) = cmd;
                 // In this p2p mode we don't receive consensus events so we must update manually.
-                if self.conf.p2p.mode == P2pMode::LaneManager {
+                if matches!(self.conf.p2p.mode, P2pMode::LaneManager | P2pMode::Archive) {
+                    self.new_admission_slot();
                     if let Err(e) = self.staking.process_block(block.as_ref()) {
                         tracing::error!("Error processing block in mempool: {:?}", e);
                     }
@@ -125,9 +143,15 @@ impl Module for Mempool {
                 }
 
             }
+            listen<StateSyncEvent> evt => {
+                self.handle_state_sync_event(evt);
+            }
             command_response<QueryNewCut, Cut> staking => {
                 self.handle_querynewcut(staking)
             }
+            command_response<QueryLanesTip, LanesTip> _ => {
+                Ok(self.lanes.lanes_tip.clone())
+            }
             Some(event) = self.inner.processing_dps.join_next() => {
                 if let Ok(event) = log_error!(event, "Processing DPs from JoinSet") {
                     if let Ok(event) = log_error!(event, "Error in running task") {
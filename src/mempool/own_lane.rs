@@ -6,10 +6,12 @@ use crate::{bus::BusClientSender, model::*};
 use anyhow::{bail, Context, Result};
 use client_sdk::tcp_client::TcpServerMessage;
 use futures::StreamExt;
+use indexmap::IndexMap;
 use std::collections::HashSet;
 use std::sync::Arc;
 use tracing::{debug, trace};
 
+use super::priority::priority_fee;
 use super::storage::LaneEntryMetadata;
 use super::verifiers::{verify_proof, verify_recursive_proof};
 use super::{api::RestApiMessage, storage::Storage};
@@ -24,6 +26,16 @@ impl super::Mempool {
         LaneId(self.crypto.validator_pubkey().clone())
     }
 
+    /// Resets the per-contract admission quotas at the beginning of a slot
+    pub(super) fn new_admission_slot(&mut self) {
+        #[allow(clippy::expect_used, reason = "not held across await")]
+        self.inner
+            .admission
+            .write()
+            .expect("logic issue")
+            .new_slot();
+    }
+
     pub(super) fn on_data_vote(&mut self, vdag: ValidatorDAG) -> Result<()> {
         self.metrics.on_data_vote.add(1, &[]);
 
@@ -247,27 +259,50 @@ impl super::Mempool {
 
     /// Inits DataProposal preparation if there are pending transactions
     fn init_dp_preparation_if_pending(&mut self) -> Result<Option<DataProposal>> {
-        self.metrics
-            .snapshot_pending_tx(self.waiting_dissemination_txs.len());
+        self.snapshot_pending_metrics();
         if self.waiting_dissemination_txs.is_empty()
             || !self.own_data_proposal_in_preparation.is_empty()
         {
             return Ok(None);
         }
 
+        // Highest priority fees first, arrival order otherwise (the sort is stable)
+        let own_validator = self.crypto.validator_pubkey().clone();
+        let mut by_priority: Vec<(u128, TxHash)> = self
+            .waiting_dissemination_txs
+            .iter()
+            .map(|(tx_hash, tx)| (priority_fee(tx, &own_validator), tx_hash.clone()))
+            .collect();
+        by_priority.sort_by_key(|(fee, _)| std::cmp::Reverse(*fee));
+
         let mut cumulative_size = 0;
-        let mut current_idx = 0;
-        while cumulative_size < 40_000_000 && current_idx < self.waiting_dissemination_txs.len() {
-            if let Some((_tx_hash, tx)) = self.waiting_dissemination_txs.get_index(current_idx) {
+        let mut selected: Vec<TxHash> = vec![];
+        for (_, tx_hash) in by_priority {
+            if cumulative_size >= 40_000_000 {
+                break;
+            }
+            if let Some(tx) = self.waiting_dissemination_txs.get(&tx_hash) {
                 cumulative_size += tx.estimate_size();
-                current_idx += 1;
+                selected.push(tx_hash);
             }
         }
-        let collected_txs: Vec<Transaction> = self
-            .waiting_dissemination_txs
-            .drain(0..current_idx)
-            .map(|(_tx_hash, tx)| tx)
+        let selected_set: HashSet<&TxHash> = selected.iter().collect();
+        let (mut taken, remaining): (IndexMap<TxHash, Transaction>, IndexMap<TxHash, Transaction>) =
+            std::mem::take(&mut self.waiting_dissemination_txs.0)
+                .into_iter()
+                .partition(|(tx_hash, _)| selected_set.contains(tx_hash));
+        self.waiting_dissemination_txs.0 = remaining;
+        let collected_txs: Vec<Transaction> = selected
+            .iter()
+            .filter_map(|tx_hash| taken.swap_remove(tx_hash))
             .collect();
+        {
+            #[allow(clippy::expect_used, reason = "not held across await")]
+            let mut admission = self.inner.admission.write().expect("logic issue");
+            for tx in collected_txs.iter() {
+                admission.release(tx);
+            }
+        }
 
         debug!(
             "🌝 Creating new data proposals with {} txs (est. size {}). {} tx remain.",
@@ -334,6 +369,8 @@ impl super::Mempool {
     pub(super) fn handle_tcp_server_message(&mut self, command: TcpServerMessage) -> Result<()> {
         match command {
             TcpServerMessage::NewTx(tx) => self.on_new_api_tx(tx)?,
+            // Watches are handled by the TCP server
+            TcpServerMessage::WatchTx(_) => {}
         }
         Ok(())
     }
@@ -406,6 +443,17 @@ impl super::Mempool {
             debug!("Dropping duplicate tx {}", tx_hash);
             self.metrics.drop_api_tx(tx_type);
         } else {
+            #[allow(clippy::expect_used, reason = "not held across await")]
+            let admitted = self
+                .inner
+                .admission
+                .write()
+                .expect("logic issue")
+                .admit(&tx);
+            if let Err(e) = admitted {
+                self.metrics.drop_api_tx(tx_type);
+                bail!("Tx {} refused by admission control: {}", tx_hash, e);
+            }
             self.waiting_dissemination_txs
                 .insert(tx_hash.clone(), tx.clone());
 
@@ -425,12 +473,19 @@ impl super::Mempool {
                 .context("Sending Status event for TX")?;
         }
 
-        self.metrics
-            .snapshot_pending_tx(self.waiting_dissemination_txs.len());
+        self.snapshot_pending_metrics();
 
         Ok(())
     }
 
+    fn snapshot_pending_metrics(&self) {
+        self.metrics
+            .snapshot_pending_tx(self.waiting_dissemination_txs.len());
+        #[allow(clippy::expect_used, reason = "not held across await")]
+        self.metrics
+            .snapshot_admission(&self.inner.admission.read().expect("logic issue"));
+    }
+
     fn process_proof_tx(
         known_contracts: Arc<std::sync::RwLock<KnownContracts>>,
         mut tx: Transaction,
@@ -512,6 +567,7 @@ pub mod test {
     };
     use anyhow::Result;
     use hyle_crypto::BlstCrypto;
+    use hyllar::HyllarAction;
 
     use crate::mempool::test::*;
 
@@ -600,6 +656,61 @@ pub mod test {
         Ok(())
     }
 
+    fn make_fee_tx(identity: &str, holder: &ValidatorPublicKey, fee: u128) -> Transaction {
+        BlobTransaction::new(
+            identity,
+            vec![
+                StakingAction::DepositForFees {
+                    holder: holder.clone(),
+                    amount: fee,
+                }
+                .as_blob("staking".into(), None, None),
+                HyllarAction::Transfer {
+                    recipient: "staking".to_string(),
+                    amount: fee,
+                }
+                .as_blob("hyllar".into(), None, None),
+            ],
+        )
+        .with_fee(fee)
+        .into()
+    }
+
+    #[test_log::test(tokio::test)]
+    async fn test_data_proposal_orders_txs_by_priority_fee() -> Result<()> {
+        let mut ctx = MempoolTestCtx::new("mempool").await;
+        let own = ctx.validator_pubkey().clone();
+        let other = ValidatorPublicKey(vec![1, 2, 3]);
+
+        let no_fee = make_register_contract_tx(ContractName::new("test1"));
+        let low_fee = make_fee_tx("a@hydentity", &own, 10);
+        let fee_for_other = make_fee_tx("b@hydentity", &other, 1000);
+        let high_fee = make_fee_tx("c@hydentity", &own, 100);
+
+        ctx.submit_tx(&no_fee);
+        ctx.submit_tx(&low_fee);
+        ctx.submit_tx(&fee_for_other);
+        ctx.submit_tx(&high_fee);
+
+        ctx.timer_tick().await?;
+
+        let dp_hash = ctx
+            .mempool
+            .lanes
+            .get_lane_hash_tip(&ctx.own_lane())
+            .unwrap();
+        let dp = ctx
+            .mempool
+            .lanes
+            .get_dp_by_hash(&ctx.own_lane(), dp_hash)
+            .unwrap()
+            .unwrap();
+
+        // Fees paid to other validators don't count
+        assert_eq!(dp.txs, vec![high_fee, low_fee, no_fee, fee_for_other]);
+        Ok(())
+    }
+
     #[test_log::test(tokio::test)]
     async fn test_send_poda_update() -> Result<()> {
         let mut ctx = MempoolTestCtx::new("mempool").await;
@@ -0,0 +1,143 @@
+//! Priority ordering and per-client rate limiting of the transactions submitted to our own lane.
+//!
+//! A blob transaction declares the priority fee it pays in its `fee` field, and deposits it for
+//! the validator disseminating it with staking `DepositForFees` blobs. The staking contract only
+//! settles a deposit followed by the matching token transfer, and the node state fails the
+//! transaction at settlement if the deposits don't cover the declared fee.
+//!
+//! Transactions are ordered before they settle, so only the fees covered by deposits for this
+//! validator count. The deposit can still fail when the transaction settles, e.g. for lack of
+//! funds: the per-client rate limit bounds what such transactions can get. Transactions waiting
+//! for dissemination are sorted again each time a data proposal is built, so a higher fee goes
+//! ahead of every transaction not disseminated yet, not only of those of the same data proposal.
+
+use std::{collections::HashMap, net::IpAddr};
+
+use hyle_model::{api::MempoolAdmissionError, utils::TimestampMs};
+use hyle_modules::node_state::priority_fee::check_priority_fee;
+
+use crate::{model::*, utils::conf::MempoolConf};
+
+/// Don't bother cleaning up expired windows below this number of tracked clients
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Priority fee the transaction pays to the given validator.
+/// Proofs and transactions whose fee is not deposited for that validator have no priority.
+pub fn priority_fee(tx: &Transaction, disseminator: &ValidatorPublicKey) -> u128 {
+    let TransactionData::Blob(blob_tx) = &tx.transaction_data else {
+        return 0;
+    };
+    match check_priority_fee(
+        blob_tx.fee,
+        &LaneId(disseminator.clone()),
+        blob_tx.blobs.iter(),
+    ) {
+        Ok(()) => blob_tx.fee,
+        Err(_) => 0,
+    }
+}
+
+/// Limits the number of transactions each client can submit in a time window.
+/// Clients are identified by their IP address, as the identity of a transaction is chosen freely.
+#[derive(Debug, Default)]
+pub struct ClientRateLimiter {
+    /// Start of the current window and number of transactions received in it, per client
+    windows: HashMap<IpAddr, (TimestampMs, u32)>,
+}
+
+impl ClientRateLimiter {
+    pub fn check(
+        &mut self,
+        client: IpAddr,
+        now: TimestampMs,
+        conf: &MempoolConf,
+    ) -> Result<(), MempoolAdmissionError> {
+        if conf.max_txs_per_client == 0 {
+            return Ok(());
+        }
+        let window = conf.rate_limit_window.as_millis();
+
+        if self.windows.len() > MAX_TRACKED_CLIENTS {
+            self.windows
+                .retain(|_, (start, _)| now.0.saturating_sub(start.0) < window);
+        }
+
+        let (start, count) = self.windows.entry(client).or_insert((now.clone(), 0));
+        if now.0.saturating_sub(start.0) >= window {
+            *start = now;
+            *count = 0;
+        }
+        if *count >= conf.max_txs_per_client {
+            return Err(MempoolAdmissionError::RateLimited {
+                max_txs: conf.max_txs_per_client,
+                window_ms: window,
+            });
+        }
+        *count += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, time::Duration};
+
+    use hyle_modules::node_state::priority_fee::FEE_CONTRACT;
+
+    use super::*;
+
+    fn fee_blob(holder: &ValidatorPublicKey, amount: u128) -> Blob {
+        StakingAction::DepositForFees {
+            holder: holder.clone(),
+            amount,
+        }
+        .as_blob(FEE_CONTRACT.into(), None, None)
+    }
+
+    #[test]
+    fn test_priority_fee() {
+        let validator = ValidatorPublicKey(vec![1]);
+        let other = ValidatorPublicKey(vec![2]);
+
+        let tx: Transaction = BlobTransaction::new(
+            "id@hydentity",
+            vec![fee_blob(&validator, 10), fee_blob(&validator, 5)],
+        )
+        .with_fee(15)
+        .into();
+        assert_eq!(priority_fee(&tx, &validator), 15);
+        assert_eq!(priority_fee(&tx, &other), 0);
+
+        // The declared fee must be covered by the deposits
+        let unpaid: Transaction =
+            BlobTransaction::new("id@hydentity", vec![fee_blob(&validator, 10)])
+                .with_fee(20)
+                .into();
+        assert_eq!(priority_fee(&unpaid, &validator), 0);
+
+        // Deposits alone don't declare a fee
+        let undeclared: Transaction =
+            BlobTransaction::new("id@hydentity", vec![fee_blob(&validator, 10)]).into();
+        assert_eq!(priority_fee(&undeclared, &validator), 0);
+    }
+
+    #[test]
+    fn test_client_rate_limit() {
+        let conf = MempoolConf {
+            max_txs_per_client: 2,
+            rate_limit_window: Duration::from_millis(1000),
+            ..Default::default()
+        };
+        let mut limiter = ClientRateLimiter::default();
+        let alice = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let bob = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+        assert!(limiter.check(alice, TimestampMs(0), &conf).is_ok());
+        assert!(limiter.check(alice, TimestampMs(10), &conf).is_ok());
+        assert!(limiter.check(alice, TimestampMs(20), &conf).is_err());
+        // Other clients are not affected
+        assert!(limiter.check(bob, TimestampMs(20), &conf).is_ok());
+        // Next window
+        assert!(limiter.check(alice, TimestampMs(1000), &conf).is_ok());
+    }
+}
@@ -6,11 +6,13 @@ use hyle_modules::modules::SharedBuildApiCtx;
 // Re-export
 pub use hyle_model::*;
 
-use crate::utils::conf::SharedConf;
+use crate::{mempool::admission::SharedAdmission, utils::conf::SharedConf};
 
 #[derive(Clone)]
 pub struct SharedRunContext {
     pub config: SharedConf,
     pub api: SharedBuildApiCtx,
     pub crypto: SharedBlstCrypto,
+    /// Mempool admission quotas, shared with the APIs receiving transactions
+    pub admission: SharedAdmission,
 }
@@ -4,7 +4,7 @@ use std::collections::HashSet;
 
 use crate::{
     bus::BusClientSender, consensus::ConsensusNetMessage, mempool::MempoolNetMessage,
-    model::SharedRunContext, utils::conf::SharedConf,
+    model::SharedRunContext, state_sync::StateSyncNetMessage, utils::conf::SharedConf,
 };
 use anyhow::{bail, Context, Error, Result};
 use hyle_crypto::{BlstCrypto, SharedBlstCrypto};
@@ -37,6 +37,7 @@ module_bus_client! {
 struct P2PBusClient {
     sender(MsgWithHeader<MempoolNetMessage>),
     sender(MsgWithHeader<ConsensusNetMessage>),
+    sender(MsgWithHeader<StateSyncNetMessage>),
     sender(PeerEvent),
     receiver(P2PCommand),
     receiver(NodeStateEvent),
@@ -82,6 +83,7 @@ impl P2P {
         match msg {
             NetMessage::MempoolMessage(_) => Canal::new("mempool"),
             NetMessage::ConsensusMessage(_) => Canal::new("consensus"),
+            NetMessage::StateSyncMessage(_) => Canal::new("state_sync"),
         }
     }
 
@@ -93,7 +95,11 @@ impl P2P {
             Some(self.config.p2p.max_frame_length),
             self.config.p2p.public_address.clone(),
             self.config.da_public_address.clone(),
-            HashSet::from_iter(vec![Canal::new("mempool"), Canal::new("consensus")]),
+            HashSet::from_iter(vec![
+                Canal::new("mempool"),
+                Canal::new("consensus"),
+                Canal::new("state_sync"),
+            ]),
         )
         .await?;
 
@@ -104,7 +110,8 @@ impl P2P {
 
         for peer_ip in self.config.p2p.peers.clone() {
             _ = p2p_server.try_start_connection(peer_ip.clone(), Canal::new("mempool"));
-            _ = p2p_server.try_start_connection(peer_ip, Canal::new("consensus"));
+            _ = p2p_server.try_start_connection(peer_ip.clone(), Canal::new("consensus"));
+            _ = p2p_server.try_start_connection(peer_ip, Canal::new("state_sync"));
         }
 
         module_handle_messages! {
@@ -226,6 +233,18 @@ impl P2P {
                     .send(consensus_msg)
                     .context("Receiving consensus net message")?;
             }
+            NetMessage::StateSyncMessage(state_sync_msg) => {
+                trace!("Received new state sync net message {}", state_sync_msg.msg);
+                Self::verify_msg_header(state_sync_msg.clone())?;
+                self.log_message_delay(
+                    &state_sync_msg.header.signature.validator,
+                    &state_sync_msg.header.msg,
+                    "state_sync",
+                );
+                self.bus
+                    .send(state_sync_msg)
+                    .context("Receiving state sync net message")?;
+            }
         }
         Ok(())
     }
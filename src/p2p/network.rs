@@ -1,9 +1,10 @@
 use crate::consensus::ConsensusNetMessage;
 use crate::mempool::MempoolNetMessage;
 use crate::model::ValidatorPublicKey;
+use crate::state_sync::StateSyncNetMessage;
 use anyhow::Context;
 use borsh::{BorshDeserialize, BorshSerialize};
-use hyle_crypto::BlstCrypto;
+use hyle_crypto::{signer::SigningKind, BlstCrypto};
 use hyle_model::{BlockHeight, SignedByValidator};
 use hyle_net::clock::TimestampMsClock;
 use hyle_net::tcp::P2PTcpMessage;
@@ -62,6 +63,10 @@ impl Display for NetMessage {
                 _ = write!(f, "NetMessage::{enum_variant} ");
                 write!(f, "{} (sent at {})", msg.msg, msg.header.msg.timestamp)
             }
+            NetMessage::StateSyncMessage(msg) => {
+                _ = write!(f, "NetMessage::{enum_variant} ");
+                write!(f, "{} (sent at {})", msg.msg, msg.header.msg.timestamp)
+            }
         }
     }
 }
@@ -84,6 +89,7 @@ impl Display for NetMessage {
 pub enum NetMessage {
     MempoolMessage(MsgWithHeader<MempoolNetMessage>),
     ConsensusMessage(MsgWithHeader<ConsensusNetMessage>),
+    StateSyncMessage(MsgWithHeader<StateSyncNetMessage>),
 }
 
 impl From<NetMessage> for P2PTcpMessage<NetMessage> {
@@ -104,6 +110,12 @@ impl From<MsgWithHeader<ConsensusNetMessage>> for NetMessage {
     }
 }
 
+impl From<MsgWithHeader<StateSyncNetMessage>> for NetMessage {
+    fn from(msg: MsgWithHeader<StateSyncNetMessage>) -> Self {
+        NetMessage::StateSyncMessage(msg)
+    }
+}
+
 impl NetMessage {
     pub fn to_binary(&self) -> anyhow::Result<Vec<u8>> {
         borsh::to_vec(self).context("Could not serialize NetMessage")
@@ -116,6 +128,12 @@ pub struct HeaderSignableData(pub Vec<u8>);
 // Can't be regular Into as I don't want to take ownership
 pub trait IntoHeaderSignableData {
     fn to_header_signable_data(&self) -> HeaderSignableData;
+
+    /// Describes the message, whose header has `timestamp`, to the signer for its
+    /// slashing-protection rules
+    fn signing_kind(&self, _timestamp: u128) -> SigningKind {
+        SigningKind::Generic
+    }
 }
 #[derive(Debug, Serialize, Deserialize, Clone, BorshSerialize, BorshDeserialize, Eq, PartialEq)]
 pub struct MsgHeader {
@@ -167,7 +185,8 @@ impl HeaderSigner for BlstCrypto {
             timestamp: TimestampMsClock::now().0,
             hash: msg.to_header_signable_data(),
         };
-        let signature = self.sign(header)?;
+        let kind = msg.signing_kind(header.timestamp);
+        let signature = self.sign_with_kind(header, kind)?;
         Ok(MsgWithHeader::<T> {
             msg,
             header: signature,
@@ -10,7 +10,7 @@ use crate::model::*;
 use crate::utils::conf::SharedConf;
 use anyhow::Result;
 use borsh::{BorshDeserialize, BorshSerialize};
-use hyle_crypto::SharedBlstCrypto;
+use hyle_crypto::{signer::SigningKind, SharedBlstCrypto};
 use hyle_modules::bus::SharedMessageBus;
 use hyle_modules::modules::module_bus_client;
 use hyle_modules::modules::Module;
@@ -191,9 +191,15 @@ impl SingleNodeConsensus {
 
         self.store.last_consensus_proposal_hash = consensus_proposal.hashed();
 
-        let certificate = self
-            .crypto
-            .sign_aggregate((consensus_proposal.hashed(), ConfirmAckMarker), &[])?;
+        let proposal_hash = consensus_proposal.hashed();
+        let certificate = self.crypto.sign_aggregate_with_kind(
+            (proposal_hash, ConfirmAckMarker),
+            SigningKind::ConfirmAck {
+                proposal: Box::new(consensus_proposal.clone()),
+                view: 0,
+            },
+            &[],
+        )?;
 
         self.bus.send(ConsensusEvent::CommitConsensusProposal(
             CommittedConsensusProposal {
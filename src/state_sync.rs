@@ -0,0 +1,860 @@
+//! State sync for joining validators.
+//!
+//! A fresh validator fetches the node state, the consensus staking state and the mempool
+//! lane tips from its peers on a dedicated P2P canal, instead of replaying every block
+//! since genesis. Peers take snapshots every `snapshot_interval` blocks, so they all serve
+//! the same ones. Snapshots are split in chunks that are individually hash-verified,
+//! so they can be downloaded from every peer advertising the same (height, hash).
+//!
+//! A snapshot is only trusted if enough peers advertise it, by default a majority of the
+//! configured peers, and if those peers hold more than 2/3 of the stake of the validators
+//! the node is configured to trust.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use anyhow::{bail, Context, Result};
+use borsh::{BorshDeserialize, BorshSerialize};
+use hyle_crypto::SharedBlstCrypto;
+use hyle_model::{
+    BlockHeight, ConsensusProposalHash, Contract, DataProposalHash, LaneBytesSize, LaneId,
+    ValidatorPublicKey,
+};
+use hyle_modules::{
+    bus::{
+        command_response::{CmdRespClient, Query},
+        BusClientSender, SharedMessageBus,
+    },
+    log_error, log_warn, module_bus_client, module_handle_messages,
+    modules::Module,
+    node_state::{
+        module::{LoadNodeStateSnapshot, QueryPeriodicNodeStateSnapshot},
+        snapshot::NodeStateSnapshot,
+    },
+};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use staking::state::Staking;
+use strum_macros::IntoStaticStr;
+use tracing::{debug, info, warn};
+
+use crate::{
+    genesis::GenesisEvent,
+    mempool::QueryLanesTip,
+    model::SharedRunContext,
+    p2p::network::{
+        HeaderSignableData, HeaderSigner, IntoHeaderSignableData, MsgWithHeader, OutboundMessage,
+    },
+    utils::conf::{Conf, P2pMode, SharedConf},
+};
+
+/// Number of periodic snapshots served, so in-flight downloads can complete
+/// and joining nodes find one that peers lagging behind also serve
+const SERVED_SNAPSHOTS: usize = 2;
+/// Maximum number of chunks requested at each tick
+const MAX_CHUNK_REQUESTS_PER_TICK: usize = 16;
+/// Number of ticks without receiving any chunk before restarting the sync from scratch
+const MAX_IDLE_TICKS: u32 = 10;
+
+pub type LanesTip = BTreeMap<LaneId, (DataProposalHash, LaneBytesSize)>;
+
+#[derive(
+    Debug,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+pub struct StateSyncHash(pub String);
+
+impl StateSyncHash {
+    pub fn of(bytes: &[u8]) -> Self {
+        let mut hasher = Sha3_256::new();
+        hasher.update(bytes);
+        Self(hex::encode(hasher.finalize()))
+    }
+}
+
+/// Description of a snapshot a peer can serve.
+/// The payload is the node state snapshot, which holds the staking state at the same height.
+#[derive(Debug, Serialize, Deserialize, Clone, BorshSerialize, BorshDeserialize, Eq, PartialEq)]
+pub struct StateSyncManifest {
+    pub height: BlockHeight,
+    pub block_hash: ConsensusProposalHash,
+    /// Hash of the whole serialized payload
+    pub payload_hash: StateSyncHash,
+    pub chunk_hashes: Vec<StateSyncHash>,
+    /// Lane tips of the peer, they are not part of the payload as they differ from one peer to another
+    pub lanes_tip: LanesTip,
+}
+
+#[derive(
+    Debug,
+    Serialize,
+    Deserialize,
+    Clone,
+    BorshSerialize,
+    BorshDeserialize,
+    Eq,
+    PartialEq,
+    IntoStaticStr,
+)]
+pub enum StateSyncNetMessage {
+    SnapshotRequest,
+    SnapshotManifest(StateSyncManifest),
+    ChunkRequest {
+        payload_hash: StateSyncHash,
+        index: u32,
+    },
+    Chunk {
+        payload_hash: StateSyncHash,
+        index: u32,
+        data: Vec<u8>,
+    },
+}
+
+impl std::fmt::Display for StateSyncNetMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let enum_variant: &'static str = self.into();
+        write!(f, "{enum_variant}")
+    }
+}
+
+impl IntoHeaderSignableData for StateSyncNetMessage {
+    fn to_header_signable_data(&self) -> HeaderSignableData {
+        match self {
+            // We get away with only signing the hash of the data - verification checks the chunk hash anyways
+            StateSyncNetMessage::Chunk {
+                payload_hash,
+                index,
+                data,
+            } => HeaderSignableData(
+                [
+                    payload_hash.0.clone().into_bytes(),
+                    index.to_le_bytes().to_vec(),
+                    StateSyncHash::of(data).0.into_bytes(),
+                ]
+                .concat(),
+            ),
+            msg => HeaderSignableData(borsh::to_vec(msg).unwrap_or_default()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StateSyncEvent {
+    /// The node state snapshot was loaded, other modules can resume from the given block
+    Synced {
+        height: BlockHeight,
+        block_hash: ConsensusProposalHash,
+        staking: Staking,
+        lanes_tip: LanesTip,
+        /// Contracts registered in the synced node state
+        contracts: Vec<Contract>,
+    },
+}
+
+/// Whether the node starts without local state and should fetch it from its peers.
+/// Only validators taking part in a multi-node consensus run the state sync.
+pub fn should_sync_state(config: &Conf) -> bool {
+    config.state_sync.enabled
+        && config.p2p.mode == P2pMode::FullValidator
+        && !config.consensus.solo
+        && !config.data_directory.join("genesis.bin").exists()
+}
+
+module_bus_client! {
+struct StateSyncBusClient {
+    sender(OutboundMessage),
+    sender(StateSyncEvent),
+    sender(Query<QueryPeriodicNodeStateSnapshot, Option<NodeStateSnapshot>>),
+    sender(Query<LoadNodeStateSnapshot, ()>),
+    sender(Query<QueryLanesTip, LanesTip>),
+    receiver(MsgWithHeader<StateSyncNetMessage>),
+    receiver(GenesisEvent),
+}
+}
+
+struct ServedSnapshot {
+    /// Manifest without lane tips, they are added when it is sent
+    manifest: StateSyncManifest,
+    chunks: Vec<Vec<u8>>,
+}
+
+/// Progress of the download of a snapshot, on the joining side
+#[derive(Default)]
+struct JoiningSync {
+    /// Last manifest advertised by each peer
+    manifests: HashMap<ValidatorPublicKey, StateSyncManifest>,
+    /// Manifest being downloaded and the peers advertising it
+    target: Option<(StateSyncManifest, Vec<ValidatorPublicKey>)>,
+    chunks: Vec<Option<Vec<u8>>>,
+    idle_ticks: u32,
+}
+
+pub struct StateSync {
+    config: SharedConf,
+    bus: StateSyncBusClient,
+    crypto: SharedBlstCrypto,
+    served: VecDeque<ServedSnapshot>,
+    needs_sync: bool,
+    joining: Option<JoiningSync>,
+}
+
+impl Module for StateSync {
+    type Context = SharedRunContext;
+
+    async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
+        let needs_sync = should_sync_state(&ctx.config);
+        if needs_sync && ctx.config.state_sync.trusted_validators.is_empty() {
+            bail!("State sync needs trusted validators to check snapshots against, set state_sync.trusted_validators");
+        }
+        let bus = StateSyncBusClient::new_from_bus(bus.new_handle()).await;
+        Ok(StateSync {
+            needs_sync,
+            config: ctx.config.clone(),
+            bus,
+            crypto: ctx.crypto.clone(),
+            served: VecDeque::new(),
+            joining: None,
+        })
+    }
+
+    async fn run(&mut self) -> Result<()> {
+        let mut sync_ticker = tokio::time::interval(self.config.state_sync.retry_interval);
+        sync_ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        module_handle_messages! {
+            on_self self,
+            listen<GenesisEvent> cmd => {
+                if matches!(cmd, GenesisEvent::NoGenesis) && self.needs_sync && self.joining.is_none() {
+                    info!("🔄 No local state, starting state sync from peers");
+                    self.joining = Some(JoiningSync::default());
+                }
+            }
+            listen<MsgWithHeader<StateSyncNetMessage>> msg => {
+                _ = log_warn!(self.handle_net_message(msg).await, "Handling StateSyncNetMessage");
+            }
+            _ = sync_ticker.tick() => {
+                _ = log_error!(self.on_sync_tick(), "State sync tick");
+            }
+        };
+
+        Ok(())
+    }
+}
+
+impl StateSync {
+    async fn handle_net_message(&mut self, msg: MsgWithHeader<StateSyncNetMessage>) -> Result<()> {
+        let peer = msg.header.signature.validator.clone();
+        match msg.msg {
+            StateSyncNetMessage::SnapshotRequest => {
+                self.refresh_served_snapshots().await?;
+                let lanes_tip = self
+                    .bus
+                    .shutdown_aware_request::<Self>(QueryLanesTip {})
+                    .await
+                    .context("Querying mempool lanes tip")?;
+                // Newest first: joining nodes keep the last manifest of each peer,
+                // which is then the one peers lagging behind also serve
+                let manifests: Vec<StateSyncManifest> = self
+                    .served
+                    .iter()
+                    .rev()
+                    .map(|served| StateSyncManifest {
+                        lanes_tip: lanes_tip.clone(),
+                        ..served.manifest.clone()
+                    })
+                    .collect();
+                for manifest in manifests {
+                    debug!(
+                        "Sending state sync manifest at height {} to {}",
+                        manifest.height, peer
+                    );
+                    self.send(
+                        peer.clone(),
+                        StateSyncNetMessage::SnapshotManifest(manifest),
+                    )?;
+                }
+                Ok(())
+            }
+            StateSyncNetMessage::ChunkRequest {
+                payload_hash,
+                index,
+            } => {
+                let Some(data) = self
+                    .served
+                    .iter()
+                    .find(|s| s.manifest.payload_hash == payload_hash)
+                    .and_then(|s| s.chunks.get(index as usize))
+                    .cloned()
+                else {
+                    bail!(
+                        "Peer {} requested chunk {} of unknown snapshot {}",
+                        peer,
+                        index,
+                        payload_hash.0
+                    );
+                };
+                self.send(
+                    peer,
+                    StateSyncNetMessage::Chunk {
+                        payload_hash,
+                        index,
+                        data,
+                    },
+                )
+            }
+            StateSyncNetMessage::SnapshotManifest(manifest) => {
+                let max_height_gap =
+                    SERVED_SNAPSHOTS as u64 * self.config.state_sync.snapshot_interval;
+                if let Some(joining) = self.joining.as_mut() {
+                    if joining.target.is_none() {
+                        joining.on_manifest(
+                            peer,
+                            manifest,
+                            &self.config.state_sync.trusted_validators,
+                            max_height_gap,
+                        );
+                    }
+                }
+                Ok(())
+            }
+            StateSyncNetMessage::Chunk {
+                payload_hash,
+                index,
+                data,
+            } => {
+                let Some(joining) = self.joining.as_mut() else {
+                    return Ok(());
+                };
+                joining.on_chunk(&payload_hash, index, data)?;
+                if joining.is_complete() {
+                    self.finish_sync().await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn send(&mut self, peer: ValidatorPublicKey, msg: StateSyncNetMessage) -> Result<()> {
+        let signed = self.crypto.sign_msg_with_header(msg)?;
+        self.bus
+            .send(OutboundMessage::send(peer, signed))
+            .context("Sending state sync message")?;
+        Ok(())
+    }
+
+    /// Serves the last periodic snapshot of the node state, along with the previous ones.
+    async fn refresh_served_snapshots(&mut self) -> Result<()> {
+        let known = self.served.back().map(|served| served.manifest.height);
+        let Some(snapshot) = self
+            .bus
+            .shutdown_aware_request::<Self>(QueryPeriodicNodeStateSnapshot { known })
+            .await
+            .context("Querying periodic node state snapshot")?
+        else {
+            return Ok(());
+        };
+
+        let served = ServedSnapshot::new(&snapshot, self.config.state_sync.chunk_size)?;
+        info!(
+            "📸 Serving state sync snapshot at height {} ({} chunks)",
+            served.manifest.height,
+            served.chunks.len()
+        );
+        self.served.push_back(served);
+        while self.served.len() > SERVED_SNAPSHOTS {
+            self.served.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Number of peers that must advertise a snapshot: the configured one,
+    /// or a majority of the configured peers
+    fn min_agreeing_peers(&self) -> usize {
+        match self.config.state_sync.min_agreeing_peers {
+            0 => self.config.p2p.peers.len() / 2 + 1,
+            n => n,
+        }
+    }
+
+    fn on_sync_tick(&mut self) -> Result<()> {
+        let min_agreeing_peers = self.min_agreeing_peers();
+        let Some(joining) = self.joining.as_mut() else {
+            return Ok(());
+        };
+        if joining.target.is_none()
+            && !joining.select_target(
+                min_agreeing_peers,
+                &self.config.state_sync.trusted_validators,
+            )
+        {
+            debug!(
+                "Requesting state sync manifests ({} received)",
+                joining.manifests.len()
+            );
+            let msg = self
+                .crypto
+                .sign_msg_with_header(StateSyncNetMessage::SnapshotRequest)?;
+            self.bus
+                .send(OutboundMessage::broadcast(msg))
+                .context("Broadcasting state sync request")?;
+            return Ok(());
+        }
+
+        joining.idle_ticks += 1;
+        if joining.idle_ticks > MAX_IDLE_TICKS {
+            warn!("🔄 State sync stalled, restarting it");
+            *joining = JoiningSync::default();
+            return Ok(());
+        }
+
+        for (peer, msg) in joining.next_chunk_requests() {
+            let signed = self.crypto.sign_msg_with_header(msg)?;
+            self.bus
+                .send(OutboundMessage::send(peer, signed))
+                .context("Requesting state sync chunk")?;
+        }
+        Ok(())
+    }
+
+    async fn finish_sync(&mut self) -> Result<()> {
+        let Some(joining) = self.joining.take() else {
+            return Ok(());
+        };
+        let (manifest, lanes_tip, snapshot) = match joining.into_payload(
+            self.min_agreeing_peers(),
+            &self.config.state_sync.trusted_validators,
+        ) {
+            Ok(res) => res,
+            Err(e) => {
+                // Whoever sent us this snapshot lied about it, start over
+                self.joining = Some(JoiningSync::default());
+                return Err(e);
+            }
+        };
+
+        let contracts = snapshot.store.contracts.values().cloned().collect();
+        let staking = snapshot.staking.clone();
+        self.bus
+            .shutdown_aware_request::<Self>(LoadNodeStateSnapshot(snapshot))
+            .await
+            .context("Loading synced node state snapshot")?;
+
+        info!(
+            "🔄 State synced at height {} (block {})",
+            manifest.height, manifest.block_hash
+        );
+        self.needs_sync = false;
+        self.bus
+            .send(StateSyncEvent::Synced {
+                height: manifest.height,
+                block_hash: manifest.block_hash,
+                staking,
+                lanes_tip,
+                contracts,
+            })
+            .context("Sending StateSyncEvent")?;
+        Ok(())
+    }
+}
+
+impl ServedSnapshot {
+    fn new(snapshot: &NodeStateSnapshot, chunk_size: usize) -> Result<Self> {
+        let bytes = borsh::to_vec(snapshot).context("Serializing state sync payload")?;
+        let chunks: Vec<Vec<u8>> = bytes
+            .chunks(chunk_size.max(1))
+            .map(|c| c.to_vec())
+            .collect();
+        Ok(ServedSnapshot {
+            manifest: StateSyncManifest {
+                height: snapshot.height(),
+                block_hash: snapshot.block_hash.clone(),
+                payload_hash: StateSyncHash::of(&bytes),
+                chunk_hashes: chunks.iter().map(|c| StateSyncHash::of(c)).collect(),
+                lanes_tip: LanesTip::new(),
+            },
+            chunks,
+        })
+    }
+}
+
+impl JoiningSync {
+    /// Records the manifest advertised by a peer, replacing its previous one.
+    /// Manifests more than `max_height_gap` blocks below the highest one advertised
+    /// by a trusted validator are dropped, so stale or bogus heights don't pile up.
+    fn on_manifest(
+        &mut self,
+        peer: ValidatorPublicKey,
+        manifest: StateSyncManifest,
+        trusted_validators: &BTreeMap<ValidatorPublicKey, u128>,
+        max_height_gap: u64,
+    ) {
+        self.manifests.insert(peer, manifest);
+        let Some(best_height) = self
+            .manifests
+            .iter()
+            .filter(|(peer, _)| trusted_validators.contains_key(peer))
+            .map(|(_, manifest)| manifest.height.0)
+            .max()
+        else {
+            return;
+        };
+        self.manifests
+            .retain(|_, manifest| manifest.height.0.saturating_add(max_height_gap) >= best_height);
+    }
+
+    /// Picks the highest snapshot whose (height, hash) is advertised by enough peers,
+    /// holding more than 2/3 of the stake of the trusted validators.
+    /// Returns false if no snapshot is advertised by enough peers yet.
+    fn select_target(
+        &mut self,
+        min_agreeing_peers: usize,
+        trusted_validators: &BTreeMap<ValidatorPublicKey, u128>,
+    ) -> bool {
+        let mut by_snapshot: HashMap<(BlockHeight, &StateSyncHash), Vec<ValidatorPublicKey>> =
+            HashMap::new();
+        for (peer, manifest) in self.manifests.iter() {
+            by_snapshot
+                .entry((manifest.height, &manifest.payload_hash))
+                .or_default()
+                .push(peer.clone());
+        }
+        let Some((manifest, peers)) = by_snapshot
+            .into_iter()
+            .filter(|(_, peers)| {
+                peers.len() >= min_agreeing_peers.max(1)
+                    && holds_trusted_quorum(trusted_validators, peers)
+            })
+            .filter_map(|(_, peers)| Some((self.manifests.get(peers.first()?)?, peers)))
+            .max_by_key(|(manifest, peers)| (manifest.height, peers.len()))
+        else {
+            return false;
+        };
+
+        info!(
+            "🔄 Downloading state snapshot at height {} from {} peer(s)",
+            manifest.height,
+            peers.len()
+        );
+        self.chunks = vec![None; manifest.chunk_hashes.len()];
+        self.target = Some((manifest.clone(), peers));
+        true
+    }
+
+    /// Requests for missing chunks, spread over the peers advertising the snapshot
+    fn next_chunk_requests(&self) -> Vec<(ValidatorPublicKey, StateSyncNetMessage)> {
+        let Some((manifest, peers)) = self.target.as_ref() else {
+            return vec![];
+        };
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.is_none())
+            .take(MAX_CHUNK_REQUESTS_PER_TICK)
+            .zip(peers.iter().cycle())
+            .map(|((index, _), peer)| {
+                (
+                    peer.clone(),
+                    StateSyncNetMessage::ChunkRequest {
+                        payload_hash: manifest.payload_hash.clone(),
+                        index: index as u32,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn on_chunk(&mut self, payload_hash: &StateSyncHash, index: u32, data: Vec<u8>) -> Result<()> {
+        let Some((manifest, _)) = self.target.as_ref() else {
+            return Ok(());
+        };
+        if &manifest.payload_hash != payload_hash {
+            return Ok(());
+        }
+        let Some(expected) = manifest.chunk_hashes.get(index as usize) else {
+            bail!("Received out of bounds state sync chunk {}", index);
+        };
+        if &StateSyncHash::of(&data) != expected {
+            bail!("Received state sync chunk {} with invalid hash", index);
+        }
+        if let Some(chunk) = self.chunks.get_mut(index as usize) {
+            *chunk = Some(data);
+            self.idle_ticks = 0;
+        }
+        Ok(())
+    }
+
+    fn is_complete(&self) -> bool {
+        self.target.is_some() && self.chunks.iter().all(|c| c.is_some())
+    }
+
+    /// Reassembles and checks the downloaded payload.
+    /// Only the lane tips the peers advertising the snapshot agree on are kept, with the
+    /// same threshold as the snapshot itself, other lanes are left to the mempool sync.
+    fn into_payload(
+        self,
+        min_agreeing_peers: usize,
+        trusted_validators: &BTreeMap<ValidatorPublicKey, u128>,
+    ) -> Result<(StateSyncManifest, LanesTip, NodeStateSnapshot)> {
+        let Some((manifest, peers)) = self.target else {
+            bail!("No state snapshot selected");
+        };
+        let bytes: Vec<u8> = self.chunks.into_iter().flatten().flatten().collect();
+        if StateSyncHash::of(&bytes) != manifest.payload_hash {
+            bail!("State sync payload does not match its hash");
+        }
+        let snapshot: NodeStateSnapshot =
+            borsh::from_slice(&bytes).context("Decoding state sync payload")?;
+        if snapshot.height() != manifest.height || snapshot.block_hash != manifest.block_hash {
+            bail!("State sync payload does not match its manifest");
+        }
+
+        let mut by_tip: BTreeMap<(LaneId, DataProposalHash, u64), Vec<ValidatorPublicKey>> =
+            BTreeMap::new();
+        for peer in peers {
+            let Some(advertised) = self.manifests.get(&peer) else {
+                continue;
+            };
+            if advertised.height != manifest.height
+                || advertised.payload_hash != manifest.payload_hash
+            {
+                continue;
+            }
+            for (lane_id, (dp_hash, size)) in advertised.lanes_tip.iter() {
+                by_tip
+                    .entry((lane_id.clone(), dp_hash.clone(), size.0))
+                    .or_default()
+                    .push(peer.clone());
+            }
+        }
+        let lanes_tip: LanesTip = by_tip
+            .into_iter()
+            .filter(|(_, peers)| {
+                peers.len() >= min_agreeing_peers.max(1)
+                    && holds_trusted_quorum(trusted_validators, peers)
+            })
+            .map(|((lane_id, dp_hash, size), _)| (lane_id, (dp_hash, LaneBytesSize(size))))
+            .collect();
+
+        Ok((manifest, lanes_tip, snapshot))
+    }
+}
+
+/// Whether `peers` hold more than 2/3 of the stake of the trusted validators
+fn holds_trusted_quorum(
+    trusted_validators: &BTreeMap<ValidatorPublicKey, u128>,
+    peers: &[ValidatorPublicKey],
+) -> bool {
+    let total: u128 = trusted_validators.values().sum();
+    let agreeing: u128 = peers
+        .iter()
+        .filter_map(|peer| trusted_validators.get(peer))
+        .sum();
+    3 * agreeing > 2 * total
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::indexing_slicing)]
+
+    use hyle_model::Identity;
+    use hyle_modules::node_state::NodeStateStore;
+
+    use super::*;
+
+    fn peer(name: &str) -> ValidatorPublicKey {
+        ValidatorPublicKey(name.as_bytes().to_vec())
+    }
+
+    /// Peers 1 and 2 are trusted with a stake of 100, peer 3 with 50
+    fn trusted() -> BTreeMap<ValidatorPublicKey, u128> {
+        BTreeMap::from([(peer("1"), 100), (peer("2"), 100), (peer("3"), 50)])
+    }
+
+    /// Snapshot at `height`, where peers 1 and 2 stake 100 and peer 3 stakes 50
+    fn snapshot(height: u64) -> NodeStateSnapshot {
+        let mut store = NodeStateStore::default();
+        store.current_height = BlockHeight(height);
+        let mut staking = Staking::default();
+        for (name, stake) in [("1", 100), ("2", 100), ("3", 50)] {
+            let staker = Identity::new(format!("{name}@hydentity"));
+            staking.stake(staker.clone(), stake).unwrap();
+            staking.delegate_to(staker, peer(name)).unwrap();
+            staking.bond(peer(name)).unwrap();
+        }
+        NodeStateSnapshot::new(ConsensusProposalHash(format!("{height}")), store, staking)
+    }
+
+    fn lanes_tip(lane: &str, hash: &str, size: u64) -> LanesTip {
+        LanesTip::from([(
+            LaneId(peer(lane)),
+            (DataProposalHash(hash.into()), LaneBytesSize(size)),
+        )])
+    }
+
+    fn advertise(joining: &mut JoiningSync, name: &str, manifest: &StateSyncManifest) {
+        joining.on_manifest(peer(name), manifest.clone(), &trusted(), 10);
+    }
+
+    #[test]
+    fn test_state_sync_download() -> Result<()> {
+        let served = ServedSnapshot::new(&snapshot(12), 8)?;
+        assert!(served.chunks.len() > 1);
+        let newer = ServedSnapshot::new(&snapshot(13), 8)?;
+
+        let mut joining = JoiningSync::default();
+        advertise(
+            &mut joining,
+            "1",
+            &StateSyncManifest {
+                lanes_tip: lanes_tip("a", "dp1", 10),
+                ..served.manifest.clone()
+            },
+        );
+        advertise(
+            &mut joining,
+            "2",
+            &StateSyncManifest {
+                lanes_tip: lanes_tip("a", "dp1", 10)
+                    .into_iter()
+                    .chain(lanes_tip("b", "dp2", 20))
+                    .collect(),
+                ..served.manifest.clone()
+            },
+        );
+        // Peer 3 already took the next snapshot, and still serves the previous one
+        advertise(&mut joining, "3", &newer.manifest);
+
+        // Peers 1 and 2 hold 200 of the 250 trusted stake, but only 2 of them agree
+        assert!(!joining.select_target(3, &trusted()));
+        advertise(
+            &mut joining,
+            "3",
+            &StateSyncManifest {
+                lanes_tip: lanes_tip("a", "dp1", 10),
+                ..served.manifest.clone()
+            },
+        );
+        assert!(joining.select_target(3, &trusted()));
+
+        // Chunks are requested from all peers serving the snapshot at height 12
+        let requests = joining.next_chunk_requests();
+        for name in ["1", "2", "3"] {
+            assert!(requests.iter().any(|(p, _)| p == &peer(name)));
+        }
+
+        // Tampered chunks are refused
+        assert!(joining
+            .on_chunk(&served.manifest.payload_hash, 0, vec![1, 2, 3])
+            .is_err());
+
+        for (index, chunk) in served.chunks.iter().enumerate() {
+            assert!(!joining.is_complete());
+            joining.on_chunk(&served.manifest.payload_hash, index as u32, chunk.clone())?;
+        }
+        assert!(joining.is_complete());
+
+        let (manifest, synced_tips, snapshot) = joining.into_payload(3, &trusted())?;
+        assert_eq!(manifest.height, BlockHeight(12));
+        assert_eq!(snapshot.height(), BlockHeight(12));
+        assert!(snapshot.staking.is_bonded(&peer("3")));
+        // Only the lane tip all peers agree on is kept
+        assert_eq!(synced_tips, lanes_tip("a", "dp1", 10));
+        Ok(())
+    }
+
+    #[test]
+    fn test_state_sync_agrees_on_height_and_hash() -> Result<()> {
+        let served = ServedSnapshot::new(&snapshot(12), 8)?;
+        let mut tampered = snapshot(12);
+        tampered
+            .staking
+            .stake(Identity::new("4@hydentity"), 1_000)
+            .unwrap();
+        let tampered = ServedSnapshot::new(&tampered, 8)?;
+        assert_ne!(served.manifest.payload_hash, tampered.manifest.payload_hash);
+
+        // Same height, different snapshots: none of them gathers a quorum
+        let mut joining = JoiningSync::default();
+        advertise(&mut joining, "1", &served.manifest);
+        advertise(&mut joining, "2", &tampered.manifest);
+        advertise(&mut joining, "3", &served.manifest);
+        assert!(!joining.select_target(1, &trusted()));
+
+        advertise(&mut joining, "2", &served.manifest);
+        assert!(joining.select_target(1, &trusted()));
+        assert_eq!(
+            joining.target.as_ref().map(|(m, _)| &m.payload_hash),
+            Some(&served.manifest.payload_hash)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_state_sync_rejects_mismatching_manifest() -> Result<()> {
+        let served = ServedSnapshot::new(&snapshot(12), 8)?;
+        let mut manifest = served.manifest.clone();
+        manifest.height = BlockHeight(50);
+
+        let mut joining = JoiningSync::default();
+        for name in ["1", "2", "3"] {
+            advertise(&mut joining, name, &manifest);
+        }
+        assert!(joining.select_target(1, &trusted()));
+        for (index, chunk) in served.chunks.iter().enumerate() {
+            joining.on_chunk(&served.manifest.payload_hash, index as u32, chunk.clone())?;
+        }
+        assert!(joining.into_payload(1, &trusted()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_state_sync_requires_trusted_stake() -> Result<()> {
+        let served = ServedSnapshot::new(&snapshot(12), 8)?;
+
+        // Peers 3 and 4 hold 50 of the 250 trusted stake, whatever the snapshot says
+        let mut joining = JoiningSync::default();
+        advertise(&mut joining, "3", &served.manifest);
+        advertise(&mut joining, "4", &served.manifest);
+        assert!(!joining.select_target(2, &trusted()));
+
+        // 150 of 250 is still not more than 2/3
+        advertise(&mut joining, "1", &served.manifest);
+        assert!(!joining.select_target(2, &trusted()));
+
+        advertise(&mut joining, "2", &served.manifest);
+        assert!(joining.select_target(2, &trusted()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_state_sync_bounds_manifests() -> Result<()> {
+        let old = ServedSnapshot::new(&snapshot(12), 8)?;
+        let served = ServedSnapshot::new(&snapshot(40), 8)?;
+
+        // One manifest is kept per peer
+        let mut joining = JoiningSync::default();
+        advertise(&mut joining, "4", &old.manifest);
+        advertise(&mut joining, "4", &served.manifest);
+        assert_eq!(joining.manifests.len(), 1);
+
+        // Heights far below the best one advertised by a trusted validator are dropped
+        advertise(&mut joining, "5", &old.manifest);
+        advertise(&mut joining, "1", &served.manifest);
+        assert!(!joining.manifests.contains_key(&peer("5")));
+        advertise(&mut joining, "3", &old.manifest);
+        assert!(!joining.manifests.contains_key(&peer("3")));
+
+        // An untrusted peer can't push the others out with a bogus height
+        let bogus = ServedSnapshot::new(&snapshot(1_000), 8)?;
+        advertise(&mut joining, "6", &bogus.manifest);
+        assert!(joining.manifests.contains_key(&peer("1")));
+        Ok(())
+    }
+}
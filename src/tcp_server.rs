@@ -1,37 +1,55 @@
-use crate::bus::BusClientSender;
+use crate::{
+    bus::BusClientSender, mempool::admission::SharedAdmission, model::Hashed,
+    node_state::module::NodeStateEvent,
+};
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 
 use anyhow::Result;
-use client_sdk::tcp_client::{TcpApiServer, TcpServerMessage};
+use client_sdk::tcp_client::{TcpApiServer, TcpServerMessage, TcpServerResponse};
 use hyle_modules::{
     bus::SharedMessageBus,
     log_error, module_handle_messages,
     modules::{module_bus_client, Module},
+    utils::tx_watchers::{TxWatchers, WatchUpdate},
 };
-use hyle_net::tcp::TcpEvent;
-use tracing::info;
+use hyle_net::{clock::TimestampMsClock, tcp::TcpEvent};
+use tracing::{debug, info};
 
 module_bus_client! {
 #[derive(Debug)]
 struct TcpServerBusClient {
     sender(TcpServerMessage),
+    receiver(NodeStateEvent),
+}
 }
+
+pub struct TcpServerCtx {
+    pub port: u16,
+    /// Quotas checked before forwarding transactions to the mempool
+    pub admission: SharedAdmission,
 }
 
 #[derive(Debug)]
 pub struct TcpServer {
     tcp_server_port: u16,
+    admission: SharedAdmission,
     bus: TcpServerBusClient,
+    /// Connections waiting for the state transitions of transactions
+    watchers: TxWatchers<String>,
 }
 
 impl Module for TcpServer {
-    type Context = u16;
+    type Context = TcpServerCtx;
 
     async fn build(bus: SharedMessageBus, ctx: Self::Context) -> Result<Self> {
         let bus = TcpServerBusClient::new_from_bus(bus.new_handle()).await;
 
         Ok(TcpServer {
-            tcp_server_port: ctx,
+            tcp_server_port: ctx.port,
+            admission: ctx.admission,
             bus,
+            watchers: TxWatchers::default(),
         })
     }
 
@@ -53,13 +71,69 @@ impl TcpServer {
 
         module_handle_messages! {
             on_self self,
+            listen<NodeStateEvent> NodeStateEvent::NewBlock(block) => {
+                for update in self.watchers.on_block(&block) {
+                    let (dest, response) = match update {
+                        WatchUpdate::Event(dest, event) => (dest, TcpServerResponse::TxEvent(event)),
+                        WatchUpdate::Expired(dest, tx_hash) => (dest, TcpServerResponse::WatchEnded {
+                            tx_hash,
+                            reason: "Watch expired".to_string(),
+                        }),
+                    };
+                    _ = log_error!(server.send(dest, response).await, "Sending tx event to TCP client");
+                }
+            }
             Some(tcp_event) = server.listen_next() => {
-                if let TcpEvent::Message { dest: _, data } = tcp_event {
-                    _ = log_error!(self.bus.send(data), "Sending message on TcpServerMessage topic from connection pool");
+                match tcp_event {
+                    TcpEvent::Message { dest, data: TcpServerMessage::WatchTx(tx_hash) } => {
+                        debug!("Watching tx {} for {}", tx_hash, dest);
+                        if let Err(e) = self.watchers.watch(dest.clone(), tx_hash.clone()) {
+                            let response = TcpServerResponse::WatchEnded { tx_hash, reason: e.to_string() };
+                            _ = log_error!(server.send(dest, response).await, "Sending watch refusal to TCP client");
+                        }
+                    }
+                    TcpEvent::Message { dest, data } => {
+                        if let Some(response) = self.check_admission(&dest, &data) {
+                            _ = log_error!(server.send(dest, response).await, "Sending admission rejection to TCP client");
+                        } else {
+                            _ = log_error!(self.bus.send(data), "Sending message on TcpServerMessage topic from connection pool");
+                        }
+                    }
+                    TcpEvent::Closed { dest } => {
+                        self.watchers.disconnect(&dest);
+                    }
+                    TcpEvent::Error { .. } => {}
                 }
             }
         };
 
         Ok(())
     }
+
+    /// Returns the rejection to send back if the transaction exceeds the mempool quotas
+    /// or the rate limit of the client at `dest`
+    fn check_admission(&self, dest: &str, msg: &TcpServerMessage) -> Option<TcpServerResponse> {
+        let TcpServerMessage::NewTx(tx) = msg else {
+            return None;
+        };
+        // Connections are named after the peer socket address; unparsable ones share one limit
+        let client = dest
+            .parse::<SocketAddr>()
+            .map(|addr| addr.ip())
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        #[allow(clippy::expect_used, reason = "not held across await")]
+        let checked = self.admission.write().expect("logic issue").submit(
+            tx,
+            client,
+            TimestampMsClock::now(),
+        );
+        match checked {
+            Ok(()) => None,
+            Err(error) => {
+                let tx_hash = tx.hashed();
+                debug!("Rejecting tx {} received over TCP: {}", tx_hash, error);
+                Some(TcpServerResponse::Rejected { tx_hash, error })
+            }
+        }
+    }
 }
@@ -1,10 +1,17 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use config::{Config, Environment, File};
-use hyle_modules::modules::websocket::WebSocketConfig;
+use hyle_model::ValidatorPublicKey;
+use hyle_modules::{modules::websocket::WebSocketConfig, utils::light_client::TrustedValidatorSet};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use serde_with::DurationMilliSeconds;
-use std::{collections::HashMap, fmt::Debug, path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
 use strum_macros::IntoStaticStr;
 
 use crate::indexer::IndexerConf;
@@ -22,6 +29,8 @@ pub struct Consensus {
     pub solo: bool,
     /// The timestamp of the genesis block, in seconds since the Unix epoch.
     pub genesis_timestamp: u64,
+    /// Whether to send a slashing transaction when a validator is caught double-signing.
+    pub submit_slashing_evidence: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, IntoStaticStr)]
@@ -41,6 +50,8 @@ pub struct GenesisConf {
     pub stakers: HashMap<String, u64>,
     /// Used for testing - if true, token balance will remain in the faucet.
     pub keep_tokens_in_faucet: bool,
+    /// Path to a TOML or JSON file describing the contracts, allocations and stakers of the genesis
+    pub file: Option<PathBuf>,
 }
 
 /// Configuration for the P2P layer
@@ -65,11 +76,73 @@ pub enum P2pMode {
     FullValidator,
     /// Run a full node without consensus (assumes you have your own lane)
     LaneManager,
+    /// Run a read-only full node without consensus, that keeps every block and the state
+    /// of the contracts at every height, to answer historical queries without an indexer
+    Archive,
     /// Run a limited node that subscribes to another one for DA
     #[default]
     None,
 }
 
+/// Configuration for the blocks storage of the DataAvailability module
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DaStorageConf {
+    /// Storage backend for blocks
+    #[serde(default)]
+    pub backend: DaStorageBackend,
+    /// Number of most recent blocks to keep, older ones get pruned. 0 keeps every block.
+    pub keep_last_blocks: u64,
+    /// Prune blocks older than this many seconds, relative to the latest block timestamp. 0 disables.
+    pub keep_blocks_newer_than_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub enum DaStorageBackend {
+    /// Persist blocks on disk in a fjall keyspace
+    #[default]
+    Fjall,
+    /// A backend registered under this name with `register_blocks_storage`
+    Custom(String),
+}
+
+/// Configuration for the transactions submitted to our own lane
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MempoolConf {
+    /// Maximum number of transactions a client IP address can submit per window. 0 disables the limit.
+    pub max_txs_per_client: u32,
+    /// Window over which the per-client rate limit applies
+    #[serde_as(as = "DurationMilliSeconds")]
+    pub rate_limit_window: Duration,
+    /// Maximum number of transactions of an identity waiting for dissemination. 0 disables the quota.
+    pub max_pending_txs_per_identity: u32,
+    /// Maximum number of bytes accepted per contract in a slot. 0 disables the quota.
+    pub max_contract_bytes_per_slot: u64,
+    /// Maximum number of bytes waiting for dissemination. 0 disables the cap.
+    pub max_pending_bytes: u64,
+}
+
+/// Configuration for the state sync of joining validators
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct StateSyncConf {
+    /// Whether a node starting without data fetches its state from peers instead of replaying all blocks
+    pub enabled: bool,
+    /// Size of the chunks snapshots are split in
+    pub chunk_size: usize,
+    /// Number of peers that must advertise the same snapshot before downloading it.
+    /// 0 requires a majority of the configured peers.
+    pub min_agreeing_peers: usize,
+    /// Validators trusted by a joining node and their stake. The peers advertising a snapshot
+    /// must hold more than 2/3 of this stake, whatever the snapshot itself says.
+    pub trusted_validators: BTreeMap<ValidatorPublicKey, u128>,
+    /// Snapshots are taken every N blocks, so that peers serve the same ones. 0 disables serving them.
+    pub snapshot_interval: u64,
+    /// Interval between requests to peers while syncing
+    #[serde_as(as = "DurationMilliSeconds")]
+    pub retry_interval: Duration,
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct NodeWebSocketConfig {
     /// Wether the WebSocket server is enabled
@@ -84,6 +157,8 @@ pub struct NodeWebSocketConfig {
     pub peer_check_interval: u64,
     /// List of events to stream on the websocket
     pub events: Vec<String>,
+    /// Number of messages buffered per connection before it is dropped
+    pub peer_buffer_size: usize,
 }
 
 impl From<NodeWebSocketConfig> for WebSocketConfig {
@@ -93,6 +168,7 @@ impl From<NodeWebSocketConfig> for WebSocketConfig {
             ws_path: config.ws_path,
             health_path: config.health_path,
             peer_check_interval: Duration::from_millis(config.peer_check_interval),
+            peer_buffer_size: config.peer_buffer_size,
         }
     }
 }
@@ -125,6 +201,13 @@ pub struct Conf {
     pub da_server_port: u16,
     /// Server port for the DA API
     pub da_max_frame_length: usize,
+    /// Mempool configuration
+    pub mempool: MempoolConf,
+
+    /// Blocks storage backend and retention policy of the DA
+    pub da_storage: DaStorageConf,
+    /// State sync for joining validators
+    pub state_sync: StateSyncConf,
 
     pub run_rest_server: bool,
     /// Server port for the REST API
@@ -148,8 +231,15 @@ pub struct Conf {
     pub database_url: String,
     /// When running only the indexer, the address of the DA server to connect to
     pub da_read_from: String,
+    /// DA servers to fail over to, in order, when da_read_from stalls or cannot be reached
+    pub da_fallbacks: Vec<String>,
+    /// Also stream blocks from the next DA server, only process the blocks both agree on,
+    /// and halt if they disagree or the next server cannot be reached
+    pub da_cross_check: bool,
     /// Timeout for DA client requests, in seconds, before it tries to reconnect to stream blocks
     pub da_timeout_client_secs: u64,
+    /// Verify the blocks streamed from da_read_from against this validator set, instead of trusting the DA server
+    pub da_trusted_validators: Option<TrustedValidatorSet>,
 
     /// Websocket configuration
     pub websocket: NodeWebSocketConfig,
@@ -200,6 +290,14 @@ impl Conf {
                 .unwrap_or(1000),
             );
         }
+
+        if conf.p2p.mode == P2pMode::Archive
+            && (conf.da_storage.backend != DaStorageBackend::Fjall
+                || conf.da_storage.keep_last_blocks > 0
+                || conf.da_storage.keep_blocks_newer_than_secs > 0)
+        {
+            bail!("Archive nodes keep every block: da_storage must use Fjall without retention");
+        }
         Ok(conf)
     }
 }
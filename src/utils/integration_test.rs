@@ -17,16 +17,16 @@ use tracing::info;
 use crate::bus::metrics::BusMetrics;
 use crate::bus::{bus_client, BusClientReceiver, SharedMessageBus};
 use crate::consensus::Consensus;
-use crate::data_availability::DataAvailability;
+use crate::data_availability::{DataAvailability, DataAvailabilityCtx};
 use crate::genesis::{Genesis, GenesisEvent};
 use crate::indexer::Indexer;
-use crate::mempool::Mempool;
+use crate::mempool::{admission::AdmissionControl, Mempool};
 use crate::model::SharedRunContext;
 use crate::node_state::module::{NodeStateEvent, NodeStateModule};
 use crate::p2p::P2P;
 use crate::rest::{RestApi, RestApiRunContext};
 use crate::single_node_consensus::SingleNodeConsensus;
-use crate::tcp_server::TcpServer;
+use crate::tcp_server::{TcpServer, TcpServerCtx};
 use crate::utils::conf::Conf;
 use hyle_crypto::BlstCrypto;
 
@@ -260,6 +260,7 @@ impl NodeIntegrationCtx {
                 openapi: Default::default(),
             }),
             crypto,
+            admission: AdmissionControl::shared(config.mempool.clone()),
         };
 
         let mut handler = ModulesHandler::new(&bus).await;
@@ -279,13 +280,22 @@ impl NodeIntegrationCtx {
             Self::build_module::<Indexer>(
                 &mut handler,
                 &ctx,
-                (config.clone(), ctx.api.clone()),
+                (config.clone(), ctx.api.clone(), Default::default()),
                 &mut mocks,
             )
             .await?;
         }
 
-        Self::build_module::<DataAvailability>(&mut handler, &ctx, ctx.clone(), &mut mocks).await?;
+        Self::build_module::<DataAvailability>(
+            &mut handler,
+            &ctx,
+            DataAvailabilityCtx {
+                common: ctx.clone(),
+                blocks: None,
+            },
+            &mut mocks,
+        )
+        .await?;
         Self::build_module::<NodeStateModule>(
             &mut handler,
             &ctx,
@@ -293,6 +303,8 @@ impl NodeIntegrationCtx {
                 node_id: config.id.clone(),
                 data_directory: config.data_directory.clone(),
                 api: ctx.api.clone(),
+                archive: false,
+                snapshot_interval: config.state_sync.snapshot_interval,
             },
             &mut mocks,
         )
@@ -335,7 +347,10 @@ impl NodeIntegrationCtx {
             Self::build_module::<TcpServer>(
                 &mut handler,
                 &ctx,
-                ctx.config.tcp_server_port,
+                TcpServerCtx {
+                    port: ctx.config.tcp_server_port,
+                    admission: ctx.admission.clone(),
+                },
                 &mut mocks,
             )
             .await?;
@@ -0,0 +1,164 @@
+//! `hyle keys` subcommand, managing encrypted validator keystores.
+//!
+//! Passwords are read from `HYLE_VALIDATOR_KEYSTORE_PASSWORD` when set, and prompted for otherwise.
+
+use std::{
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Context, Result};
+use clap::Subcommand;
+use hyle_crypto::{keystore::Keystore, BlstCrypto};
+
+#[derive(Subcommand, Debug)]
+pub enum KeysCommand {
+    /// Generate a new random validator key into an encrypted keystore
+    Generate {
+        #[arg(long)]
+        keystore: PathBuf,
+        #[arg(long, default_value = "")]
+        description: String,
+    },
+    /// Encrypt an existing hex secret, as used in HYLE_VALIDATOR_SECRET, read from stdin
+    Import {
+        #[arg(long)]
+        keystore: PathBuf,
+        #[arg(long, default_value = "")]
+        description: String,
+    },
+    /// Print the hex secret of a keystore
+    Export {
+        #[arg(long)]
+        keystore: PathBuf,
+    },
+    /// Print the public information of a keystore, without decrypting it
+    Inspect {
+        #[arg(long)]
+        keystore: PathBuf,
+    },
+    /// Replace the key of a keystore with a new random one, archiving the previous keystore.
+    /// The new key is a new validator identity, which must be bonded again to take part in consensus
+    Rotate {
+        #[arg(long)]
+        keystore: PathBuf,
+        /// Confirm the change of validator identity without being prompted
+        #[arg(long)]
+        new_identity: bool,
+    },
+}
+
+pub fn run(command: KeysCommand) -> Result<()> {
+    match command {
+        KeysCommand::Generate {
+            keystore,
+            description,
+        } => {
+            if keystore.exists() {
+                bail!("{} already exists", keystore.display());
+            }
+            let password = new_password()?;
+            let ks = Keystore::encrypt(&BlstCrypto::generate_secret(), &password, &description)?;
+            ks.save(&keystore)?;
+            println!("Generated key {} in {}", ks.pubkey, keystore.display());
+        }
+        KeysCommand::Import {
+            keystore,
+            description,
+        } => {
+            if keystore.exists() {
+                bail!("{} already exists", keystore.display());
+            }
+            let mut secret = String::new();
+            std::io::stdin()
+                .read_to_string(&mut secret)
+                .context("Reading secret from stdin")?;
+            let secret = hex::decode(secret.trim()).context("Secret must be hex encoded")?;
+            let password = new_password()?;
+            let ks = Keystore::encrypt(&secret, &password, &description)?;
+            ks.save(&keystore)?;
+            println!("Imported key {} in {}", ks.pubkey, keystore.display());
+        }
+        KeysCommand::Export { keystore } => {
+            let ks = Keystore::load(&keystore)?;
+            let secret = ks.decrypt(&password("Keystore password: ")?)?;
+            println!("{}", hex::encode(secret));
+        }
+        KeysCommand::Inspect { keystore } => {
+            let ks = Keystore::load(&keystore)?;
+            println!("pubkey:      {}", ks.pubkey);
+            println!("description: {}", ks.description);
+            println!("uuid:        {}", ks.uuid);
+            println!("version:     {}", ks.version);
+            println!(
+                "kdf:         {} ({}, {} rounds)",
+                ks.crypto.kdf.function, ks.crypto.kdf.params.prf, ks.crypto.kdf.params.c
+            );
+            println!("cipher:      {}", ks.crypto.cipher.function);
+        }
+        KeysCommand::Rotate {
+            keystore,
+            new_identity,
+        } => {
+            let previous = Keystore::load(&keystore)?;
+            if !new_identity {
+                confirm_new_identity(&previous.pubkey)?;
+            }
+            let current = password("Current keystore password: ")?;
+            let new = new_password()?;
+            let archive = Keystore::rotate(&keystore, &current, &new)?;
+            let ks = Keystore::load(&keystore)?;
+            println!(
+                "Rotated to key {} in {}, previous keystore moved to {}",
+                ks.pubkey,
+                keystore.display(),
+                archive.display()
+            );
+            println!(
+                "Bond {} before restarting the node, and unbond {}.",
+                ks.pubkey, previous.pubkey
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Rotating replaces the validator identity: the new key holds no stake, so the node stops
+/// taking part in consensus until it is bonded again. Asks the operator to type "rotate".
+fn confirm_new_identity(pubkey: &str) -> Result<()> {
+    eprintln!(
+        "Rotating replaces validator {pubkey} with a new validator identity. The new key holds \
+         no stake: the node won't take part in consensus until it is bonded again."
+    );
+    eprint!("Type 'rotate' to continue: ");
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .context("Reading confirmation")?;
+    if answer.trim() != "rotate" {
+        bail!("Rotation not confirmed, the keystore is unchanged");
+    }
+    Ok(())
+}
+
+fn password(prompt: &str) -> Result<String> {
+    if let Ok(password) = std::env::var("HYLE_VALIDATOR_KEYSTORE_PASSWORD") {
+        return Ok(password);
+    }
+    rpassword::prompt_password(prompt).context("Reading password")
+}
+
+fn new_password() -> Result<String> {
+    if let Ok(password) = std::env::var("HYLE_VALIDATOR_KEYSTORE_PASSWORD") {
+        return Ok(password);
+    }
+    let password = rpassword::prompt_password("New keystore password: ")?;
+    if password.is_empty() {
+        bail!("Password must not be empty");
+    }
+    if rpassword::prompt_password("Confirm password: ")? != password {
+        bail!("Passwords do not match");
+    }
+    Ok(password)
+}
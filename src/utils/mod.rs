@@ -1,5 +1,6 @@
 //! Utilities.
 pub mod conf;
 pub mod integration_test;
+pub mod keys;
 pub mod modules;
 pub mod serialize;
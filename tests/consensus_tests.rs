@@ -19,11 +19,11 @@ mod e2e_consensus {
     use hyle_contract_sdk::Identity;
     use hyle_contract_sdk::ZkContract;
     use hyle_contracts::{HYDENTITY_ELF, HYLLAR_ELF, STAKING_ELF};
-    use hyle_model::{ContractName, StateCommitment, TxHash};
+    use hyle_model::{ContractName, StateCommitment, TxHash, ValidatorPublicKey};
     use hyllar::client::tx_executor_handler::transfer;
     use hyllar::erc20::ERC20;
     use hyllar::{Hyllar, FAUCET_ID};
-    use staking::client::tx_executor_handler::{delegate, stake};
+    use staking::client::tx_executor_handler::{delegate, stake, unstake};
     use staking::state::Staking;
     use tracing::{info, warn};
 
@@ -48,7 +48,12 @@ mod e2e_consensus {
         Ok(())
     }
 
-    async fn scenario_rejoin_common(ctx: &mut E2ECtx, stake_amount: u128) -> Result<()> {
+    /// Adds a node staking `stake_amount`, and returns the transaction executor, the identity
+    /// it staked with and its validator key
+    async fn scenario_rejoin_common(
+        ctx: &mut E2ECtx,
+        stake_amount: u128,
+    ) -> Result<(TxExecutor<States>, Identity, ValidatorPublicKey)> {
         ctx.wait_height(2).await?;
 
         let joining_client = ctx.add_node().await?;
@@ -163,13 +168,14 @@ mod e2e_consensus {
 
         assert_eq!(consensus.validators.len(), 3, "expected 3 validators");
 
+        let node_pubkey = node_info.pubkey.unwrap();
         assert!(
-            consensus.validators.contains(&node_info.pubkey.unwrap()),
+            consensus.validators.contains(&node_pubkey),
             "node pubkey not found in validators",
         );
 
         ctx.wait_height(1).await?;
-        Ok(())
+        Ok((tx_ctx, node_identity, node_pubkey))
     }
 
     async fn gen_txs(
@@ -260,6 +266,59 @@ mod e2e_consensus {
         Ok(())
     }
 
+    #[test_log::test(tokio::test)]
+    async fn can_unstake_and_leave_consensus() -> Result<()> {
+        let mut ctx = E2ECtx::new_multi_with_indexer(2, 500).await?;
+
+        let (mut tx_ctx, node_identity, node_pubkey) =
+            scenario_rejoin_common(&mut ctx, 100).await?;
+
+        {
+            let mut transaction = ProvableBlobTx::new(node_identity.clone());
+
+            verify_identity(
+                &mut transaction,
+                "hydentity".into(),
+                &tx_ctx.hydentity,
+                "password".to_string(),
+            )?;
+
+            unstake(&mut transaction, ContractName::new("staking"), 80)?;
+
+            let tx_hash = send_transaction(ctx.client(), transaction, &mut tx_ctx).await;
+            tracing::warn!("Unstake TX Hash: {:?}", tx_hash);
+        }
+
+        // Wait until the unstake is settled
+        let staking: Staking = loop {
+            let staking = ctx.client().get_consensus_staking_state().await?;
+            if staking.unbonding.contains_key(&node_identity) {
+                break staking.into();
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        };
+        assert_eq!(staking.get_stake(&node_pubkey), Some(20));
+        assert_eq!(staking.validators_to_unbond(), vec![node_pubkey.clone()]);
+
+        // The consensus copy of the staking state matches the proven one
+        let staking_state = StateCommitment(
+            ctx.indexer_client()
+                .get_indexer_contract(&"staking".into())
+                .await?
+                .state_commitment,
+        );
+        assert_eq!(staking_state, staking.commit());
+
+        // The node, now below the minimum stake, leaves the consensus, which keeps going
+        ctx.wait_height(2).await?;
+        let consensus = ctx.client().get_consensus_info().await?;
+        assert_eq!(consensus.validators.len(), 2, "expected 2 validators");
+        assert!(!consensus.validators.contains(&node_pubkey));
+        ctx.wait_height(1).await?;
+
+        Ok(())
+    }
+
     async fn init_states(ctx: &mut E2ECtx) -> TxExecutor<States> {
         let hyllar: Hyllar = ctx
             .indexer_client()